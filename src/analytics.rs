@@ -0,0 +1,625 @@
+// How fast the cases of a country grow right now: the growth factor of the
+// new cases and the doubling time of the cumulative cases, forecasts from
+// exponential or logistic curves fitted to the last weeks, the effective
+// reproduction number, whether the new cases rise or fall, how many days
+// the deaths follow them, the days that were reported unusually, when the
+// cases reached 100, 1,000 and so on and how the resolved cases ended.
+
+use chrono::NaiveDate;
+use std::str::FromStr;
+
+use crate::i18n;
+use crate::table::{format_change, format_count, format_decimal, Align, Table};
+use crate::waves;
+
+// days the exponential of the doubling time is fitted to
+pub const DOUBLING_DAYS: usize = 7;
+
+// New cases of the last day by those of the day before, from cumulative
+// counts. `None` without new cases the day before.
+pub fn growth_factor(cumulative: &[(NaiveDate, i64)]) -> Option<f64> {
+    match cumulative {
+        [.., before, yesterday, today] => {
+            let (new, previous) = (today.1 - yesterday.1, yesterday.1 - before.1);
+            if previous <= 0 {
+                None
+            } else {
+                Some(new.max(0) as f64 / previous as f64)
+            }
+        }
+        _ => None,
+    }
+}
+
+// Days the cumulative count takes to double at the pace of the last `days`
+// days, from a least squares fit of its logarithm. `None` when it does not
+// grow or there are no cases to fit.
+pub fn doubling_time(cumulative: &[(NaiveDate, i64)], days: usize) -> Option<f64> {
+    let last = &cumulative[cumulative.len().saturating_sub(days)..];
+    if last.len() < 2 || last.iter().any(|(_, v)| *v <= 0) {
+        return None;
+    }
+    let points: Vec<(f64, f64)> = last
+        .iter()
+        .enumerate()
+        .map(|(x, (_, v))| (x as f64, (*v as f64).ln()))
+        .collect();
+    let (rate, _) = linear_fit(&points)?;
+    if rate <= 0.0 {
+        None
+    } else {
+        Some(std::f64::consts::LN_2 / rate)
+    }
+}
+
+// Slope and intercept of the least squares line through `points`.
+fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if variance == 0.0 {
+        return None;
+    }
+    let slope = covariance / variance;
+    Some((slope, mean_y - slope * mean_x))
+}
+
+fn format_optional(value: Option<f64>, unit: &str) -> String {
+    match value {
+        Some(value) => format!("{}{}", format_decimal(value, 2), unit),
+        None => "-".to_string(),
+    }
+}
+
+// The growth of each country's cumulative confirmed cases.
+pub fn growth_table(countries: &[(String, Vec<(NaiveDate, i64)>)]) -> Table {
+    let mut table = Table::new(&["Country", "New cases", "Growth factor", "Doubling time"])
+        .align(1, Align::Right)
+        .align(2, Align::Right)
+        .align(3, Align::Right);
+    for (country, cumulative) in countries.iter() {
+        let new = match cumulative.as_slice() {
+            [.., yesterday, today] => format_change(today.1 - yesterday.1),
+            _ => "-".to_string(),
+        };
+        table.add_row(vec![
+            country.clone(),
+            new,
+            format_optional(growth_factor(cumulative), ""),
+            format_optional(doubling_time(cumulative, DOUBLING_DAYS), " d"),
+        ]);
+    }
+    table
+}
+
+// days of data a forecast is fitted to without --fit-days
+pub const FIT_DAYS: usize = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    // a * e^(rate * day)
+    Exponential,
+    // capacity / (1 + e^(-rate * (day - midpoint)))
+    Logistic,
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Model, String> {
+        match s {
+            "exponential" => Ok(Model::Exponential),
+            "logistic" => Ok(Model::Logistic),
+            _ => Err(format!(
+                "unknown model '{}', expected exponential or logistic",
+                s
+            )),
+        }
+    }
+}
+
+// A curve through cumulative counts, days counted from the first day of the
+// fitted data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+    pub model: Model,
+    pub start: NaiveDate,
+    pub rate: f64,
+    // the value at day 0 of an exponential, the final size of a logistic
+    pub scale: f64,
+    // the day of the steepest growth of a logistic
+    pub midpoint: f64,
+    // root mean square error on the fitted days
+    pub rmse: f64,
+}
+
+impl Fit {
+    pub fn value(&self, day: f64) -> f64 {
+        match self.model {
+            Model::Exponential => self.scale * (self.rate * day).exp(),
+            Model::Logistic => self.scale / (1.0 + (-self.rate * (day - self.midpoint)).exp()),
+        }
+    }
+
+    pub fn value_at(&self, date: &NaiveDate) -> f64 {
+        self.value((*date - self.start).num_days() as f64)
+    }
+
+    pub fn parameters(&self) -> Vec<(&'static str, f64)> {
+        match self.model {
+            Model::Exponential => vec![("a", self.scale), ("rate", self.rate)],
+            Model::Logistic => vec![
+                ("capacity", self.scale),
+                ("rate", self.rate),
+                ("midpoint", self.midpoint),
+            ],
+        }
+    }
+
+    fn with_rmse(mut self, data: &[(f64, f64)]) -> Fit {
+        let squares: f64 = data.iter().map(|(x, y)| (self.value(*x) - y).powi(2)).sum();
+        self.rmse = (squares / data.len() as f64).sqrt();
+        self
+    }
+}
+
+// Fits `model` to the cumulative counts. The exponential is a line through
+// the logarithms; the logistic one such line for each of a range of final
+// sizes above the last count, keeping the closest. `None` for fewer than
+// three days with cases.
+pub fn fit(cumulative: &[(NaiveDate, i64)], model: Model) -> Option<Fit> {
+    let start = cumulative.iter().find(|(_, v)| *v > 0)?.0;
+    let data: Vec<(f64, f64)> = cumulative
+        .iter()
+        .filter(|(_, v)| *v > 0)
+        .map(|(date, v)| ((*date - start).num_days() as f64, *v as f64))
+        .collect();
+    if data.len() < 3 {
+        return None;
+    }
+    let fit = Fit {
+        model,
+        start,
+        rate: 0.0,
+        scale: 0.0,
+        midpoint: 0.0,
+        rmse: 0.0,
+    };
+    match model {
+        Model::Exponential => {
+            let points: Vec<(f64, f64)> = data.iter().map(|(x, y)| (*x, y.ln())).collect();
+            let (rate, intercept) = linear_fit(&points)?;
+            Some(
+                Fit {
+                    rate,
+                    scale: intercept.exp(),
+                    ..fit
+                }
+                .with_rmse(&data),
+            )
+        }
+        Model::Logistic => {
+            let last = data[data.len() - 1].1;
+            // final sizes from just above the last count to 20 times it
+            (1..=200)
+                .filter_map(|step| {
+                    let capacity = last * (1.0 + step as f64 * step as f64 / 2000.0);
+                    let points: Vec<(f64, f64)> = data
+                        .iter()
+                        .map(|(x, y)| (*x, (capacity / y - 1.0).ln()))
+                        .collect();
+                    let (slope, intercept) = linear_fit(&points)?;
+                    let rate = -slope;
+                    if rate <= 0.0 {
+                        return None;
+                    }
+                    Some(
+                        Fit {
+                            rate,
+                            scale: capacity,
+                            midpoint: intercept / rate,
+                            ..fit.clone()
+                        }
+                        .with_rmse(&data),
+                    )
+                })
+                .min_by(|a, b| a.rmse.total_cmp(&b.rmse))
+        }
+    }
+}
+
+// The fitted curve continued for `days` days after the last one of
+// `cumulative`: date, cumulative and new cases.
+pub fn project(fit: &Fit, last: &NaiveDate, days: usize) -> Vec<(NaiveDate, f64, f64)> {
+    (1..=days as i64)
+        .map(|n| {
+            let date = *last + chrono::Duration::days(n);
+            let value = fit.value_at(&date);
+            let before = fit.value_at(&(date - chrono::Duration::days(1)));
+            (date, value, value - before)
+        })
+        .collect()
+}
+
+// The time between the symptoms of an infector and of the infected, as a
+// gamma distribution. The default of 4.7 ± 2.9 days is the estimate of
+// Nishiura et al. for the first months of 2020.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerialInterval {
+    pub mean: f64,
+    pub sd: f64,
+}
+
+pub const SERIAL_INTERVAL: SerialInterval = SerialInterval { mean: 4.7, sd: 2.9 };
+// days of the sliding window of the Rt estimate
+pub const RT_WINDOW: usize = 7;
+// longest serial interval considered
+const SERIAL_DAYS: usize = 30;
+// gamma prior of Rt with a mean of 5 and a standard deviation of 5
+const PRIOR_SHAPE: f64 = 1.0;
+const PRIOR_SCALE: f64 = 5.0;
+// the standard normal quantile of a 95% interval
+const Z_95: f64 = 1.959_964;
+
+impl SerialInterval {
+    // The share of the serial intervals of 1, 2, ... days.
+    fn weights(&self) -> Vec<f64> {
+        let shape = (self.mean / self.sd).powi(2);
+        let scale = self.sd.powi(2) / self.mean;
+        let density: Vec<f64> = (1..=SERIAL_DAYS)
+            .map(|s| (s as f64).powf(shape - 1.0) * (-(s as f64) / scale).exp())
+            .collect();
+        let total: f64 = density.iter().sum();
+        density.iter().map(|d| d / total).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rt {
+    pub date: NaiveDate,
+    pub mean: f64,
+    // the 95% credible interval
+    pub low: f64,
+    pub high: f64,
+}
+
+// Quantile of a gamma distribution by the Wilson-Hilferty approximation, good
+// enough for the shapes of a few cases on.
+fn gamma_quantile(shape: f64, scale: f64, z: f64) -> f64 {
+    let c = 1.0 / (9.0 * shape);
+    (shape * (1.0 - c + z * c.sqrt()).powi(3)).max(0.0) * scale
+}
+
+// The effective reproduction number of every day by the sliding window method
+// of Cori et al. (2013): the new cases of the last `window` days against the
+// infectiousness of the cases before them, with a gamma prior. Days without
+// cases in the window or before it are left out.
+pub fn rt(new_cases: &[(NaiveDate, f64)], serial: SerialInterval, window: usize) -> Vec<Rt> {
+    let weights = serial.weights();
+    let infectiousness: Vec<f64> = (0..new_cases.len())
+        .map(|t| {
+            weights
+                .iter()
+                .enumerate()
+                .take_while(|(s, _)| *s < t)
+                .map(|(s, w)| new_cases[t - s - 1].1 * w)
+                .sum()
+        })
+        .collect();
+    let window = window.max(1);
+    (window..new_cases.len())
+        .filter_map(|t| {
+            let from = t + 1 - window;
+            let cases: f64 = new_cases[from..=t].iter().map(|v| v.1).sum();
+            let lambda: f64 = infectiousness[from..=t].iter().sum();
+            if cases <= 0.0 || lambda <= 0.0 {
+                return None;
+            }
+            let shape = PRIOR_SHAPE + cases;
+            let scale = 1.0 / (1.0 / PRIOR_SCALE + lambda);
+            Some(Rt {
+                date: new_cases[t].0,
+                mean: shape * scale,
+                low: gamma_quantile(shape, scale, -Z_95),
+                high: gamma_quantile(shape, scale, Z_95),
+            })
+        })
+        .collect()
+}
+
+// days of each of the two averages a trend compares
+pub const TREND_DAYS: usize = 7;
+// changes within this many percent either way are flat
+const FLAT_PERCENT: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trend {
+    Rising,
+    Flat,
+    Falling,
+}
+
+impl Trend {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Trend::Rising => "Rising",
+            Trend::Flat => "Flat",
+            Trend::Falling => "Falling",
+        }
+    }
+}
+
+// The mean of the new cases of the last 7 days against that of the 7 days
+// before, from cumulative counts, with the change in percent. The change is
+// `None` after a week without new cases. `None` for fewer than 15 days.
+pub fn trend(cumulative: &[(NaiveDate, i64)]) -> Option<(Trend, f64, f64, Option<f64>)> {
+    if cumulative.len() < 2 * TREND_DAYS + 1 {
+        return None;
+    }
+    let value = |back: usize| cumulative[cumulative.len() - 1 - back].1;
+    let days = TREND_DAYS as f64;
+    let last = (value(0) - value(TREND_DAYS)).max(0) as f64 / days;
+    let previous = (value(TREND_DAYS) - value(2 * TREND_DAYS)).max(0) as f64 / days;
+    let change = if previous > 0.0 {
+        Some((last - previous) / previous * 100.0)
+    } else {
+        None
+    };
+    let trend = match change {
+        Some(c) if c > FLAT_PERCENT => Trend::Rising,
+        Some(c) if c < -FLAT_PERCENT => Trend::Falling,
+        Some(_) => Trend::Flat,
+        None if last > 0.0 => Trend::Rising,
+        None => Trend::Flat,
+    };
+    Some((trend, last, previous, change))
+}
+
+// Countries with their trend, rising ones first and the fastest first within
+// each trend.
+pub fn trend_table(countries: &[(String, Vec<(NaiveDate, i64)>)]) -> Table {
+    let mut trends: Vec<(&String, Trend, f64, f64, Option<f64>)> = countries
+        .iter()
+        .filter_map(|(country, cumulative)| {
+            let (trend, last, previous, change) = trend(cumulative)?;
+            Some((country, trend, last, previous, change))
+        })
+        .collect();
+    trends.sort_by(|a, b| {
+        a.1.cmp(&b.1)
+            .then_with(|| {
+                let change = |c: Option<f64>| c.unwrap_or(f64::INFINITY);
+                change(b.4).total_cmp(&change(a.4))
+            })
+            .then_with(|| a.0.cmp(b.0))
+    });
+    let mut table = Table::new(&[
+        "Trend",
+        "Country",
+        "Last 7 days",
+        "Previous 7 days",
+        "Change",
+    ])
+    .align(2, Align::Right)
+    .align(3, Align::Right)
+    .align(4, Align::Right);
+    for (country, trend, last, previous, change) in trends {
+        let change = match change {
+            Some(c) => format!(
+                "{}{}%",
+                if c < 0.0 { "-" } else { "+" },
+                format_decimal(c.abs(), 1)
+            ),
+            None => "-".to_string(),
+        };
+        table.add_row(vec![
+            i18n::label(trend.label()).to_string(),
+            country.clone(),
+            format_count(last.round() as u64),
+            format_count(previous.round() as u64),
+            change,
+        ]);
+    }
+    table
+}
+
+// longest case to death delay tried without --max-lag
+pub const MAX_LAG: usize = 28;
+// fewest days two series must overlap on to be correlated
+const MIN_OVERLAP: usize = 14;
+
+// Pearson's correlation coefficient, `None` when either side is constant.
+fn correlation(pairs: &[(f64, f64)]) -> Option<f64> {
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = pairs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let var_x: f64 = pairs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let var_y: f64 = pairs.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    if var_x == 0.0 || var_y == 0.0 {
+        None
+    } else {
+        Some(covariance / (var_x * var_y).sqrt())
+    }
+}
+
+// The correlation of the 7-day means of new cases and of new deaths `lag`
+// days later, for every lag from 0 to `max_lag` with enough days to compare.
+// Both from cumulative counts.
+pub fn lag_correlations(
+    confirmed: &[(NaiveDate, i64)],
+    deaths: &[(NaiveDate, i64)],
+    max_lag: usize,
+) -> Vec<(usize, f64)> {
+    let cases = waves::smooth(&waves::daily_new(confirmed), TREND_DAYS);
+    let deaths: std::collections::HashMap<NaiveDate, f64> =
+        waves::smooth(&waves::daily_new(deaths), TREND_DAYS)
+            .into_iter()
+            .collect();
+    (0..=max_lag)
+        .filter_map(|lag| {
+            let pairs: Vec<(f64, f64)> = cases
+                .iter()
+                .filter_map(|(date, new)| {
+                    let later = *date + chrono::Duration::days(lag as i64);
+                    Some((*new, *deaths.get(&later)?))
+                })
+                .collect();
+            if pairs.len() < MIN_OVERLAP {
+                return None;
+            }
+            Some((lag, correlation(&pairs)?))
+        })
+        .collect()
+}
+
+// The lag with the highest correlation, the typical days from a reported
+// case to a reported death.
+pub fn best_lag(correlations: &[(usize, f64)]) -> Option<(usize, f64)> {
+    correlations
+        .iter()
+        .copied()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+// robust z-scores beyond this are anomalies without --anomalies=<z>, the
+// cut-off of Iglewicz and Hoaglin
+pub const ANOMALY_Z: f64 = 3.5;
+// days before and after each day its baseline is taken from
+const ANOMALY_RADIUS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnomalyKind {
+    // far more than the days around, e.g. a backlog reported at once
+    Spike,
+    // far less than the days around
+    Drop,
+    // a negative daily increase, a correction of earlier days
+    Correction,
+}
+
+impl AnomalyKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnomalyKind::Spike => "Spike",
+            AnomalyKind::Drop => "Drop",
+            AnomalyKind::Correction => "Correction",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub date: NaiveDate,
+    pub change: i64,
+    // the median of the days around
+    pub median: f64,
+    pub z: f64,
+    pub kind: AnomalyKind,
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+// The daily increases of cumulative counts that stand out from the days
+// around them: a robust z-score, by the median and the median absolute
+// deviation of the 10 days before and after, beyond `threshold`, and every
+// negative increase.
+pub fn anomalies(cumulative: &[(NaiveDate, i64)], threshold: f64) -> Vec<Anomaly> {
+    let changes: Vec<(NaiveDate, i64)> = cumulative
+        .windows(2)
+        .map(|w| (w[1].0, w[1].1 - w[0].1))
+        .collect();
+    (0..changes.len())
+        .filter_map(|i| {
+            let (date, change) = changes[i];
+            let window = &changes
+                [i.saturating_sub(ANOMALY_RADIUS)..(i + ANOMALY_RADIUS + 1).min(changes.len())];
+            let mut values: Vec<f64> = window.iter().map(|(_, v)| *v as f64).collect();
+            let middle = median(&mut values);
+            let mut deviations: Vec<f64> = values.iter().map(|v| (v - middle).abs()).collect();
+            // with more than half of the days alike the mean absolute
+            // deviation stands in for the median one
+            let scale = match median(&mut deviations) {
+                mad if mad > 0.0 => mad / 0.6745,
+                _ => deviations.iter().sum::<f64>() / deviations.len() as f64 * 1.2533,
+            };
+            let z = if scale > 0.0 {
+                (change as f64 - middle) / scale
+            } else {
+                0.0
+            };
+            let kind = if change < 0 {
+                AnomalyKind::Correction
+            } else if z > threshold {
+                AnomalyKind::Spike
+            } else if z < -threshold {
+                AnomalyKind::Drop
+            } else {
+                return None;
+            };
+            Some(Anomaly {
+                date,
+                change,
+                median: middle,
+                z,
+                kind,
+            })
+        })
+        .collect()
+}
+
+// cumulative counts worth a date of their own
+pub const MILESTONES: &[i64] = &[100, 1_000, 10_000, 100_000, 1_000_000];
+
+// The first day the cumulative count reached each milestone, for those it
+// reached.
+pub fn milestones(cumulative: &[(NaiveDate, i64)]) -> Vec<(i64, NaiveDate)> {
+    MILESTONES
+        .iter()
+        .filter_map(|milestone| {
+            let (date, _) = cumulative.iter().find(|(_, v)| v >= milestone)?;
+            Some((*milestone, *date))
+        })
+        .collect()
+}
+
+// Of the cases resolved by each day, deaths plus recovered, the share that
+// died and the share that recovered, for the days with both counts and any
+// resolved cases.
+pub fn outcome_shares(
+    deaths: &[(NaiveDate, i64)],
+    recovered: &[(NaiveDate, i64)],
+) -> Vec<(NaiveDate, f64, f64)> {
+    let recovered: std::collections::HashMap<NaiveDate, i64> = recovered.iter().copied().collect();
+    deaths
+        .iter()
+        .filter_map(|(date, died)| {
+            let recovered = *recovered.get(date)?;
+            let resolved = died + recovered;
+            if resolved <= 0 {
+                return None;
+            }
+            Some((
+                *date,
+                *died as f64 / resolved as f64,
+                recovered as f64 / resolved as f64,
+            ))
+        })
+        .collect()
+}
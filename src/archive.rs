@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use tracing::{debug, warn};
+
+use crate::chain::{Chain, Layer};
+use crate::data::{GITHUB_API, UPSTREAM};
+use crate::exit::Failure;
+use crate::inflate;
+
+pub const BASE: &str = "https://codeload.github.com/CSSEGISandData/COVID-19/";
+const SNAPSHOT: &str = "zip/refs/heads/master";
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    offset: usize,
+    method: u16,
+    compressed: usize,
+    size: usize,
+    crc: u32,
+}
+
+// A zip file kept in memory as downloaded. Only the central directory is read
+// up front, a file is inflated when it is asked for, so a snapshot of the
+// whole repository costs little more than its download.
+pub struct Archive {
+    data: Vec<u8>,
+    entries: BTreeMap<String, Entry>,
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16, String> {
+    let bytes = data.get(pos..pos + 2).ok_or("zip file ends early")?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32, String> {
+    let bytes = data.get(pos..pos + 4).ok_or("zip file ends early")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl Archive {
+    // The snapshots of GitHub have all files below a "<repository>-<branch>/"
+    // directory, which is left out of the names.
+    pub fn parse(data: Vec<u8>) -> Result<Archive, String> {
+        // the end record is followed by a comment of up to 64 KiB
+        let end = (0..data.len().saturating_sub(21))
+            .rev()
+            .take(65536 + 22)
+            .find(|pos| u32_at(&data, *pos) == Ok(END_OF_CENTRAL_DIRECTORY))
+            .ok_or("not a zip file")?;
+        let count = u16_at(&data, end + 10)?;
+        let mut pos = u32_at(&data, end + 16)? as usize;
+        if count == 0xffff || pos == 0xffff_ffff {
+            return Err("ZIP64 archives are not supported".to_string());
+        }
+
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            if u32_at(&data, pos)? != CENTRAL_HEADER {
+                return Err("invalid zip central directory".to_string());
+            }
+            let name_length = u16_at(&data, pos + 28)? as usize;
+            let extra_length = u16_at(&data, pos + 30)? as usize;
+            let comment_length = u16_at(&data, pos + 32)? as usize;
+            let name = data
+                .get(pos + 46..pos + 46 + name_length)
+                .ok_or("zip file ends early")?;
+            let name = String::from_utf8_lossy(name);
+            let entry = Entry {
+                method: u16_at(&data, pos + 10)?,
+                crc: u32_at(&data, pos + 16)?,
+                compressed: u32_at(&data, pos + 20)? as usize,
+                size: u32_at(&data, pos + 24)? as usize,
+                offset: u32_at(&data, pos + 42)? as usize,
+            };
+            pos += 46 + name_length + extra_length + comment_length;
+            if name.ends_with('/') {
+                continue;
+            }
+            if entry.offset == 0xffff_ffff || entry.compressed == 0xffff_ffff {
+                return Err("ZIP64 archives are not supported".to_string());
+            }
+            let name = match name.split_once('/') {
+                Some((_, rest)) => rest.to_string(),
+                None => name.to_string(),
+            };
+            entries.insert(name, entry);
+        }
+        debug!(
+            files = entries.len(),
+            bytes = data.len(),
+            "read zip directory"
+        );
+        Ok(Archive { data, entries })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    // The file names directly inside `dir`.
+    pub fn list(&self, dir: &str) -> Vec<&str> {
+        let dir = format!("{}/", dir.trim_end_matches('/'));
+        self.entries
+            .keys()
+            .filter_map(|name| name.strip_prefix(dir.as_str()))
+            .filter(|name| !name.contains('/'))
+            .collect()
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or_else(|| format!("{} is not in the archive", name))?;
+        if u32_at(&self.data, entry.offset)? != LOCAL_HEADER {
+            return Err(format!("invalid zip header of {}", name));
+        }
+        // name and extra field may differ from the central directory
+        let start = entry.offset
+            + 30
+            + u16_at(&self.data, entry.offset + 26)? as usize
+            + u16_at(&self.data, entry.offset + 28)? as usize;
+        let stored = self
+            .data
+            .get(start..start + entry.compressed)
+            .ok_or("zip file ends early")?;
+        let data = match entry.method {
+            0 => stored.to_vec(),
+            8 => inflate::inflate(stored)?.0,
+            method => return Err(format!("unsupported compression {} of {}", method, name)),
+        };
+        if data.len() != entry.size || inflate::crc32(&data) != entry.crc {
+            return Err(format!("checksum mismatch of {}", name));
+        }
+        Ok(data)
+    }
+}
+
+// Downloads the snapshot of the upstream repository in one request.
+#[tokio::main]
+pub async fn download(chain: &Chain) -> Result<Archive, Box<dyn Error>> {
+    let data = chain.fetch_bytes(&format!("{}{}", BASE, SNAPSHOT)).await?;
+    Ok(Archive::parse(data).map_err(Failure::Parse)?)
+}
+
+// Answers upstream URLs and directory listings of the GitHub contents API
+// from a snapshot, like a checkout of the repository would.
+pub struct ArchiveLayer {
+    archive: Archive,
+}
+
+impl ArchiveLayer {
+    pub fn new(archive: Archive) -> ArchiveLayer {
+        ArchiveLayer { archive }
+    }
+}
+
+impl Layer for ArchiveLayer {
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        if let Some(path) = key.strip_prefix(UPSTREAM) {
+            if !self.archive.contains(path) {
+                return None;
+            }
+            return match self.archive.read(path) {
+                Ok(data) => Some(String::from_utf8_lossy(&data).into_owned()),
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            };
+        }
+        let entries: Vec<String> = self
+            .archive
+            .list(key.strip_prefix(GITHUB_API)?)
+            .iter()
+            .map(|name| format!("{{\"name\": \"{}\", \"type\": \"file\"}}", name))
+            .collect();
+        Some(format!("[{}]", entries.join(", ")))
+    }
+
+    fn put(&self, _key: &str, _body: &str) {}
+
+    fn covers(&self, key: &str) -> bool {
+        key.starts_with(UPSTREAM) || key.starts_with(GITHUB_API)
+    }
+}
@@ -0,0 +1,267 @@
+use std::fs::{self, FileTimes};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+use crate::chain::Layer;
+use crate::data::URL_DAILY_REPORT;
+use crate::table::{Align, Table};
+use crate::{deflate, inflate};
+
+// A downloaded file is used for this long without asking upstream again,
+// after that its validators make asking cheap.
+pub const MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+// The parts of the cache directory and what they hold.
+pub const PARTS: &[(&str, &str)] = &[
+    ("reports", "daily reports"),
+    ("files", "time series and other files"),
+    ("COVID-19", "checkout of --git"),
+];
+
+// The name of the file of `url`, the same for everything stored per URL.
+pub(crate) fn file_name(url: &str) -> String {
+    url.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Everything in the cache is stored gzipped. The CRC-32 and size in the gzip
+// trailer are checked on every read, a damaged file is removed and counts as
+// missing, so it is downloaded again.
+pub(crate) fn write(path: &Path, body: &str) -> io::Result<()> {
+    fs::write(path, deflate::gzip(body.as_bytes()))
+}
+
+pub(crate) fn read(path: &Path) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    touch(path);
+    match inflate::gunzip(&data).map(String::from_utf8) {
+        Ok(Ok(body)) => Some(body),
+        Ok(Err(_)) => remove_damaged(path, "not UTF-8"),
+        Err(e) => remove_damaged(path, &e),
+    }
+}
+
+// Marks a file as used for the eviction of the least recently used files.
+// The access time is set explicitly, file systems mounted with noatime or
+// relatime would not keep it current.
+fn touch(path: &Path) {
+    let touched = fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_times(FileTimes::new().set_accessed(SystemTime::now())));
+    if let Err(e) = touched {
+        debug!(path = %path.display(), "could not mark as used: {}", e);
+    }
+}
+
+pub(crate) fn remove_damaged(path: &Path, reason: &str) -> Option<String> {
+    warn!(
+        "{} is damaged ({}), fetching it again",
+        path.display(),
+        reason
+    );
+    let _ = fs::remove_file(path);
+    None
+}
+
+// Every downloaded file except the daily reports, which the report store
+// keeps, in `<dir>/<url>.body.gz` next to the validators of the file.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_age: Option<Duration>,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf) -> DiskCache {
+        DiskCache {
+            dir,
+            max_age: Some(MAX_AGE),
+        }
+    }
+
+    // Without network a file of any age is better than none.
+    pub fn offline(mut self, offline: bool) -> DiskCache {
+        if offline {
+            self.max_age = None;
+        }
+        self
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        if key.starts_with(URL_DAILY_REPORT) {
+            return None;
+        }
+        Some(self.dir.join(format!("{}.body.gz", file_name(key))))
+    }
+}
+
+impl Layer for DiskCache {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let path = self.path(key)?;
+        let age = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+        if self.max_age.map(|max| age > max).unwrap_or(false) {
+            debug!(url = key, age = age.as_secs(), "stale");
+            return None;
+        }
+        read(&path)
+    }
+
+    fn put(&self, key: &str, body: &str) {
+        let path = match self.path(key) {
+            Some(path) => path,
+            None => return,
+        };
+        if let Err(e) = fs::create_dir_all(&self.dir).and_then(|_| write(&path, body)) {
+            warn!("could not cache {}: {}", key, e);
+        }
+    }
+}
+
+// Files and bytes below `path`.
+fn usage(path: &Path) -> (u64, u64) {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return (0, 0),
+    };
+    let mut total = (0, 0);
+    for entry in entries.flatten() {
+        match entry.metadata() {
+            Ok(m) if m.is_dir() => {
+                let (files, bytes) = usage(&entry.path());
+                total = (total.0 + files, total.1 + bytes);
+            }
+            Ok(m) => total = (total.0 + 1, total.1 + m.len()),
+            Err(_) => {}
+        }
+    }
+    total
+}
+
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MB", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} kB", b as f64 / (1u64 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
+
+// Bounds for the parts of the cache that grow with every run, the checkout
+// of --git is left alone.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub max_size: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        self.max_size.is_none() && self.max_age.is_none()
+    }
+}
+
+const PRUNED_PARTS: &[&str] = &["reports", "files"];
+
+// The stored files below `path` with their size and when they were last used.
+fn entries(path: &Path, found: &mut Vec<(PathBuf, u64, SystemTime)>) {
+    for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+        let path = entry.path();
+        match entry.metadata() {
+            Ok(m) if m.is_dir() => entries(&path, found),
+            Ok(m) if path.extension().map(|e| e == "gz").unwrap_or(false) => {
+                let used = [m.accessed(), m.modified()]
+                    .iter()
+                    .flat_map(|t| t.as_ref().ok().copied())
+                    .max()
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                found.push((path, m.len(), used));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Removes the files of the cache in `dir` not used within the max age, then
+// the least recently used ones until the rest fits the max size. Returns the
+// number of files and bytes removed. A removed file is downloaded again the
+// next time it is needed.
+pub fn prune(dir: &Path, limits: &Limits) -> io::Result<(u64, u64)> {
+    let mut found = Vec::new();
+    for part in PRUNED_PARTS.iter() {
+        entries(&dir.join(part), &mut found);
+    }
+    found.sort_by_key(|(_, _, used)| *used);
+    let now = SystemTime::now();
+    let mut size: u64 = found.iter().map(|(_, bytes, _)| bytes).sum();
+    let mut removed = (0, 0);
+    for (path, bytes, used) in found.iter() {
+        let expired = limits
+            .max_age
+            .map(|max| {
+                now.duration_since(*used)
+                    .map(|age| age > max)
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false);
+        let too_large = limits.max_size.map(|max| size > max).unwrap_or(false);
+        if !expired && !too_large {
+            continue;
+        }
+        fs::remove_file(path)?;
+        // the validators of a body are useless without it
+        if let Some(name) = path.to_str().and_then(|p| p.strip_suffix(".body.gz")) {
+            let _ = fs::remove_file(format!("{}.meta", name));
+        }
+        debug!(path = %path.display(), expired, "evicted");
+        size -= bytes;
+        removed = (removed.0 + 1, removed.1 + bytes);
+    }
+    Ok(removed)
+}
+
+// What every part of the cache in `dir` holds.
+pub fn status(dir: &Path) -> Table {
+    let mut table = Table::new(&["Part", "Contents", "Files", "Size"])
+        .align(2, Align::Right)
+        .align(3, Align::Right);
+    for (part, contents) in PARTS.iter() {
+        let (files, bytes) = usage(&dir.join(part));
+        table.add_row(vec![
+            part.to_string(),
+            contents.to_string(),
+            files.to_string(),
+            format_size(bytes),
+        ]);
+    }
+    table
+}
+
+// Removes every part of the cache in `dir`, returns how many files were in
+// them. Anything else in `dir` is left alone.
+pub fn clear(dir: &Path) -> io::Result<u64> {
+    let mut removed = 0;
+    for (part, _) in PARTS.iter() {
+        let path = dir.join(part);
+        if path.exists() {
+            removed += usage(&path).0;
+            fs::remove_dir_all(&path)?;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,478 @@
+use chrono::{Timelike, Utc};
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::client::{CoronaClient, NotFound};
+use crate::data::UPSTREAM;
+use crate::exit::Failure;
+use crate::hooks::Hooks;
+use crate::mock;
+use crate::progress::Progress;
+use crate::provenance::Provenance;
+use crate::source::{DataSource, JhuSource};
+use crate::validators::{ValidatorStore, Validators};
+
+// A read-through lookup: every layer is asked in order (memory, store, cache),
+// the network is the last resort, and whatever a lower layer answers is written
+// back into the enabled layers above it.
+pub trait Layer: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn get(&self, key: &str) -> Option<String>;
+    fn put(&self, key: &str, body: &str);
+
+    // Whether the layer holds everything below `key`, so a miss means the
+    // file does not exist and the network is not asked.
+    fn covers(&self, _key: &str) -> bool {
+        false
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryLayer {
+    entries: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryLayer {
+    pub fn new() -> MemoryLayer {
+        MemoryLayer::default()
+    }
+}
+
+impl Layer for MemoryLayer {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, body: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), body.to_string());
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct Slot {
+    layer: Box<dyn Layer>,
+    enabled: bool,
+    counters: Counters,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayerMetrics {
+    pub name: &'static str,
+    pub enabled: bool,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+pub enum Download {
+    Body(Vec<u8>, Validators),
+    NotModified,
+}
+
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = Result<Download, Box<dyn Error>>> + 'a>>;
+
+// Where a file comes from when no layer has it: HTTP through the client of
+// the chain, unless replaced, e.g. with `mock::Fixtures` to run the pipeline
+// in tests without a server. A missing file is a `NotFound` error.
+pub trait Fetcher: Send + Sync {
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        validators: Option<&'a Validators>,
+        progress: &'a Progress,
+    ) -> FetchFuture<'a>;
+}
+
+pub struct Chain {
+    slots: Vec<Slot>,
+    network: bool,
+    requests: AtomicU64,
+    // URLs below a prefix are fetched from its bases, in order
+    mirrors: Vec<(String, Vec<String>)>,
+    discovery: bool,
+    source: Box<dyn DataSource>,
+    progress: Progress,
+    client: CoronaClient,
+    concurrency: usize,
+    deadline: Option<Instant>,
+    validators: Option<ValidatorStore>,
+    fetcher: Option<Box<dyn Fetcher>>,
+    // repository and commit of the checkout answering upstream URLs
+    checkout: Option<(String, String)>,
+    hooks: Hooks,
+}
+
+impl Default for Chain {
+    fn default() -> Chain {
+        Chain::new().with_layer(MemoryLayer::new())
+    }
+}
+
+impl Chain {
+    pub fn new() -> Chain {
+        Chain {
+            slots: Vec::new(),
+            network: true,
+            requests: AtomicU64::new(0),
+            mirrors: Vec::new(),
+            discovery: false,
+            source: Box::new(JhuSource::default()),
+            progress: Progress::default(),
+            client: CoronaClient::new(),
+            concurrency: DEFAULT_CONCURRENCY,
+            deadline: None,
+            validators: None,
+            fetcher: None,
+            checkout: None,
+            hooks: Hooks::default(),
+        }
+    }
+
+    // A chain answered from the sample data bundled with the crate, a few
+    // days of March 2020 for a handful of countries, to try every command
+    // without network access.
+    pub fn demo() -> Chain {
+        let mut chain = Chain::default();
+        chain.set_fetcher(Box::new(mock::Fixtures));
+        chain
+    }
+
+    pub fn set_progress(&mut self, enabled: bool) {
+        self.progress = Progress::new(enabled);
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub fn set_retries(&mut self, retries: u32) {
+        self.client.set_retries(retries);
+    }
+
+    pub fn add_header(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.client.add_header(prefix, name, value)
+    }
+
+    pub fn set_rate_limit(&mut self, rate: f64) {
+        self.client.set_rate_limit(rate);
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.client.set_timeout(timeout);
+    }
+
+    pub fn set_proxy(&mut self, url: &str) -> Result<(), Box<dyn Error>> {
+        self.client.set_proxy(url)
+    }
+
+    // All downloads of this chain, including retries, have to be done within
+    // `limit` from now.
+    pub fn set_deadline(&mut self, limit: Duration) {
+        self.deadline = Some(Instant::now() + limit);
+    }
+
+    // How many downloads may run at the same time when many files are needed.
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1);
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    // Replaces HTTP, the retry, timeout and proxy settings then no longer
+    // apply. Mirrors, rewrites and the deadline still do.
+    pub fn set_fetcher(&mut self, fetcher: Box<dyn Fetcher>) {
+        self.fetcher = Some(fetcher);
+    }
+
+    // Downloads are asked for with the validators of their last version in
+    // `store`, an unchanged file then costs a 304 instead of its body.
+    pub fn set_validators(&mut self, store: ValidatorStore) {
+        self.validators = Some(store);
+    }
+
+    // Fetches URLs starting with `from` from `to` instead, e.g. to point the
+    // upstream repository at a mock server. Layers still see the original URL.
+    pub fn with_rewrite(self, from: &str, to: &str) -> Chain {
+        self.with_mirrors(from, &[to.to_string()])
+    }
+
+    // Like `with_rewrite` with several bases that are tried one after the
+    // other until one answers, so the outage of a single host is survived.
+    // A 404 is final though, all mirrors carry the same files.
+    pub fn with_mirrors(mut self, from: &str, mirrors: &[String]) -> Chain {
+        if !mirrors.is_empty() {
+            self.mirrors.retain(|(prefix, _)| prefix != from);
+            self.mirrors.push((from.to_string(), mirrors.to_vec()));
+        }
+        self
+    }
+
+    // Whether the files that exist upstream should be listed (through the
+    // GitHub API) instead of probing every date.
+    pub fn set_discovery(&mut self, enabled: bool) {
+        self.discovery = enabled;
+    }
+
+    pub fn discovery(&self) -> bool {
+        self.discovery
+    }
+
+    // Where time series come from, the JHU repository by default.
+    pub fn set_source(&mut self, source: Box<dyn DataSource>) {
+        self.source = source;
+    }
+
+    pub fn source(&self) -> &dyn DataSource {
+        self.source.as_ref()
+    }
+
+    // Commands run on refreshes and alerts, and on every daily report loaded.
+    pub fn set_hooks(&mut self, hooks: Hooks) {
+        self.hooks = hooks;
+    }
+
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    // Upstream files come from a checkout of `repository` at `commit`.
+    pub fn set_checkout(&mut self, repository: &str, commit: &str) {
+        self.checkout = Some((repository.to_string(), commit.to_string()));
+    }
+
+    // Where the data of the source comes from as of now: the checkout, or the
+    // first base its URL is fetched from.
+    pub fn provenance(&self) -> Provenance {
+        let url = self.source.url();
+        let (url, commit) = match &self.checkout {
+            Some((repository, commit)) if url.starts_with(UPSTREAM) => {
+                (repository.clone(), Some(commit.clone()))
+            }
+            _ => match self
+                .mirrors
+                .iter()
+                .find(|(from, _)| url.starts_with(from.as_str()))
+            {
+                Some((from, mirrors)) => (format!("{}{}", mirrors[0], &url[from.len()..]), None),
+                None => (url, None),
+            },
+        };
+        let now = Utc::now().naive_utc();
+        Provenance {
+            source: self.source.name().to_string(),
+            url,
+            // whole seconds, as it is stored
+            fetched: now.with_nanosecond(0).unwrap_or(now),
+            commit,
+            metrics: self.source.resolved(),
+        }
+    }
+
+    // Layers are consulted in the order they were added.
+    pub fn with_layer<L: Layer + 'static>(mut self, layer: L) -> Chain {
+        self.slots.push(Slot {
+            layer: Box::new(layer),
+            enabled: true,
+            counters: Counters::default(),
+        });
+        self
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if name == "network" {
+            self.network = enabled;
+        }
+        for slot in self.slots.iter_mut().filter(|s| s.layer.name() == name) {
+            slot.enabled = enabled;
+        }
+    }
+
+    pub fn network_enabled(&self) -> bool {
+        self.network
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<String, Box<dyn Error>> {
+        for (index, slot) in self.slots.iter().enumerate() {
+            if !slot.enabled {
+                continue;
+            }
+            match slot.layer.get(url) {
+                Some(body) => {
+                    debug!(layer = slot.layer.name(), url, "hit");
+                    slot.counters.hits.fetch_add(1, Ordering::Relaxed);
+                    self.fill(index, url, &body);
+                    return Ok(body);
+                }
+                None => {
+                    slot.counters.misses.fetch_add(1, Ordering::Relaxed);
+                    if slot.layer.covers(url) {
+                        debug!(layer = slot.layer.name(), url, "not found");
+                        return Err(NotFound {
+                            url: url.to_string(),
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+
+        let started = Instant::now();
+        let stored = self.validators.as_ref().and_then(|store| store.load(url));
+        let (target, download) = self
+            .download_url(url, stored.as_ref().map(|(validators, _)| validators))
+            .await?;
+        let body = match (download, stored) {
+            (Download::NotModified, Some((_, body))) => {
+                info!(url = %target, ms = started.elapsed().as_millis() as u64, "not modified");
+                body
+            }
+            (Download::NotModified, None) => {
+                return Err(format!("{} answered 304 without a stored copy", target).into())
+            }
+            (Download::Body(bytes, validators), _) => {
+                // no copy of the (large) body unless it has to be repaired
+                let body = String::from_utf8(bytes)
+                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+                info!(url = %target, bytes = body.len(), ms = started.elapsed().as_millis() as u64, "fetched");
+                if let Some(store) = &self.validators {
+                    store.save(url, &validators, &body);
+                }
+                body
+            }
+        };
+        self.fill(self.slots.len(), url, &body);
+        Ok(body)
+    }
+
+    // A binary download, which the layers cannot hold: straight from the
+    // network, with mirrors, retries and the deadline.
+    pub async fn fetch_bytes(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let started = Instant::now();
+        let (target, bytes) = match self.download_url(url, None).await? {
+            (target, Download::Body(bytes, _)) => (target, bytes),
+            (target, Download::NotModified) => {
+                return Err(format!("{} answered 304 to an unconditional request", target).into())
+            }
+        };
+        info!(url = %target, bytes = bytes.len(), ms = started.elapsed().as_millis() as u64, "fetched");
+        Ok(bytes)
+    }
+
+    async fn download_url(
+        &self,
+        url: &str,
+        validators: Option<&Validators>,
+    ) -> Result<(String, Download), Box<dyn Error>> {
+        if !self.network {
+            return Err(Failure::Offline(url.to_string()).into());
+        }
+
+        let targets: Vec<String> = match self
+            .mirrors
+            .iter()
+            .find(|(from, _)| url.starts_with(from.as_str()))
+        {
+            Some((from, mirrors)) => mirrors
+                .iter()
+                .map(|base| format!("{}{}", base, &url[from.len()..]))
+                .collect(),
+            None => vec![url.to_string()],
+        };
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match tokio::time::timeout(remaining, self.download_any(&targets, validators)).await
+                {
+                    Ok(download) => download,
+                    Err(_) => Err(format!("deadline exceeded while fetching {}", url).into()),
+                }
+            }
+            None => self.download_any(&targets, validators).await,
+        }
+    }
+
+    async fn download_any(
+        &self,
+        targets: &[String],
+        validators: Option<&Validators>,
+    ) -> Result<(String, Download), Box<dyn Error>> {
+        let mut failure = None;
+        for target in targets.iter() {
+            match self.download(target, validators).await {
+                Ok(download) => return Ok((target.clone(), download)),
+                Err(e) if e.is::<NotFound>() => return Err(e),
+                Err(e) => {
+                    if targets.len() > 1 {
+                        warn!("mirror failed: {}", e);
+                    }
+                    failure = Some(e);
+                }
+            }
+        }
+        Err(failure.unwrap_or_else(|| "no mirror configured".into()))
+    }
+
+    async fn download(
+        &self,
+        target: &str,
+        validators: Option<&Validators>,
+    ) -> Result<Download, Box<dyn Error>> {
+        match &self.fetcher {
+            Some(fetcher) => fetcher.fetch(target, validators, &self.progress).await,
+            None => self.client.fetch(target, validators, &self.progress).await,
+        }
+    }
+
+    fn fill(&self, upto: usize, url: &str, body: &str) {
+        for slot in self.slots.iter().take(upto).filter(|s| s.enabled) {
+            slot.layer.put(url, body);
+        }
+    }
+
+    pub fn metrics(&self) -> Vec<LayerMetrics> {
+        let mut metrics: Vec<LayerMetrics> = self
+            .slots
+            .iter()
+            .map(|s| LayerMetrics {
+                name: s.layer.name(),
+                enabled: s.enabled,
+                hits: s.counters.hits.load(Ordering::Relaxed),
+                misses: s.counters.misses.load(Ordering::Relaxed),
+            })
+            .collect();
+        metrics.push(LayerMetrics {
+            name: "network",
+            enabled: self.network,
+            hits: self.requests.load(Ordering::Relaxed),
+            misses: 0,
+        });
+        metrics
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::Duration;
+
+use corona_stats::analytics;
+use corona_stats::data::Metric;
+use corona_stats::derived::{Aggregation, Transform};
+use corona_stats::filter::{Glob, LocationFilter, Regex};
+use corona_stats::groups;
+use corona_stats::output::Sort;
+use corona_stats::waves::Alignment;
+
+// Options that never take a value, the OPTIONS below are written as
+// `--name value` or `--name=value`, and any other option is an error.
+pub const FLAGS: &[&str] = &[
+    "no-color",
+    "desc",
+    "quiet",
+    "verbose",
+    "discover",
+    "mock",
+    "demo",
+    "no-cache",
+    "offline",
+    "keep-revisions",
+    "json-schema",
+    "legacy-time-series",
+    "git",
+    "archive",
+    "no-header",
+    "per-capita",
+    "peaks",
+    "anomalies",
+];
+
+// Flags that can be given a number as well, `--peaks 0.25` is `--peaks=0.25`
+// while `--peaks Italy` leaves the country alone.
+pub const NUMERIC_FLAGS: &[&str] = &["per-capita", "peaks", "anomalies"];
+
+pub const COMMANDS: &[&str] = &[
+    "summary",
+    "series",
+    "today",
+    "history",
+    "diff",
+    "search",
+    "provinces",
+    "us",
+    "compare-waves",
+    "waves",
+    "forecast",
+    "rt",
+    "trends",
+    "lag",
+    "anomalies",
+    "milestones",
+    "validate",
+    "reconcile",
+    "watch",
+    "alert",
+    "repl",
+    "top",
+    "completions",
+    "config",
+    "cache",
+    "ingest",
+    "query",
+    "info",
+    "revisions",
+    "export",
+    "report",
+    "badge",
+    "feed",
+    "site",
+];
+
+pub const OPTIONS: &[&str] = &[
+    "config",
+    "source",
+    "precedence",
+    "concurrency",
+    "retries",
+    "rate-limit",
+    "timeout",
+    "deadline",
+    "mirrors",
+    "proxy",
+    "git-rev",
+    "data-dir",
+    "input",
+    "db",
+    "format",
+    "layout",
+    "output",
+    "out",
+    "template",
+    "locale",
+    "smooth",
+    "resample",
+    "aggregate",
+    "lag",
+    "lang",
+    "from",
+    "to",
+    "country",
+    "country-regex",
+    "province-glob",
+    "state",
+    "county",
+    "sort",
+    "align-on",
+    "metric",
+    "by",
+    "above",
+    "below",
+    "interval",
+    "on-refresh",
+    "on-alert",
+    "transform-record",
+    "days",
+    "fit-days",
+    "model",
+    "window",
+    "si-mean",
+    "si-sd",
+    "max-lag",
+    "group",
+    "fill",
+    "tolerance",
+];
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: Option<String>,
+    pub positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+    short: Vec<char>,
+    // names of options that are neither in FLAGS nor in OPTIONS
+    unknown: Vec<String>,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Args {
+        let mut parsed = Args::default();
+        let mut iter = args.into_iter().peekable();
+
+        while let Some(arg) = iter.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                let option = name.split('=').next().unwrap_or_default();
+                if !FLAGS.contains(&option) && !OPTIONS.contains(&option) {
+                    parsed.unknown.push(option.to_string());
+                }
+                if let Some((name, value)) = name.split_once('=') {
+                    parsed.options.insert(name.to_string(), value.to_string());
+                } else if FLAGS.contains(&name) {
+                    let numeric = NUMERIC_FLAGS.contains(&name);
+                    match iter.next_if(|next| numeric && next.parse::<f64>().is_ok()) {
+                        Some(value) => {
+                            parsed.options.insert(name.to_string(), value);
+                        }
+                        None => {
+                            parsed.flags.insert(name.to_string());
+                        }
+                    }
+                } else {
+                    match iter.next_if(|next| !next.starts_with("--")) {
+                        Some(value) => {
+                            parsed.options.insert(name.to_string(), value);
+                        }
+                        None => {
+                            parsed.flags.insert(name.to_string());
+                        }
+                    }
+                }
+            } else if arg.len() > 1
+                && arg.starts_with('-')
+                && arg[1..].chars().all(|c| c.is_ascii_alphabetic())
+            {
+                // bundled short flags like -vv
+                parsed.short.extend(arg[1..].chars());
+            } else if parsed.command.is_none() {
+                parsed.command = Some(arg);
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        parsed
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    // The first option given that does not exist, e.g. a misspelled one.
+    pub fn unknown(&self) -> Option<&str> {
+        self.unknown.first().map(String::as_str)
+    }
+
+    // -v, -vv, ... or --verbose for a single level
+    pub fn verbosity(&self) -> usize {
+        self.short.iter().filter(|c| **c == 'v').count() + self.flag("verbose") as usize
+    }
+
+    pub fn quiet(&self) -> bool {
+        self.flag("quiet") || self.short.contains(&'q')
+    }
+
+    pub fn sort(&self) -> Result<Sort, String> {
+        Ok(Sort {
+            key: match self.value("sort") {
+                Some(key) => Some(key.parse()?),
+                None => None,
+            },
+            desc: self.flag("desc"),
+        })
+    }
+
+    pub fn align(&self) -> Result<Option<Alignment>, String> {
+        self.value("align-on").map(|a| a.parse()).transpose()
+    }
+
+    // --metric, with the days of --lag for the lagged CFR
+    pub fn metric(&self) -> Result<Metric, String> {
+        let metric: Metric = self.value("metric").unwrap_or("all").parse()?;
+        match self.value("lag") {
+            Some(lag) => lag
+                .parse()
+                .map(|lag| metric.with_lag(lag))
+                .map_err(|_| format!("invalid --lag '{}', expected a number of days", lag)),
+            None => Ok(metric),
+        }
+    }
+
+    // --peaks alone for peaks of at least a tenth of the highest, --peaks=0.25
+    // for a quarter
+    pub fn peaks(&self) -> Result<Option<f64>, String> {
+        match self.value("peaks") {
+            Some(share) => match share.parse::<f64>() {
+                Ok(share) if (0.0..=1.0).contains(&share) => Ok(Some(share)),
+                _ => Err(format!(
+                    "invalid --peaks '{}', expected a share between 0 and 1",
+                    share
+                )),
+            },
+            None if self.flag("peaks") => Ok(Some(0.1)),
+            None => Ok(None),
+        }
+    }
+
+    // --anomalies, optionally with the robust z-score an anomaly exceeds
+    pub fn anomalies(&self) -> Result<Option<f64>, String> {
+        match self.value("anomalies") {
+            Some(z) => match z.parse::<f64>() {
+                Ok(z) if z > 0.0 => Ok(Some(z)),
+                _ => Err(format!(
+                    "invalid --anomalies '{}', expected a positive z-score",
+                    z
+                )),
+            },
+            None if self.flag("anomalies") => Ok(Some(analytics::ANOMALY_Z)),
+            None => Ok(None),
+        }
+    }
+
+    // --days 14, --window 7 and the like, a positive number of days
+    pub fn days(&self, name: &str, default: usize) -> Result<usize, String> {
+        match self.value(name) {
+            Some(value) => match value.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!(
+                    "invalid --{} '{}', expected a number of days",
+                    name, value
+                )),
+            },
+            None => Ok(default),
+        }
+    }
+
+    // --smooth 7, the window of the rolling mean in days, --resample weekly
+    // with --aggregate, by default the sum of daily numbers and the last
+    // value of everything else, and --fill forward or linear for missing days
+    pub fn transform(&self, metric: Metric) -> Result<Transform, String> {
+        let smooth = match self.value("smooth").map(|s| s.parse::<usize>()) {
+            Some(Ok(window)) if window > 0 => Some(window),
+            Some(_) => {
+                return Err(format!(
+                    "invalid --smooth '{}', expected a number of days",
+                    self.value("smooth").unwrap_or("")
+                ))
+            }
+            None => None,
+        };
+        let aggregation = match self.value("aggregate") {
+            Some(aggregation) => aggregation.parse()?,
+            None => match metric {
+                Metric::NewConfirmed | Metric::NewDeaths => Aggregation::Sum,
+                _ => Aggregation::Last,
+            },
+        };
+        let resample = match self.value("resample") {
+            Some(period) => Some((period.parse()?, aggregation)),
+            None => None,
+        };
+        // --per-capita alone is per 100,000 people, --per-capita=1000000 per million
+        let per_capita = match self.value("per-capita") {
+            Some(per) => match per.parse::<u64>() {
+                Ok(per) if per > 0 => Some(per),
+                _ => {
+                    return Err(format!(
+                        "invalid --per-capita '{}', expected a number of people",
+                        per
+                    ))
+                }
+            },
+            None if self.flag("per-capita") => Some(100_000),
+            None => None,
+        };
+        let rate = matches!(
+            metric,
+            Metric::Incidence | Metric::Cfr | Metric::LaggedCfr(_) | Metric::Outcomes
+        );
+        if per_capita.is_some() && rate {
+            return Err(
+                "--per-capita does not apply to the incidence, the CFR and the outcomes, they are rates already"
+                    .to_string(),
+            );
+        }
+        let fill = match self.value("fill") {
+            Some(fill) => Some(fill.parse()?),
+            None => None,
+        };
+        Ok(Transform {
+            fill,
+            smooth,
+            resample,
+            per_capita,
+        })
+    }
+
+    pub fn filter(&self) -> Result<LocationFilter, String> {
+        Ok(LocationFilter {
+            country: None,
+            country_regex: match self.value("country-regex") {
+                Some(pattern) => Some(Regex::new(pattern)?),
+                None => None,
+            },
+            province_glob: match self.value("province-glob") {
+                Some(pattern) => Some(Glob::new(pattern)?),
+                None => None,
+            },
+            group: match self.value("group") {
+                Some(name) => {
+                    Some(
+                        groups::members(name).ok_or_else(|| match groups::names().as_slice() {
+                            [] => format!("unknown group '{}', none are configured", name),
+                            names => format!(
+                                "unknown group '{}', expected one of {}",
+                                name,
+                                names.join(", ")
+                            ),
+                        })?,
+                    )
+                }
+                None => None,
+            },
+        })
+    }
+
+    // fills in a value from the config file unless it was given on the command line
+    pub fn set_default(&mut self, name: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.options
+                .entry(name.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|v| v.as_str())
+    }
+
+    // --out is short for --output
+    pub fn output(&self) -> Option<&Path> {
+        self.value("output")
+            .or_else(|| self.value("out"))
+            .map(Path::new)
+    }
+
+    // colors only on a terminal, and either --no-color or a NO_COLOR that is
+    // set and not empty (https://no-color.org) turns them off there too
+    pub fn color(&self) -> bool {
+        !self.flag("no-color")
+            && std::env::var_os("NO_COLOR")
+                .filter(|v| !v.is_empty())
+                .is_none()
+            && self.value("format") != Some("tsv")
+            && std::io::stdout().is_terminal()
+    }
+}
+
+// "90s", "15m", "1h", "2d"; a plain number is taken as seconds
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(format!("invalid duration unit in '{}'", s)),
+    };
+    let seconds = number
+        .checked_mul(unit)
+        .ok_or_else(|| format!("invalid duration '{}'", s))?;
+    Ok(Duration::from_secs(seconds))
+}
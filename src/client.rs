@@ -0,0 +1,360 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
+
+use reqwest::header::{HeaderName, HeaderValue};
+
+use crate::chain::{Download, FetchFuture, Fetcher, DEFAULT_CONCURRENCY};
+use crate::inflate;
+use crate::progress::Progress;
+use crate::validators::Validators;
+
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_RATE_LIMIT: f64 = 10.0;
+const BACKOFF: Duration = Duration::from_millis(500);
+
+// The server answered 404: upstream has not published the file (yet), which
+// is an expected outcome for daily reports and not worth a retry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotFound {
+    pub url: String,
+}
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not published", self.url)
+    }
+}
+
+impl Error for NotFound {}
+
+// A token bucket shared by all clones of a client: `rate` requests per second
+// on average, at most `burst` of them at once after a quiet period. A request
+// takes its token up front and waits for it to arrive, so waiting requests
+// are let through in the order they asked.
+struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    // tokens left, negative when requests wait, and when they were counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> RateLimiter {
+        let burst = rate.max(1.0);
+        RateLimiter {
+            rate,
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(state.1).as_secs_f64() * self.rate;
+            *state = ((state.0 + refill).min(self.burst) - 1.0, now);
+            -state.0 / self.rate
+        };
+        if wait > 0.0 {
+            debug!(ms = (wait * 1000.0) as u64, "rate limited");
+            tokio::time::delay_for(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
+
+// HTTP access for every download. A single reqwest client keeps a pool of
+// open connections, so the many daily reports and time series files share
+// TCP connections and TLS sessions instead of a handshake per file.
+#[derive(Clone)]
+pub struct CoronaClient {
+    // why the client could not be set up, reported by every request rather
+    // than falling back to one without the proxy and timeouts
+    http: Result<reqwest::Client, String>,
+    retries: u32,
+    timeout: Duration,
+    proxy: Option<reqwest::Proxy>,
+    limiter: Option<Arc<RateLimiter>>,
+    // sent with the requests to URLs starting with the prefix
+    headers: Vec<(String, HeaderName, HeaderValue)>,
+}
+
+impl Default for CoronaClient {
+    fn default() -> CoronaClient {
+        CoronaClient::new()
+    }
+}
+
+impl CoronaClient {
+    pub fn new() -> CoronaClient {
+        // reqwest reads HTTP_PROXY and HTTPS_PROXY itself, ALL_PROXY is the
+        // fallback for both
+        let proxy = match (
+            env_proxy(&["HTTP_PROXY", "http_proxy", "HTTPS_PROXY", "https_proxy"]),
+            env_proxy(&["ALL_PROXY", "all_proxy"]),
+        ) {
+            (None, Some(all)) => reqwest::Proxy::all(&all)
+                .map_err(|e| warn!("ignoring ALL_PROXY '{}': {}", all, e))
+                .ok(),
+            _ => None,
+        };
+        CoronaClient {
+            http: build(DEFAULT_TIMEOUT, proxy.clone()),
+            retries: DEFAULT_RETRIES,
+            timeout: DEFAULT_TIMEOUT,
+            proxy,
+            limiter: Some(Arc::new(RateLimiter::new(DEFAULT_RATE_LIMIT))),
+            headers: Vec::new(),
+        }
+    }
+
+    // Limit for a single request from connecting until the last byte of the
+    // body, a hung connection fails (and is retried) instead of stalling.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+        self.http = build(self.timeout, self.proxy.clone());
+    }
+
+    // An http:// or https:// proxy for all requests, taking precedence over
+    // the environment. SOCKS needs reqwest's socks feature, which this build
+    // does not have.
+    pub fn set_proxy(&mut self, url: &str) -> Result<(), Box<dyn Error>> {
+        if url.starts_with("socks") {
+            return Err(format!("SOCKS proxies are not supported: {}", url).into());
+        }
+        let proxy =
+            reqwest::Proxy::all(url).map_err(|e| format!("invalid proxy '{}': {}", url, e))?;
+        self.http = Ok(build(self.timeout, Some(proxy.clone()))?);
+        self.proxy = Some(proxy);
+        Ok(())
+    }
+
+    // How often a request is repeated after a network error or a 5xx answer.
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    // A header for every request to a URL starting with `prefix` ("" for all
+    // of them), e.g. a token only the GitHub API should see. A User-Agent
+    // replaces the default one.
+    pub fn add_header(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("invalid value of header {}", name))?;
+        self.headers.push((prefix.to_string(), name, value));
+        Ok(())
+    }
+
+    // At most `rate` requests per second, retries included, so parallel
+    // downloads do not get the client blocked by the host. 0 is unlimited.
+    pub fn set_rate_limit(&mut self, rate: f64) {
+        self.limiter = Some(rate)
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Arc::new(RateLimiter::new(rate)));
+    }
+
+    // A successful response, `NotFound` for a 404 and the last failure once
+    // all retries are used up. Retries wait 0.5s, 1s, 2s, ... plus up to half
+    // of that again at random, so parallel downloads do not retry in lockstep.
+    pub async fn get(&self, url: &str) -> Result<reqwest::Response, Box<dyn Error>> {
+        self.get_if_changed(url, None).await
+    }
+
+    // Like `get`, but with the validators of a stored copy the server may
+    // answer 304 Not Modified, which is passed on as it is.
+    pub async fn get_if_changed(
+        &self,
+        url: &str,
+        validators: Option<&Validators>,
+    ) -> Result<reqwest::Response, Box<dyn Error>> {
+        let http = self.http.as_ref().map_err(|e| e.clone())?;
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+            // reqwest is built without its decoders, `Chain` inflates gzip itself
+            let mut request = http
+                .get(url)
+                .header(reqwest::header::ACCEPT_ENCODING, "gzip");
+            for (_, name, value) in self
+                .headers
+                .iter()
+                .filter(|(prefix, _, _)| url.starts_with(prefix.as_str()))
+            {
+                request = request.header(name.clone(), value.clone());
+            }
+            for (name, value) in validators.map(Validators::headers).unwrap_or_default() {
+                request = request.header(name, value);
+            }
+            let failure: Box<dyn Error> = match request.send().await {
+                Ok(response) if response.status() == reqwest::StatusCode::NOT_MODIFIED => {
+                    return Ok(response)
+                }
+                Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+                    return Err(NotFound {
+                        url: url.to_string(),
+                    }
+                    .into())
+                }
+                Ok(response) if response.status().is_server_error() => {
+                    format!("{} answered {}", url, response.status()).into()
+                }
+                Ok(response) if !response.status().is_success() => {
+                    return Err(format!("{} answered {}", url, response.status()).into())
+                }
+                Ok(response) => return Ok(response),
+                Err(e) => e.into(),
+            };
+            if attempt >= self.retries {
+                return Err(failure);
+            }
+            let delay = backoff(attempt, url);
+            warn!(
+                attempt = attempt + 1,
+                ms = delay.as_millis() as u64,
+                "retrying after {}",
+                failure
+            );
+            tokio::time::delay_for(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl CoronaClient {
+    // The body of `url`, inflated when the server sent it gzipped. Only gzip
+    // is asked for, which the crate decodes itself; the compressed chunks are
+    // inflated on a thread as they arrive instead of being collected first.
+    async fn download(
+        &self,
+        url: &str,
+        validators: Option<&Validators>,
+        progress: &Progress,
+    ) -> Result<Download, Box<dyn Error>> {
+        let mut response = self.get_if_changed(url, validators).await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(Download::NotModified);
+        }
+        let validators = Validators::from_headers(response.headers());
+        progress.file_started(url, response.content_length());
+        let gzip = response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .map(|e| e.as_bytes().eq_ignore_ascii_case(b"gzip"))
+            .unwrap_or(false);
+        if !gzip {
+            let mut bytes = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                bytes.extend_from_slice(&chunk);
+                progress.file_bytes(bytes.len() as u64);
+            }
+            return Ok(Download::Body(bytes, validators));
+        }
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+        let inflater = thread::spawn(move || inflate::gunzip_stream(Chunks::new(receiver)));
+        let mut compressed = 0;
+        while let Some(chunk) = response.chunk().await? {
+            compressed += chunk.len();
+            progress.file_bytes(compressed as u64);
+            // a broken stream ends the inflater early, its error is reported below
+            if sender.send(chunk.to_vec()).is_err() {
+                break;
+            }
+        }
+        drop(sender);
+        let bytes = inflater
+            .join()
+            .map_err(|_| format!("{}: inflating failed", url))?
+            .map_err(|e| format!("{}: {}", url, e))?;
+        debug!(url, compressed, bytes = bytes.len(), "inflated");
+        Ok(Download::Body(bytes, validators))
+    }
+}
+
+// The bytes of the chunks sent to `receiver`, until the sender is dropped.
+struct Chunks {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    at: usize,
+}
+
+impl Chunks {
+    fn new(receiver: mpsc::Receiver<Vec<u8>>) -> Chunks {
+        Chunks {
+            receiver,
+            chunk: Vec::new(),
+            at: 0,
+        }
+    }
+}
+
+impl Iterator for Chunks {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while self.at == self.chunk.len() {
+            self.chunk = self.receiver.recv().ok()?;
+            self.at = 0;
+        }
+        self.at += 1;
+        Some(self.chunk[self.at - 1])
+    }
+}
+
+impl Fetcher for CoronaClient {
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        validators: Option<&'a Validators>,
+        progress: &'a Progress,
+    ) -> FetchFuture<'a> {
+        Box::pin(self.download(url, validators, progress))
+    }
+}
+
+fn env_proxy(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+fn build(timeout: Duration, proxy: Option<reqwest::Proxy>) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy);
+    }
+    builder
+        // enough idle connections for all parallel downloads
+        .pool_max_idle_per_host(DEFAULT_CONCURRENCY)
+        .pool_idle_timeout(Duration::from_secs(90))
+        // the GitHub API refuses requests without one; ends up as
+        // "corona-stats/x.y.z"
+        .user_agent(concat!("corona-stats/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(timeout.min(Duration::from_secs(10)))
+        .timeout(timeout)
+        .build()
+        .map_err(|e| format!("could not set up the HTTP client: {}", e))
+}
+
+fn backoff(attempt: u32, url: &str) -> Duration {
+    let base = BACKOFF * 2u32.pow(attempt.min(10));
+    let mut hasher = DefaultHasher::new();
+    (url, attempt, SystemTime::now()).hash(&mut hasher);
+    let jitter = hasher.finish() % (base.as_millis() as u64 / 2 + 1);
+    base + Duration::from_millis(jitter)
+}
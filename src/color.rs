@@ -0,0 +1,74 @@
+use crate::table::format_decimal;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Red,
+    Green,
+    Dim,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Dim => "2",
+        }
+    }
+}
+
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", color.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+// Width of a string as shown in a terminal, ignoring ANSI escape sequences.
+pub fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut escape = false;
+    for c in s.chars() {
+        if escape {
+            if c == 'm' {
+                escape = false;
+            }
+        } else if c == '\x1b' {
+            escape = true;
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+// "▲ +3.2%" compared to the previous value. For confirmed cases and deaths a
+// rise is bad (red), for recoveries it is good (green).
+pub fn trend(current: u64, previous: Option<u64>, rising_is_good: bool, enabled: bool) -> String {
+    let previous = match previous {
+        Some(p) => p,
+        None => return paint("-", Color::Dim, enabled),
+    };
+    if current == previous {
+        return paint("= 0.0%", Color::Dim, enabled);
+    }
+    let change = if previous == 0 {
+        100.0
+    } else {
+        (current as f64 - previous as f64) / previous as f64 * 100.0
+    };
+    let rising = current > previous;
+    let text = format!(
+        "{} {}{}%",
+        if rising { "▲" } else { "▼" },
+        if change < 0.0 { "-" } else { "+" },
+        format_decimal(change.abs(), 1)
+    );
+    let color = if rising == rising_is_good {
+        Color::Green
+    } else {
+        Color::Red
+    };
+    paint(&text, color, enabled)
+}
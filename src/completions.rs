@@ -0,0 +1,270 @@
+use corona_stats::countries::COUNTRIES;
+
+use crate::cli::{COMMANDS, FLAGS, OPTIONS};
+
+// Commands and options whose argument is a country name.
+const TAKES_COUNTRY: &[&str] = &[
+    "--country",
+    "series",
+    "provinces",
+    "compare-waves",
+    "waves",
+    "forecast",
+    "rt",
+    "trends",
+    "lag",
+    "anomalies",
+    "milestones",
+    "validate",
+    "reconcile",
+];
+const SORT_KEYS: &[&str] = &["confirmed", "deaths", "recovered", "new_cases", "cfr"];
+const METRICS: &[&str] = &[
+    "confirmed",
+    "deaths",
+    "recovered",
+    "new_cases",
+    "new_deaths",
+    "new_recovered",
+    "active",
+    "vaccinations",
+    "outcomes",
+    "all",
+];
+const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+pub fn generate(shell: &str) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash()),
+        "zsh" => Ok(zsh()),
+        "fish" => Ok(fish()),
+        "powershell" => Ok(powershell()),
+        _ => Err(format!(
+            "unsupported shell '{}', expected one of {}",
+            shell,
+            SHELLS.join(", ")
+        )),
+    }
+}
+
+fn options() -> Vec<String> {
+    OPTIONS
+        .iter()
+        .chain(FLAGS.iter())
+        .map(|o| format!("--{}", o))
+        .collect()
+}
+
+// 'single quoted' for POSIX shells
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn bash() -> String {
+    format!(
+        r#"_corona_stats() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    local IFS=$'\n'
+    local countries=({countries})
+
+    case "$prev" in
+        {takes_country})
+            # not compgen -W, names like "Cote d'Ivoire" would break its quoting
+            local country
+            COMPREPLY=()
+            for country in "${{countries[@]}}"; do
+                [[ "$country" == "$cur"* ]] && COMPREPLY+=("${{country// /\\ }}")
+            done
+            return ;;
+        --sort)
+            COMPREPLY=($(compgen -W $'{sort}' -- "$cur"))
+            return ;;
+        --metric)
+            COMPREPLY=($(compgen -W $'{metrics}' -- "$cur"))
+            return ;;
+        --source)
+            COMPREPLY=($(compgen -W "jhu owid ecdc disease.sh mock" -- "$cur"))
+            return ;;
+        completions)
+            COMPREPLY=($(compgen -W $'{shells}' -- "$cur"))
+            return ;;
+    esac
+
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W $'{commands}' -- "$cur"))
+    else
+        COMPREPLY=($(compgen -W $'{options}' -- "$cur"))
+    fi
+}}
+complete -F _corona_stats corona-stats
+"#,
+        countries = COUNTRIES
+            .iter()
+            .map(|c| quote(c))
+            .collect::<Vec<_>>()
+            .join(" "),
+        takes_country = TAKES_COUNTRY.join("|"),
+        sort = SORT_KEYS.join("\\n"),
+        metrics = METRICS.join("\\n"),
+        shells = SHELLS.join("\\n"),
+        commands = COMMANDS.join("\\n"),
+        options = options().join("\\n"),
+    )
+}
+
+fn zsh() -> String {
+    format!(
+        r#"#compdef corona-stats
+
+_corona_stats() {{
+    local -a countries
+    countries=({countries})
+
+    case "$words[CURRENT-1]" in
+        {takes_country})
+            compadd -a countries
+            return ;;
+        --sort)
+            compadd {sort}
+            return ;;
+        --metric)
+            compadd {metrics}
+            return ;;
+        --source)
+            compadd jhu owid ecdc disease.sh mock
+            return ;;
+        completions)
+            compadd {shells}
+            return ;;
+    esac
+
+    if (( CURRENT == 2 )); then
+        compadd {commands}
+    else
+        compadd -- {options}
+    fi
+}}
+
+compdef _corona_stats corona-stats
+"#,
+        countries = COUNTRIES
+            .iter()
+            .map(|c| quote(c))
+            .collect::<Vec<_>>()
+            .join(" "),
+        takes_country = TAKES_COUNTRY.join("|"),
+        sort = SORT_KEYS.join(" "),
+        metrics = METRICS.join(" "),
+        shells = SHELLS.join(" "),
+        commands = COMMANDS.join(" "),
+        options = options().join(" "),
+    )
+}
+
+fn fish() -> String {
+    let mut out = String::from("function __corona_stats_countries\n    printf '%s\\n'");
+    for c in COUNTRIES.iter() {
+        out.push_str(" \\\n        ");
+        out.push_str(&quote(c));
+    }
+    out.push_str("\nend\n\n");
+
+    out.push_str("complete -c corona-stats -f\n");
+    out.push_str(&format!(
+        "complete -c corona-stats -n '__fish_use_subcommand' -a '{}'\n",
+        COMMANDS.join(" ")
+    ));
+    out.push_str(&format!(
+        "complete -c corona-stats -n '__fish_seen_subcommand_from {}' -a '(__corona_stats_countries)'\n",
+        TAKES_COUNTRY
+            .iter()
+            .filter(|c| !c.starts_with("--"))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ")
+    ));
+    out.push_str(&format!(
+        "complete -c corona-stats -n '__fish_seen_subcommand_from completions' -a '{}'\n",
+        SHELLS.join(" ")
+    ));
+    for option in OPTIONS.iter() {
+        let values = match *option {
+            "country" => "(__corona_stats_countries)".to_string(),
+            "sort" => SORT_KEYS.join(" "),
+            "metric" => METRICS.join(" "),
+            "source" => "jhu owid ecdc disease.sh mock".to_string(),
+            _ => String::new(),
+        };
+        if values.is_empty() {
+            out.push_str(&format!("complete -c corona-stats -l {} -r\n", option));
+        } else {
+            out.push_str(&format!(
+                "complete -c corona-stats -l {} -xa '{}'\n",
+                option, values
+            ));
+        }
+    }
+    for flag in FLAGS.iter() {
+        out.push_str(&format!("complete -c corona-stats -l {}\n", flag));
+    }
+    out
+}
+
+fn powershell() -> String {
+    // 'single quoted' for PowerShell doubles embedded quotes
+    let list = |items: &mut dyn Iterator<Item = String>| {
+        items
+            .map(|s| format!("'{}'", s.replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        r#"Register-ArgumentCompleter -Native -CommandName corona-stats -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $commands = @({commands})
+    $options = @({options})
+    $countries = @({countries})
+    $takesCountry = @({takes_country})
+
+    $elements = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})
+    if ($wordToComplete) {{
+        $position = $elements.Count - 1
+    }} else {{
+        $position = $elements.Count
+    }}
+    $previous = $elements[$position - 1]
+
+    if ($takesCountry -contains $previous) {{
+        $candidates = $countries
+    }} elseif ($previous -eq '--sort') {{
+        $candidates = @({sort})
+    }} elseif ($previous -eq '--metric') {{
+        $candidates = @({metrics})
+    }} elseif ($previous -eq '--source') {{
+        $candidates = @('jhu', 'owid', 'ecdc', 'disease.sh', 'mock')
+    }} elseif ($previous -eq 'completions') {{
+        $candidates = @({shells})
+    }} elseif ($position -eq 1) {{
+        $candidates = $commands
+    }} else {{
+        $candidates = $options
+    }}
+
+    $candidates | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+        $text = if ($_ -match '\s') {{ "'$_'" }} else {{ $_ }}
+        [System.Management.Automation.CompletionResult]::new($text, $_, 'ParameterValue', $_)
+    }}
+}}
+"#,
+        commands = list(&mut COMMANDS.iter().map(|c| c.to_string())),
+        options = list(&mut options().into_iter()),
+        countries = list(&mut COUNTRIES.iter().map(|c| c.to_string())),
+        takes_country = list(&mut TAKES_COUNTRY.iter().map(|c| c.to_string())),
+        sort = list(&mut SORT_KEYS.iter().map(|c| c.to_string())),
+        metrics = list(&mut METRICS.iter().map(|c| c.to_string())),
+        shells = list(&mut SHELLS.iter().map(|c| c.to_string())),
+    )
+}
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cache::Limits;
+use crate::export;
+use crate::hooks::Hooks;
+use crate::privacy::{Perturbation, PrivacyPolicy};
+
+// Defaults read from ~/.config/corona-stats/config.toml (or
+// $XDG_CONFIG_HOME/corona-stats/config.toml). Everything is optional, a
+// missing file is the same as an empty one and command line flags always win.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub countries: Vec<String>,
+    pub format: Option<String>,
+    pub cache_dir: Option<PathBuf>,
+    pub proxy: Option<String>,
+    // requests per second, 0 for no limit
+    pub rate_limit: Option<u32>,
+    // for the GitHub API only, which allows few requests without one
+    pub github_token: Option<String>,
+    // sent with every request, in file order
+    pub headers: Vec<(String, String)>,
+    pub sources: HashMap<String, String>,
+    // bases tried in order instead of the upstream repository
+    pub mirrors: Vec<String>,
+    pub cache_limits: Limits,
+    // named lists of countries, in file order
+    pub groups: Vec<(String, Vec<String>)>,
+    // noise for the small counts of some export formats
+    pub privacy: PrivacyPolicy,
+    // sources merged into one, like --source jhu,owid
+    pub source: Vec<String>,
+    // the order of sources per metric, like --precedence
+    pub precedence: Vec<(String, Vec<String>)>,
+    // commands run by watch, alert and on every daily report, like --on-refresh
+    pub hooks: Hooks,
+}
+
+pub const TEMPLATE: &str = r#"# corona-stats configuration, command line flags override everything here
+
+# countries used when a command is run without any
+# countries = ["Italy", "Austria"]
+
+# output format: table, csv or json
+# format = "table"
+
+# where downloaded files are kept
+# cache_dir = "~/.cache/corona-stats"
+
+# proxy for all downloads, e.g. "http://proxy.example.com:3128"
+# proxy = ""
+
+# requests per second to any host, 0 for no limit
+# rate_limit = 10
+
+# token for the GitHub API listing of --discover, $GITHUB_TOKEN takes precedence
+# github_token = ""
+
+# time series sources, several are merged and the first one that has a
+# location wins unless [precedence] says otherwise
+# source = ["jhu", "owid"]
+
+[cache]
+# reports and files are evicted, least recently used first, once the cache
+# grows beyond this many megabytes or they were not used for this many days
+# max_size_mb = 500
+# max_age_days = 90
+
+[sources]
+# base of the JHU CSSE repository the reports and time series are read from
+# upstream = "https://raw.githubusercontent.com/CSSEGISandData/COVID-19/master/"
+
+# bases tried one after the other when a host is down, replaces upstream
+# mirrors = [
+#     "https://raw.githubusercontent.com/CSSEGISandData/COVID-19/master/",
+#     "https://cdn.jsdelivr.net/gh/CSSEGISandData/COVID-19@master/",
+# ]
+
+# repository checked out into the cache directory with --git
+# git = "https://github.com/CSSEGISandData/COVID-19.git"
+
+[precedence]
+# the sources tried in order for a metric, when merging several
+# confirmed = ["jhu", "owid"]
+# vaccinations = ["owid"]
+# hospitalized = ["owid", "ecdc"]
+
+[hooks]
+# shell commands, each gets its payload on stdin and $CORONA_HOOK set to its name
+# after every refresh of watch, stdin holds the changed locations
+# on_refresh = "notify-send corona-stats"
+# when an alert triggers, stdin holds the message
+# on_alert = "mail -s 'corona-stats alert' me@example.com"
+# every daily report passes through as CSV, what the command prints is kept
+# transform_record = "grep -v ',Diamond Princess,'"
+
+[groups]
+# names usable wherever a country is, summing up their members
+# DACH = ["Germany", "Austria", "Switzerland"]
+# Benelux = ["Belgium", "Netherlands", "Luxembourg"]
+
+# noise for the small counts of an export format, e.g. a CSV that is
+# published while the JSON stays exact; analytics always use the exact counts
+# [privacy.csv]
+# counts below this are perturbed
+# threshold = 10
+# privacy budget of the Laplace noise, smaller is noisier, 0 for none
+# epsilon = 1.0
+# how much one person changes a count
+# sensitivity = 1
+# keys the noise so that the same export always gets the same, keep it secret
+# seed = 0
+
+[headers]
+# sent with every request, a User-Agent replaces "corona-stats/<version>"
+# User-Agent = "corona-stats (me@example.com)"
+"#;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("corona-stats").join("config.toml"))
+    }
+
+    // `cache_dir` from the file, otherwise ~/.cache/corona-stats (or
+    // $XDG_CACHE_HOME/corona-stats).
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.cache_dir {
+            return Some(dir.clone());
+        }
+        let base = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        Some(base.join("corona-stats"))
+    }
+
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(path)?;
+        Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (section, key, value) in parse_toml(text)? {
+            match (section.as_str(), key.as_str(), value) {
+                ("", "countries", Value::Array(items)) => {
+                    config.countries = strings("countries", items)?
+                }
+                ("sources", "mirrors", Value::Array(items)) => {
+                    config.mirrors = strings("sources.mirrors", items)?
+                }
+                ("", "source", Value::Array(items)) => config.source = strings("source", items)?,
+                ("precedence", metric, Value::Array(items)) => {
+                    let order = strings(&format!("precedence.{}", metric), items)?;
+                    config.precedence.push((metric.to_string(), order))
+                }
+                ("", "format", Value::String(s)) => config.format = Some(s),
+                ("", "cache_dir", Value::String(s)) => config.cache_dir = Some(expand_home(&s)),
+                ("", "proxy", Value::String(s)) => config.proxy = Some(s).filter(|s| !s.is_empty()),
+                ("", "rate_limit", Value::Integer(n)) => {
+                    config.rate_limit =
+                        Some(u32::try_from(n).map_err(|_| format!("invalid rate_limit {}", n))?)
+                }
+                ("", "github_token", Value::String(s)) => {
+                    config.github_token = Some(s).filter(|s| !s.is_empty())
+                }
+                ("cache", "max_size_mb", Value::Integer(n)) => {
+                    let mb = u64::try_from(n).map_err(|_| format!("invalid max_size_mb {}", n))?;
+                    config.cache_limits.max_size = Some(mb * 1024 * 1024)
+                }
+                ("cache", "max_age_days", Value::Integer(n)) => {
+                    let days =
+                        u64::try_from(n).map_err(|_| format!("invalid max_age_days {}", n))?;
+                    config.cache_limits.max_age = Some(Duration::from_secs(days * 24 * 60 * 60))
+                }
+                ("headers", name, Value::String(s)) => config.headers.push((name.to_string(), s)),
+                ("hooks", "on_refresh", Value::String(s)) => config.hooks.on_refresh = Some(s),
+                ("hooks", "on_alert", Value::String(s)) => config.hooks.on_alert = Some(s),
+                ("hooks", "transform_record", Value::String(s)) => {
+                    config.hooks.transform_record = Some(s)
+                }
+                (section, key, value) if section.starts_with("privacy.") => {
+                    let target = &section["privacy.".len()..];
+                    if !export::FORMATS.contains(&target) {
+                        return Err(format!(
+                            "unknown export format '{}' in [{}], expected {}",
+                            target,
+                            section,
+                            export::FORMATS.join(", ")
+                        ));
+                    }
+                    let mut perturbation = config
+                        .privacy
+                        .for_target(target)
+                        .cloned()
+                        .unwrap_or_default();
+                    set_perturbation(&mut perturbation, section, key, value)?;
+                    config.privacy =
+                        std::mem::take(&mut config.privacy).with_target(target, perturbation);
+                }
+                ("groups", name, Value::Array(items)) => {
+                    let members = strings(&format!("groups.{}", name), items)?;
+                    config.groups.push((name.to_string(), members))
+                }
+                ("sources", name, Value::String(s)) => {
+                    config.sources.insert(name.to_string(), s);
+                }
+                (_, _, value) => {
+                    let name = if section.is_empty() {
+                        key
+                    } else {
+                        format!("{}.{}", section, key)
+                    };
+                    return Err(format!("unexpected setting {} = {:?}", name, value));
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    // Writes the commented template, an existing file is never overwritten.
+    pub fn init(path: &Path) -> Result<(), Box<dyn Error>> {
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()).into());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, TEMPLATE)?;
+        Ok(())
+    }
+}
+
+fn strings(name: &str, items: Vec<Value>) -> Result<Vec<String>, String> {
+    items
+        .into_iter()
+        .map(|v| match v {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("{} has to be a list of strings", name)),
+        })
+        .collect()
+}
+
+fn set_perturbation(
+    perturbation: &mut Perturbation,
+    section: &str,
+    key: &str,
+    value: Value,
+) -> Result<(), String> {
+    let invalid = |value: &Value| format!("invalid {}.{} {:?}", section, key, value);
+    match (key, &value) {
+        ("threshold", Value::Integer(n)) => {
+            perturbation.threshold = u32::try_from(*n).map_err(|_| invalid(&value))?
+        }
+        ("seed", Value::Integer(n)) => {
+            perturbation.seed = u64::try_from(*n).map_err(|_| invalid(&value))?
+        }
+        ("epsilon", _) | ("sensitivity", _) => {
+            let number = match value {
+                Value::Integer(n) => n as f64,
+                Value::Float(f) => f,
+                _ => return Err(invalid(&value)),
+            };
+            if !(number >= 0.0 && number.is_finite()) {
+                return Err(invalid(&value));
+            }
+            if key == "epsilon" {
+                perturbation.epsilon = number;
+            } else {
+                perturbation.sensitivity = number;
+            }
+        }
+        _ => {
+            return Err(format!(
+                "unexpected setting {}.{} = {:?}",
+                section, key, value
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// The part of TOML a flat settings file needs: `[section]` headers and
+// `key = value` pairs with strings, integers, booleans and arrays of those,
+// which may span several lines. Returns (section, key, value) in file order.
+fn parse_toml(text: &str) -> Result<Vec<(String, String, Value)>, String> {
+    let mut entries = Vec::new();
+    let mut section = String::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            section = line
+                .strip_prefix('[')
+                .and_then(|l| l.strip_suffix(']'))
+                .map(|s| s.trim().to_string())
+                .ok_or_else(|| error("invalid section header".to_string()))?;
+            continue;
+        }
+        // arrays may continue on the next lines until the brackets balance
+        while depth(&line) > 0 {
+            match lines.next() {
+                Some((_, next)) => {
+                    line.push(' ');
+                    line.push_str(strip_comment(next).trim());
+                }
+                None => return Err(error("unterminated array".to_string())),
+            }
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected key = value".to_string()))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let chars: Vec<char> = value.trim().chars().collect();
+        let mut pos = 0;
+        let value = parse_value(&chars, &mut pos).map_err(error)?;
+        if pos != chars.len() {
+            return Err(error("unexpected text after value".to_string()));
+        }
+        entries.push((section.clone(), key, value));
+    }
+    Ok(entries)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => {
+                escaped = !escaped;
+                continue;
+            }
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+// open brackets outside of strings
+fn depth(line: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            '\\' if in_string => {
+                escaped = !escaped;
+                continue;
+            }
+            '"' if !escaped => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+fn skip_spaces(chars: &[char], pos: &mut usize) {
+    while chars.get(*pos).map(|c| c.is_whitespace()).unwrap_or(false) {
+        *pos += 1;
+    }
+}
+
+fn parse_value(chars: &[char], pos: &mut usize) -> Result<Value, String> {
+    skip_spaces(chars, pos);
+    match chars.get(*pos) {
+        Some('"') => {
+            *pos += 1;
+            let mut s = String::new();
+            loop {
+                let c = *chars.get(*pos).ok_or("unterminated string")?;
+                *pos += 1;
+                match c {
+                    '"' => break,
+                    '\\' => {
+                        let escaped = *chars.get(*pos).ok_or("unterminated string")?;
+                        *pos += 1;
+                        s.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            '"' | '\\' => escaped,
+                            _ => return Err(format!("unknown escape '\\{}'", escaped)),
+                        });
+                    }
+                    _ => s.push(c),
+                }
+            }
+            Ok(Value::String(s))
+        }
+        Some('[') => {
+            *pos += 1;
+            let mut items = Vec::new();
+            loop {
+                skip_spaces(chars, pos);
+                if chars.get(*pos) == Some(&']') {
+                    *pos += 1;
+                    break;
+                }
+                items.push(parse_value(chars, pos)?);
+                skip_spaces(chars, pos);
+                match chars.get(*pos) {
+                    Some(',') => *pos += 1,
+                    Some(']') => {}
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        Some(_) => {
+            let start = *pos;
+            while chars
+                .get(*pos)
+                .map(|c| !c.is_whitespace() && *c != ',' && *c != ']')
+                .unwrap_or(false)
+            {
+                *pos += 1;
+            }
+            let word: String = chars[start..*pos].iter().collect();
+            match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                _ => {
+                    let number = word.replace('_', "");
+                    number
+                        .parse()
+                        .map(Value::Integer)
+                        .or_else(|_| number.parse().map(Value::Float))
+                        .map_err(|_| format!("invalid value '{}'", word))
+                }
+            }
+        }
+        None => Err("missing value".to_string()),
+    }
+}
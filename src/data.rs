@@ -1,13 +1,44 @@
 use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike, Utc};
 use csv::{ReaderBuilder, StringRecord};
+use futures_util::stream::{self, StreamExt};
 use serde::de;
 use serde::Deserialize;
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{debug, warn};
 
-const URL_DAILY_REPORT: &str = "https://raw.githubusercontent.com/CSSEGISandData/COVID-19/master/csse_covid_19_data/csse_covid_19_daily_reports/";
-const URL_TIME_SERIES: &str = "https://raw.githubusercontent.com/CSSEGISandData/COVID-19/master/csse_covid_19_data/csse_covid_19_time_series/time_series_19-covid-";
+use crate::analytics::{self, Model, SerialInterval};
+use crate::chain::Chain;
+use crate::client::NotFound;
+use crate::color::trend;
+use crate::derived::{self, Transform};
+use crate::exit::{CoronaError, Failure};
+use crate::filter::LocationFilter;
+use crate::groups;
+use crate::i18n;
+use crate::json;
+use crate::locale;
+use crate::output::{ratio, Sort, SortKey, Sortable};
+use crate::population::{self, Populations};
+use crate::quality;
+use crate::search;
+use crate::source;
+use crate::svg;
+use crate::sync::ReportStore;
+use crate::table::{
+    self, format_change, format_count, format_decimal, format_signed, Align, Table,
+};
+use crate::waves::{self, Alignment};
+
+pub const UPSTREAM: &str = "https://raw.githubusercontent.com/CSSEGISandData/COVID-19/master/";
+pub const GITHUB_API: &str = "https://api.github.com/repos/CSSEGISandData/COVID-19/contents/";
+pub(crate) const URL_DAILY_REPORT: &str = "https://raw.githubusercontent.com/CSSEGISandData/COVID-19/master/csse_covid_19_data/csse_covid_19_daily_reports/";
+pub(crate) const URL_REPORT_LISTING: &str = "https://api.github.com/repos/CSSEGISandData/COVID-19/contents/csse_covid_19_data/csse_covid_19_daily_reports";
 
 //https://stackoverflow.com/questions/57614558/how-to-use-custom-serde-deserializer-for-chrono-timestamps
 struct NaiveDateTimeVisitor;
@@ -46,124 +77,1910 @@ struct CsvRecord {
     recovered: u32,
     lat: Option<f32>,
     long: Option<f32>,
+    active: Option<u32>,
+    incident_rate: Option<f64>,
+    case_fatality_ratio: Option<f64>,
+    combined_key: Option<String>,
 }
 
+// A row of a daily report. The optional fields at the end are only in the
+// reports since 2020-03-22: the incident rate is per 100,000 people and the
+// case fatality ratio a percentage, both as computed by JHU.
 #[derive(Debug, Deserialize, Clone)]
-struct Record {
-    province: String,
-    country: String,
+pub struct Record {
+    pub province: String,
+    pub country: String,
     #[serde(deserialize_with = "from_timestamp")]
-    updated: NaiveDateTime,
-    confirmed: u32,
-    deaths: u32,
-    recovered: u32,
-    lat: Option<f32>,
-    long: Option<f32>,
+    pub updated: NaiveDateTime,
+    pub confirmed: u32,
+    pub deaths: u32,
+    pub recovered: u32,
+    pub lat: Option<f32>,
+    pub long: Option<f32>,
+    #[serde(default)]
+    pub active: Option<u32>,
+    #[serde(default)]
+    pub incident_rate: Option<f64>,
+    #[serde(default)]
+    pub case_fatality_ratio: Option<f64>,
+    #[serde(default)]
+    pub combined_key: Option<String>,
+}
+
+impl Record {
+    // The Active column where the report has one, else confirmed minus deaths
+    // minus recovered. Reports that stopped counting recovered cases
+    // overestimate it.
+    pub fn active_cases(&self) -> u32 {
+        self.active.unwrap_or_else(|| {
+            self.confirmed
+                .saturating_sub(self.deaths)
+                .saturating_sub(self.recovered)
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
-struct TimeSeries {
-    province: String,
-    country: String,
-    lat: Option<f32>,
-    long: Option<f32>,
-    data: BTreeMap<String, i32>,
-    state: String,
+pub struct TimeSeries {
+    pub province: String,
+    pub country: String,
+    pub lat: Option<f32>,
+    pub long: Option<f32>,
+    pub data: BTreeMap<String, i32>,
+    pub state: String,
+    // the values are whole numbers, `scale` of them make one: 1 for counts,
+    // more for the ratios kept with their decimals
+    pub scale: u32,
+}
+
+impl TimeSeries {
+    // A value of the series as the number it stands for.
+    pub fn value(&self, value: i32) -> f64 {
+        f64::from(value) / f64::from(self.scale.max(1))
+    }
+
+    // A value as the exports write it, counts as whole numbers.
+    pub fn format_value(&self, value: i32) -> String {
+        match self.scale {
+            0 | 1 => value.to_string(),
+            _ => self.value(value).to_string(),
+        }
+    }
+}
+
+// Which of the time series to show; active cases are derived as confirmed
+// minus deaths minus recovered, new cases and deaths as the increase over
+// the day before, the incidence with the populations of the lookup table,
+// the case fatality ratio as deaths by confirmed cases of the same day or
+// `lag` days before, the outcomes as the shares of the resolved cases that
+// died and recovered, vaccinations always come from OWID.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Confirmed,
+    Deaths,
+    Recovered,
+    Active,
+    Vaccinations,
+    NewConfirmed,
+    NewDeaths,
+    Incidence,
+    Cfr,
+    LaggedCfr(usize),
+    Outcomes,
+    All,
+}
+
+impl std::str::FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Metric, String> {
+        match s {
+            "confirmed" => Ok(Metric::Confirmed),
+            "deaths" => Ok(Metric::Deaths),
+            "recovered" => Ok(Metric::Recovered),
+            "active" => Ok(Metric::Active),
+            "vaccinations" => Ok(Metric::Vaccinations),
+            "new_confirmed" | "new_cases" => Ok(Metric::NewConfirmed),
+            "new_deaths" => Ok(Metric::NewDeaths),
+            "incidence" => Ok(Metric::Incidence),
+            "cfr" => Ok(Metric::Cfr),
+            "cfr_lagged" => Ok(Metric::LaggedCfr(derived::DEFAULT_LAG)),
+            "outcomes" => Ok(Metric::Outcomes),
+            "all" => Ok(Metric::All),
+            _ => Err(format!(
+                "unknown metric '{}', expected confirmed, deaths, recovered, active, vaccinations, new_confirmed, new_deaths, incidence, cfr, cfr_lagged, outcomes or all",
+                s
+            )),
+        }
+    }
+}
+
+impl Metric {
+    pub fn includes(&self, state: &str) -> bool {
+        match self {
+            Metric::Confirmed => state == "Confirmed",
+            Metric::Deaths => state == "Deaths",
+            Metric::Recovered => state == "Recovered",
+            Metric::Active => state == "Active",
+            Metric::Vaccinations => state == "Vaccinations",
+            Metric::NewConfirmed => state == derived::NEW_CONFIRMED,
+            Metric::NewDeaths => state == derived::NEW_DEATHS,
+            Metric::Incidence => state == derived::INCIDENCE,
+            Metric::Cfr => state == derived::CFR,
+            Metric::LaggedCfr(_) => state == derived::CFR_LAGGED,
+            Metric::Outcomes => state == derived::DIED_SHARE || state == derived::RECOVERED_SHARE,
+            Metric::All => true,
+        }
+    }
+
+    // The lagged CFR with `lag` days, other metrics as they are.
+    pub fn with_lag(self, lag: usize) -> Metric {
+        match self {
+            Metric::LaggedCfr(_) => Metric::LaggedCfr(lag),
+            metric => metric,
+        }
+    }
+}
+
+// All rows of the daily report published for a date.
+pub type DatedReport = (NaiveDate, Vec<Record>);
+
+// The daily reports of a run that loaded, and the days that failed to
+// download or parse. A day that failed does not end the run.
+#[derive(Debug, Default)]
+pub struct FetchReport {
+    pub ok: Vec<DatedReport>,
+    pub failed: Vec<(NaiveDate, CoronaError)>,
+}
+
+impl FetchReport {
+    // The reports that loaded, with a warning per failed day. Only when every
+    // day failed is the first failure returned.
+    pub fn into_reports(mut self) -> Result<Vec<DatedReport>, CoronaError> {
+        if self.failed.is_empty() {
+            return Ok(self.ok);
+        }
+        if self.ok.is_empty() {
+            return Err(self.failed.remove(0).1);
+        }
+        warn!(
+            "{} daily reports could not be loaded, results are incomplete",
+            self.failed.len()
+        );
+        for (date, error) in self.failed.iter() {
+            warn!("{}: {}", date, error);
+        }
+        Ok(self.ok)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CountrySummary {
+    pub country: String,
+    pub confirmed: u64,
+    pub deaths: u64,
+    pub recovered: u64,
+    // the Active column, estimated for the reports before 2020-03-22
+    pub active: Option<u64>,
+    // only known when the previous report was loaded as well
+    pub new_confirmed: Option<i64>,
+    pub new_deaths: Option<i64>,
 }
 
-pub fn get_data() -> Result<(), Box<dyn Error>> {
-    let mut map = HashMap::new();
+impl Sortable for CountrySummary {
+    fn label(&self) -> &str {
+        &self.country
+    }
 
-    for elem in get_dates().iter() {
-        for e in get_data_from(elem)?.iter() {
-            let entry = map.entry(e.country.clone()).or_insert(Vec::new());
-            entry.push(e.clone());
+    fn sort_value(&self, key: SortKey) -> Option<f64> {
+        match key {
+            SortKey::Confirmed => Some(self.confirmed as f64),
+            SortKey::Deaths => Some(self.deaths as f64),
+            SortKey::Recovered => Some(self.recovered as f64),
+            SortKey::NewCases => self.new_confirmed.map(|v| v as f64),
+            SortKey::Cfr => ratio(self.deaths, self.confirmed),
         }
     }
-    println!("{:?}", map);
+}
+
+pub fn get_data(
+    chain: &Chain,
+    color: bool,
+    filter: &LocationFilter,
+    sort: &Sort,
+) -> Result<(), Box<dyn Error>> {
+    let mut latest = latest_reports(chain, 2)?.into_reports()?.into_iter();
+    let current = match latest.next() {
+        Some((_, rows)) => rows,
+        None => return Err(Failure::NoData("no daily report available".to_string()).into()),
+    };
+    let previous = latest.next().map(|(_, r)| r);
+    print_summary(&current, previous.as_deref(), color, filter, sort);
+    Ok(())
+}
+
+// Summary of a daily report read from a file or stdin instead of upstream.
+pub fn get_data_from_input(
+    input: &str,
+    color: bool,
+    filter: &LocationFilter,
+    sort: &Sort,
+) -> Result<(), Box<dyn Error>> {
+    let body = if input == "-" {
+        let mut body = String::new();
+        std::io::stdin().read_to_string(&mut body)?;
+        body
+    } else {
+        std::fs::read_to_string(input).map_err(|e| format!("{}: {}", input, e))?
+    };
+    if !body.lines().next().unwrap_or("").contains("Country") {
+        return Err(Failure::Parse(format!("{} is not a daily report", input)).into());
+    }
+    print_summary(&parse_report(&body)?, None, color, filter, sort);
     Ok(())
 }
 
-pub fn get_series() -> Result<(), Box<dyn Error>> {
-    for elem in get_time_series()?.iter() {
-        if elem.country == "Italy" {
-            println!("{:?}", elem.country);
-            for d in elem.data.iter() {
-                println!("{:?}", d);
+fn print_summary(
+    current: &[Record],
+    previous: Option<&[Record]>,
+    color: bool,
+    filter: &LocationFilter,
+    sort: &Sort,
+) {
+    let current = summarize(&select(current, filter));
+    let previous = previous.map(|r| summarize(&select(r, filter)));
+
+    let mut current = match &previous {
+        Some(previous) => with_previous(current, previous),
+        None => current,
+    };
+    sort.apply(&mut current);
+    print!("{}", summary_table(&current, previous.as_deref(), color));
+}
+
+// Fills in the new cases and deaths compared to the previous report.
+pub fn with_previous(
+    current: Vec<CountrySummary>,
+    previous: &[CountrySummary],
+) -> Vec<CountrySummary> {
+    let before: HashMap<&str, &CountrySummary> =
+        previous.iter().map(|s| (s.country.as_str(), s)).collect();
+    current
+        .into_iter()
+        .map(|mut s| {
+            let (confirmed, deaths) = match before.get(s.country.as_str()) {
+                Some(p) => (p.confirmed, p.deaths),
+                None => (0, 0),
+            };
+            s.new_confirmed = Some(s.confirmed as i64 - confirmed as i64);
+            s.new_deaths = Some(s.deaths as i64 - deaths as i64);
+            s
+        })
+        .collect()
+}
+
+pub fn select(records: &[Record], filter: &LocationFilter) -> Vec<Record> {
+    records
+        .iter()
+        .filter(|r| filter.matches(&r.country, &r.province))
+        .cloned()
+        .collect()
+}
+
+pub fn summarize(records: &[Record]) -> Vec<CountrySummary> {
+    let mut map: HashMap<&str, CountrySummary> = HashMap::new();
+    for r in records.iter() {
+        let entry = map.entry(&r.country).or_insert_with(|| CountrySummary {
+            country: r.country.clone(),
+            ..CountrySummary::default()
+        });
+        entry.confirmed += u64::from(r.confirmed);
+        entry.deaths += u64::from(r.deaths);
+        entry.recovered += u64::from(r.recovered);
+        *entry.active.get_or_insert(0) += u64::from(r.active_cases());
+    }
+    let mut summaries: Vec<CountrySummary> = map.into_values().collect();
+    summaries.sort_by(|a, b| {
+        b.confirmed
+            .cmp(&a.confirmed)
+            .then(a.country.cmp(&b.country))
+    });
+    summaries
+}
+
+fn plain_summary_table(summaries: &[CountrySummary]) -> Table {
+    let active = has_active(summaries);
+    let mut headers = vec!["Country", "Confirmed", "Deaths", "Recovered"];
+    if active {
+        headers.push("Active");
+    }
+    let mut table = Table::new(&headers);
+    for column in 1..headers.len() {
+        table = table.align(column, Align::Right);
+    }
+    for s in summaries.iter() {
+        let mut row = vec![
+            s.country.clone(),
+            format_count(s.confirmed),
+            format_count(s.deaths),
+            format_count(s.recovered),
+        ];
+        if active {
+            row.push(format_active(s.active));
+        }
+        table.add_row(row);
+    }
+    let mut footer = vec![
+        i18n::label("Total").to_string(),
+        format_count(summaries.iter().map(|s| s.confirmed).sum()),
+        format_count(summaries.iter().map(|s| s.deaths).sum()),
+        format_count(summaries.iter().map(|s| s.recovered).sum()),
+    ];
+    if active {
+        footer.push(format_count(
+            summaries.iter().filter_map(|s| s.active).sum(),
+        ));
+    }
+    table.set_footer(footer);
+    table
+}
+
+// The Active column is left out for summaries without active cases.
+fn has_active(summaries: &[CountrySummary]) -> bool {
+    summaries.iter().any(|s| s.active.is_some())
+}
+
+fn format_active(active: Option<u64>) -> String {
+    active.map(format_count).unwrap_or_else(|| "-".to_string())
+}
+
+pub fn summary_table(
+    summaries: &[CountrySummary],
+    previous: Option<&[CountrySummary]>,
+    color: bool,
+) -> Table {
+    if previous.is_none() {
+        return plain_summary_table(summaries);
+    }
+    let active = has_active(summaries);
+    let mut headers = vec!["Country", "Confirmed", "", "Deaths", "", "Recovered", ""];
+    if active {
+        headers.push("Active");
+    }
+    let mut table = Table::new(&headers);
+    for column in 1..headers.len() {
+        table = table.align(column, Align::Right);
+    }
+    let before: HashMap<&str, &CountrySummary> = previous
+        .unwrap_or(&[])
+        .iter()
+        .map(|s| (s.country.as_str(), s))
+        .collect();
+    let compare = |country: &str, value: fn(&CountrySummary) -> u64| {
+        previous.map(|_| before.get(country).map(|s| value(s)).unwrap_or(0))
+    };
+
+    let mut total = CountrySummary::default();
+    for s in summaries.iter() {
+        let mut row = vec![
+            s.country.clone(),
+            format_count(s.confirmed),
+            trend(
+                s.confirmed,
+                compare(&s.country, |s| s.confirmed),
+                false,
+                color,
+            ),
+            format_count(s.deaths),
+            trend(s.deaths, compare(&s.country, |s| s.deaths), false, color),
+            format_count(s.recovered),
+            trend(
+                s.recovered,
+                compare(&s.country, |s| s.recovered),
+                true,
+                color,
+            ),
+        ];
+        if active {
+            row.push(format_active(s.active));
+        }
+        table.add_row(row);
+        total.confirmed += s.confirmed;
+        total.deaths += s.deaths;
+        total.recovered += s.recovered;
+    }
+    let prev_total = previous.map(|p| {
+        p.iter().fold(CountrySummary::default(), |mut t, s| {
+            t.confirmed += s.confirmed;
+            t.deaths += s.deaths;
+            t.recovered += s.recovered;
+            t
+        })
+    });
+    let mut footer = vec![
+        i18n::label("Total").to_string(),
+        format_count(total.confirmed),
+        trend(
+            total.confirmed,
+            prev_total.as_ref().map(|t| t.confirmed),
+            false,
+            color,
+        ),
+        format_count(total.deaths),
+        trend(
+            total.deaths,
+            prev_total.as_ref().map(|t| t.deaths),
+            false,
+            color,
+        ),
+        format_count(total.recovered),
+        trend(
+            total.recovered,
+            prev_total.as_ref().map(|t| t.recovered),
+            true,
+            color,
+        ),
+    ];
+    if active {
+        footer.push(format_count(
+            summaries.iter().filter_map(|s| s.active).sum(),
+        ));
+    }
+    table.set_footer(footer);
+    table
+}
+
+// With an alignment the dates are replaced by days since the location crossed
+// the threshold, locations that never did are left out. `transform` smooths
+// or resamples the shown series. `peaks` adds the peaks of the daily increase
+// of the cumulative series with at least that relative prominence,
+// `anomalies` the daily increases beyond that robust z-score.
+pub fn get_series(
+    chain: &Chain,
+    filter: &LocationFilter,
+    metric: Metric,
+    align: Option<&Alignment>,
+    transform: &Transform,
+    peaks: Option<f64>,
+    anomalies: Option<f64>,
+) -> Result<(), Box<dyn Error>> {
+    let mut series = transform.fill_gaps(get_time_series(chain)?, filter);
+    if metric == Metric::Vaccinations {
+        let vaccinations = with_names_of(get_vaccinations(chain)?, &series);
+        series.extend(vaccinations);
+    }
+    if let Some(group) = &filter.country {
+        let totals = groups::aggregate(&series, group);
+        series.extend(totals);
+    }
+    let daily = derived::derive(&series, metric);
+    series.extend(daily);
+    if metric == Metric::Incidence {
+        let incidence = derived::incidence(&series, &get_populations(chain)?);
+        series.extend(incidence);
+    }
+    let active = if metric.includes("Active") {
+        active_series(&series)
+    } else {
+        Vec::new()
+    };
+    let populations = match transform.per_capita {
+        Some(_) => get_populations(chain)?,
+        None => Populations::default(),
+    };
+    for elem in series.iter().chain(active.iter()) {
+        if metric.includes(&elem.state) && filter.matches(&elem.country, &elem.province) {
+            let day_zero = match align {
+                Some(align) => {
+                    let counts = series.iter().find(|s| {
+                        s.country == elem.country
+                            && s.province == elem.province
+                            && s.state == align.state
+                    });
+                    match counts.and_then(|c| align.day_zero(&dated(&c.data))) {
+                        Some(date) => Some(date),
+                        None => {
+                            debug!(country = %elem.country, province = %elem.province, "threshold never reached");
+                            continue;
+                        }
+                    }
+                }
+                None => None,
+            };
+            let raw = elem;
+            let transformed;
+            let elem = if transform.is_identity() {
+                elem
+            } else {
+                match transform.apply(elem, &populations) {
+                    Some(t) => {
+                        transformed = t;
+                        &transformed
+                    }
+                    None => {
+                        debug!(country = %elem.country, province = %elem.province, "no population");
+                        continue;
+                    }
+                }
+            };
+            if elem.province.is_empty() {
+                println!("{:?} {}", elem.country, elem.state);
+            } else {
+                println!("{:?} {:?} {}", elem.country, elem.province, elem.state);
+            }
+            // the ratios with their decimals
+            match day_zero {
+                Some(day_zero) => {
+                    for (date, value) in elem.data.iter() {
+                        let day = match date.parse::<NaiveDate>() {
+                            Ok(date) => (date - day_zero).num_days(),
+                            Err(_) => continue,
+                        };
+                        if day >= 0 {
+                            println!("({:?}, {})", day, elem.format_value(*value));
+                        }
+                    }
+                }
+                None => {
+                    for (date, value) in elem.data.iter() {
+                        println!("({:?}, {})", date, elem.format_value(*value));
+                    }
+                }
+            }
+            // of the daily increase, which only the downloaded counts have
+            let cumulative = ["Confirmed", "Deaths", "Recovered"].contains(&raw.state.as_str());
+            if let (Some(min_prominence), true) = (peaks, cumulative) {
+                println!("{} peaks", raw.state);
+                for (date, value) in raw.peaks(min_prominence) {
+                    match day_zero {
+                        Some(day_zero) => {
+                            println!("{:?}", ((date - day_zero).num_days(), value.round() as i64))
+                        }
+                        None => println!("{:?}", (date.to_string(), value.round() as i64)),
+                    }
+                }
+            }
+            if let (Some(threshold), true) = (anomalies, cumulative) {
+                println!("{} anomalies", raw.state);
+                for anomaly in analytics::anomalies(&dated(&raw.data), threshold) {
+                    let z = (anomaly.z * 10.0).round() / 10.0;
+                    match day_zero {
+                        Some(day_zero) => println!(
+                            "{:?}",
+                            ((anomaly.date - day_zero).num_days(), anomaly.change, z)
+                        ),
+                        None => println!("{:?}", (anomaly.date.to_string(), anomaly.change, z)),
+                    }
+                }
             }
             //println!("{:?}", elem);
-        }    
+        }
+    }
+    Ok(())
+}
+
+pub fn diff(
+    chain: &Chain,
+    from: &NaiveDate,
+    to: &NaiveDate,
+    filter: &LocationFilter,
+    sort: &Sort,
+) -> Result<(), Box<dyn Error>> {
+    let report = |date: &NaiveDate| -> Result<Vec<Record>, Box<dyn Error>> {
+        let rows = get_data_from(chain, date)?;
+        if rows.is_empty() {
+            return Err(Failure::NoData(format!("no daily report for {}", date)).into());
+        }
+        Ok(rows)
+    };
+    let before = summarize(&select(&report(from)?, filter));
+    let after = summarize(&select(&report(to)?, filter));
+    let mut diffs = diff_summaries(&before, &after);
+    sort.apply(&mut diffs);
+    print!("{}", diff_table(&diffs));
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SummaryDiff {
+    pub country: String,
+    pub confirmed: i64,
+    pub deaths: i64,
+    pub recovered: i64,
+}
+
+impl Sortable for SummaryDiff {
+    fn label(&self) -> &str {
+        &self.country
+    }
+
+    fn sort_value(&self, key: SortKey) -> Option<f64> {
+        match key {
+            SortKey::Confirmed | SortKey::NewCases => Some(self.confirmed as f64),
+            SortKey::Deaths => Some(self.deaths as f64),
+            SortKey::Recovered => Some(self.recovered as f64),
+            SortKey::Cfr if self.confirmed > 0 => Some(self.deaths as f64 / self.confirmed as f64),
+            SortKey::Cfr => None,
+        }
+    }
+}
+
+// Per country change between two summaries, largest increase of confirmed first.
+pub fn diff_summaries(before: &[CountrySummary], after: &[CountrySummary]) -> Vec<SummaryDiff> {
+    let mut map: HashMap<&str, SummaryDiff> = HashMap::new();
+    for (sign, summaries) in [(-1, before), (1, after)].iter() {
+        for s in summaries.iter() {
+            let entry = map.entry(&s.country).or_insert_with(|| SummaryDiff {
+                country: s.country.clone(),
+                ..SummaryDiff::default()
+            });
+            entry.confirmed += sign * s.confirmed as i64;
+            entry.deaths += sign * s.deaths as i64;
+            entry.recovered += sign * s.recovered as i64;
+        }
+    }
+    let mut diffs: Vec<SummaryDiff> = map.into_values().collect();
+    diffs.sort_by(|a, b| {
+        b.confirmed
+            .cmp(&a.confirmed)
+            .then(a.country.cmp(&b.country))
+    });
+    diffs
+}
+
+pub fn diff_table(diffs: &[SummaryDiff]) -> Table {
+    let mut table = Table::new(&["Country", "Confirmed", "Deaths", "Recovered"])
+        .align(1, Align::Right)
+        .align(2, Align::Right)
+        .align(3, Align::Right);
+    for d in diffs.iter() {
+        table.add_row(vec![
+            d.country.clone(),
+            format_change(d.confirmed),
+            format_change(d.deaths),
+            format_change(d.recovered),
+        ]);
+    }
+    table.set_footer(vec![
+        i18n::label("Total").to_string(),
+        format_change(diffs.iter().map(|d| d.confirmed).sum()),
+        format_change(diffs.iter().map(|d| d.deaths).sum()),
+        format_change(diffs.iter().map(|d| d.recovered).sum()),
+    ]);
+    table
+}
+
+// What changed between the stored versions of the report of `date`, one
+// table per correction with the changed locations.
+pub fn revisions(store: &ReportStore, date: &NaiveDate) -> Result<(), Box<dyn Error>> {
+    let versions = store.revisions(date);
+    if versions.is_empty() {
+        return Err(Failure::NoData(format!("the report of {} is not stored", date)).into());
+    }
+    if versions.len() == 1 {
+        println!(
+            "no corrections of the report of {} seen, fetched at {}",
+            date, versions[0].0
+        );
+        return Ok(());
+    }
+    let mut previous: Option<(NaiveDateTime, Vec<Record>)> = None;
+    for (fetched, body) in versions.into_iter() {
+        let rows = parse_report(&body)?;
+        if let Some((before, old)) = previous {
+            println!("{} -> {}", before, fetched);
+            print!("{}", revision_table(&old, &rows));
+        }
+        previous = Some((fetched, rows));
+    }
+    Ok(())
+}
+
+fn revision_table(before: &[Record], after: &[Record]) -> Table {
+    let key = |r: &Record| (r.country.clone(), r.province.clone());
+    let mut changes: BTreeMap<(String, String), [i64; 3]> = BTreeMap::new();
+    for (sign, rows) in [(-1, before), (1, after)].iter() {
+        for r in rows.iter() {
+            let change = changes.entry(key(r)).or_default();
+            change[0] += sign * i64::from(r.confirmed);
+            change[1] += sign * i64::from(r.deaths);
+            change[2] += sign * i64::from(r.recovered);
+        }
+    }
+    let mut table = Table::new(&["Country", "Province", "Confirmed", "Deaths", "Recovered"])
+        .align(2, Align::Right)
+        .align(3, Align::Right)
+        .align(4, Align::Right);
+    for ((country, province), change) in changes.iter().filter(|(_, c)| **c != [0; 3]) {
+        table.add_row(vec![
+            country.clone(),
+            province.clone(),
+            format_change(change[0]),
+            format_change(change[1]),
+            format_change(change[2]),
+        ]);
+    }
+    table
+}
+
+// Polls the time series every `interval` and prints the latest values of every
+// location that changed since the previous poll. Failed polls are reported and
+// retried on the next tick.
+pub fn watch(
+    chain: &Chain,
+    country: Option<&str>,
+    interval: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let mut last: HashMap<(String, String, String), (String, i32)> = HashMap::new();
+    let mut first = true;
+    loop {
+        match get_time_series(chain) {
+            Ok(series) => {
+                let now = Utc::now().format("%Y-%m-%d %H:%M:%S");
+                let mut changes = Vec::new();
+                for elem in series.iter().filter(|s| {
+                    country
+                        .map(|c| groups::covers(c, &s.country))
+                        .unwrap_or(true)
+                }) {
+                    let latest = match elem.data.iter().next_back() {
+                        Some((date, value)) => (date.clone(), *value),
+                        None => continue,
+                    };
+                    let key = (
+                        elem.country.clone(),
+                        elem.province.clone(),
+                        elem.state.clone(),
+                    );
+                    let previous = last.insert(key, latest.clone());
+                    if first || previous.as_ref() == Some(&latest) {
+                        continue;
+                    }
+                    let change = previous
+                        .map(|p| i64::from(latest.1) - i64::from(p.1))
+                        .unwrap_or(0);
+                    changes.push(format!(
+                        "[{}] {}{} {} {} ({}) as of {}",
+                        now,
+                        elem.country,
+                        if elem.province.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" / {}", elem.province)
+                        },
+                        elem.state,
+                        format_count(latest.1.max(0) as u64),
+                        format_change(change),
+                        latest.0
+                    ));
+                }
+                for change in changes.iter() {
+                    println!("{}", change);
+                }
+                if !changes.is_empty() {
+                    if let Err(e) = chain.hooks().refresh(&changes) {
+                        warn!("{}", e);
+                    }
+                }
+                if first {
+                    eprintln!("[{}] watching {} locations", now, last.len());
+                    first = false;
+                }
+            }
+            Err(e) => warn!("poll failed: {}", e),
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+// The series and whether it is counted per day for the metric names of
+// `alert` and `badge`.
+fn single_metric(metric: &str) -> Result<(&'static str, bool), Failure> {
+    Ok(match metric {
+        "confirmed" => ("Confirmed", false),
+        "deaths" => ("Deaths", false),
+        "recovered" => ("Recovered", false),
+        "new_cases" => ("Confirmed", true),
+        "new_deaths" => ("Deaths", true),
+        "new_recovered" => ("Recovered", true),
+        _ => return Err(Failure::Parse(format!("unknown metric '{}'", metric))),
+    })
+}
+
+// Latest value of `metric` for a country, compared to the thresholds. Returns
+// the alert message when a threshold is crossed.
+pub fn check_alert(
+    chain: &Chain,
+    country: &str,
+    metric: &str,
+    above: Option<i64>,
+    below: Option<i64>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let (state, daily) = single_metric(metric)?;
+    let series = country_series(&get_time_series(chain)?, country, state);
+    let (date, value) = match series.as_slice() {
+        [.., before, last] if daily => (last.0, last.1 - before.1),
+        [.., last] if !daily => *last,
+        _ => return Err(Failure::NoData(format!("no data for {}", country)).into()),
+    };
+
+    let message = |relation: &str, threshold: i64| {
+        format!(
+            "{} {} on {} is {} ({} {})",
+            country, metric, date, value, relation, threshold
+        )
+    };
+    Ok(match (above, below) {
+        (Some(t), _) if value > t => Some(message("above", t)),
+        (_, Some(t)) if value < t => Some(message("below", t)),
+        _ => None,
+    })
+}
+
+// Colors of badges, for numbers getting worse, better or staying the same.
+const WORSE: &str = "#e05d44";
+const BETTER: &str = "#44cc11";
+const UNCHANGED: &str = "#9f9f9f";
+
+// A shields.io style badge with the latest value of `metric` for a country,
+// colored by the last week against the week before.
+pub fn badge(
+    chain: &Chain,
+    country: &str,
+    metric: &str,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let (state, daily) = single_metric(metric)?;
+    let series = country_series(&get_time_series(chain)?, country, state);
+    let at = |days: usize| {
+        series
+            .len()
+            .checked_sub(days + 1)
+            .map(|i| series[i].1)
+            .unwrap_or(0)
+    };
+    let value = match series.len() {
+        0 => return Err(Failure::NoData(format!("no data for {}", country)).into()),
+        _ if daily => at(0) - at(1),
+        _ => at(0),
+    };
+    let (week, before) = (at(0) - at(7), at(7) - at(14));
+    let rising_is_good = state == "Recovered";
+    let (arrow, color) = match week.cmp(&before) {
+        Ordering::Equal => ("", UNCHANGED),
+        order => {
+            let rising = order == Ordering::Greater;
+            (
+                if rising { " ▲" } else { " ▼" },
+                if rising == rising_is_good {
+                    BETTER
+                } else {
+                    WORSE
+                },
+            )
+        }
+    };
+    let svg = svg::badge(
+        &format!("{} {}", country, metric.replace('_', " ")),
+        &format!("{}{}", format_signed(value), arrow),
+        color,
+    );
+    match output {
+        Some(path) => std::fs::write(path, svg)?,
+        None => print!("{}", svg),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LocationSummary {
+    pub country: String,
+    pub province: String,
+    pub date: String,
+    pub confirmed: u64,
+    pub deaths: u64,
+    pub recovered: u64,
+}
+
+// Latest cumulative numbers of every location in the time series.
+pub fn latest_by_location(series: &[TimeSeries]) -> Vec<LocationSummary> {
+    let mut map: BTreeMap<(&str, &str), LocationSummary> = BTreeMap::new();
+    for elem in series.iter() {
+        let entry = map
+            .entry((&elem.country, &elem.province))
+            .or_insert_with(|| LocationSummary {
+                country: elem.country.clone(),
+                province: elem.province.clone(),
+                ..LocationSummary::default()
+            });
+        if let Some((date, value)) = elem.data.iter().next_back() {
+            let value = (*value).max(0) as u64;
+            match elem.state.as_str() {
+                "Confirmed" => entry.confirmed = value,
+                "Deaths" => entry.deaths = value,
+                "Recovered" => entry.recovered = value,
+                _ => {}
+            }
+            if *date > entry.date {
+                entry.date = date.clone();
+            }
+        }
+    }
+    map.into_values().collect()
+}
+
+impl Sortable for LocationSummary {
+    fn label(&self) -> &str {
+        if self.province.is_empty() {
+            &self.country
+        } else {
+            &self.province
+        }
+    }
+
+    fn sort_value(&self, key: SortKey) -> Option<f64> {
+        match key {
+            SortKey::Confirmed => Some(self.confirmed as f64),
+            SortKey::Deaths => Some(self.deaths as f64),
+            SortKey::Recovered => Some(self.recovered as f64),
+            SortKey::NewCases => None,
+            SortKey::Cfr => ratio(self.deaths, self.confirmed),
+        }
+    }
+}
+
+pub fn search(
+    chain: &Chain,
+    query: &str,
+    filter: &LocationFilter,
+    sort: &Sort,
+) -> Result<(), Box<dyn Error>> {
+    let locations = latest_by_location(&get_time_series(chain)?);
+    let mut matches: Vec<(u32, &LocationSummary)> = locations
+        .iter()
+        .filter(|l| filter.matches(&l.country, &l.province))
+        .filter_map(|l| {
+            let country = search::score(query, &l.country);
+            let province = search::score(query, &l.province);
+            country.max(province).map(|s| (s, l))
+        })
+        .collect();
+    if matches.is_empty() {
+        return Err(Failure::NoData(format!("nothing matches '{}'", query)).into());
+    }
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.confirmed.cmp(&a.1.confirmed)));
+    let mut matches: Vec<LocationSummary> = matches
+        .into_iter()
+        .take(20)
+        .map(|(_, l)| l.clone())
+        .collect();
+    sort.apply(&mut matches);
+
+    let mut table = Table::new(&[
+        "Country",
+        "Province",
+        "Confirmed",
+        "Deaths",
+        "Recovered",
+        "As of",
+    ])
+    .align(2, Align::Right)
+    .align(3, Align::Right)
+    .align(4, Align::Right);
+    for l in matches.iter() {
+        table.add_row(vec![
+            l.country.clone(),
+            l.province.clone(),
+            format_count(l.confirmed),
+            format_count(l.deaths),
+            format_count(l.recovered),
+            l.date.clone(),
+        ]);
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+pub fn provinces(
+    chain: &Chain,
+    country: &str,
+    filter: &LocationFilter,
+    sort: &Sort,
+) -> Result<(), Box<dyn Error>> {
+    let country = search::alias(country).unwrap_or(country);
+    let mut rows: Vec<LocationSummary> = latest_by_location(&get_time_series(chain)?)
+        .into_iter()
+        .filter(|l| l.country == country && filter.matches(&l.country, &l.province))
+        .collect();
+    if rows.is_empty() {
+        return Err(Failure::NoData(format!("no data for {}", country)).into());
+    }
+    rows.sort_by(|a, b| {
+        b.confirmed
+            .cmp(&a.confirmed)
+            .then(a.province.cmp(&b.province))
+    });
+    sort.apply(&mut rows);
+
+    let total: u64 = rows.iter().map(|l| l.confirmed).sum();
+    let mut table = Table::new(&[
+        "Province",
+        "Confirmed",
+        "Share",
+        "Deaths",
+        "Recovered",
+        "As of",
+    ])
+    .align(1, Align::Right)
+    .align(2, Align::Right)
+    .align(3, Align::Right)
+    .align(4, Align::Right);
+    for l in rows.iter() {
+        table.add_row(vec![
+            if l.province.is_empty() {
+                "(country level)".to_string()
+            } else {
+                l.province.clone()
+            },
+            format_count(l.confirmed),
+            format_share(l.confirmed, total),
+            format_count(l.deaths),
+            format_count(l.recovered),
+            l.date.clone(),
+        ]);
+    }
+    table.set_footer(vec![
+        country.to_string(),
+        format_count(total),
+        format_share(total, total),
+        format_count(rows.iter().map(|l| l.deaths).sum()),
+        format_count(rows.iter().map(|l| l.recovered).sum()),
+        String::new(),
+    ]);
+    print!("{}", table);
+    Ok(())
+}
+
+fn format_share(part: u64, total: u64) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{}%", format_decimal(part as f64 / total as f64 * 100.0, 1))
+}
+
+// Every non-empty daily report between `from` and `to`, oldest first.
+#[tokio::main]
+pub async fn get_history(
+    chain: &Chain,
+    from: &NaiveDate,
+    to: &NaiveDate,
+) -> Result<FetchReport, Box<dyn Error>> {
+    // nothing is published after the end of the time series, the years of
+    // missing reports since upstream stopped are not asked for on every run
+    let to = match last_published(chain).await? {
+        Some(last) => last.min(*to),
+        None => *to,
+    };
+    let dates: Vec<NaiveDate> = report_dates(chain)
+        .await?
+        .into_iter()
+        .filter(|d| d >= from && *d <= to)
+        .collect();
+    let mut report = FetchReport::default();
+    chain.progress().start(dates.len());
+    // up to `concurrency` downloads in flight, finishing in any order
+    let mut fetches = stream::iter(dates.iter())
+        .map(|date| async move { (*date, fetch_report(chain, date).await) })
+        .buffer_unordered(chain.concurrency());
+    while let Some((date, rows)) = fetches.next().await {
+        chain.progress().advance();
+        match rows {
+            Ok(rows) if rows.is_empty() => {}
+            Ok(rows) => report.ok.push((date, rows)),
+            Err(e) => report.failed.push((date, e.into())),
+        }
+    }
+    chain.progress().finish();
+    report.ok.sort_by_key(|r| r.0);
+    report.failed.sort_by_key(|f| f.0);
+    Ok(report)
+}
+
+// Global totals per day with the change to the previous report.
+pub fn history(
+    chain: &Chain,
+    from: &NaiveDate,
+    to: &NaiveDate,
+    filter: &LocationFilter,
+) -> Result<(), Box<dyn Error>> {
+    let mut table = Table::new(&[
+        "Date",
+        "Confirmed",
+        "New cases",
+        "Deaths",
+        "New deaths",
+        "Recovered",
+    ]);
+    for column in 1..6 {
+        table = table.align(column, Align::Right);
+    }
+    let mut previous: Option<CountrySummary> = None;
+    let reports = get_history(chain, from, to)?.into_reports()?;
+    if reports.is_empty() {
+        return Err(
+            Failure::NoData(format!("no daily reports between {} and {}", from, to)).into(),
+        );
     }
+    for (date, rows) in reports.iter() {
+        let total =
+            summarize(&select(rows, filter))
+                .iter()
+                .fold(CountrySummary::default(), |mut t, s| {
+                    t.confirmed += s.confirmed;
+                    t.deaths += s.deaths;
+                    t.recovered += s.recovered;
+                    t
+                });
+        let change = |value: fn(&CountrySummary) -> u64| match &previous {
+            Some(p) => format_change(value(&total) as i64 - value(p) as i64),
+            None => "-".to_string(),
+        };
+        table.add_row(vec![
+            locale::format_date(date),
+            format_count(total.confirmed),
+            change(|s| s.confirmed),
+            format_count(total.deaths),
+            change(|s| s.deaths),
+            format_count(total.recovered),
+        ]);
+        previous = Some(total);
+    }
+    print!("{}", table);
     Ok(())
 }
 
+// The last date of the JHU time series, no daily report is published after it.
+async fn last_published(chain: &Chain) -> Result<Option<NaiveDate>, Box<dyn Error>> {
+    // the daily reports are JHU's, whatever source the time series come from
+    Ok(source::jhu::time_series(chain, source::jhu::GLOBAL)
+        .await?
+        .iter()
+        .filter_map(|s| s.data.keys().next_back())
+        .max()
+        .and_then(|d| d.parse::<NaiveDate>().ok()))
+}
+
+// The `count` most recent non-empty daily reports, newest first. The last
+// date of the time series tells where to start looking, so the years of
+// missing reports after upstream stopped publishing are never requested.
 #[tokio::main]
-async fn get_data_from(date: &NaiveDate) -> Result<Vec<Record>, Box<dyn Error>> {
-    let mut data = Vec::new();
+pub async fn latest_reports(chain: &Chain, count: usize) -> Result<FetchReport, Box<dyn Error>> {
+    let last = last_published(chain).await?;
+
+    let mut report = FetchReport::default();
+    for date in report_dates(chain)
+        .await?
+        .iter()
+        .rev()
+        .skip_while(|d| last.map(|l| **d > l).unwrap_or(false))
+        .take(count + 7)
+    {
+        match fetch_report(chain, date).await {
+            Ok(rows) if rows.is_empty() => {}
+            Ok(rows) => {
+                report.ok.push((*date, rows));
+                if report.ok.len() == count {
+                    break;
+                }
+            }
+            Err(e) => report.failed.push((*date, e.into())),
+        }
+    }
+    Ok(report)
+}
+
+pub fn today(
+    chain: &Chain,
+    top: usize,
+    filter: &LocationFilter,
+    sort: &Sort,
+) -> Result<(), Box<dyn Error>> {
+    let reports = latest_reports(chain, 2)?.into_reports()?;
+    let (date, current) = match reports.first() {
+        Some((date, rows)) => (date, summarize(&select(rows, filter))),
+        None => return Err(Failure::NoData("no daily report available".to_string()).into()),
+    };
+    let previous = reports
+        .get(1)
+        .map(|(_, rows)| summarize(&select(rows, filter)))
+        .unwrap_or_default();
+    let mut current = with_previous(current, &previous);
+    sort.apply(&mut current);
+
+    let mut table = Table::new(&[
+        "Country",
+        "Confirmed",
+        "New cases",
+        "Deaths",
+        "New deaths",
+        "Recovered",
+    ]);
+    for column in 1..6 {
+        table = table.align(column, Align::Right);
+    }
+    let mut global = CountrySummary::default();
+    let (mut new_cases, mut new_deaths) = (0, 0);
+    for s in current.iter() {
+        global.confirmed += s.confirmed;
+        global.deaths += s.deaths;
+        global.recovered += s.recovered;
+        new_cases += s.new_confirmed.unwrap_or(0);
+        new_deaths += s.new_deaths.unwrap_or(0);
+    }
+    for s in current.iter().take(top) {
+        table.add_row(vec![
+            s.country.clone(),
+            format_count(s.confirmed),
+            format_change(s.new_confirmed.unwrap_or(0)),
+            format_count(s.deaths),
+            format_change(s.new_deaths.unwrap_or(0)),
+            format_count(s.recovered),
+        ]);
+    }
+    table.set_footer(vec![
+        i18n::label("World").to_string(),
+        format_count(global.confirmed),
+        format_change(new_cases),
+        format_count(global.deaths),
+        format_change(new_deaths),
+        format_count(global.recovered),
+    ]);
+
+    if !table::is_tsv() {
+        println!("{} {}", i18n::label("Report of"), locale::format_date(date));
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+pub fn compare_waves(
+    chain: &Chain,
+    countries: &[String],
+    align: Option<&Alignment>,
+) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let mut synchronized = Vec::new();
+    let mut growth = Vec::new();
+    for country in countries.iter() {
+        let confirmed = country_series(&series, country, "Confirmed");
+        if !confirmed.is_empty() {
+            growth.push((country.clone(), confirmed.clone()));
+        }
+        match align {
+            Some(align) => match align.day_zero(&country_series(&series, country, align.state)) {
+                Some(day_zero) => {
+                    synchronized.push(waves::synchronize_at(country, &confirmed, day_zero))
+                }
+                None => warn!("{} never reached the alignment threshold", country),
+            },
+            None => match waves::synchronize(country, &confirmed) {
+                Some(s) => synchronized.push(s),
+                None => warn!("no waves found for {}", country),
+            },
+        }
+    }
+    print!("{}", waves::comparison_table(&synchronized));
+    if !table::is_tsv() {
+        println!();
+        print!("{}", analytics::growth_table(&growth));
+        println!();
+        print!("{}", waves::overlay_chart(&synchronized, 72, 16));
+    }
+    Ok(())
+}
+
+// `corona-stats forecast Italy`: `model` fitted to the confirmed cases of the
+// last `fit_days` days, continued for `days` days.
+pub fn forecast(
+    chain: &Chain,
+    country: &str,
+    model: Model,
+    days: usize,
+    fit_days: usize,
+) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let confirmed = country_series(&series, country, "Confirmed");
+    let last = match confirmed.last() {
+        Some((date, _)) => *date,
+        None => return Err(Failure::NoData(format!("no data for {}", country)).into()),
+    };
+    let recent = &confirmed[confirmed.len().saturating_sub(fit_days)..];
+    let fit = analytics::fit(recent, model).ok_or_else(|| {
+        Failure::NoData(format!(
+            "too few cases in {} over the last {} days to fit a curve",
+            country, fit_days
+        ))
+    })?;
+    if !table::is_tsv() {
+        let parameters: Vec<String> = fit
+            .parameters()
+            .iter()
+            .map(|(name, value)| format!("{} = {:.4}", name, value))
+            .collect();
+        println!(
+            "{:?} fitted to {} days from {}: {}, RMSE {}",
+            model,
+            recent.len(),
+            locale::format_date(&fit.start),
+            parameters.join(", "),
+            format_decimal(fit.rmse, 1)
+        );
+    }
+    let mut table = Table::new(&["Date", "Confirmed", "New cases"])
+        .align(1, Align::Right)
+        .align(2, Align::Right);
+    for (date, value, new) in analytics::project(&fit, &last, days) {
+        table.add_row(vec![
+            locale::format_date(&date),
+            format_count(value.max(0.0).round() as u64),
+            format_change(new.round() as i64),
+        ]);
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+// `corona-stats rt Italy`: the reproduction number of the last `days` days
+// with its 95% credible interval.
+pub fn rt(
+    chain: &Chain,
+    country: &str,
+    serial: SerialInterval,
+    window: usize,
+    days: usize,
+) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let confirmed = country_series(&series, country, "Confirmed");
+    if confirmed.is_empty() {
+        return Err(Failure::NoData(format!("no data for {}", country)).into());
+    }
+    let new_cases = waves::daily_new(&confirmed);
+    let estimates = analytics::rt(&new_cases, serial, window);
+    if estimates.is_empty() {
+        return Err(Failure::NoData(format!("too few cases in {} to estimate Rt", country)).into());
+    }
+    let mut table = Table::new(&["Date", "New cases", "Rt", "95% low", "95% high"]);
+    for column in 1..5 {
+        table = table.align(column, Align::Right);
+    }
+    for r in estimates[estimates.len().saturating_sub(days)..].iter() {
+        let new = new_cases
+            .iter()
+            .find(|(date, _)| *date == r.date)
+            .map(|(_, v)| *v)
+            .unwrap_or(0.0);
+        table.add_row(vec![
+            locale::format_date(&r.date),
+            format_count(new as u64),
+            format_decimal(r.mean, 2),
+            format_decimal(r.low, 2),
+            format_decimal(r.high, 2),
+        ]);
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+// `corona-stats lag Italy`: the delay from new cases to new deaths with the
+// highest correlation, and the correlation of every delay tried.
+pub fn lag(chain: &Chain, country: &str, max_lag: usize) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let confirmed = country_series(&series, country, "Confirmed");
+    if confirmed.is_empty() {
+        return Err(Failure::NoData(format!("no data for {}", country)).into());
+    }
+    let deaths = country_series(&series, country, "Deaths");
+    let correlations = analytics::lag_correlations(&confirmed, &deaths, max_lag);
+    let (best, r) = analytics::best_lag(&correlations).ok_or_else(|| {
+        Failure::NoData(format!(
+            "too few cases and deaths in {} to correlate",
+            country
+        ))
+    })?;
+    if !table::is_tsv() {
+        println!(
+            "Deaths in {} follow new cases by {} days (r = {})",
+            country,
+            best,
+            format_decimal(r, 2)
+        );
+    }
+    let mut table = Table::new(&["Lag", "Correlation"])
+        .align(0, Align::Right)
+        .align(1, Align::Right);
+    for (lag, r) in correlations.iter() {
+        table.add_row(vec![lag.to_string(), format_decimal(*r, 2)]);
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+// `corona-stats validate [Italy]`: every day a cumulative count of a
+// location, or of the locations of `country`, was lower than the day before,
+// as a table, CSV or JSON.
+pub fn validate(chain: &Chain, country: Option<&str>, format: &str) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    if let Some(country) = country {
+        if !series.iter().any(|s| groups::covers(country, &s.country)) {
+            return Err(Failure::NoData(format!("no data for {}", country)).into());
+        }
+    }
+    let mut found: Vec<(&TimeSeries, quality::Decrease)> = Vec::new();
+    for s in series.iter().filter(|s| {
+        ["Confirmed", "Deaths", "Recovered"].contains(&s.state.as_str())
+            && country
+                .map(|c| groups::covers(c, &s.country))
+                .unwrap_or(true)
+    }) {
+        for decrease in quality::decreases(&dated(&s.data)) {
+            found.push((s, decrease));
+        }
+    }
+    found.sort_by(|a, b| {
+        (&a.0.country, &a.0.province, a.1.date).cmp(&(&b.0.country, &b.0.province, b.1.date))
+    });
+    match format {
+        "json" => {
+            let rows = found
+                .iter()
+                .map(|(s, d)| {
+                    json::Value::Object(vec![
+                        (
+                            "country".to_string(),
+                            json::Value::String(s.country.clone()),
+                        ),
+                        (
+                            "province".to_string(),
+                            json::Value::String(s.province.clone()),
+                        ),
+                        (
+                            "metric".to_string(),
+                            json::Value::String(s.state.to_lowercase()),
+                        ),
+                        ("date".to_string(), json::Value::String(d.date.to_string())),
+                        ("before".to_string(), json::Value::Number(d.before as f64)),
+                        ("after".to_string(), json::Value::Number(d.after as f64)),
+                        ("change".to_string(), json::Value::Number(d.change() as f64)),
+                    ])
+                })
+                .collect();
+            println!("{}", json::Value::Array(rows));
+        }
+        "csv" => {
+            let mut wtr = csv::Writer::from_writer(std::io::stdout());
+            wtr.write_record([
+                "country", "province", "metric", "date", "before", "after", "change",
+            ])?;
+            for (s, d) in found.iter() {
+                wtr.write_record([
+                    s.country.clone(),
+                    s.province.clone(),
+                    s.state.to_lowercase(),
+                    d.date.to_string(),
+                    d.before.to_string(),
+                    d.after.to_string(),
+                    d.change().to_string(),
+                ])?;
+            }
+            wtr.flush()?;
+        }
+        "table" | "tsv" => {
+            if found.is_empty() && !table::is_tsv() {
+                eprintln!("No cumulative count ever decreased");
+                return Ok(());
+            }
+            let mut table = Table::new(&[
+                "Country", "Province", "Series", "Date", "Before", "After", "Change",
+            ]);
+            for column in 4..7 {
+                table = table.align(column, Align::Right);
+            }
+            for (s, d) in found.iter() {
+                table.add_row(vec![
+                    s.country.clone(),
+                    s.province.clone(),
+                    i18n::label(&s.state).to_string(),
+                    locale::format_date(&d.date),
+                    format_count(d.before.max(0) as u64),
+                    format_count(d.after.max(0) as u64),
+                    format_change(d.change()),
+                ]);
+            }
+            print!("{}", table);
+        }
+        _ => {
+            return Err(Failure::Parse(format!(
+                "unknown format '{}', expected table, tsv, csv or json",
+                format
+            ))
+            .into())
+        }
+    }
+    Ok(())
+}
+
+// reads one count of a country's summary
+type Count = fn(&CountrySummary) -> u64;
+
+#[tokio::main]
+async fn get_jhu_time_series(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    source::jhu::time_series(chain, source::jhu::GLOBAL).await
+}
+
+// `corona-stats reconcile Italy`: the daily reports of `country` between
+// `from` and `to` joined with its time series by date, and every count that
+// differs by more than `tolerance`. Both are JHU's, whatever --source says;
+// days only one of them has are left out.
+pub fn reconcile(
+    chain: &Chain,
+    country: &str,
+    from: &NaiveDate,
+    to: &NaiveDate,
+    tolerance: quality::Tolerance,
+) -> Result<(), Box<dyn Error>> {
+    let series = get_jhu_time_series(chain)?;
+    let reports = get_history(chain, from, to)?.into_reports()?;
+    let states: [(&str, Count); 3] = [
+        ("Confirmed", |s| s.confirmed),
+        ("Deaths", |s| s.deaths),
+        ("Recovered", |s| s.recovered),
+    ];
+    let series: Vec<BTreeMap<NaiveDate, i64>> = states
+        .iter()
+        .map(|(state, _)| {
+            country_series(&series, country, state)
+                .into_iter()
+                .collect()
+        })
+        .collect();
+    if series[0].is_empty() {
+        return Err(Failure::NoData(format!("no time series for {}", country)).into());
+    }
+    let mut table = Table::new(&[
+        "Date",
+        "Series",
+        "Daily report",
+        "Time series",
+        "Difference",
+    ]);
+    for column in 2..5 {
+        table = table.align(column, Align::Right);
+    }
+    let mut days = 0;
+    for (date, rows) in reports.iter() {
+        let summaries: Vec<CountrySummary> = summarize(rows)
+            .into_iter()
+            .filter(|s| groups::covers(country, &s.country))
+            .collect();
+        if summaries.is_empty() {
+            continue;
+        }
+        let mut compared = false;
+        for ((state, count), values) in states.iter().zip(series.iter()) {
+            let reported: i64 = summaries.iter().map(|s| count(s) as i64).sum();
+            let value = match values.get(date) {
+                Some(value) => *value,
+                None => continue,
+            };
+            compared = true;
+            if tolerance.exceeded(reported, value) {
+                table.add_row(vec![
+                    locale::format_date(date),
+                    i18n::label(state).to_string(),
+                    format_signed(reported),
+                    format_signed(value),
+                    format_change(value - reported),
+                ]);
+            }
+        }
+        if compared {
+            days += 1;
+        }
+    }
+    if days == 0 {
+        return Err(Failure::NoData(format!(
+            "no days with a daily report and time series for {} between {} and {}",
+            country, from, to
+        ))
+        .into());
+    }
+    if table.is_empty() && !table::is_tsv() {
+        eprintln!(
+            "Daily reports and time series of {} agree on all {} days",
+            country, days
+        );
+        return Ok(());
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+// `corona-stats milestones Italy`: when the confirmed cases reached 100,
+// 1,000 and so on, and the days from one milestone to the next.
+pub fn milestones(chain: &Chain, country: &str) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let confirmed = country_series(&series, country, "Confirmed");
+    if confirmed.is_empty() {
+        return Err(Failure::NoData(format!("no data for {}", country)).into());
+    }
+    let milestones = analytics::milestones(&confirmed);
+    if milestones.is_empty() {
+        return Err(Failure::NoData(format!(
+            "{} never reached {} cases",
+            country,
+            format_count(analytics::MILESTONES[0] as u64)
+        ))
+        .into());
+    }
+    let mut table = Table::new(&["Confirmed", "Date", "Days"])
+        .align(0, Align::Right)
+        .align(2, Align::Right);
+    let mut previous: Option<NaiveDate> = None;
+    for (milestone, date) in milestones.iter() {
+        table.add_row(vec![
+            format_count(*milestone as u64),
+            locale::format_date(date),
+            match previous {
+                Some(previous) => (*date - previous).num_days().to_string(),
+                None => "-".to_string(),
+            },
+        ]);
+        previous = Some(*date);
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+// `corona-stats anomalies Italy`: the days whose new cases or new deaths
+// stand out from the days around them.
+pub fn anomalies(chain: &Chain, country: &str, threshold: f64) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let mut found = Vec::new();
+    for state in ["Confirmed", "Deaths"].iter() {
+        let cumulative = country_series(&series, country, state);
+        for anomaly in analytics::anomalies(&cumulative, threshold) {
+            found.push((*state, anomaly));
+        }
+    }
+    if country_series(&series, country, "Confirmed").is_empty() {
+        return Err(Failure::NoData(format!("no data for {}", country)).into());
+    }
+    found.sort_by(|a, b| a.1.date.cmp(&b.1.date).then_with(|| a.0.cmp(b.0)));
+    let mut table = Table::new(&["Date", "Series", "Change", "Median", "z", "Kind"])
+        .align(2, Align::Right)
+        .align(3, Align::Right)
+        .align(4, Align::Right);
+    for (state, anomaly) in found.iter() {
+        table.add_row(vec![
+            locale::format_date(&anomaly.date),
+            i18n::label(state).to_string(),
+            format_change(anomaly.change),
+            format_decimal(anomaly.median, 0),
+            format_decimal(anomaly.z, 1),
+            i18n::label(anomaly.kind.label()).to_string(),
+        ]);
+    }
+    if table.is_empty() && !table::is_tsv() {
+        eprintln!("No anomalies in {}", country);
+        return Ok(());
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+// `corona-stats trends`: every country, or only `countries`, by whether its
+// new cases rise, stay flat or fall.
+pub fn trends(chain: &Chain, countries: &[String]) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let names: Vec<String> = if countries.is_empty() {
+        let mut names: Vec<String> = series
+            .iter()
+            .filter(|s| s.state == "Confirmed")
+            .map(|s| s.country.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    } else {
+        countries.to_vec()
+    };
+    let mut found = Vec::new();
+    for country in names.iter() {
+        let confirmed = country_series(&series, country, "Confirmed");
+        if confirmed.is_empty() {
+            warn!("no data for {}", country);
+            continue;
+        }
+        found.push((country.clone(), confirmed));
+    }
+    let table = analytics::trend_table(&found);
+    if table.is_empty() {
+        return Err(Failure::NoData("too few days of data for trends".to_string()).into());
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+// `corona-stats waves Italy`: the waves of new cases of every country.
+pub fn waves(chain: &Chain, countries: &[String]) -> Result<(), Box<dyn Error>> {
+    let series = get_time_series(chain)?;
+    let mut found = Vec::new();
+    for country in countries.iter() {
+        let confirmed = country_series(&series, country, "Confirmed");
+        if confirmed.is_empty() {
+            warn!("no data for {}", country);
+            continue;
+        }
+        let waves = waves::segment(&confirmed);
+        if waves.is_empty() {
+            warn!("no waves found for {}", country);
+            continue;
+        }
+        found.push((country.clone(), waves));
+    }
+    if found.is_empty() {
+        return Err(Failure::NoData("no waves found".to_string()).into());
+    }
+    print!("{}", waves::wave_table(&found));
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn get_data_from(chain: &Chain, date: &NaiveDate) -> Result<Vec<Record>, Box<dyn Error>> {
+    fetch_report(chain, date).await
+}
+
+pub async fn fetch_report(chain: &Chain, date: &NaiveDate) -> Result<Vec<Record>, Box<dyn Error>> {
     let url = format!("{}{}.csv", URL_DAILY_REPORT, date.format("%m-%d-%Y"));
-    
-    let body = reqwest::get(&url).await?.text().await?;
 
+    // reports of days that were never published count as empty
+    match chain.fetch(&url).await {
+        Ok(body) => chain.hooks().transform(parse_report(&body)?),
+        Err(e) if e.is::<NotFound>() => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn parse_report(body: &str) -> Result<Vec<Record>, Box<dyn Error>> {
+    let mut data = Vec::new();
     let mut rdr = ReaderBuilder::new()
         .delimiter(b',')
         .from_reader(body.as_bytes());
 
+    let columns = Columns::new(rdr.headers()?)?;
     for result in rdr.records() {
-        let row: Record = to_record(normalize(result?));
+        let row: Record = to_record(normalize(&result?, &columns));
+        if row.updated.year() == 1970 {
+            debug!(country = %row.country, province = %row.province, "unparseable last update");
+        }
         data.push(row);
     }
+    debug!(rows = data.len(), "parsed daily report");
     Ok(data)
 }
 
-fn normalize(record: StringRecord) -> CsvRecord {
+// Writes records back in the daily report layout understood by `parse_report`.
+pub fn write_report(records: &[Record]) -> Result<String, Box<dyn Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "Province/State",
+        "Country/Region",
+        "Last Update",
+        "Confirmed",
+        "Deaths",
+        "Recovered",
+        "Latitude",
+        "Longitude",
+        "Active",
+        "Incident_Rate",
+        "Case_Fatality_Ratio",
+        "Combined_Key",
+    ])?;
+    let optional = |v: Option<String>| v.unwrap_or_default();
+    for r in records.iter() {
+        wtr.write_record(&[
+            r.province.clone(),
+            r.country.clone(),
+            r.updated.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            r.confirmed.to_string(),
+            r.deaths.to_string(),
+            r.recovered.to_string(),
+            r.lat.map(|v| v.to_string()).unwrap_or_default(),
+            r.long.map(|v| v.to_string()).unwrap_or_default(),
+            optional(r.active.map(|v| v.to_string())),
+            optional(r.incident_rate.map(|v| v.to_string())),
+            optional(r.case_fatality_ratio.map(|v| v.to_string())),
+            optional(r.combined_key.clone()),
+        ])?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+// Positions of the fields in a daily report, found by their names. JHU
+// renamed and reordered the columns over time: until 2020-03-21 the reports
+// start with "Province/State,Country/Region,Last Update" (the coordinates
+// were added on 2020-03-01 as "Latitude,Longitude"), since 2020-03-22 with
+// "FIPS,Admin2,Province_State,Country_Region,Last_Update,Lat,Long_".
+struct Columns {
+    province: Option<usize>,
+    country: usize,
+    updated: Option<usize>,
+    confirmed: Option<usize>,
+    deaths: Option<usize>,
+    recovered: Option<usize>,
+    lat: Option<usize>,
+    long: Option<usize>,
+    active: Option<usize>,
+    incident_rate: Option<usize>,
+    case_fatality_ratio: Option<usize>,
+    combined_key: Option<usize>,
+}
+
+impl Columns {
+    fn new(headers: &StringRecord) -> Result<Columns, Failure> {
+        // some reports start with a byte order mark
+        let find = |names: &[&str]| {
+            headers
+                .iter()
+                .position(|h| names.contains(&h.trim_start_matches('\u{feff}').trim()))
+        };
+        Ok(Columns {
+            province: find(&["Province/State", "Province_State"]),
+            country: find(&["Country/Region", "Country_Region"]).ok_or_else(|| {
+                Failure::Parse("daily report without a Country/Region column".to_string())
+            })?,
+            updated: find(&["Last Update", "Last_Update"]),
+            confirmed: find(&["Confirmed"]),
+            deaths: find(&["Deaths"]),
+            recovered: find(&["Recovered"]),
+            lat: find(&["Latitude", "Lat"]),
+            long: find(&["Longitude", "Long_"]),
+            active: find(&["Active"]),
+            // renamed in November 2020
+            incident_rate: find(&["Incident_Rate", "Incidence_Rate"]),
+            case_fatality_ratio: find(&["Case-Fatality_Ratio", "Case_Fatality_Ratio"]),
+            combined_key: find(&["Combined_Key"]),
+        })
+    }
+}
+
+fn normalize(record: &StringRecord, columns: &Columns) -> CsvRecord {
+    let get = |index: Option<usize>| index.and_then(|i| record.get(i));
+    // counts are empty for some locations, and written as "1.0" in a few files
+    let count = |index: Option<usize>| match get(index) {
+        Some(t) => t
+            .parse::<u32>()
+            .or_else(|_| t.parse::<f64>().map(|v| v.max(0.0) as u32))
+            .unwrap_or(0),
+        None => 0,
+    };
     CsvRecord {
-        province: match record.get(0) {
-            Some(t) => t.to_string(),
-            None => "".to_string(),
-        },
-        country: match record.get(1) {
-            Some(t) => t.to_string(),
-            None => "".to_string(),
-        },
-        updated: match record.get(2) {
-            Some(t) => t.to_string(),
-            None => "".to_string(),
-        },
-        confirmed: match record.get(3) {
-            Some(t) => match t.to_string().parse::<u32>() {
-                Ok(t) => t,
-                Err(_) => 0,
-            },
-            None => 0,
-        },
-        deaths: match record.get(4) {
-            Some(t) => match t.to_string().parse::<u32>() {
-                Ok(t) => t,
-                Err(_) => 0,
-            },
-            None => 0,
-        },
-        recovered: match record.get(5) {
-            Some(t) => match t.to_string().parse::<u32>() {
-                Ok(t) => t,
-                Err(_) => 0,
-            },
-            None => 0,
-        },
-        lat: match record.get(6) {
-            Some(t) => match t.to_string().parse::<f32>() {
-                Ok(t) => Some(t),
-                Err(_) => None::<f32>,
-            },
-            None => None::<f32>,
-        },
-        long: match record.get(7) {
-            Some(t) => match t.to_string().parse::<f32>() {
-                Ok(t) => Some(t),
-                Err(_) => None::<f32>,
-            },
-            None => None::<f32>,
-        },
+        province: get(columns.province).unwrap_or_default().to_string(),
+        country: get(Some(columns.country)).unwrap_or_default().to_string(),
+        updated: get(columns.updated).unwrap_or_default().to_string(),
+        confirmed: count(columns.confirmed),
+        deaths: count(columns.deaths),
+        recovered: count(columns.recovered),
+        lat: get(columns.lat).and_then(|t| t.parse::<f32>().ok()),
+        long: get(columns.long).and_then(|t| t.parse::<f32>().ok()),
+        active: get(columns.active)
+            .and_then(|t| t.parse::<f64>().ok())
+            .map(|v| v.max(0.0) as u32),
+        incident_rate: get(columns.incident_rate).and_then(|t| t.parse::<f64>().ok()),
+        case_fatality_ratio: get(columns.case_fatality_ratio).and_then(|t| t.parse::<f64>().ok()),
+        combined_key: get(columns.combined_key)
+            .filter(|t| !t.is_empty())
+            .map(String::from),
     }
 }
 
@@ -177,6 +1994,10 @@ fn to_record(record: CsvRecord) -> Record {
         recovered: record.recovered,
         lat: record.lat,
         long: record.long,
+        active: record.active,
+        incident_rate: record.incident_rate,
+        case_fatality_ratio: record.case_fatality_ratio,
+        combined_key: record.combined_key,
     }
 }
 
@@ -189,99 +2010,161 @@ fn parse_date(s: String) -> NaiveDateTime {
     ]
     .iter()
     {
-        match NaiveDateTime::parse_from_str(&s, format) {
-            Ok(t) => {
-                if t.year() < 2000 {
-                    return NaiveDate::from_ymd(t.year() + 2000, t.month(), t.day()).and_hms(
-                        t.hour(),
-                        t.minute(),
-                        t.second(),
-                    );
-                } else {
-                    return t;
-                }
+        if let Ok(t) = NaiveDateTime::parse_from_str(&s, format) {
+            if t.year() < 2000 {
+                return ymd(t.year() + 2000, t.month(), t.day())
+                    .and_hms_opt(t.hour(), t.minute(), t.second())
+                    .unwrap();
+            } else {
+                return t;
             }
-            Err(_) => (),
         }
     }
-    NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0)
+    ymd(1970, 1, 1).and_hms_opt(0, 0, 0).unwrap()
+}
+
+pub(crate) fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+// Dates to fetch daily reports for: with discovery exactly the files listed by
+// the GitHub contents API, otherwise every day since the first report.
+async fn report_dates(chain: &Chain) -> Result<Vec<NaiveDate>, Box<dyn Error>> {
+    if !chain.discovery() {
+        return Ok(get_dates());
+    }
+    let listing = chain.fetch(URL_REPORT_LISTING).await?;
+    // a listing cut off by the API misses reports, which are then probed for
+    // every day like without discovery; the listings of a checkout or an
+    // archive are complete, whatever their length
+    let entries = listing.matches("\"name\"").count();
+    if entries == LISTING_LIMIT {
+        warn!(
+            entries,
+            "the report listing is truncated, probing every date instead"
+        );
+        return Ok(get_dates());
+    }
+    let mut dates = listed_dates(&listing);
+    dates.sort();
+    debug!(reports = dates.len(), "listed daily reports");
+    Ok(dates)
+}
+
+// The GitHub contents API lists at most this many entries of a directory.
+const LISTING_LIMIT: usize = 1000;
+
+// The listing is a JSON array of objects with a "name" each, the daily
+// reports are the ones named MM-DD-YYYY.csv.
+fn listed_dates(listing: &str) -> Vec<NaiveDate> {
+    listing
+        .split("\"name\"")
+        .skip(1)
+        .filter_map(|rest| {
+            let value = rest.trim_start().strip_prefix(':')?.trim_start();
+            let name = value.strip_prefix('"')?.split('"').next()?;
+            NaiveDate::parse_from_str(name.strip_suffix(".csv")?, "%m-%d-%Y").ok()
+        })
+        .collect()
 }
 
 fn get_dates() -> Vec<NaiveDate> {
     let mut dates = Vec::new();
-    let mut date = NaiveDate::from_ymd(2020, 1, 22);
+    let mut date = ymd(2020, 1, 22);
     let now = Utc::now();
-    let mut now = NaiveDate::from_ymd(now.year(), now.month(), now.day());
-    now = now.succ();
+    let mut now = ymd(now.year(), now.month(), now.day());
+    now = now.succ_opt().unwrap();
 
     while date != now {
         dates.push(date);
-        date = date.succ();
+        date = date.succ_opt().unwrap();
     }
 
     dates
 }
 
 #[tokio::main]
-async fn get_time_series() -> Result<Vec<TimeSeries>, Box<dyn Error>> {
-    let mut series = Vec::new();
-
-    for state in ["Confirmed", "Deaths", "Recovered"].iter() {
-        let url = format!("{}{}.csv", URL_TIME_SERIES, state);
-        
-        let body = reqwest::get(&url).await?.text().await?;
-
-        let mut rdr = ReaderBuilder::new()
-            .delimiter(b',')
-            .from_reader(body.as_bytes());
-
-        for rlt in rdr.records() {
-            let result: StringRecord = rlt?;
-            let mut record = TimeSeries {
-                province: match result.get(0) {
-                    Some(t) => t.to_string(),
-                    None => "".to_string(),
-                },
-                country: match result.get(1) {
-                    Some(t) => t.to_string(),
-                    None => "".to_string(),
-                },
-                lat: match result.get(2) {
-                    Some(t) => match t.to_string().parse::<f32>() {
-                        Ok(t) => Some(t),
-                        Err(_) => None::<f32>,
-                    },
-                    None => None::<f32>,
-                },
-                long: match result.get(3) {
-                    Some(t) => match t.to_string().parse::<f32>() {
-                        Ok(t) => Some(t),
-                        Err(_) => None::<f32>,
-                    },
-                    None => None::<f32>,
-                },
-                data: BTreeMap::new(),
-                state: state.to_string(),
-            };
-            let mut index = 4;
-            let mut date = NaiveDate::from_ymd(2020, 1, 22);
-            loop {
-                record.data.insert(date.to_string(), match result.get(index) {
-                    Some(t) => match t.to_string().parse::<i32>() {
-                        Ok(t) => t,
-                        Err(_) => -1,
-                    },
-                    None => break,
-                });
-                if *record.data.get(&date.to_string()).unwrap() < 0 {
-                    record.data.remove(&date.to_string());
-                }
-                index += 1;
-                date = date.succ();
+pub async fn get_time_series(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    fetch_time_series(chain).await
+}
+
+#[tokio::main]
+pub async fn get_populations(chain: &Chain) -> Result<Populations, Box<dyn Error>> {
+    population::fetch(chain).await
+}
+
+#[tokio::main]
+pub async fn get_vaccinations(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    source::vaccinations::time_series(chain).await
+}
+
+// Renames OWID's countries to the names used in `series` where they differ
+// ("United States" and "US"), so both are found with the same filter.
+pub(crate) fn with_names_of(mut other: Vec<TimeSeries>, series: &[TimeSeries]) -> Vec<TimeSeries> {
+    for elem in other.iter_mut() {
+        if let Some(name) = search::alias(&elem.country) {
+            if series.iter().any(|s| s.country == name) {
+                elem.country = name.to_string();
             }
-            series.push(record);
         }
     }
+    other
+}
+
+// Time series of the selected source, JHU unless the chain says otherwise.
+pub async fn fetch_time_series(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    chain.source().time_series(chain).await
+}
+
+// Confirmed minus deaths minus recovered of every location, as "Active" series.
+pub fn active_series(series: &[TimeSeries]) -> Vec<TimeSeries> {
+    let find = |elem: &TimeSeries, state: &str| {
+        series
+            .iter()
+            .find(|s| s.country == elem.country && s.province == elem.province && s.state == state)
+    };
+    series
+        .iter()
+        .filter(|s| s.state == "Confirmed")
+        .map(|confirmed| {
+            let (deaths, recovered) = (find(confirmed, "Deaths"), find(confirmed, "Recovered"));
+            let value = |other: Option<&TimeSeries>, date: &str| {
+                other.and_then(|o| o.data.get(date)).cloned().unwrap_or(0)
+            };
+            TimeSeries {
+                state: "Active".to_string(),
+                data: confirmed
+                    .data
+                    .iter()
+                    .map(|(date, c)| {
+                        let active = c - value(deaths, date) - value(recovered, date);
+                        (date.clone(), active)
+                    })
+                    .collect(),
+                ..confirmed.clone()
+            }
+        })
+        .collect()
+}
 
-    Ok(series)
+fn dated(data: &BTreeMap<String, i32>) -> Vec<(NaiveDate, i64)> {
+    data.iter()
+        .filter_map(|(date, value)| Some((date.parse().ok()?, i64::from(*value))))
+        .collect()
+}
+
+// Sums all province rows of a country into one cumulative series for `state`.
+pub fn country_series(series: &[TimeSeries], country: &str, state: &str) -> Vec<(NaiveDate, i64)> {
+    let mut totals: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for elem in series
+        .iter()
+        .filter(|s| s.state == state && groups::covers(country, &s.country))
+    {
+        for (date, value) in elem.data.iter() {
+            if let Ok(date) = date.parse::<NaiveDate>() {
+                *totals.entry(date).or_insert(0) += i64::from(*value);
+            }
+        }
+    }
+    totals.into_iter().collect()
 }
@@ -0,0 +1,342 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::chain::Chain;
+use crate::data::{self, ymd, DatedReport, Record, TimeSeries};
+use crate::exit::Failure;
+use crate::provenance::Provenance;
+
+const MAGIC: &[u8; 4] = b"CSDS";
+// version 1 had no provenance, version 2 not the sources of each metric,
+// version 3 not the scale of the series
+const VERSION: u64 = 4;
+
+// Everything parsed from upstream in one value: the daily reports and the
+// time series of the chain's source. An application embedding the library
+// can save it and load it again much faster than parsing the CSV files.
+#[derive(Debug, Clone, Default)]
+pub struct Dataset {
+    pub reports: Vec<DatedReport>,
+    pub series: Vec<TimeSeries>,
+    provenance: Option<Provenance>,
+}
+
+impl Dataset {
+    pub fn new(
+        reports: Vec<DatedReport>,
+        series: Vec<TimeSeries>,
+        provenance: Option<Provenance>,
+    ) -> Dataset {
+        Dataset {
+            reports,
+            series,
+            provenance,
+        }
+    }
+
+    // All daily reports up to today and the time series.
+    pub fn fetch(chain: &Chain) -> Result<Dataset, Box<dyn Error>> {
+        let today = Utc::now().naive_utc().date();
+        Ok(Dataset {
+            reports: data::get_history(chain, &ymd(2020, 1, 22), &today)?.into_reports()?,
+            series: data::get_time_series(chain)?,
+            provenance: Some(chain.provenance()),
+        })
+    }
+
+    // Where and when the data was fetched, none for a dataset put together
+    // by hand.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.encode())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Dataset, Box<dyn Error>> {
+        let data = fs::read(path)?;
+        Ok(Dataset::decode(&data)
+            .map_err(|e| Failure::Parse(format!("{}: {}", path.display(), e)))?)
+    }
+
+    // A magic number and version, then the provenance, the reports and the
+    // series. Integers
+    // are LEB128 varints (zigzag for signed ones), dates are days since the
+    // first of January of the year 1 and the dates of a series are deltas.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.out.extend_from_slice(MAGIC);
+        w.uint(VERSION);
+        w.option(self.provenance.as_ref(), Writer::provenance);
+        w.uint(self.reports.len() as u64);
+        for (date, rows) in self.reports.iter() {
+            w.date(date);
+            w.uint(rows.len() as u64);
+            for row in rows.iter() {
+                w.record(row);
+            }
+        }
+        w.uint(self.series.len() as u64);
+        for s in self.series.iter() {
+            w.str(&s.province);
+            w.str(&s.country);
+            w.option(s.lat, Writer::f32);
+            w.option(s.long, Writer::f32);
+            w.str(&s.state);
+            w.uint(u64::from(s.scale));
+            w.uint(s.data.len() as u64);
+            let mut previous = 0;
+            for (date, value) in s.data.iter() {
+                let days = date
+                    .parse::<NaiveDate>()
+                    .map(|d| i64::from(d.num_days_from_ce()))
+                    .unwrap_or(previous);
+                w.int(days - previous);
+                w.int(i64::from(*value));
+                previous = days;
+            }
+        }
+        w.out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Dataset, String> {
+        if !data.starts_with(MAGIC) {
+            return Err("not a dataset snapshot".to_string());
+        }
+        let mut r = Reader {
+            data,
+            pos: 4,
+            version: 0,
+        };
+        let version = r.uint()?;
+        r.version = version;
+        if version == 0 || version > VERSION {
+            return Err(format!("unsupported snapshot version {}", version));
+        }
+        let mut dataset = Dataset::default();
+        if version >= 2 {
+            dataset.provenance = r.option(Reader::provenance)?;
+        }
+        for _ in 0..r.uint()? {
+            let date = r.date()?;
+            let count = r.uint()?;
+            let mut rows = Vec::new();
+            for _ in 0..count {
+                rows.push(r.record()?);
+            }
+            dataset.reports.push((date, rows));
+        }
+        for _ in 0..r.uint()? {
+            let mut s = TimeSeries {
+                province: r.str()?,
+                country: r.str()?,
+                lat: r.option(Reader::f32)?,
+                long: r.option(Reader::f32)?,
+                state: r.str()?,
+                scale: if r.version >= 4 { r.uint()? as u32 } else { 1 },
+                data: BTreeMap::new(),
+            };
+            let mut days = 0;
+            for _ in 0..r.uint()? {
+                days += r.int()?;
+                let date = NaiveDate::from_num_days_from_ce_opt(days as i32)
+                    .ok_or("invalid date in series")?;
+                s.data.insert(date.to_string(), r.int()? as i32);
+            }
+            dataset.series.push(s);
+        }
+        Ok(dataset)
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn uint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.out.push(value as u8);
+    }
+
+    fn int(&mut self, value: i64) {
+        self.uint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.uint(value.len() as u64);
+        self.out.extend_from_slice(value.as_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn option<T>(&mut self, value: Option<T>, write: fn(&mut Writer, T)) {
+        match value {
+            Some(value) => {
+                self.out.push(1);
+                write(self, value);
+            }
+            None => self.out.push(0),
+        }
+    }
+
+    fn date(&mut self, date: &NaiveDate) {
+        self.int(i64::from(date.num_days_from_ce()));
+    }
+
+    fn provenance(&mut self, provenance: &Provenance) {
+        self.str(&provenance.source);
+        self.str(&provenance.url);
+        self.int(provenance.fetched.and_utc().timestamp());
+        self.option(provenance.commit.as_deref(), Writer::str);
+        self.uint(provenance.metrics.len() as u64);
+        for (metric, sources) in provenance.metrics.iter() {
+            self.str(metric);
+            self.str(sources);
+        }
+    }
+
+    fn record(&mut self, row: &Record) {
+        self.str(&row.province);
+        self.str(&row.country);
+        self.int(row.updated.and_utc().timestamp());
+        self.uint(u64::from(row.confirmed));
+        self.uint(u64::from(row.deaths));
+        self.uint(u64::from(row.recovered));
+        self.option(row.lat, Writer::f32);
+        self.option(row.long, Writer::f32);
+        self.option(row.active, |w, v| w.uint(u64::from(v)));
+        self.option(row.incident_rate, Writer::f64);
+        self.option(row.case_fatality_ratio, Writer::f64);
+        self.option(row.combined_key.as_deref(), Writer::str);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    version: u64,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or("snapshot ends early")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.bytes(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid varint".to_string())
+    }
+
+    fn int(&mut self) -> Result<i64, String> {
+        let value = self.uint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let value = self.uint()?;
+        u32::try_from(value).map_err(|_| format!("{} is out of range", value))
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let length = self.uint()? as usize;
+        String::from_utf8(self.bytes(length)?.to_vec()).map_err(|_| "invalid UTF-8".to_string())
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        let b = self.bytes(4)?;
+        Ok(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.bytes(8)?);
+        Ok(f64::from_le_bytes(b))
+    }
+
+    fn option<T>(
+        &mut self,
+        read: fn(&mut Reader<'a>) -> Result<T, String>,
+    ) -> Result<Option<T>, String> {
+        match self.bytes(1)?[0] {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err("invalid option flag".to_string()),
+        }
+    }
+
+    fn date(&mut self) -> Result<NaiveDate, String> {
+        let days = self.int()?;
+        NaiveDate::from_num_days_from_ce_opt(days as i32).ok_or_else(|| "invalid date".to_string())
+    }
+
+    fn timestamp(&mut self) -> Result<NaiveDateTime, String> {
+        let seconds = self.int()?;
+        DateTime::from_timestamp(seconds, 0)
+            .map(|t| t.naive_utc())
+            .ok_or_else(|| "invalid timestamp".to_string())
+    }
+
+    fn provenance(&mut self) -> Result<Provenance, String> {
+        Ok(Provenance {
+            source: self.str()?,
+            url: self.str()?,
+            fetched: self.timestamp()?,
+            commit: self.option(Reader::str)?,
+            metrics: if self.version >= 3 {
+                let mut metrics = Vec::new();
+                for _ in 0..self.uint()? {
+                    metrics.push((self.str()?, self.str()?));
+                }
+                metrics
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    fn record(&mut self) -> Result<Record, String> {
+        Ok(Record {
+            province: self.str()?,
+            country: self.str()?,
+            updated: self.timestamp()?,
+            confirmed: self.u32()?,
+            deaths: self.u32()?,
+            recovered: self.u32()?,
+            lat: self.option(Reader::f32)?,
+            long: self.option(Reader::f32)?,
+            active: self.option(Reader::u32)?,
+            incident_rate: self.option(Reader::f64)?,
+            case_fatality_ratio: self.option(Reader::f64)?,
+            combined_key: self.option(Reader::str)?,
+        })
+    }
+}
@@ -0,0 +1,276 @@
+use chrono::{Duration, NaiveDate, Utc};
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tracing::info;
+
+use crate::chain::Chain;
+use crate::data::{self, ymd, DatedReport, TimeSeries};
+use crate::exit::Failure;
+use crate::provenance::Provenance;
+use crate::table::Table;
+
+// Like the git checkout, the database is handled by the command line tool,
+// which every system with SQLite has, instead of linking the library.
+const SQLITE: &str = "sqlite3";
+
+// Reports of the last days before the newest one in the database are
+// ingested again, JHU still corrects them.
+const REINGEST_DAYS: i64 = 3;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS locations (
+    id INTEGER PRIMARY KEY,
+    country TEXT NOT NULL,
+    province TEXT NOT NULL DEFAULT '',
+    lat REAL,
+    long REAL,
+    UNIQUE (country, province)
+);
+CREATE TABLE IF NOT EXISTS daily_stats (
+    location_id INTEGER NOT NULL REFERENCES locations (id),
+    date TEXT NOT NULL,
+    updated TEXT NOT NULL,
+    confirmed INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    recovered INTEGER NOT NULL,
+    active INTEGER,
+    incident_rate REAL,
+    case_fatality_ratio REAL,
+    PRIMARY KEY (location_id, date)
+);
+CREATE TABLE IF NOT EXISTS series (
+    location_id INTEGER NOT NULL REFERENCES locations (id),
+    state TEXT NOT NULL,
+    date TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    PRIMARY KEY (location_id, state, date)
+);
+CREATE TABLE IF NOT EXISTS provenance (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    source TEXT NOT NULL,
+    url TEXT NOT NULL,
+    fetched TEXT NOT NULL,
+    commit_hash TEXT
+);
+";
+
+// Queries see the tables with the location columns joined in, the way the
+// data is usually wanted, e.g. `SELECT country, max(confirmed) FROM
+// daily_stats GROUP BY country`. The views only live as long as the query.
+const VIEWS: &str = "
+CREATE TEMP VIEW daily_stats AS SELECT l.country, l.province, l.lat, l.long, d.*
+    FROM main.daily_stats d JOIN main.locations l ON l.id = d.location_id;
+CREATE TEMP VIEW series AS SELECT l.country, l.province, l.lat, l.long, s.*
+    FROM main.series s JOIN main.locations l ON l.id = s.location_id;
+";
+
+fn sqlite(db: &Path, options: &[&str], sql: &str) -> Result<String, Box<dyn Error>> {
+    let mut child = Command::new(SQLITE)
+        .arg("-bail")
+        .args(options)
+        .arg(db)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", SQLITE, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(sql.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Failure::Parse(format!(
+            "{}: {}",
+            db.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "NULL".to_string())
+}
+
+fn location(sql: &mut String, country: &str, province: &str, lat: Option<f32>, long: Option<f32>) {
+    let _ = writeln!(
+        sql,
+        "INSERT INTO locations (country, province, lat, long) VALUES ({}, {}, {}, {}) \
+         ON CONFLICT (country, province) DO UPDATE SET \
+         lat = coalesce(excluded.lat, lat), long = coalesce(excluded.long, long);",
+        text(country),
+        text(province),
+        optional(lat),
+        optional(long),
+    );
+}
+
+fn location_id(country: &str, province: &str) -> String {
+    format!(
+        "(SELECT id FROM locations WHERE country = {} AND province = {})",
+        text(country),
+        text(province)
+    )
+}
+
+fn reports_sql(sql: &mut String, reports: &[DatedReport]) {
+    for (date, rows) in reports.iter() {
+        for row in rows.iter() {
+            location(sql, &row.country, &row.province, row.lat, row.long);
+            let _ = writeln!(
+                sql,
+                "INSERT INTO daily_stats VALUES ({}, '{}', '{}', {}, {}, {}, {}, {}, {}) \
+                 ON CONFLICT (location_id, date) DO UPDATE SET \
+                 updated = excluded.updated, confirmed = excluded.confirmed, \
+                 deaths = excluded.deaths, recovered = excluded.recovered, \
+                 active = excluded.active, incident_rate = excluded.incident_rate, \
+                 case_fatality_ratio = excluded.case_fatality_ratio;",
+                location_id(&row.country, &row.province),
+                date,
+                row.updated.format("%Y-%m-%dT%H:%M:%S"),
+                row.confirmed,
+                row.deaths,
+                row.recovered,
+                optional(row.active),
+                optional(row.incident_rate),
+                optional(row.case_fatality_ratio),
+            );
+        }
+    }
+}
+
+fn series_sql(sql: &mut String, series: &[TimeSeries], from: &NaiveDate) -> usize {
+    let from = from.to_string();
+    let mut values = 0;
+    for s in series.iter() {
+        location(sql, &s.country, &s.province, s.lat, s.long);
+        for (date, value) in s.data.range(from.clone()..) {
+            let _ = writeln!(
+                sql,
+                "INSERT INTO series VALUES ({}, {}, '{}', {}) \
+                 ON CONFLICT (location_id, state, date) DO UPDATE SET value = excluded.value;",
+                location_id(&s.country, &s.province),
+                text(&s.state),
+                date,
+                value,
+            );
+            values += 1;
+        }
+    }
+    values
+}
+
+// Loads the daily reports and the time series of the chain's source into the
+// database `db`, created when missing. Later runs only add what is new since
+// the last one, and update what changed in the days before.
+pub fn ingest(chain: &Chain, db: &Path) -> Result<(), Box<dyn Error>> {
+    sqlite(db, &[], SCHEMA)?;
+    let newest = sqlite(db, &[], "SELECT max(date) FROM daily_stats;")?;
+    let from = match newest.trim().parse::<NaiveDate>() {
+        Ok(newest) => newest - Duration::days(REINGEST_DAYS),
+        Err(_) => ymd(2020, 1, 22),
+    };
+    let to = Utc::now().naive_utc().date();
+    info!(db = %db.display(), %from, "ingesting");
+
+    let reports = data::get_history(chain, &from, &to)?.into_reports()?;
+    let series = data::get_time_series(chain)?;
+
+    let mut sql = String::from("BEGIN;\n");
+    reports_sql(&mut sql, &reports);
+    let values = series_sql(&mut sql, &series, &from);
+    let provenance = chain.provenance();
+    let _ = writeln!(
+        sql,
+        "INSERT OR REPLACE INTO provenance VALUES (1, {}, {}, '{}', {});",
+        text(&provenance.source),
+        text(&provenance.url),
+        provenance.fetched(),
+        optional(provenance.commit.as_deref().map(text)),
+    );
+    sql.push_str("COMMIT;\n");
+    sqlite(db, &[], &sql)?;
+
+    eprintln!(
+        "ingested {} daily reports and {} time series values from {} into {}",
+        reports.len(),
+        values,
+        from,
+        db.display()
+    );
+    Ok(())
+}
+
+// Where the data of the last ingest came from, none before the first one.
+pub fn provenance(db: &Path) -> Result<Option<Provenance>, Box<dyn Error>> {
+    if !db.exists() {
+        return Ok(None);
+    }
+    let output = sqlite(
+        db,
+        &["-readonly"],
+        "SELECT 'source: ' || source || char(10) || 'url: ' || url || char(10) || \
+         'fetched: ' || fetched || coalesce(char(10) || 'commit: ' || commit_hash, '') \
+         FROM provenance WHERE id = 1;",
+    );
+    match output {
+        Ok(output) => Ok(Provenance::parse(&output)),
+        // a database from before provenance was recorded
+        Err(e) if e.to_string().contains("no such table") => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Runs read-only SQL against `db` and prints the result as a table, CSV or
+// JSON. Writing statements fail, the database is opened read-only.
+pub fn query(db: &Path, sql: &str, format: &str) -> Result<(), Box<dyn Error>> {
+    // the shell runs lines starting with a dot as its own commands, .shell
+    // and .output included, whether the database is read-only or not
+    if sql.lines().any(|line| line.trim_start().starts_with('.')) {
+        return Err(Failure::Parse(
+            "queries are SQL only, lines starting with '.' are sqlite3 commands".to_string(),
+        )
+        .into());
+    }
+    if !db.exists() {
+        return Err(
+            Failure::NoData(format!("{} does not exist, run ingest first", db.display())).into(),
+        );
+    }
+    let sql = format!("{}{};\n", VIEWS, sql.trim().trim_end_matches(';'));
+    match format {
+        "json" => print!("{}", sqlite(db, &["-readonly", "-json"], &sql)?),
+        "csv" => print!("{}", sqlite(db, &["-readonly", "-csv", "-header"], &sql)?),
+        "table" => {
+            let output = sqlite(db, &["-readonly", "-csv", "-header"], &sql)?;
+            let mut rdr = csv::ReaderBuilder::new().from_reader(output.as_bytes());
+            let headers: Vec<String> = rdr.headers()?.iter().map(String::from).collect();
+            if headers.is_empty() {
+                return Ok(());
+            }
+            let mut table = Table::new(&headers.iter().map(String::as_str).collect::<Vec<_>>());
+            for record in rdr.records() {
+                table.add_row(record?.iter().map(String::from).collect());
+            }
+            print!("{}", table);
+        }
+        _ => {
+            return Err(Failure::Parse(format!(
+                "unknown format '{}', expected table, csv or json",
+                format
+            ))
+            .into())
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+// Encoder for DEFLATE (RFC 1951) and gzip (RFC 1952), the counterpart of
+// `inflate` for what this tool writes itself. A single block with the fixed
+// Huffman codes and greedy matching over hash chains: far from the ratio of
+// zlib, but CSV files are mostly repeated digits and separators and shrink to
+// a fraction of their size anyway.
+
+use crate::inflate::{crc32, DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+// how many earlier positions with the same hash are compared at most
+const MAX_CHAIN: usize = 64;
+const NONE: usize = usize::MAX;
+
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    // `n` bits of `value`, least significant first
+    fn put(&mut self, value: u32, n: u32) {
+        self.buffer |= u64::from(value) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are stored most significant bit first
+    fn code(&mut self, code: u32, length: u32) {
+        let reversed = (0..length).fold(0, |r, i| (r << 1) | ((code >> i) & 1));
+        self.put(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+fn literal(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.code(0x30 + symbol, 8),
+        144..=255 => bits.code(0x190 + symbol - 144, 9),
+        256..=279 => bits.code(symbol - 256, 7),
+        _ => bits.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn copy(bits: &mut BitWriter, length: usize, distance: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|base| *base as usize <= length)
+        .unwrap_or(0);
+    literal(bits, 257 + index as u32);
+    bits.put(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        u32::from(LENGTH_EXTRA[index]),
+    );
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|base| *base as usize <= distance)
+        .unwrap_or(0);
+    bits.code(index as u32, 5);
+    bits.put(
+        (distance - DISTANCE_BASE[index] as usize) as u32,
+        u32::from(DISTANCE_EXTRA[index]),
+    );
+}
+
+// Compresses `data` into a raw DEFLATE stream.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter {
+        out: Vec::with_capacity(data.len() / 4),
+        buffer: 0,
+        count: 0,
+    };
+    // final block, fixed codes
+    bits.put(1, 1);
+    bits.put(1, 2);
+
+    let hash = |pos: usize| {
+        ((usize::from(data[pos]) << 10)
+            ^ (usize::from(data[pos + 1]) << 5)
+            ^ usize::from(data[pos + 2]))
+            & ((1 << HASH_BITS) - 1)
+    };
+    // the latest position of every hash, and the one before each position
+    let mut head = vec![NONE; 1 << HASH_BITS];
+    let mut previous = vec![NONE; WINDOW];
+    let insert = |pos: usize, head: &mut [usize], previous: &mut [usize]| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(pos);
+            previous[pos % WINDOW] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let (mut best, mut distance) = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            let longest = (data.len() - pos).min(MAX_MATCH);
+            let mut candidate = head[hash(pos)];
+            let mut chain = MAX_CHAIN;
+            while candidate != NONE && candidate < pos && pos - candidate <= WINDOW && chain > 0 {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(data[pos..pos + longest].iter())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best {
+                    best = length;
+                    distance = pos - candidate;
+                    if length == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate % WINDOW];
+                chain -= 1;
+            }
+        }
+        if best >= MIN_MATCH {
+            copy(&mut bits, best, distance);
+            for p in pos..pos + best {
+                insert(p, &mut head, &mut previous);
+            }
+            pos += best;
+        } else {
+            literal(&mut bits, u32::from(data[pos]));
+            insert(pos, &mut head, &mut previous);
+            pos += 1;
+        }
+    }
+    literal(&mut bits, 256);
+    bits.finish()
+}
+
+// A gzip member without file name or time, readable by `inflate::gunzip`.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
@@ -0,0 +1,485 @@
+// Metrics computed from the cumulative time series rather than downloaded:
+// the new cases and new deaths of every day, the 7-day incidence, the case
+// fatality ratio, the outcomes of the resolved cases, rolling means against
+// the weekday pattern of the reporting, weekly or monthly buckets and the
+// days missing in between.
+
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::analytics;
+use crate::data::{Metric, TimeSeries};
+use crate::filter::LocationFilter;
+use crate::population::{per_100k, Populations};
+use crate::waves;
+
+pub const NEW_CONFIRMED: &str = "New confirmed";
+pub const NEW_DEATHS: &str = "New deaths";
+pub const INCIDENCE: &str = "Incidence";
+pub const CFR: &str = "CFR";
+pub const CFR_LAGGED: &str = "CFR lagged";
+pub const DIED_SHARE: &str = "Died share";
+pub const RECOVERED_SHARE: &str = "Recovered share";
+
+// days of the incidence
+pub const INCIDENCE_DAYS: usize = 7;
+// days of the mean that peaks are found in
+const PEAK_WINDOW: usize = 7;
+// days from a confirmed case to a death of the lagged CFR without --lag
+pub const DEFAULT_LAG: usize = 14;
+// Series are whole numbers, so the CFR is kept in deaths per 100,000
+// confirmed cases with this scale: 5.7% is 5700, written out as 0.057. The
+// same for the shares of the outcomes.
+pub const RATIO_SCALE: u32 = 100_000;
+// per capita values are kept to the thousandth
+const PER_CAPITA_SCALE: u32 = 1000;
+
+// The increase of `series` over the day before, for every day but the first.
+// Corrections of earlier days make it negative.
+pub fn daily(series: &TimeSeries, state: &str) -> TimeSeries {
+    let mut data = BTreeMap::new();
+    let mut previous: Option<i32> = None;
+    // the dates are ISO, their order is the order of the days
+    for (date, value) in series.data.iter() {
+        if let Some(previous) = previous {
+            data.insert(date.clone(), value - previous);
+        }
+        previous = Some(*value);
+    }
+    TimeSeries {
+        state: state.to_string(),
+        data,
+        ..series.clone()
+    }
+}
+
+impl TimeSeries {
+    // The mean of every `window` days up to and including each day, rounded.
+    // The first `window - 1` days have no full window and are left out
+    // rather than averaged over fewer days.
+    pub fn rolling_mean(&self, window: usize) -> TimeSeries {
+        let window = window.max(1);
+        let values: Vec<(&String, i32)> = self.data.iter().map(|(d, v)| (d, *v)).collect();
+        let data = values
+            .windows(window)
+            .map(|days| {
+                let sum: i64 = days.iter().map(|(_, v)| i64::from(*v)).sum();
+                let mean = (sum as f64 / window as f64).round() as i32;
+                (days[window - 1].0.clone(), mean)
+            })
+            .collect();
+        TimeSeries {
+            data,
+            ..self.clone()
+        }
+    }
+}
+
+// New cases of the last 7 days per 100,000 people from cumulative confirmed
+// cases, for every day with 7 days before it. Downward corrections count as
+// no new cases.
+pub fn incidence_of(confirmed: &[(NaiveDate, i64)], population: u64) -> Vec<(NaiveDate, f64)> {
+    confirmed
+        .windows(INCIDENCE_DAYS + 1)
+        .filter_map(|days| {
+            let (date, last) = days[INCIDENCE_DAYS];
+            let cases = (last - days[0].1).max(0) as u64;
+            Some((date, per_100k(cases, population)?))
+        })
+        .collect()
+}
+
+// The incidence of every location with a known population, rounded to whole
+// numbers like all series.
+pub fn incidence(series: &[TimeSeries], populations: &Populations) -> Vec<TimeSeries> {
+    series
+        .iter()
+        .filter(|s| s.state == "Confirmed")
+        .filter_map(|s| {
+            let population = populations.get(&s.country, &s.province)?;
+            let confirmed: Vec<(NaiveDate, i64)> = s
+                .data
+                .iter()
+                .filter_map(|(date, value)| Some((date.parse().ok()?, i64::from(*value))))
+                .collect();
+            let data = incidence_of(&confirmed, population)
+                .into_iter()
+                .map(|(date, value)| (date.to_string(), value.round() as i32))
+                .collect();
+            Some(TimeSeries {
+                state: INCIDENCE.to_string(),
+                data,
+                ..s.clone()
+            })
+        })
+        .collect()
+}
+
+// Deaths by confirmed cases `lag` days before, for the days with cases on
+// that day. A lag of 0 is the plain CFR.
+pub fn case_fatality(series: &[TimeSeries], lag: usize, state: &str) -> Vec<TimeSeries> {
+    series
+        .iter()
+        .filter(|s| s.state == "Confirmed")
+        .filter_map(|confirmed| {
+            let deaths = series.iter().find(|s| {
+                s.state == "Deaths"
+                    && s.country == confirmed.country
+                    && s.province == confirmed.province
+            })?;
+            let data = deaths
+                .data
+                .iter()
+                .filter_map(|(date, deaths)| {
+                    let day: NaiveDate = date.parse().ok()?;
+                    let then = day.checked_sub_days(chrono::Days::new(lag as u64))?;
+                    let cases = *confirmed.data.get(&then.to_string())?;
+                    if cases <= 0 {
+                        return None;
+                    }
+                    let ratio = f64::from(*deaths) / f64::from(cases) * f64::from(RATIO_SCALE);
+                    Some((date.clone(), ratio.round() as i32))
+                })
+                .collect();
+            Some(TimeSeries {
+                state: state.to_string(),
+                data,
+                scale: RATIO_SCALE,
+                ..confirmed.clone()
+            })
+        })
+        .collect()
+}
+
+fn dated(series: &TimeSeries) -> Vec<(NaiveDate, i64)> {
+    series
+        .data
+        .iter()
+        .filter_map(|(date, value)| Some((date.parse().ok()?, i64::from(*value))))
+        .collect()
+}
+
+// The shares of the resolved cases of every location that reports
+// recoveries that died and that recovered, as two series of ratios.
+pub fn outcomes(series: &[TimeSeries]) -> Vec<TimeSeries> {
+    series
+        .iter()
+        .filter(|s| s.state == "Deaths")
+        .filter_map(|deaths| {
+            let recovered = series.iter().find(|s| {
+                s.state == "Recovered"
+                    && s.country == deaths.country
+                    && s.province == deaths.province
+            })?;
+            let shares = analytics::outcome_shares(&dated(deaths), &dated(recovered));
+            let scaled = |share: f64| (share * f64::from(RATIO_SCALE)).round() as i32;
+            Some(vec![
+                TimeSeries {
+                    state: DIED_SHARE.to_string(),
+                    scale: RATIO_SCALE,
+                    data: shares
+                        .iter()
+                        .map(|(date, died, _)| (date.to_string(), scaled(*died)))
+                        .collect(),
+                    ..deaths.clone()
+                },
+                TimeSeries {
+                    state: RECOVERED_SHARE.to_string(),
+                    scale: RATIO_SCALE,
+                    data: shares
+                        .iter()
+                        .map(|(date, _, recovered)| (date.to_string(), scaled(*recovered)))
+                        .collect(),
+                    ..deaths.clone()
+                },
+            ])
+        })
+        .flatten()
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    // ISO weeks, Monday to Sunday
+    Weekly,
+    Monthly,
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Period, String> {
+        match s {
+            "weekly" | "week" => Ok(Period::Weekly),
+            "monthly" | "month" => Ok(Period::Monthly),
+            _ => Err(format!(
+                "unknown period '{}', expected weekly or monthly",
+                s
+            )),
+        }
+    }
+}
+
+impl Period {
+    fn bucket(&self, date: &NaiveDate) -> (i32, u32) {
+        match self {
+            Period::Weekly => (date.iso_week().year(), date.iso_week().week()),
+            Period::Monthly => (date.year(), date.month()),
+        }
+    }
+}
+
+// How the days of a period become one value: sums for daily numbers like new
+// cases, the last day for cumulative ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    Sum,
+    Mean,
+    Last,
+}
+
+impl FromStr for Aggregation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Aggregation, String> {
+        match s {
+            "sum" => Ok(Aggregation::Sum),
+            "mean" => Ok(Aggregation::Mean),
+            "last" => Ok(Aggregation::Last),
+            _ => Err(format!(
+                "unknown aggregation '{}', expected sum, mean or last",
+                s
+            )),
+        }
+    }
+}
+
+impl TimeSeries {
+    // One value per week or month, dated with the last day of the period
+    // that has data. The first and last period may be partial.
+    pub fn resample(&self, period: Period, aggregation: Aggregation) -> TimeSeries {
+        let mut buckets: Vec<((i32, u32), String, Vec<i64>)> = Vec::new();
+        for (date, value) in self.data.iter() {
+            let bucket = match date.parse::<NaiveDate>() {
+                Ok(day) => period.bucket(&day),
+                Err(_) => continue,
+            };
+            match buckets.last_mut() {
+                Some((last, last_date, values)) if *last == bucket => {
+                    *last_date = date.clone();
+                    values.push(i64::from(*value));
+                }
+                _ => buckets.push((bucket, date.clone(), vec![i64::from(*value)])),
+            }
+        }
+        let data = buckets
+            .into_iter()
+            .map(|(_, date, values)| {
+                let value = match aggregation {
+                    Aggregation::Sum => values.iter().sum(),
+                    Aggregation::Mean => {
+                        (values.iter().sum::<i64>() as f64 / values.len() as f64).round() as i64
+                    }
+                    Aggregation::Last => values[values.len() - 1],
+                };
+                (
+                    date,
+                    value.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32,
+                )
+            })
+            .collect();
+        TimeSeries {
+            data,
+            ..self.clone()
+        }
+    }
+}
+
+impl TimeSeries {
+    // Every value per `per` people of `population`, to the thousandth.
+    pub fn per_capita(&self, population: u64, per: u64) -> TimeSeries {
+        let scale = f64::from(PER_CAPITA_SCALE);
+        let data = self
+            .data
+            .iter()
+            .map(|(date, value)| {
+                let value = self.value(*value) / population.max(1) as f64 * per as f64;
+                let value = (value * scale).round();
+                (
+                    date.clone(),
+                    value.clamp(f64::from(i32::MIN), f64::from(i32::MAX)) as i32,
+                )
+            })
+            .collect();
+        TimeSeries {
+            data,
+            scale: PER_CAPITA_SCALE,
+            ..self.clone()
+        }
+    }
+}
+
+// Whether the series of `state` is a rate already, which has no per capita
+// values.
+pub fn is_rate(state: &str) -> bool {
+    [INCIDENCE, CFR, CFR_LAGGED, DIED_SHARE, RECOVERED_SHARE].contains(&state)
+}
+
+impl TimeSeries {
+    // The peaks of the 7-day mean of the daily increase of a cumulative
+    // series, e.g. the waves of new cases, with a prominence of at least
+    // `min_prominence` of the highest day.
+    pub fn peaks(&self, min_prominence: f64) -> Vec<(NaiveDate, f64)> {
+        let cumulative: Vec<(NaiveDate, i64)> = self
+            .data
+            .iter()
+            .filter_map(|(date, value)| Some((date.parse().ok()?, i64::from(*value))))
+            .collect();
+        let smoothed = waves::smooth(&waves::daily_new(&cumulative), PEAK_WINDOW);
+        waves::peaks(&smoothed, min_prominence)
+    }
+}
+
+// How the days missing between the first and the last day of a series, like
+// the values of the time series files that do not parse, are filled in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+    // the value of the day before
+    Forward,
+    // on the line between the days around the gap, rounded
+    Linear,
+}
+
+impl FromStr for Fill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Fill, String> {
+        match s {
+            "forward" | "ffill" => Ok(Fill::Forward),
+            "linear" => Ok(Fill::Linear),
+            _ => Err(format!("unknown fill '{}', expected forward or linear", s)),
+        }
+    }
+}
+
+impl TimeSeries {
+    // The runs of days without a value between the first and the last day
+    // with one, as the first and last missing day of each.
+    pub fn gaps(&self) -> Vec<(NaiveDate, NaiveDate)> {
+        let days: Vec<NaiveDate> = self.data.keys().filter_map(|d| d.parse().ok()).collect();
+        days.windows(2)
+            .filter(|w| (w[1] - w[0]).num_days() > 1)
+            .filter_map(|w| Some((w[0].succ_opt()?, w[1].pred_opt()?)))
+            .collect()
+    }
+
+    // The series with every gap filled in.
+    pub fn fill(&self, fill: Fill) -> TimeSeries {
+        let days: Vec<(NaiveDate, i32)> = self
+            .data
+            .iter()
+            .filter_map(|(date, value)| Some((date.parse().ok()?, *value)))
+            .collect();
+        let mut data = self.data.clone();
+        for w in days.windows(2) {
+            let ((from, before), (to, after)) = (w[0], w[1]);
+            let span = (to - from).num_days();
+            for n in 1..span {
+                let value = match fill {
+                    Fill::Forward => before,
+                    Fill::Linear => {
+                        let step = f64::from(after - before) / span as f64;
+                        (f64::from(before) + step * n as f64).round() as i32
+                    }
+                };
+                data.insert((from + chrono::Duration::days(n)).to_string(), value);
+            }
+        }
+        TimeSeries {
+            data,
+            ..self.clone()
+        }
+    }
+}
+
+// What `series` and `export` do to every series they show: gaps filled in,
+// a rolling mean, buckets of weeks or months, and values per `per_capita`
+// people for everything but the rates.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Transform {
+    pub fill: Option<Fill>,
+    pub smooth: Option<usize>,
+    pub resample: Option<(Period, Aggregation)>,
+    pub per_capita: Option<u64>,
+}
+
+impl Transform {
+    // Fills the gaps of the downloaded series before anything is derived
+    // from them, or warns about those of the selected ones without --fill.
+    pub fn fill_gaps(&self, series: Vec<TimeSeries>, filter: &LocationFilter) -> Vec<TimeSeries> {
+        match self.fill {
+            Some(fill) => series.iter().map(|s| s.fill(fill)).collect(),
+            None => {
+                for s in series
+                    .iter()
+                    .filter(|s| filter.matches(&s.country, &s.province))
+                {
+                    let missing: i64 = s
+                        .gaps()
+                        .iter()
+                        .map(|(from, to)| (*to - *from).num_days() + 1)
+                        .sum();
+                    if missing > 0 {
+                        warn!(country = %s.country, province = %s.province, state = %s.state, missing, "days missing, --fill forward or linear fills them in");
+                    }
+                }
+                series
+            }
+        }
+    }
+
+    // Whether `apply` changes anything; the fill happens before.
+    pub fn is_identity(&self) -> bool {
+        self.smooth.is_none() && self.resample.is_none() && self.per_capita.is_none()
+    }
+
+    // `None` for a location without a population when per capita values are
+    // asked for.
+    pub fn apply(&self, series: &TimeSeries, populations: &Populations) -> Option<TimeSeries> {
+        let mut series = match self.smooth {
+            Some(window) => series.rolling_mean(window),
+            None => series.clone(),
+        };
+        if let Some((period, aggregation)) = self.resample {
+            series = series.resample(period, aggregation);
+        }
+        if let (Some(per), false) = (self.per_capita, is_rate(&series.state)) {
+            let population = populations.get(&series.country, &series.province)?;
+            series = series.per_capita(population, per);
+        }
+        Some(series)
+    }
+}
+
+// The daily series of every location's `from` series, named `to`.
+pub fn daily_series(series: &[TimeSeries], from: &str, to: &str) -> Vec<TimeSeries> {
+    series
+        .iter()
+        .filter(|s| s.state == from)
+        .map(|s| daily(s, to))
+        .collect()
+}
+
+// The derived series `metric` asks for, none for the downloaded metrics.
+pub fn derive(series: &[TimeSeries], metric: Metric) -> Vec<TimeSeries> {
+    match metric {
+        Metric::NewConfirmed => daily_series(series, "Confirmed", NEW_CONFIRMED),
+        Metric::NewDeaths => daily_series(series, "Deaths", NEW_DEATHS),
+        Metric::Cfr => case_fatality(series, 0, CFR),
+        Metric::LaggedCfr(lag) => case_fatality(series, lag, CFR_LAGGED),
+        Metric::Outcomes => outcomes(series),
+        _ => Vec::new(),
+    }
+}
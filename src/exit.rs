@@ -0,0 +1,94 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::client::NotFound;
+
+// Exit codes of the command line tool, so scripts can tell failures apart
+// without looking at the message on stderr:
+//
+// 0 success
+// 1 network or other I/O failure (also anything not covered below)
+// 2 invalid input: command line arguments or a malformed report
+// 3 no data for the requested date, location or query, or not cached offline
+// 4 an alert threshold was crossed
+pub const OK: i32 = 0;
+pub const NETWORK: i32 = 1;
+pub const PARSE: i32 = 2;
+pub const NO_DATA: i32 = 3;
+pub const THRESHOLD: i32 = 4;
+
+// Failures that are not already distinguishable by their error type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    Parse(String),
+    NoData(String),
+    // the URL of a file no layer has while the network is disabled
+    Offline(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Parse(message) | Failure::NoData(message) => write!(f, "{}", message),
+            Failure::Offline(url) => {
+                write!(f, "{} is not cached and the network is disabled", url)
+            }
+        }
+    }
+}
+
+impl Error for Failure {}
+
+// A failure kept as a value with its exit code, for one of many downloads
+// that may fail on their own without ending the run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoronaError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl From<Box<dyn Error>> for CoronaError {
+    fn from(error: Box<dyn Error>) -> CoronaError {
+        CoronaError {
+            code: code(&*error),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CoronaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CoronaError {}
+
+pub fn code(error: &(dyn Error + 'static)) -> i32 {
+    let mut current = Some(error);
+    while let Some(e) = current {
+        if let Some(failure) = e.downcast_ref::<CoronaError>() {
+            return failure.code;
+        }
+        match e.downcast_ref::<Failure>() {
+            Some(Failure::Parse(_)) => return PARSE,
+            Some(Failure::NoData(_)) | Some(Failure::Offline(_)) => return NO_DATA,
+            None => {}
+        }
+        if e.is::<NotFound>() {
+            return NO_DATA;
+        }
+        if e.is::<reqwest::Error>() || e.is::<std::io::Error>() {
+            return NETWORK;
+        }
+        if e.is::<csv::Error>()
+            || e.is::<std::num::ParseIntError>()
+            || e.is::<std::num::ParseFloatError>()
+            || e.is::<chrono::ParseError>()
+        {
+            return PARSE;
+        }
+        current = e.source();
+    }
+    NETWORK
+}
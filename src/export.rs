@@ -0,0 +1,824 @@
+use chrono::{Timelike, Utc};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chain::Chain;
+use crate::data::{self, LocationSummary, Metric, TimeSeries};
+use crate::dataset::Dataset;
+use crate::derived::{self, Transform};
+use crate::exit::Failure;
+use crate::filter::LocationFilter;
+use crate::groups;
+use crate::json::{self, Value};
+use crate::output::{OutputWriter, SeriesStream};
+#[cfg(feature = "arrow")]
+use crate::parquet::{self, Column};
+use crate::population::{per_100k, Populations};
+use crate::privacy::Perturbation;
+use crate::provenance::Provenance;
+use crate::svg::escape;
+use crate::table::format_count;
+use crate::xlsx::{self, Cell, Sheet};
+#[cfg(feature = "arrow")]
+use chrono::NaiveDate;
+use tracing::warn;
+
+// How the time series are laid out in a CSV export. Long is one row per
+// date, location and metric, the tidy layout pandas and R work with best;
+// wide has a column per metric.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Long,
+    Wide,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        match s {
+            "long" => Ok(Layout::Long),
+            "wide" => Ok(Layout::Wide),
+            _ => Err(format!("unknown layout '{}', expected long or wide", s)),
+        }
+    }
+}
+
+// Raised whenever a field is removed or changes its meaning, added fields
+// keep the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+// The JSON Schema of `--format json`, printed by `--json-schema`.
+pub const JSON_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "corona-stats export",
+  "type": "object",
+  "required": ["schema_version", "generated_at", "source", "countries"],
+  "properties": {
+    "schema_version": {"const": 1},
+    "generated_at": {"type": "string", "format": "date-time"},
+    "source": {
+      "type": "object",
+      "required": ["name", "url"],
+      "properties": {
+        "name": {"type": "string"},
+        "url": {"type": "string"},
+        "commit": {"type": ["string", "null"]}
+      }
+    },
+    "countries": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "required": ["country", "province", "metrics"],
+        "properties": {
+          "country": {"type": "string"},
+          "province": {"type": "string", "description": "empty for the whole country"},
+          "lat": {"type": ["number", "null"]},
+          "long": {"type": ["number", "null"]},
+          "metrics": {
+            "type": "object",
+            "description": "values per metric (confirmed, deaths, ...) and date, whole numbers for counts, decimals per capita, and ratios for cfr, cfr_lagged, died_share and recovered_share, 0.057 for 5.7%",
+            "additionalProperties": {
+              "type": "object",
+              "propertyNames": {"pattern": "^[0-9]{4}-[0-9]{2}-[0-9]{2}$"},
+              "additionalProperties": {"type": "number"}
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+// "Fully vaccinated" becomes "fully_vaccinated"
+pub fn metric_name(state: &str) -> String {
+    state.to_lowercase().replace(' ', "_")
+}
+
+// The time series of `metric` for the locations of `filter`, with active
+// cases and vaccinations the way `series` shows them, each transformed by
+// `transform` only when it is taken.
+pub fn select<'a>(
+    chain: &Chain,
+    filter: &LocationFilter,
+    metric: Metric,
+    transform: &'a Transform,
+) -> Result<Box<SeriesStream<'a>>, Box<dyn Error>> {
+    let mut series = transform.fill_gaps(data::get_time_series(chain)?, filter);
+    if metric == Metric::Vaccinations {
+        let vaccinations = data::with_names_of(data::get_vaccinations(chain)?, &series);
+        series.extend(vaccinations);
+    }
+    if let Some(group) = &filter.country {
+        let totals = groups::aggregate(&series, group);
+        series.extend(totals);
+    }
+    let daily = derived::derive(&series, metric);
+    series.extend(daily);
+    if metric == Metric::Incidence {
+        let incidence = derived::incidence(&series, &data::get_populations(chain)?);
+        series.extend(incidence);
+    }
+    if metric.includes("Active") {
+        let active = data::active_series(&series);
+        series.extend(active);
+    }
+    series.retain(|s| metric.includes(&s.state) && filter.matches(&s.country, &s.province));
+    if transform.is_identity() {
+        return Ok(Box::new(series.into_iter()));
+    }
+    let populations = match transform.per_capita {
+        Some(_) => data::get_populations(chain)?,
+        None => Populations::default(),
+    };
+    Ok(Box::new(
+        series
+            .into_iter()
+            .filter_map(move |s| transform.apply(&s, &populations)),
+    ))
+}
+
+pub const FORMATS: &[&str] = &["csv", "json", "ndjson", "geojson", "kml", "parquet", "xlsx"];
+
+// The writer of `format` on `out`. The chain is only asked for what a format
+// needs besides the time series, the populations of GeoJSON.
+pub fn writer<'a, W: Write + 'a>(
+    chain: &Chain,
+    format: &str,
+    layout: Layout,
+    out: W,
+) -> Result<Box<dyn OutputWriter + 'a>, Failure> {
+    Ok(match format {
+        "csv" => Box::new(CsvWriter { out, layout }),
+        "json" => Box::new(JsonWriter { out }),
+        "ndjson" => Box::new(NdjsonWriter { out }),
+        "geojson" => {
+            let populations = data::get_populations(chain).unwrap_or_else(|e| {
+                warn!("no populations, the rates per capita are left out: {}", e);
+                Populations::default()
+            });
+            Box::new(GeojsonWriter { out, populations })
+        }
+        "kml" => Box::new(KmlWriter { out }),
+        "parquet" => Box::new(ParquetWriter { out }),
+        "xlsx" => Box::new(XlsxWriter { out }),
+        _ => return Err(unknown_format(format)),
+    })
+}
+
+fn unknown_format(format: &str) -> Failure {
+    Failure::Parse(format!(
+        "unknown export format '{}', expected {}",
+        format,
+        FORMATS.join(", ")
+    ))
+}
+
+// What an export is written as: the format, the layout of CSV and the noise
+// the config file has for the format.
+#[derive(Debug, Clone, Copy)]
+pub struct Target<'a> {
+    pub format: &'a str,
+    pub layout: Layout,
+    pub perturbation: Option<&'a Perturbation>,
+}
+
+// Writes the selected time series as `target` to `output`, stdout without.
+pub fn export(
+    chain: &Chain,
+    filter: &LocationFilter,
+    metric: Metric,
+    transform: &Transform,
+    target: &Target,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    // before the file is created
+    if !FORMATS.contains(&target.format) {
+        return Err(unknown_format(target.format).into());
+    }
+    let stdout = io::stdout();
+    let out: Box<dyn Write> = match output {
+        Some(path) => Box::new(io::BufWriter::new(File::create(path)?)),
+        None => Box::new(io::BufWriter::new(stdout.lock())),
+    };
+    let mut writer = writer(chain, target.format, target.layout, out)?;
+    if let Some(perturbation) = target.perturbation {
+        writer = Box::new(PerturbedWriter {
+            inner: writer,
+            perturbation: perturbation.clone(),
+        });
+    }
+    match export_with(chain, filter, metric, transform, &mut *writer) {
+        // a closed pipe, e.g. `| head`, ends the export early
+        Err(e) if broken_pipe(&*e) => Ok(()),
+        written => written,
+    }
+}
+
+// Writes the selected time series with any writer.
+pub fn export_with(
+    chain: &Chain,
+    filter: &LocationFilter,
+    metric: Metric,
+    transform: &Transform,
+    writer: &mut dyn OutputWriter,
+) -> Result<(), Box<dyn Error>> {
+    let mut series = select(chain, filter, metric, transform)?.peekable();
+    if series.peek().is_none() {
+        return Err(Failure::NoData("no time series match".to_string()).into());
+    }
+    writer.write_stream(&mut series, Some(chain.provenance()))
+}
+
+// Adds the noise of `perturbation` to the small counts before `inner` writes
+// them, a series at a time when the export streams.
+pub struct PerturbedWriter<'a> {
+    pub inner: Box<dyn OutputWriter + 'a>,
+    pub perturbation: Perturbation,
+}
+
+impl OutputWriter for PerturbedWriter<'_> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        let mut series = dataset.series.clone().into_iter();
+        self.write_stream(&mut series, dataset.provenance().cloned())
+    }
+
+    fn write_stream(
+        &mut self,
+        series: &mut SeriesStream,
+        provenance: Option<Provenance>,
+    ) -> Result<(), Box<dyn Error>> {
+        let perturbation = &self.perturbation;
+        let mut perturbed = series.map(|mut s| {
+            perturbation.perturb_series(&mut s);
+            s
+        });
+        self.inner.write_stream(&mut perturbed, provenance)
+    }
+}
+
+pub struct CsvWriter<W> {
+    pub out: W,
+    pub layout: Layout,
+}
+
+impl<W: Write> OutputWriter for CsvWriter<W> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        write_csv(&mut self.out, &dataset.series, self.layout)
+    }
+}
+
+pub struct JsonWriter<W> {
+    pub out: W,
+}
+
+impl<W: Write> OutputWriter for JsonWriter<W> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        // a dataset put together by hand has no source
+        let provenance = dataset.provenance().cloned().unwrap_or_else(|| Provenance {
+            source: String::new(),
+            url: String::new(),
+            fetched: Utc::now()
+                .naive_utc()
+                .with_nanosecond(0)
+                .unwrap_or_default(),
+            commit: None,
+            metrics: Vec::new(),
+        });
+        write_json(&mut self.out, &dataset.series, &provenance)
+    }
+}
+
+pub struct NdjsonWriter<W> {
+    pub out: W,
+}
+
+impl<W: Write> OutputWriter for NdjsonWriter<W> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        write_ndjson(&mut self.out, dataset.series.iter())
+    }
+
+    fn write_stream(
+        &mut self,
+        series: &mut SeriesStream,
+        _provenance: Option<Provenance>,
+    ) -> Result<(), Box<dyn Error>> {
+        write_ndjson(&mut self.out, series)
+    }
+}
+
+pub struct GeojsonWriter<W> {
+    pub out: W,
+    pub populations: Populations,
+}
+
+impl<W: Write> OutputWriter for GeojsonWriter<W> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        write_geojson(&mut self.out, &dataset.series, &self.populations)
+    }
+}
+
+pub struct KmlWriter<W> {
+    pub out: W,
+}
+
+impl<W: Write> OutputWriter for KmlWriter<W> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        write_kml(&mut self.out, &dataset.series)
+    }
+}
+
+pub struct ParquetWriter<W> {
+    pub out: W,
+}
+
+impl<W: Write> OutputWriter for ParquetWriter<W> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        write_parquet(&mut self.out, &dataset.series)
+    }
+}
+
+pub struct XlsxWriter<W> {
+    pub out: W,
+}
+
+impl<W: Write> OutputWriter for XlsxWriter<W> {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+        write_xlsx(&mut self.out, &dataset.series)
+    }
+}
+
+fn broken_pipe(error: &(dyn Error + 'static)) -> bool {
+    let io = match error.downcast_ref::<csv::Error>() {
+        Some(e) => match e.kind() {
+            csv::ErrorKind::Io(e) => Some(e),
+            _ => None,
+        },
+        None => error.downcast_ref::<io::Error>(),
+    };
+    io.map(|e| e.kind() == io::ErrorKind::BrokenPipe)
+        .unwrap_or(false)
+}
+
+fn write_json<W: Write>(
+    mut out: W,
+    series: &[TimeSeries],
+    provenance: &Provenance,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "{}", to_json(series, provenance))?;
+    out.flush()?;
+    Ok(())
+}
+
+// The long layout as a Parquet file with a date column, for polars, pandas
+// or DuckDB to load directly.
+#[cfg(feature = "arrow")]
+pub fn write_parquet<W: Write>(out: W, series: &[TimeSeries]) -> Result<(), Box<dyn Error>> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    let rows: usize = series.iter().map(|s| s.data.len()).sum();
+    let (mut dates, mut countries, mut provinces, mut metrics, mut values) = (
+        Vec::with_capacity(rows),
+        Vec::with_capacity(rows),
+        Vec::with_capacity(rows),
+        Vec::with_capacity(rows),
+        Vec::with_capacity(rows),
+    );
+    for s in series.iter() {
+        let metric = metric_name(&s.state);
+        for (date, value) in s.data.iter() {
+            let date = date.parse::<NaiveDate>()?;
+            dates.push((date - epoch).num_days() as i32);
+            countries.push(s.country.clone());
+            provinces.push(s.province.clone());
+            metrics.push(metric.clone());
+            values.push((*value, s.value(*value)));
+        }
+    }
+    // whole numbers unless a series keeps decimals
+    let values = if series.iter().all(|s| s.scale <= 1) {
+        Column::Int32(values.into_iter().map(|(value, _)| value).collect())
+    } else {
+        Column::Double(values.into_iter().map(|(_, value)| value).collect())
+    };
+    parquet::write(
+        out,
+        &[
+            ("date", Column::Date(dates)),
+            ("country", Column::Utf8(countries)),
+            ("province", Column::Utf8(provinces)),
+            ("metric", Column::Utf8(metrics)),
+            ("value", values),
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(not(feature = "arrow"))]
+pub fn write_parquet<W: Write>(_out: W, _series: &[TimeSeries]) -> Result<(), Box<dyn Error>> {
+    Err(Failure::Parse("parquet needs a build with the arrow feature".to_string()).into())
+}
+
+// values by country and date
+type ByCountry<'a> = BTreeMap<&'a str, BTreeMap<&'a str, f64>>;
+
+// A workbook for spreadsheet users: a summary sheet with the latest values
+// per country, then a sheet per metric with a row per country and a column
+// per date. Provinces are summed up into their country.
+pub fn write_xlsx<W: Write>(mut out: W, series: &[TimeSeries]) -> Result<(), Box<dyn Error>> {
+    let mut metrics: Vec<(&str, ByCountry)> = Vec::new();
+    for s in series.iter() {
+        let index = match metrics.iter().position(|(m, _)| *m == s.state) {
+            Some(index) => index,
+            None => {
+                metrics.push((&s.state, BTreeMap::new()));
+                metrics.len() - 1
+            }
+        };
+        let country = metrics[index].1.entry(&s.country).or_default();
+        for (date, value) in s.data.iter() {
+            *country.entry(date).or_default() += s.value(*value);
+        }
+    }
+    let mut countries: Vec<&str> = metrics
+        .iter()
+        .flat_map(|(_, by_country)| by_country.keys().copied())
+        .collect();
+    countries.sort_unstable();
+    countries.dedup();
+
+    let text = |s: &str| Cell::Text(s.to_string());
+    let mut header = vec![text("Country")];
+    header.extend(metrics.iter().map(|(m, _)| text(m)));
+    header.push(text("As of"));
+    let mut summary = vec![header];
+    for country in countries.iter() {
+        let mut row = vec![text(country)];
+        let mut as_of = "";
+        for (_, by_country) in metrics.iter() {
+            match by_country.get(country).and_then(|d| d.iter().next_back()) {
+                Some((date, value)) => {
+                    as_of = as_of.max(date);
+                    row.push(Cell::Number(*value));
+                }
+                None => row.push(Cell::Empty),
+            }
+        }
+        row.push(text(as_of));
+        summary.push(row);
+    }
+    let mut sheets = vec![Sheet {
+        name: "Summary".to_string(),
+        rows: summary,
+        header: true,
+    }];
+
+    for (metric, by_country) in metrics.iter() {
+        let mut dates: Vec<&str> = by_country
+            .values()
+            .flat_map(|d| d.keys().copied())
+            .collect();
+        dates.sort_unstable();
+        dates.dedup();
+        let mut header = vec![text("Country")];
+        header.extend(dates.iter().map(|d| text(d)));
+        let mut rows = vec![header];
+        for (country, values) in by_country.iter() {
+            let mut row = vec![text(country)];
+            row.extend(dates.iter().map(|d| match values.get(d) {
+                Some(value) => Cell::Number(*value),
+                None => Cell::Empty,
+            }));
+            rows.push(row);
+        }
+        sheets.push(Sheet {
+            name: metric.to_string(),
+            rows,
+            header: true,
+        });
+    }
+    out.write_all(&xlsx::workbook(&sheets))?;
+    out.flush()?;
+    Ok(())
+}
+
+// A JSON object per line with the fields of the long CSV layout, written a
+// series at a time as they come: the lines of a series are out before the
+// next one is derived or transformed.
+pub fn write_ndjson<W, S, I>(mut out: W, series: I) -> Result<(), Box<dyn Error>>
+where
+    W: Write,
+    S: Borrow<TimeSeries>,
+    I: IntoIterator<Item = S>,
+{
+    for s in series {
+        let s = s.borrow();
+        // the same for every line of the series
+        let location = format!(
+            "\"country\":{},\"province\":{},\"metric\":{}",
+            json::quote(&s.country),
+            json::quote(&s.province),
+            json::quote(&metric_name(&s.state))
+        );
+        for (date, value) in s.data.iter() {
+            writeln!(
+                out,
+                "{{\"date\":{},{},\"value\":{}}}",
+                json::quote(date),
+                location,
+                s.format_value(*value)
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Coordinates as they were written in the data, 47.5162 rather than the
+// 47.516201 of the f32 widened.
+fn degrees(value: f32) -> Value {
+    Value::Number(
+        value
+            .to_string()
+            .parse()
+            .unwrap_or_else(|_| f64::from(value)),
+    )
+}
+
+// The latest numbers of every location with its latitude and longitude,
+// locations without coordinates are left out.
+fn located(series: &[TimeSeries]) -> Vec<(LocationSummary, f32, f32)> {
+    let mut coordinates = HashMap::new();
+    for s in series.iter() {
+        // unknown places sit at 0, 0 in some versions of the data
+        if let (Some(lat), Some(long)) = (s.lat, s.long) {
+            if lat != 0.0 || long != 0.0 {
+                coordinates.insert((s.country.as_str(), s.province.as_str()), (lat, long));
+            }
+        }
+    }
+    data::latest_by_location(series)
+        .into_iter()
+        .filter_map(|l| {
+            let (lat, long) = *coordinates.get(&(l.country.as_str(), l.province.as_str()))?;
+            Some((l, lat, long))
+        })
+        .collect()
+}
+
+// A FeatureCollection with a point per location and its latest numbers, to
+// drop into Leaflet, Mapbox or QGIS.
+pub fn write_geojson<W: Write>(
+    mut out: W,
+    series: &[TimeSeries],
+    populations: &Populations,
+) -> Result<(), Box<dyn Error>> {
+    let features = located(series)
+        .into_iter()
+        .map(|(l, lat, long)| {
+            let population = populations.get(&l.country, &l.province);
+            let rate = |value: u64| {
+                population
+                    .and_then(|p| per_100k(value, p))
+                    .map(|r| Value::Number((r * 100.0).round() / 100.0))
+                    .unwrap_or(Value::Null)
+            };
+            let properties = vec![
+                ("country".to_string(), Value::String(l.country.clone())),
+                ("province".to_string(), Value::String(l.province.clone())),
+                ("date".to_string(), Value::String(l.date.clone())),
+                ("confirmed".to_string(), Value::Number(l.confirmed as f64)),
+                ("deaths".to_string(), Value::Number(l.deaths as f64)),
+                ("recovered".to_string(), Value::Number(l.recovered as f64)),
+                (
+                    "population".to_string(),
+                    population
+                        .map(|p| Value::Number(p as f64))
+                        .unwrap_or(Value::Null),
+                ),
+                ("confirmed_per_100k".to_string(), rate(l.confirmed)),
+                ("deaths_per_100k".to_string(), rate(l.deaths)),
+            ];
+            Value::Object(vec![
+                ("type".to_string(), Value::String("Feature".to_string())),
+                (
+                    "geometry".to_string(),
+                    Value::Object(vec![
+                        ("type".to_string(), Value::String("Point".to_string())),
+                        (
+                            "coordinates".to_string(),
+                            Value::Array(vec![degrees(long), degrees(lat)]),
+                        ),
+                    ]),
+                ),
+                ("properties".to_string(), Value::Object(properties)),
+            ])
+        })
+        .collect();
+    let collection = Value::Object(vec![
+        (
+            "type".to_string(),
+            Value::String("FeatureCollection".to_string()),
+        ),
+        ("features".to_string(), Value::Array(features)),
+    ]);
+    writeln!(out, "{}", collection)?;
+    out.flush()?;
+    Ok(())
+}
+
+// Marker colors from yellow to dark red, in the aabbggrr of KML, a level per
+// power of ten of confirmed cases.
+const KML_COLORS: &[&str] = &[
+    "ffb2ffff", "ff76d9fe", "ff4cb2fe", "ff3c8dfd", "ff2a4efc", "ff1c1ae3", "ff2600b1",
+];
+
+fn kml_level(confirmed: u64) -> usize {
+    let digits = confirmed.max(1).to_string().len();
+    (digits - 1).min(KML_COLORS.len() - 1)
+}
+
+// A KML document for Google Earth with a placemark per location, larger and
+// redder the more cases were confirmed.
+pub fn write_kml<W: Write>(mut out: W, series: &[TimeSeries]) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>"
+    )?;
+    writeln!(out, "<name>corona-stats</name>")?;
+    for (level, color) in KML_COLORS.iter().enumerate() {
+        writeln!(
+            out,
+            "<Style id=\"level{}\"><IconStyle><color>{}</color><scale>{:.1}</scale>\
+             <Icon><href>http://maps.google.com/mapfiles/kml/shapes/placemark_circle.png</href></Icon>\
+             </IconStyle></Style>",
+            level,
+            color,
+            0.6 + 0.3 * level as f64
+        )?;
+    }
+    for (l, lat, long) in located(series).iter() {
+        let name = if l.province.is_empty() {
+            l.country.clone()
+        } else {
+            format!("{}, {}", l.province, l.country)
+        };
+        writeln!(
+            out,
+            "<Placemark><name>{}</name><description>{} confirmed, {} deaths, {} recovered as of {}</description>\
+             <styleUrl>#level{}</styleUrl><ExtendedData>\
+             <Data name=\"confirmed\"><value>{}</value></Data>\
+             <Data name=\"deaths\"><value>{}</value></Data>\
+             <Data name=\"recovered\"><value>{}</value></Data>\
+             </ExtendedData><Point><coordinates>{},{},0</coordinates></Point></Placemark>",
+            escape(&name),
+            format_count(l.confirmed),
+            format_count(l.deaths),
+            format_count(l.recovered),
+            escape(&l.date),
+            kml_level(l.confirmed),
+            l.confirmed,
+            l.deaths,
+            l.recovered,
+            long,
+            lat
+        )?;
+    }
+    writeln!(out, "</Document>\n</kml>")?;
+    out.flush()?;
+    Ok(())
+}
+
+fn number(value: Option<f32>) -> Value {
+    value
+        .map(|v| Value::Number(f64::from(v)))
+        .unwrap_or(Value::Null)
+}
+
+// The document described by `JSON_SCHEMA`, a member of `countries` per
+// location.
+pub fn to_json(series: &[TimeSeries], provenance: &Provenance) -> Value {
+    let mut locations: Vec<(&TimeSeries, Vec<(String, Value)>)> = Vec::new();
+    for s in series.iter() {
+        let values = s
+            .data
+            .iter()
+            .map(|(date, value)| (date.clone(), Value::Number(s.value(*value))))
+            .collect();
+        let metric = (metric_name(&s.state), Value::Object(values));
+        match locations
+            .iter_mut()
+            .find(|(l, _)| l.country == s.country && l.province == s.province)
+        {
+            Some((_, metrics)) => metrics.push(metric),
+            None => locations.push((s, vec![metric])),
+        }
+    }
+    let countries = locations
+        .into_iter()
+        .map(|(s, metrics)| {
+            Value::Object(vec![
+                ("country".to_string(), Value::String(s.country.clone())),
+                ("province".to_string(), Value::String(s.province.clone())),
+                ("lat".to_string(), number(s.lat)),
+                ("long".to_string(), number(s.long)),
+                ("metrics".to_string(), Value::Object(metrics)),
+            ])
+        })
+        .collect();
+    let mut source = vec![
+        ("name".to_string(), Value::String(provenance.source.clone())),
+        ("url".to_string(), Value::String(provenance.url.clone())),
+        (
+            "commit".to_string(),
+            provenance
+                .commit
+                .clone()
+                .map(Value::String)
+                .unwrap_or(Value::Null),
+        ),
+    ];
+    // only merged sources tell where each metric came from
+    if !provenance.metrics.is_empty() {
+        let metrics = provenance
+            .metrics
+            .iter()
+            .map(|(metric, sources)| (metric.clone(), Value::String(sources.clone())))
+            .collect();
+        source.push(("metrics".to_string(), Value::Object(metrics)));
+    }
+    let source = Value::Object(source);
+    Value::Object(vec![
+        (
+            "schema_version".to_string(),
+            Value::Number(f64::from(SCHEMA_VERSION)),
+        ),
+        (
+            "generated_at".to_string(),
+            Value::String(provenance.fetched()),
+        ),
+        ("source".to_string(), source),
+        ("countries".to_string(), Value::Array(countries)),
+    ])
+}
+
+pub fn write_csv<W: Write>(
+    out: W,
+    series: &[TimeSeries],
+    layout: Layout,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = csv::Writer::from_writer(out);
+    match layout {
+        Layout::Long => {
+            wtr.write_record(["date", "country", "province", "metric", "value"])?;
+            for s in series.iter() {
+                let metric = metric_name(&s.state);
+                for (date, value) in s.data.iter() {
+                    wtr.write_record([
+                        date.as_str(),
+                        &s.country,
+                        &s.province,
+                        &metric,
+                        &s.format_value(*value),
+                    ])?;
+                }
+            }
+        }
+        Layout::Wide => {
+            let mut metrics: Vec<&str> = Vec::new();
+            for s in series.iter() {
+                if !metrics.contains(&s.state.as_str()) {
+                    metrics.push(&s.state);
+                }
+            }
+            let mut header = vec!["date".to_string(), "country".into(), "province".into()];
+            header.extend(metrics.iter().map(|m| metric_name(m)));
+            wtr.write_record(&header)?;
+            // location, then date, a cell per metric
+            let mut rows: BTreeMap<(&str, &str, &str), Vec<Option<String>>> = BTreeMap::new();
+            for s in series.iter() {
+                let column = metrics.iter().position(|m| *m == s.state).unwrap_or(0);
+                for (date, value) in s.data.iter() {
+                    let row = rows
+                        .entry((&s.country, &s.province, date))
+                        .or_insert_with(|| vec![None; metrics.len()]);
+                    row[column] = Some(s.format_value(*value));
+                }
+            }
+            for ((country, province, date), cells) in rows.iter() {
+                let mut record = vec![date.to_string(), country.to_string(), province.to_string()];
+                record.extend(cells.iter().map(|c| c.clone().unwrap_or_default()));
+                wtr.write_record(&record)?;
+            }
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,97 @@
+// An Atom feed (RFC 4287) of a country with an entry per day, for feed
+// readers to pick up the new numbers.
+
+use chrono::NaiveDate;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::chain::Chain;
+use crate::data;
+use crate::exit::Failure;
+use crate::provenance::Provenance;
+use crate::report::slug;
+use crate::svg::escape;
+use crate::table::{format_change, format_signed};
+
+// days with an entry, the newest first
+const DAYS: usize = 30;
+const WEEK: usize = 7;
+
+fn timestamp(date: &NaiveDate) -> String {
+    format!("{}T00:00:00Z", date)
+}
+
+// The feed of `country` from its cumulative confirmed cases and deaths.
+pub fn atom(
+    country: &str,
+    confirmed: &[(NaiveDate, i64)],
+    deaths: &[(NaiveDate, i64)],
+    provenance: &Provenance,
+) -> String {
+    let value = |series: &[(NaiveDate, i64)], date: &NaiveDate| {
+        series
+            .iter()
+            .find(|(d, _)| d == date)
+            .map(|(_, v)| *v)
+            .unwrap_or(0)
+    };
+    let updated = confirmed
+        .last()
+        .map(|(date, _)| timestamp(date))
+        .unwrap_or_else(|| provenance.fetched());
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <id>tag:corona-stats,2020:{}</id>\n<title>COVID-19 in {}</title>\n<updated>{}</updated>\n\
+         <author><name>corona-stats</name></author>\n<link href=\"{}\"/>\n\
+         <generator version=\"{}\">corona-stats</generator>\n",
+        slug(country),
+        escape(country),
+        updated,
+        escape(&provenance.url),
+        env!("CARGO_PKG_VERSION")
+    );
+    for i in (1..confirmed.len()).rev().take(DAYS) {
+        let (date, total) = confirmed[i];
+        let cases = total - confirmed[i - 1].1;
+        let total_deaths = value(deaths, &date);
+        let new_deaths = total_deaths - value(deaths, &confirmed[i - 1].0);
+        let week = &confirmed[(i + 1).saturating_sub(WEEK + 1)..=i];
+        let average = (total - week[0].1) as f64 / (week.len() - 1).max(1) as f64;
+        out.push_str(&format!(
+            "<entry>\n<id>tag:corona-stats,2020:{}/{}</id>\n<title>{} on {}: {} cases, {} deaths</title>\n\
+             <updated>{}</updated>\n<summary>{} new cases and {} new deaths, {} confirmed cases and {} deaths in total. \
+             {} new cases a day over the last {} days.</summary>\n</entry>\n",
+            slug(country),
+            date,
+            escape(country),
+            date,
+            format_change(cases),
+            format_change(new_deaths),
+            timestamp(&date),
+            format_signed(cases),
+            format_signed(new_deaths),
+            format_signed(total),
+            format_signed(total_deaths),
+            format_signed(average.round() as i64),
+            WEEK
+        ));
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+pub fn feed(chain: &Chain, country: &str, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let series = data::get_time_series(chain)?;
+    let confirmed = data::country_series(&series, country, "Confirmed");
+    if confirmed.is_empty() {
+        return Err(Failure::NoData(format!("no data for {}", country)).into());
+    }
+    let deaths = data::country_series(&series, country, "Deaths");
+    let feed = atom(country, &confirmed, &deaths, &chain.provenance());
+    match output {
+        Some(path) => fs::write(path, feed)?,
+        None => print!("{}", feed),
+    }
+    Ok(())
+}
@@ -0,0 +1,290 @@
+// Selection of locations by name: an exact country, a regular expression on
+// the country, a shell style glob on the province and the members of a
+// group. All given parts have to match.
+
+#[derive(Debug, Clone, Default)]
+pub struct LocationFilter {
+    pub country: Option<String>,
+    pub country_regex: Option<Regex>,
+    pub province_glob: Option<Glob>,
+    // the countries of --group
+    pub group: Option<Vec<String>>,
+}
+
+impl LocationFilter {
+    pub fn matches(&self, country: &str, province: &str) -> bool {
+        self.country.as_ref().map(|c| c == country).unwrap_or(true)
+            && self
+                .country_regex
+                .as_ref()
+                .map(|r| r.is_match(country))
+                .unwrap_or(true)
+            && self
+                .province_glob
+                .as_ref()
+                .map(|g| g.is_match(province))
+                .unwrap_or(true)
+            && self
+                .group
+                .as_ref()
+                .map(|g| g.iter().any(|c| c == country))
+                .unwrap_or(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.country.is_none()
+            && self.country_regex.is_none()
+            && self.province_glob.is_none()
+            && self.group.is_none()
+    }
+}
+
+// `*` matches any run of characters, `?` a single one and `[abc]`/`[a-z]` a
+// set, like file name globs in a shell.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, String> {
+        let mut translated = String::from("^");
+        let mut in_class = false;
+        for c in pattern.chars() {
+            match c {
+                '[' if !in_class => {
+                    in_class = true;
+                    translated.push('[');
+                }
+                ']' if in_class => {
+                    in_class = false;
+                    translated.push(']');
+                }
+                '!' if in_class && translated.ends_with('[') => translated.push('^'),
+                _ if in_class => translated.push(c),
+                '*' => translated.push_str(".*"),
+                '?' => translated.push('.'),
+                _ => {
+                    if "\\.+()|^$".contains(c) {
+                        translated.push('\\');
+                    }
+                    translated.push(c);
+                }
+            }
+        }
+        translated.push('$');
+        Ok(Glob {
+            regex: Regex::new(&translated)?,
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+// A small backtracking regular expression engine covering what is useful for
+// location names: literals, `.`, classes (`[a-z]`, `[^,]`, `\d`, `\w`, `\s`),
+// groups, alternation, the `* + ?` quantifiers and the `^ $` anchors. A match
+// anywhere in the text counts unless the pattern is anchored.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    node: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let node = parse_alt(&chars, &mut pos)?;
+        if pos < chars.len() {
+            return Err(format!("unexpected ')' in regex '{}'", pattern));
+        }
+        Ok(Regex { node })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        (0..=chars.len()).any(|start| matches(&self.node, &chars, start, &mut |_| true))
+    }
+}
+
+fn parse_alt(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    let mut branches = vec![parse_concat(chars, pos)?];
+    while *pos < chars.len() && chars[*pos] == '|' {
+        *pos += 1;
+        branches.push(parse_concat(chars, pos)?);
+    }
+    Ok(if branches.len() == 1 {
+        branches.pop().unwrap()
+    } else {
+        Node::Alt(branches)
+    })
+}
+
+fn parse_concat(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    let mut items = Vec::new();
+    while *pos < chars.len() && chars[*pos] != '|' && chars[*pos] != ')' {
+        let atom = parse_atom(chars, pos)?;
+        let atom = match chars.get(*pos) {
+            Some('*') => Node::Repeat(Box::new(atom), 0, None),
+            Some('+') => Node::Repeat(Box::new(atom), 1, None),
+            Some('?') => Node::Repeat(Box::new(atom), 0, Some(1)),
+            _ => {
+                items.push(atom);
+                continue;
+            }
+        };
+        *pos += 1;
+        items.push(atom);
+    }
+    Ok(match items.len() {
+        0 => Node::Empty,
+        1 => items.pop().unwrap(),
+        _ => Node::Concat(items),
+    })
+}
+
+fn parse_atom(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    let c = chars[*pos];
+    *pos += 1;
+    Ok(match c {
+        '.' => Node::Any,
+        '^' => Node::Start,
+        '$' => Node::End,
+        '(' => {
+            let inner = parse_alt(chars, pos)?;
+            if chars.get(*pos) != Some(&')') {
+                return Err("missing ')' in regex".to_string());
+            }
+            *pos += 1;
+            inner
+        }
+        '[' => parse_class(chars, pos)?,
+        '\\' => {
+            let escaped = *chars.get(*pos).ok_or("trailing '\\' in regex")?;
+            *pos += 1;
+            escape(escaped)
+        }
+        '*' | '+' | '?' => return Err(format!("nothing to repeat before '{}'", c)),
+        _ => Node::Char(c),
+    })
+}
+
+fn escape(c: char) -> Node {
+    match c {
+        'd' => Node::Class(vec![('0', '9')], false),
+        'w' => Node::Class(vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')], false),
+        's' => Node::Class(
+            vec![(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')],
+            false,
+        ),
+        _ => Node::Char(c),
+    }
+}
+
+fn parse_class(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    let negated = chars.get(*pos) == Some(&'^');
+    if negated {
+        *pos += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(*pos).ok_or("missing ']' in regex")?;
+        *pos += 1;
+        if c == ']' && !first {
+            break;
+        }
+        first = false;
+        let c = if c == '\\' {
+            let escaped = *chars.get(*pos).ok_or("trailing '\\' in regex")?;
+            *pos += 1;
+            if let Node::Class(r, _) = escape(escaped) {
+                ranges.extend(r);
+                continue;
+            }
+            escaped
+        } else {
+            c
+        };
+        if chars.get(*pos) == Some(&'-') && chars.get(*pos + 1).map(|n| *n != ']').unwrap_or(false)
+        {
+            ranges.push((c, chars[*pos + 1]));
+            *pos += 2;
+        } else {
+            ranges.push((c, c));
+        }
+    }
+    Ok(Node::Class(ranges, negated))
+}
+
+// Continuation passing matcher: `next` is called with every position the
+// node can end at until one of them lets the rest of the pattern match.
+fn matches(node: &Node, text: &[char], at: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+    match node {
+        Node::Empty => next(at),
+        Node::Char(c) => text.get(at) == Some(c) && next(at + 1),
+        Node::Any => at < text.len() && next(at + 1),
+        Node::Class(ranges, negated) => match text.get(at) {
+            Some(c) => {
+                let inside = ranges.iter().any(|(lo, hi)| lo <= c && c <= hi);
+                inside != *negated && next(at + 1)
+            }
+            None => false,
+        },
+        Node::Start => at == 0 && next(at),
+        Node::End => at == text.len() && next(at),
+        Node::Concat(items) => concat(items, text, at, next),
+        Node::Alt(branches) => branches.iter().any(|b| matches(b, text, at, next)),
+        Node::Repeat(inner, min, max) => repeat(inner, *min, *max, text, at, next),
+    }
+}
+
+fn concat(items: &[Node], text: &[char], at: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+    match items.split_first() {
+        Some((first, rest)) => matches(first, text, at, &mut |end| concat(rest, text, end, next)),
+        None => next(at),
+    }
+}
+
+// Greedy: tries to take one more repetition before handing over to `next`.
+fn repeat(
+    inner: &Node,
+    min: usize,
+    max: Option<usize>,
+    text: &[char],
+    at: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    if max != Some(0)
+        && matches(inner, text, at, &mut |end| {
+            // an empty repetition would loop forever
+            end > at
+                && repeat(
+                    inner,
+                    min.saturating_sub(1),
+                    max.map(|m| m - 1),
+                    text,
+                    end,
+                    next,
+                )
+        })
+    {
+        return true;
+    }
+    min == 0 && next(at)
+}
@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::{debug, info, warn};
+
+use crate::chain::Layer;
+use crate::data::{GITHUB_API, UPSTREAM};
+
+pub const REPOSITORY: &str = "https://github.com/CSSEGISandData/COVID-19.git";
+const BRANCH: &str = "master";
+// the repository also has years of web site and documentation changes, only
+// the data is checked out
+const SPARSE: &[&str] = &["csse_covid_19_data", "archived_data"];
+
+// Brings the checkout in `dir` to `rev` (a commit, tag or branch, the
+// upstream branch without one) with a shallow fetch of that single commit. A
+// checkout that is already at the pinned commit is used as it is, so pinned
+// runs need no network at all.
+pub fn sync(dir: &Path, url: &str, rev: Option<&str>, quiet: bool) -> Result<(), Box<dyn Error>> {
+    if let (Some(rev), Some(head)) = (rev, head(dir)) {
+        if head.starts_with(rev) {
+            debug!(dir = %dir.display(), %head, "checkout is at the pinned commit");
+            return Ok(());
+        }
+    }
+    if !dir.join(".git").exists() {
+        fs::create_dir_all(dir)?;
+        git(dir, &["init", "-q"], quiet)?;
+        let mut sparse = vec!["sparse-checkout", "set"];
+        sparse.extend_from_slice(SPARSE);
+        git(dir, &sparse, quiet)?;
+    }
+    let target = rev.unwrap_or(BRANCH);
+    info!(repository = url, rev = target, "fetching");
+    if let Err(e) = git(dir, &["fetch", "-q", "--depth", "1", url, target], quiet) {
+        // without network the last checkout is still better than nothing,
+        // unless a specific commit was asked for
+        if rev.is_none() && head(dir).is_some() {
+            warn!("{}, using the existing checkout", e);
+            return Ok(());
+        }
+        return Err(e);
+    }
+    git(
+        dir,
+        &["checkout", "-q", "--force", "--detach", "FETCH_HEAD"],
+        quiet,
+    )?;
+    info!(head = head(dir).as_deref().unwrap_or("?"), "checked out");
+    Ok(())
+}
+
+// The commit checked out in `dir`.
+pub fn head(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "HEAD"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn git(dir: &Path, args: &[&str], quiet: bool) -> Result<(), Box<dyn Error>> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(args).stdout(Stdio::null());
+    if quiet {
+        command.stderr(Stdio::null());
+    }
+    let status = command
+        .status()
+        .map_err(|e| format!("could not run git: {}", e))?;
+    if !status.success() {
+        return Err(format!("git {} failed with {}", args.join(" "), status).into());
+    }
+    Ok(())
+}
+
+// The files in `dir` the way the GitHub contents API lists them.
+pub(crate) fn listing(dir: &Path) -> Option<String> {
+    let entries: Vec<String> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|e| {
+            format!(
+                "{{\"name\": \"{}\", \"type\": \"file\"}}",
+                e.file_name().to_string_lossy()
+            )
+        })
+        .collect();
+    Some(format!("[{}]", entries.join(", ")))
+}
+
+// Answers upstream URLs from a checkout of the repository, and the GitHub
+// contents API with a listing of the checked out directory. Files missing
+// from the checkout do not exist upstream either.
+pub struct GitLayer {
+    dir: PathBuf,
+}
+
+impl GitLayer {
+    pub fn new(dir: PathBuf) -> GitLayer {
+        GitLayer { dir }
+    }
+}
+
+impl Layer for GitLayer {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        if let Some(path) = key.strip_prefix(UPSTREAM) {
+            return fs::read_to_string(self.dir.join(path)).ok();
+        }
+        listing(&self.dir.join(key.strip_prefix(GITHUB_API)?))
+    }
+
+    // the checkout is only ever changed by git
+    fn put(&self, _key: &str, _body: &str) {}
+
+    fn covers(&self, key: &str) -> bool {
+        key.starts_with(UPSTREAM) || key.starts_with(GITHUB_API)
+    }
+}
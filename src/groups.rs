@@ -0,0 +1,74 @@
+// Named groups of countries from the [groups] section of the config file,
+// e.g. `DACH = ["Germany", "Austria", "Switzerland"]`. A group name works
+// wherever a country does: its time series are the sums of its members'.
+// Chosen once per run like the locale.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use crate::data::TimeSeries;
+
+static GROUPS: RwLock<Vec<(String, Vec<String>)>> = RwLock::new(Vec::new());
+
+pub fn init(groups: &[(String, Vec<String>)]) {
+    if let Ok(mut current) = GROUPS.write() {
+        *current = groups.to_vec();
+    }
+}
+
+// The countries of the group `name`, `None` for anything but a group.
+pub fn members(name: &str) -> Option<Vec<String>> {
+    GROUPS
+        .read()
+        .ok()?
+        .iter()
+        .find(|(group, _)| group == name)
+        .map(|(_, members)| members.clone())
+}
+
+pub fn names() -> Vec<String> {
+    GROUPS
+        .read()
+        .map(|groups| groups.iter().map(|(name, _)| name.clone()).collect())
+        .unwrap_or_default()
+}
+
+// Whether `country` is `name` or, for a group, one of its members.
+pub fn covers(name: &str, country: &str) -> bool {
+    name == country || members(name).is_some_and(|members| members.iter().any(|m| m == country))
+}
+
+// One series of every state for the group `name`, each the sum of the
+// series of all members and their provinces. A day missing for a member
+// counts as nothing. Empty for anything but a group.
+pub fn aggregate(series: &[TimeSeries], name: &str) -> Vec<TimeSeries> {
+    let members = match members(name) {
+        Some(members) => members,
+        None => return Vec::new(),
+    };
+    let mut states: BTreeMap<&str, BTreeMap<String, i64>> = BTreeMap::new();
+    for s in series.iter().filter(|s| members.contains(&s.country)) {
+        let totals = states.entry(s.state.as_str()).or_default();
+        for (date, value) in s.data.iter() {
+            *totals.entry(date.clone()).or_insert(0) += i64::from(*value);
+        }
+    }
+    states
+        .into_iter()
+        .map(|(state, totals)| TimeSeries {
+            province: String::new(),
+            country: name.to_string(),
+            lat: None,
+            long: None,
+            data: totals
+                .into_iter()
+                .map(|(date, value)| {
+                    let value = value.clamp(i64::from(i32::MIN), i64::from(i32::MAX));
+                    (date, value as i32)
+                })
+                .collect(),
+            state: state.to_string(),
+            scale: 1,
+        })
+        .collect()
+}
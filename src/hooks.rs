@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::data::{parse_report, write_report, Record};
+
+// User supplied shell commands run at fixed points of long running modes, so
+// operators can add notifications or small transforms without patching the
+// crate. Each hook gets its payload on stdin and a few CORONA_* variables.
+// They are commands rather than rhai or lua scripts: an embedded interpreter
+// would be a large dependency for a few lines of glue, and through `sh -c` a
+// hook can be written in any language installed, rhai and lua included.
+//
+// - on_refresh: after every successful refresh, stdin holds the changes
+// - on_alert: when a threshold triggers, stdin holds the alert message
+// - transform_record: a filter that receives the records of every daily report
+//   loaded as CSV and prints the (possibly modified or reduced) CSV to keep
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hooks {
+    pub on_refresh: Option<String>,
+    pub on_alert: Option<String>,
+    pub transform_record: Option<String>,
+}
+
+impl Hooks {
+    pub fn refresh(&self, changes: &[String]) -> Result<(), Box<dyn Error>> {
+        if let Some(command) = &self.on_refresh {
+            let count = changes.len().to_string();
+            run(
+                command,
+                &[("CORONA_HOOK", "on_refresh"), ("CORONA_CHANGES", &count)],
+                &changes.join("\n"),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn alert(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(command) = &self.on_alert {
+            run(command, &[("CORONA_HOOK", "on_alert")], message)?;
+        }
+        Ok(())
+    }
+
+    pub fn transform(&self, records: Vec<Record>) -> Result<Vec<Record>, Box<dyn Error>> {
+        match &self.transform_record {
+            Some(command) => {
+                let output = run(
+                    command,
+                    &[("CORONA_HOOK", "transform_record")],
+                    &write_report(&records)?,
+                )?;
+                parse_report(&output)
+            }
+            None => Ok(records),
+        }
+    }
+}
+
+fn run(command: &str, env: &[(&str, &str)], input: &str) -> Result<String, Box<dyn Error>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().cloned())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    // written while the output is read, a hook that answers before it has
+    // read all of its input would otherwise block both sides on full pipes
+    let writer = child.stdin.take().map(|mut stdin| {
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()))
+    });
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        // a hook may exit without reading everything, a broken pipe is fine
+        match writer.join() {
+            Ok(Err(e)) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+    }
+    if !output.status.success() {
+        return Err(format!("hook '{}' failed with {}", command, output.status).into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,278 @@
+// Translations of the column headers and labels of the table output, chosen
+// once per run by --lang or the environment. Labels are keyed by their
+// English text, anything without a translation is shown as it is.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+type Translations = &'static [(&'static str, &'static str)];
+
+const DE: Translations = &[
+    ("Country", "Land"),
+    ("Province", "Provinz"),
+    ("State", "Bundesstaat"),
+    ("Date", "Datum"),
+    ("Day", "Tag"),
+    ("Confirmed", "Bestätigt"),
+    ("Deaths", "Todesfälle"),
+    ("Recovered", "Genesen"),
+    ("Active", "Aktiv"),
+    ("New cases", "Neue Fälle"),
+    ("New deaths", "Neue Todesfälle"),
+    ("Tested", "Getestet"),
+    ("Per 100k", "Je 100.000"),
+    ("As of", "Stand"),
+    ("Total", "Gesamt"),
+    ("World", "Welt"),
+    ("Share", "Anteil"),
+    ("Wave", "Welle"),
+    ("Start", "Beginn"),
+    ("Days to peak", "Tage bis Höhepunkt"),
+    ("Peak (7d avg)", "Höhepunkt (7-T.-Mittel)"),
+    ("Duration", "Dauer"),
+    ("Decay/day", "Rückgang/Tag"),
+    ("Growth factor", "Wachstumsfaktor"),
+    ("Doubling time", "Verdopplungszeit"),
+    ("Peak", "Höhepunkt"),
+    ("End", "Ende"),
+    ("Report of", "Bericht vom"),
+    ("US as of", "USA, Stand"),
+    ("95% low", "95% untere"),
+    ("95% high", "95% obere"),
+    ("Trend", "Tendenz"),
+    ("Rising", "Steigend"),
+    ("Flat", "Gleichbleibend"),
+    ("Falling", "Fallend"),
+    ("Last 7 days", "Letzte 7 Tage"),
+    ("Previous 7 days", "Vorherige 7 Tage"),
+    ("Change", "Veränderung"),
+    ("Lag", "Verzögerung"),
+    ("Correlation", "Korrelation"),
+    ("Series", "Reihe"),
+    ("Median", "Median"),
+    ("Kind", "Art"),
+    ("Spike", "Spitze"),
+    ("Drop", "Einbruch"),
+    ("Correction", "Korrektur"),
+    ("Days", "Tage"),
+    ("Before", "Vorher"),
+    ("After", "Nachher"),
+    ("Daily report", "Tagesbericht"),
+    ("Time series", "Zeitreihe"),
+    ("Difference", "Differenz"),
+];
+
+const IT: Translations = &[
+    ("Country", "Paese"),
+    ("Province", "Provincia"),
+    ("State", "Stato"),
+    ("County", "Contea"),
+    ("Date", "Data"),
+    ("Day", "Giorno"),
+    ("Confirmed", "Confermati"),
+    ("Deaths", "Decessi"),
+    ("Recovered", "Guariti"),
+    ("Active", "Attivi"),
+    ("New cases", "Nuovi casi"),
+    ("New deaths", "Nuovi decessi"),
+    ("Tested", "Testati"),
+    ("Per 100k", "Ogni 100.000"),
+    ("As of", "Aggiornato al"),
+    ("Total", "Totale"),
+    ("World", "Mondo"),
+    ("Share", "Quota"),
+    ("Wave", "Ondata"),
+    ("Start", "Inizio"),
+    ("Days to peak", "Giorni al picco"),
+    ("Peak (7d avg)", "Picco (media 7 g)"),
+    ("Duration", "Durata"),
+    ("Decay/day", "Calo/giorno"),
+    ("Growth factor", "Fattore di crescita"),
+    ("Doubling time", "Tempo di raddoppio"),
+    ("Peak", "Picco"),
+    ("End", "Fine"),
+    ("Report of", "Rapporto del"),
+    ("US as of", "USA al"),
+    ("95% low", "95% min"),
+    ("95% high", "95% max"),
+    ("Trend", "Tendenza"),
+    ("Rising", "In aumento"),
+    ("Flat", "Stabile"),
+    ("Falling", "In calo"),
+    ("Last 7 days", "Ultimi 7 giorni"),
+    ("Previous 7 days", "7 giorni precedenti"),
+    ("Change", "Variazione"),
+    ("Lag", "Ritardo"),
+    ("Correlation", "Correlazione"),
+    ("Series", "Serie"),
+    ("Median", "Mediana"),
+    ("Kind", "Tipo"),
+    ("Spike", "Picco anomalo"),
+    ("Drop", "Calo"),
+    ("Correction", "Correzione"),
+    ("Days", "Giorni"),
+    ("Before", "Prima"),
+    ("After", "Dopo"),
+    ("Daily report", "Rapporto giornaliero"),
+    ("Time series", "Serie storica"),
+    ("Difference", "Differenza"),
+];
+
+const ES: Translations = &[
+    ("Country", "País"),
+    ("Province", "Provincia"),
+    ("State", "Estado"),
+    ("County", "Condado"),
+    ("Date", "Fecha"),
+    ("Day", "Día"),
+    ("Confirmed", "Confirmados"),
+    ("Deaths", "Fallecidos"),
+    ("Recovered", "Recuperados"),
+    ("Active", "Activos"),
+    ("New cases", "Casos nuevos"),
+    ("New deaths", "Fallecidos nuevos"),
+    ("Tested", "Pruebas"),
+    ("Per 100k", "Por 100.000"),
+    ("As of", "A fecha de"),
+    ("Total", "Total"),
+    ("World", "Mundo"),
+    ("Share", "Proporción"),
+    ("Wave", "Ola"),
+    ("Start", "Inicio"),
+    ("Days to peak", "Días hasta el pico"),
+    ("Peak (7d avg)", "Pico (media 7 d)"),
+    ("Duration", "Duración"),
+    ("Decay/day", "Descenso/día"),
+    ("Growth factor", "Factor de crecimiento"),
+    ("Doubling time", "Tiempo de duplicación"),
+    ("Peak", "Pico"),
+    ("End", "Fin"),
+    ("Report of", "Informe del"),
+    ("US as of", "EE. UU. a"),
+    ("95% low", "95% mín"),
+    ("95% high", "95% máx"),
+    ("Trend", "Tendencia"),
+    ("Rising", "En aumento"),
+    ("Flat", "Estable"),
+    ("Falling", "En descenso"),
+    ("Last 7 days", "Últimos 7 días"),
+    ("Previous 7 days", "7 días anteriores"),
+    ("Change", "Cambio"),
+    ("Lag", "Retraso"),
+    ("Correlation", "Correlación"),
+    ("Series", "Serie"),
+    ("Median", "Mediana"),
+    ("Kind", "Tipo"),
+    ("Spike", "Pico anómalo"),
+    ("Drop", "Caída"),
+    ("Correction", "Corrección"),
+    ("Days", "Días"),
+    ("Before", "Antes"),
+    ("After", "Después"),
+    ("Daily report", "Informe diario"),
+    ("Time series", "Serie temporal"),
+    ("Difference", "Diferencia"),
+];
+
+const FR: Translations = &[
+    ("Country", "Pays"),
+    ("Province", "Province"),
+    ("State", "État"),
+    ("County", "Comté"),
+    ("Date", "Date"),
+    ("Day", "Jour"),
+    ("Confirmed", "Confirmés"),
+    ("Deaths", "Décès"),
+    ("Recovered", "Guéris"),
+    ("Active", "Actifs"),
+    ("New cases", "Nouveaux cas"),
+    ("New deaths", "Nouveaux décès"),
+    ("Tested", "Testés"),
+    ("Per 100k", "Pour 100 000"),
+    ("As of", "Au"),
+    ("Total", "Total"),
+    ("World", "Monde"),
+    ("Share", "Part"),
+    ("Wave", "Vague"),
+    ("Start", "Début"),
+    ("Days to peak", "Jours jusqu'au pic"),
+    ("Peak (7d avg)", "Pic (moy. 7 j)"),
+    ("Duration", "Durée"),
+    ("Decay/day", "Baisse/jour"),
+    ("Growth factor", "Facteur de croissance"),
+    ("Doubling time", "Temps de doublement"),
+    ("Peak", "Pic"),
+    ("End", "Fin"),
+    ("Report of", "Rapport du"),
+    ("US as of", "États-Unis au"),
+    ("95% low", "95% bas"),
+    ("95% high", "95% haut"),
+    ("Trend", "Tendance"),
+    ("Rising", "En hausse"),
+    ("Flat", "Stable"),
+    ("Falling", "En baisse"),
+    ("Last 7 days", "7 derniers jours"),
+    ("Previous 7 days", "7 jours précédents"),
+    ("Change", "Variation"),
+    ("Lag", "Décalage"),
+    ("Correlation", "Corrélation"),
+    ("Series", "Série"),
+    ("Median", "Médiane"),
+    ("Kind", "Type"),
+    ("Spike", "Pic anormal"),
+    ("Drop", "Chute"),
+    ("Correction", "Correction"),
+    ("Days", "Jours"),
+    ("Before", "Avant"),
+    ("After", "Après"),
+    ("Daily report", "Rapport quotidien"),
+    ("Time series", "Série temporelle"),
+    ("Difference", "Différence"),
+];
+
+// English first, it needs no translations
+pub const LANGUAGES: &[(&str, Translations)] =
+    &[("en", &[]), ("de", DE), ("it", IT), ("es", ES), ("fr", FR)];
+
+// index into LANGUAGES
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+// "de_AT.UTF-8" or "de" to the index of its language
+fn find(name: &str) -> Option<usize> {
+    let language = name.split(['_', '-', '.', '@', ':']).next().unwrap_or("");
+    LANGUAGES
+        .iter()
+        .position(|(code, _)| code.eq_ignore_ascii_case(language))
+}
+
+// The language of --lang, else of LANGUAGE, LC_ALL, LC_MESSAGES or LANG.
+// Returns an error for an unknown --lang; languages from the environment
+// without translations are English.
+pub fn init(lang: Option<&str>) -> Result<(), String> {
+    let index = match lang {
+        Some(lang) => find(lang).ok_or_else(|| {
+            let known: Vec<&str> = LANGUAGES.iter().map(|(code, _)| *code).collect();
+            format!(
+                "unknown language '{}', expected one of {}",
+                lang,
+                known.join(", ")
+            )
+        })?,
+        None => ["LANGUAGE", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| find(&value))
+            .unwrap_or(0),
+    };
+    CURRENT.store(index, Ordering::Relaxed);
+    Ok(())
+}
+
+// `label` in the current language.
+pub fn label(label: &str) -> &str {
+    LANGUAGES
+        .get(CURRENT.load(Ordering::Relaxed))
+        .and_then(|(_, translations)| translations.iter().find(|(en, _)| *en == label))
+        .map(|(_, translated)| *translated)
+        .unwrap_or(label)
+}
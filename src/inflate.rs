@@ -0,0 +1,335 @@
+// Decoder for DEFLATE (RFC 1951) and the gzip wrapper around it (RFC 1952),
+// so downloads can be transferred compressed. Follows the structure of zlib's
+// "puff" reference decoder: canonical Huffman codes decoded bit by bit, which
+// is slow compared to table driven decoders but small and plenty fast for CSV
+// files of a few megabytes.
+
+const MAX_BITS: usize = 15;
+
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(crate) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(crate) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// order in which the code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// Bits of the compressed data, read a byte at a time from wherever it comes
+// from, a slice or the chunks of a download as they arrive.
+struct Bits<'a> {
+    input: &'a mut dyn Iterator<Item = u8>,
+    // bytes read so far
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(input: &'a mut dyn Iterator<Item = u8>) -> Bits<'a> {
+        Bits {
+            input,
+            pos: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = self.input.next().ok_or("compressed data ends early")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    // `n` bits, least significant first
+    fn take(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = self.byte()?;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths.iter() {
+            counts[*length as usize] += 1;
+        }
+        // over-subscribed sets of lengths do not form a prefix code
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1) {
+            left = (left << 1) - i32::from(*count);
+            if left < 0 {
+                return Err("invalid Huffman code lengths".to_string());
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= bits.take(1)? as i32;
+            let count = i32::from(self.counts[length]);
+            if code - count < first {
+                return Ok(self.symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+// Decompresses a raw DEFLATE stream. Returns the data and the number of
+// input bytes consumed, so callers can read a trailer behind it.
+pub fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut input = data.iter().copied();
+    let mut bits = Bits::new(&mut input);
+    let out = inflate_bits(&mut bits, data.len() * 4)?;
+    Ok((out, bits.pos))
+}
+
+fn inflate_bits(bits: &mut Bits, capacity: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(capacity);
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored(bits, &mut out)?,
+            1 => {
+                let (lengths, distances) = fixed()?;
+                codes(bits, &mut out, &lengths, &distances)?
+            }
+            2 => {
+                let (lengths, distances) = dynamic(bits)?;
+                codes(bits, &mut out, &lengths, &distances)?
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>) -> Result<(), String> {
+    bits.align();
+    let mut header = [0u8; 4];
+    for byte in header.iter_mut() {
+        *byte = bits.byte()?;
+    }
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+        return Err("stored block length mismatch".to_string());
+    }
+    out.reserve(length as usize);
+    for _ in 0..length {
+        out.push(bits.byte()?);
+    }
+    Ok(())
+}
+
+fn fixed() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?))
+}
+
+fn dynamic(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.take(5)? as usize + 257;
+    let distances = bits.take(5)? as usize + 1;
+    let code_lengths = bits.take(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err("too many length or distance codes".to_string());
+    }
+
+    let mut lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[*index] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; literals + distances];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code.decode(bits)?;
+        if symbol < 16 {
+            lengths[index] = symbol as u8;
+            index += 1;
+            continue;
+        }
+        let (value, repeat) = match symbol {
+            16 => {
+                let previous = *lengths[..index]
+                    .last()
+                    .ok_or("repeat without a previous length")?;
+                (previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        for _ in 0..repeat {
+            *lengths.get_mut(index).ok_or("too many code lengths")? = value;
+            index += 1;
+        }
+    }
+    if lengths[256] == 0 {
+        return Err("missing end of block code".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..])?,
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = lengths.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err("invalid length code".to_string());
+                }
+                let length = LENGTH_BASE[index] as usize
+                    + bits.take(u32::from(LENGTH_EXTRA[index]))? as usize;
+                let index = distances.decode(bits)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("invalid distance code".to_string());
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + bits.take(u32::from(DISTANCE_EXTRA[index]))? as usize;
+                if distance > out.len() {
+                    return Err("distance too far back".to_string());
+                }
+                // byte by byte, the copy may overlap what it produces
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+}
+
+// A gzip member: header, DEFLATE data and the CRC-32 and size of the result.
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    gunzip_stream(data.iter().copied())
+}
+
+// The same for compressed data that arrives a byte at a time, e.g. from the
+// chunks of a download: nothing but the inflated data is ever held.
+pub fn gunzip_stream<I: Iterator<Item = u8>>(input: I) -> Result<Vec<u8>, String> {
+    let mut input = input;
+    let mut bits = Bits::new(&mut input);
+    let mut header = [0u8; 10];
+    for byte in header.iter_mut() {
+        *byte = bits.byte().map_err(|_| "not gzip data")?;
+    }
+    if !is_gzip(&header) || header[2] != 8 {
+        return Err("not gzip data".to_string());
+    }
+    let flags = header[3];
+    let early = |_| "gzip header ends early".to_string();
+    if flags & 0x04 != 0 {
+        let extra = [bits.byte().map_err(early)?, bits.byte().map_err(early)?];
+        for _ in 0..u16::from_le_bytes(extra) {
+            bits.byte().map_err(early)?;
+        }
+    }
+    // zero terminated file name and comment
+    for flag in [0x08, 0x10].iter() {
+        if flags & flag != 0 {
+            while bits.byte().map_err(early)? != 0 {}
+        }
+    }
+    if flags & 0x02 != 0 {
+        bits.byte().map_err(early)?;
+        bits.byte().map_err(early)?;
+    }
+    let out = inflate_bits(&mut bits, 0)?;
+    let mut trailer = [0u8; 8];
+    for byte in trailer.iter_mut() {
+        *byte = bits.byte().map_err(|_| "gzip trailer missing")?;
+    }
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err("gzip checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data.iter() {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,50 @@
+pub mod analytics;
+pub mod archive;
+pub mod cache;
+pub mod chain;
+pub mod client;
+pub mod color;
+pub mod config;
+pub mod countries;
+pub mod data;
+pub mod dataset;
+#[cfg(feature = "sqlite")]
+pub mod db;
+pub mod deflate;
+pub mod derived;
+pub mod exit;
+pub mod export;
+pub mod feed;
+pub mod filter;
+pub mod git;
+pub mod groups;
+pub mod hooks;
+pub mod i18n;
+pub mod inflate;
+pub mod json;
+pub mod locale;
+pub mod logging;
+pub mod mock;
+pub mod output;
+#[cfg(feature = "arrow")]
+pub mod parquet;
+pub mod population;
+pub mod precedence;
+pub mod privacy;
+pub mod progress;
+pub mod provenance;
+pub mod quality;
+pub mod repl;
+pub mod report;
+pub mod search;
+pub mod site;
+pub mod source;
+pub mod svg;
+pub mod sync;
+pub mod table;
+pub mod template;
+pub mod us;
+pub mod validators;
+pub mod waves;
+pub mod xlsx;
+pub mod zip;
@@ -0,0 +1,204 @@
+// How numbers and dates are written in the table output: the separators of
+// thousands and decimals and the order of day, month and year. Chosen once
+// per run by --locale or the environment; exports are never localized.
+
+use chrono::NaiveDate;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Locale {
+    pub thousands: &'static str,
+    pub decimal: char,
+    // a chrono format string
+    pub date: &'static str,
+}
+
+// ISO dates and English separators, also for C and POSIX
+const DEFAULT: Locale = Locale {
+    thousands: ",",
+    decimal: '.',
+    date: "%Y-%m-%d",
+};
+
+// By language, or language and region where they differ. The first entry is
+// the default.
+const LOCALES: &[(&str, Locale)] = &[
+    ("", DEFAULT),
+    (
+        "en_US",
+        Locale {
+            thousands: ",",
+            decimal: '.',
+            date: "%m/%d/%Y",
+        },
+    ),
+    (
+        "en_GB",
+        Locale {
+            thousands: ",",
+            decimal: '.',
+            date: "%d/%m/%Y",
+        },
+    ),
+    ("en", DEFAULT),
+    (
+        "de_CH",
+        Locale {
+            thousands: "’",
+            decimal: '.',
+            date: "%d.%m.%Y",
+        },
+    ),
+    (
+        "de",
+        Locale {
+            thousands: ".",
+            decimal: ',',
+            date: "%d.%m.%Y",
+        },
+    ),
+    (
+        "it",
+        Locale {
+            thousands: ".",
+            decimal: ',',
+            date: "%d/%m/%Y",
+        },
+    ),
+    (
+        "es",
+        Locale {
+            thousands: ".",
+            decimal: ',',
+            date: "%d/%m/%Y",
+        },
+    ),
+    (
+        "pt",
+        Locale {
+            thousands: ".",
+            decimal: ',',
+            date: "%d/%m/%Y",
+        },
+    ),
+    (
+        "nl",
+        Locale {
+            thousands: ".",
+            decimal: ',',
+            date: "%d-%m-%Y",
+        },
+    ),
+    (
+        "fr",
+        Locale {
+            thousands: "\u{202f}",
+            decimal: ',',
+            date: "%d/%m/%Y",
+        },
+    ),
+    (
+        "sv",
+        Locale {
+            thousands: "\u{a0}",
+            decimal: ',',
+            date: "%Y-%m-%d",
+        },
+    ),
+    (
+        "pl",
+        Locale {
+            thousands: "\u{a0}",
+            decimal: ',',
+            date: "%d.%m.%Y",
+        },
+    ),
+    (
+        "ru",
+        Locale {
+            thousands: "\u{a0}",
+            decimal: ',',
+            date: "%d.%m.%Y",
+        },
+    ),
+    (
+        "ja",
+        Locale {
+            thousands: ",",
+            decimal: '.',
+            date: "%Y/%m/%d",
+        },
+    ),
+    (
+        "zh",
+        Locale {
+            thousands: ",",
+            decimal: '.',
+            date: "%Y/%m/%d",
+        },
+    ),
+];
+
+// index into LOCALES
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+// "de_DE.UTF-8@euro" or "de-DE" to the index of the best match, `None` for
+// names that are not known at all.
+fn find(name: &str) -> Option<usize> {
+    let name = name
+        .split(['.', '@'])
+        .next()
+        .unwrap_or("")
+        .replace('-', "_");
+    if name.is_empty() || name == "C" || name == "POSIX" {
+        return Some(0);
+    }
+    let language = name.split('_').next().unwrap_or("");
+    LOCALES
+        .iter()
+        .position(|(n, _)| !n.is_empty() && n.eq_ignore_ascii_case(&name))
+        .or_else(|| {
+            LOCALES
+                .iter()
+                .position(|(n, _)| !n.is_empty() && n.eq_ignore_ascii_case(language))
+        })
+}
+
+// The locale of --locale, else of LC_ALL, LC_NUMERIC or LANG. Returns an
+// error for an unknown --locale; unknown ones from the environment fall
+// back to the default silently.
+pub fn init(name: Option<&str>) -> Result<(), String> {
+    let index = match name {
+        Some(name) => find(name).ok_or_else(|| {
+            let known: Vec<&str> = LOCALES
+                .iter()
+                .map(|(n, _)| *n)
+                .filter(|n| !n.is_empty())
+                .collect();
+            format!(
+                "unknown locale '{}', expected one of {}",
+                name,
+                known.join(", ")
+            )
+        })?,
+        None => ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| find(&value))
+            .unwrap_or(0),
+    };
+    CURRENT.store(index, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn current() -> Locale {
+    LOCALES
+        .get(CURRENT.load(Ordering::Relaxed))
+        .map(|(_, locale)| *locale)
+        .unwrap_or(DEFAULT)
+}
+
+pub fn format_date(date: &NaiveDate) -> String {
+    date.format(current().date).to_string()
+}
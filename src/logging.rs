@@ -0,0 +1,106 @@
+use std::fmt::{self, Write as _};
+use std::io::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+// Logs go to stderr as `elapsed LEVEL target: message key=value ...` lines so
+// stdout only ever carries the requested data.
+pub struct StderrSubscriber {
+    max: Level,
+    start: Instant,
+    next_id: AtomicU64,
+}
+
+impl StderrSubscriber {
+    pub fn new(max: Level) -> StderrSubscriber {
+        StderrSubscriber {
+            max,
+            start: Instant::now(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+// 0 = warnings only, 1 = info (-v), 2 = debug (-vv), 3 and more = trace.
+// Nothing is installed for `quiet`, which silences logging entirely.
+pub fn init(verbosity: usize, quiet: bool) {
+    if quiet {
+        return;
+    }
+    let max = match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let _ = tracing::subscriber::set_global_default(StderrSubscriber::new(max));
+}
+
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.rest, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.rest, " {}={}", field.name(), value);
+        }
+    }
+}
+
+impl Subscriber for StderrSubscriber {
+    // other crates (hyper, reqwest, ...) only get past warnings at trace level
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let max = if metadata.target().starts_with("corona_stats") || self.max == Level::TRACE {
+            self.max
+        } else {
+            Level::WARN
+        };
+        *metadata.level() <= max
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields {
+            message: String::new(),
+            rest: String::new(),
+        };
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let _ = writeln!(
+            std::io::stderr(),
+            "{:>8.3}s {:>5} {}: {}{}",
+            self.start.elapsed().as_secs_f64(),
+            metadata.level(),
+            metadata.target(),
+            fields.message,
+            fields.rest
+        );
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
@@ -1,8 +1,889 @@
 extern crate chrono;
 extern crate csv;
 
-mod data;
+mod cli;
+mod completions;
+
+use chrono::NaiveDate;
+use std::error::Error;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use corona_stats::analytics;
+use corona_stats::archive::{self, ArchiveLayer};
+use corona_stats::cache::{self, DiskCache};
+use corona_stats::chain::Chain;
+use corona_stats::config::Config;
+use corona_stats::data;
+use corona_stats::exit::{self, Failure};
+use corona_stats::export;
+use corona_stats::feed;
+use corona_stats::filter::LocationFilter;
+use corona_stats::git::{self, GitLayer};
+use corona_stats::groups;
+use corona_stats::hooks::Hooks;
+use corona_stats::i18n;
+use corona_stats::locale;
+use corona_stats::logging;
+use corona_stats::mock::MockServer;
+use corona_stats::precedence::Precedence;
+use corona_stats::privacy::PrivacyPolicy;
+use corona_stats::provenance::{self, Provenance};
+use corona_stats::repl;
+use corona_stats::site;
+use corona_stats::source;
+use corona_stats::source::FsSource;
+use corona_stats::sync::ReportStore;
+use corona_stats::table;
+use corona_stats::us;
+use corona_stats::validators::ValidatorStore;
 
 fn main() {
-    data::get_series();
+    let mut args = cli::Args::parse(std::env::args().skip(1));
+    logging::init(args.verbosity(), args.quiet());
+    if let Some(name) = args.unknown() {
+        fail(&*usage(&format!("unknown option --{}", name)));
+    }
+    if let Err(e) = locale::init(args.value("locale")).and_then(|_| i18n::init(args.value("lang")))
+    {
+        fail(&Failure::Parse(e));
+    }
+    let config_path = args
+        .value("config")
+        .map(PathBuf::from)
+        .or_else(Config::default_path);
+    if args.command.as_deref() == Some("config") {
+        if let Err(e) = config(&args, config_path) {
+            fail(&*e);
+        }
+        return;
+    }
+    let config = match config_path.as_deref().map(Config::load) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => fail(&*e),
+        None => Config::default(),
+    };
+    table::set_style(
+        args.value("format").or(config.format.as_deref()) == Some("tsv"),
+        !args.flag("no-header"),
+    );
+    groups::init(&config.groups);
+    if args.command.as_deref() == Some("cache") {
+        if let Err(e) = cache(&args, &config) {
+            fail(&*e);
+        }
+        return;
+    }
+    if args.command.as_deref() == Some("info") {
+        if let Err(e) = info(&args, &config) {
+            fail(&*e);
+        }
+        return;
+    }
+    if args.command.as_deref() == Some("revisions") {
+        if let Err(e) = revisions(&args, &config) {
+            fail(&*e);
+        }
+        return;
+    }
+    args.set_default("format", config.format.as_deref());
+    let (filter, sort, align) = match args
+        .filter()
+        .and_then(|f| Ok((f, args.sort()?, args.align()?)))
+    {
+        Ok(selection) => selection,
+        Err(e) => fail(&Failure::Parse(e)),
+    };
+    // --demo answers everything from the bundled sample data
+    let demo = args.flag("demo");
+    let mut chain = if demo {
+        Chain::demo()
+    } else {
+        Chain::default()
+    };
+    if demo && !args.quiet() {
+        eprintln!("demo mode: showing the bundled sample data, nothing is downloaded");
+    }
+    if let Some(concurrency) = args.value("concurrency") {
+        match concurrency.parse() {
+            Ok(concurrency) => chain.set_concurrency(concurrency),
+            Err(_) => fail(&Failure::Parse(format!(
+                "invalid concurrency '{}'",
+                concurrency
+            ))),
+        }
+    }
+    if let Some(retries) = args.value("retries") {
+        match retries.parse() {
+            Ok(retries) => chain.set_retries(retries),
+            Err(_) => fail(&Failure::Parse(format!("invalid retries '{}'", retries))),
+        }
+    }
+    // --rate-limit, then the config file
+    let rate_limit = args
+        .value("rate-limit")
+        .map(String::from)
+        .or_else(|| config.rate_limit.map(|n| n.to_string()));
+    if let Some(rate) = &rate_limit {
+        match rate.parse::<f64>() {
+            Ok(rate) if rate >= 0.0 && rate.is_finite() => chain.set_rate_limit(rate),
+            _ => fail(&Failure::Parse(format!("invalid rate limit '{}'", rate))),
+        }
+    }
+    for (name, set) in [
+        ("timeout", Chain::set_timeout as fn(&mut Chain, Duration)),
+        ("deadline", Chain::set_deadline),
+    ] {
+        if let Some(value) = args.value(name) {
+            match cli::parse_duration(value) {
+                Ok(duration) => set(&mut chain, duration),
+                Err(e) => fail(&Failure::Parse(format!("--{}: {}", name, e))),
+            }
+        }
+    }
+    // --offline answers from the cache only, which knows the reports it has
+    // (the sample data of --demo needs no network anyway)
+    let offline = args.flag("offline") && !demo;
+    if offline {
+        chain.set_enabled("network", false);
+    }
+    chain.set_discovery(args.flag("discover") || offline);
+    // --proxy, then the *_PROXY variables, then the config file
+    let proxy = args.value("proxy").or_else(|| {
+        let from_env = ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"]
+            .iter()
+            .any(|name| {
+                std::env::var_os(name).is_some() || std::env::var_os(name.to_lowercase()).is_some()
+            });
+        config.proxy.as_deref().filter(|_| !from_env)
+    });
+    if let Some(proxy) = proxy {
+        if let Err(e) = chain.set_proxy(proxy) {
+            fail(&Failure::Parse(e.to_string()));
+        }
+    }
+    // the token goes to the GitHub API only, never to mirrors
+    let token = std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|t| !t.is_empty())
+        .or_else(|| config.github_token.clone());
+    let headers = config
+        .headers
+        .iter()
+        .map(|(name, value)| ("", name.as_str(), value.clone()));
+    let token = token.map(|t| (data::GITHUB_API, "Authorization", format!("token {}", t)));
+    for (prefix, name, value) in headers.chain(token) {
+        if let Err(e) = chain.add_header(prefix, name, &value) {
+            fail(&Failure::Parse(e.to_string()));
+        }
+    }
+    // the sample data is keyed by the upstream URLs, which must not change
+    if !demo {
+        if let Some(upstream) = config.sources.get("upstream") {
+            chain = chain.with_rewrite(data::UPSTREAM, upstream);
+        }
+        let mirrors = match args.value("mirrors") {
+            Some(list) => list.split(',').map(|m| m.trim().to_string()).collect(),
+            None => config.mirrors.clone(),
+        };
+        chain = chain.with_mirrors(data::UPSTREAM, &mirrors);
+    }
+    // a checkout of the repository answers before any download; --git-rev
+    // pins it to a commit
+    let git_synced = args.flag("git") || args.value("git-rev").is_some();
+    if git_synced {
+        let dir = match config.cache_dir() {
+            Some(dir) => dir.join("COVID-19"),
+            None => fail(&*usage("no cache directory, set HOME or cache_dir")),
+        };
+        let url = config
+            .sources
+            .get("git")
+            .map(String::as_str)
+            .unwrap_or(git::REPOSITORY);
+        // offline the checkout is used as it is
+        if offline && !dir.join(".git").exists() {
+            fail(&Failure::NoData(format!(
+                "{} is not checked out yet",
+                dir.display()
+            )));
+        } else if !offline {
+            if let Err(e) = git::sync(&dir, url, args.value("git-rev"), args.quiet()) {
+                fail(&*e);
+            }
+        }
+        if let Some(head) = git::head(&dir) {
+            chain.set_checkout(url, &head);
+        }
+        chain = chain.with_layer(GitLayer::new(dir));
+    }
+    // --on-refresh, --on-alert and --transform-record replace the [hooks] of
+    // the config file one by one
+    let hook = |name: &str, configured: &Option<String>| {
+        args.value(name)
+            .map(String::from)
+            .or_else(|| configured.clone())
+    };
+    chain.set_hooks(Hooks {
+        on_refresh: hook("on-refresh", &config.hooks.on_refresh),
+        on_alert: hook("on-alert", &config.hooks.on_alert),
+        transform_record: hook("transform-record", &config.hooks.transform_record),
+    });
+    // the bar is drawn on stderr, but only makes sense when a human is watching
+    chain.set_progress(
+        !args.quiet()
+            && args.value("format").map(|f| f == "table").unwrap_or(true)
+            && std::io::stdout().is_terminal()
+            && std::io::stderr().is_terminal(),
+    );
+    let sources = sources(&args, &config).unwrap_or_else(|e| fail(&*e));
+    match source::by_name(&sources[0]) {
+        // several sources are merged per metric, which leaves out --data-dir
+        // and --legacy-time-series, both only for JHU
+        Ok(_) if sources.len() > 1 => match merged(&sources, &args, &config) {
+            Ok(source) => chain.set_source(Box::new(source)),
+            Err(e) => fail(&*e),
+        },
+        // the renamed JHU files replaced the old ones, which are still in the archive
+        Ok(source) if args.flag("legacy-time-series") => {
+            if source.name() != "jhu" {
+                fail(&Failure::Parse(
+                    "--legacy-time-series only applies to the jhu source".to_string(),
+                ));
+            }
+            chain.set_source(Box::new(source::JhuSource::legacy()))
+        }
+        Ok(source) => chain.set_source(source),
+        Err(e) => fail(&Failure::Parse(e)),
+    }
+    // a local checkout answers everything that would come from the JHU
+    // repository, time series and daily reports
+    if let Some(dir) = args.value("data-dir") {
+        if chain.source().name() != "jhu" || args.flag("legacy-time-series") {
+            fail(&Failure::Parse(
+                "--data-dir only applies to the current jhu time series".to_string(),
+            ));
+        }
+        let local = FsSource::new(dir);
+        if !local.dir().is_dir() {
+            fail(&Failure::Parse(format!("{} is not a directory", dir)));
+        }
+        chain.set_source(Box::new(local.clone()));
+        chain = chain.with_layer(local);
+    }
+    // --source mock is short for the JHU source with --mock; the server is
+    // kept alive for the whole run
+    let _mock = if !demo && (args.flag("mock") || sources.iter().any(|s| s == "mock")) {
+        match MockServer::start() {
+            Ok(server) => {
+                chain = chain
+                    .with_rewrite(data::UPSTREAM, &server.base_url())
+                    .with_rewrite(data::GITHUB_API, &server.api_url())
+                    .with_rewrite(source::owid::BASE, &server.source_url("owid"))
+                    .with_rewrite(source::ecdc::BASE, &server.source_url("ecdc"))
+                    .with_rewrite(source::disease_sh::BASE, &server.source_url("disease.sh"))
+                    .with_rewrite(archive::BASE, &server.source_url("archive"));
+                // the local server needs no sparing
+                if rate_limit.is_none() {
+                    chain.set_rate_limit(0.0);
+                }
+                Some(server)
+            }
+            Err(e) => {
+                eprintln!("could not start mock server: {}", e);
+                std::process::exit(exit::NETWORK);
+            }
+        }
+    } else {
+        None
+    };
+    // one download of the whole repository instead of a request per file
+    if args.flag("archive") {
+        match archive::download(&chain) {
+            Ok(snapshot) => chain = chain.with_layer(ArchiveLayer::new(snapshot)),
+            Err(e) => fail(&*e),
+        }
+    }
+    // reports downloaded by earlier runs and the last version of every other
+    // file, never filled with the fixtures or the sample data
+    let mut provenance_file = None;
+    let mut cache_dir = None;
+    if _mock.is_none() && !demo && !args.flag("no-cache") {
+        if let Some(dir) = config.cache_dir() {
+            cache_dir = Some(dir.clone());
+            provenance_file = Some(dir.join("files").join(provenance::FILE));
+            chain = chain
+                .with_layer(
+                    ReportStore::open(dir.join("reports"))
+                        .offline(offline)
+                        .keep_revisions(args.flag("keep-revisions")),
+                )
+                .with_layer(DiskCache::new(dir.join("files")).offline(offline));
+            chain.set_validators(ValidatorStore::new(dir.join("files")));
+        }
+    }
+
+    let result = match args.command.as_deref() {
+        // local files replace the download, the summary is all a single report gives
+        _ if args.value("input").is_some() => {
+            let input = args.value("input").unwrap_or("-");
+            data::get_data_from_input(input, args.color(), &filter, &sort)
+        }
+        Some("summary") => data::get_data(&chain, args.color(), &filter, &sort),
+        Some("series") | None => {
+            let mut filter = filter.clone();
+            filter.country = args.positional.first().cloned();
+            if filter.is_empty() {
+                filter.country = Some(
+                    config
+                        .countries
+                        .first()
+                        .cloned()
+                        .unwrap_or_else(|| "Italy".to_string()),
+                );
+            }
+            match args.metric().and_then(|metric| {
+                Ok((
+                    metric,
+                    args.transform(metric)?,
+                    args.peaks()?,
+                    args.anomalies()?,
+                ))
+            }) {
+                Ok((metric, transform, peaks, anomalies)) => data::get_series(
+                    &chain,
+                    &filter,
+                    metric,
+                    align.as_ref(),
+                    &transform,
+                    peaks,
+                    anomalies,
+                ),
+                Err(e) => Err(usage(&e)),
+            }
+        }
+        Some("diff") => match args.positional.as_slice() {
+            [from, to] => match (from.parse(), to.parse()) {
+                (Ok(from), Ok(to)) => data::diff(&chain, &from, &to, &filter, &sort),
+                _ => Err(usage("dates have to be given as YYYY-MM-DD")),
+            },
+            _ => Err(usage("usage: corona-stats diff <from> <to>")),
+        },
+        Some("watch") => match cli::parse_duration(args.value("interval").unwrap_or("1h")) {
+            // polling without a pause would only hammer the upstream
+            Ok(interval) if interval.as_secs() == 0 => {
+                Err(usage("--interval has to be at least 1s"))
+            }
+            Ok(interval) => {
+                // every poll has to see fresh data
+                chain.set_enabled("memory", false);
+                chain.set_enabled("disk", false);
+                data::watch(&chain, args.value("country"), interval)
+            }
+            Err(e) => Err(usage(&e)),
+        },
+        Some("alert") => alert(&chain, &args),
+        Some("feed") => match args
+            .value("country")
+            .or_else(|| config.countries.first().map(|c| c.as_str()))
+        {
+            Some(country) => feed::feed(&chain, country, args.output()),
+            None => Err(usage("--country is required")),
+        },
+        Some("site") => match args.output() {
+            Some(dir) => site::site(&chain, dir).map(|pages| {
+                eprintln!("wrote {} pages to {}", pages, dir.display());
+            }),
+            None => Err(usage("usage: corona-stats site --out <dir>")),
+        },
+        Some("badge") => match args.value("country") {
+            Some(country) => data::badge(
+                &chain,
+                country,
+                args.value("metric").unwrap_or("new_cases"),
+                args.output(),
+            ),
+            None => Err(usage("--country is required")),
+        },
+        Some("export") => export(&chain, &args, &filter, &config.privacy),
+        Some("report") => report(&chain, &args, &config.countries),
+        Some("ingest") => ingest(&chain, &args),
+        Some("query") => query(&args),
+        Some("search") => match args.positional.first() {
+            Some(query) => data::search(&chain, query, &filter, &sort),
+            None => Err(usage("usage: corona-stats search <name>")),
+        },
+        Some("provinces") => match args.positional.first() {
+            Some(country) => data::provinces(&chain, country, &filter, &sort),
+            None => Err(usage("usage: corona-stats provinces <country>")),
+        },
+        Some("us") => us::us(&chain, args.value("state"), args.value("county")),
+        Some("history") => {
+            let date = |name: &str, default: &str| args.value(name).unwrap_or(default).parse();
+            match (date("from", "2020-01-22"), date("to", "9999-12-31")) {
+                (Ok(from), Ok(to)) => data::history(&chain, &from, &to, &filter),
+                _ => Err(usage("dates have to be given as YYYY-MM-DD")),
+            }
+        }
+        Some("repl") => repl::run(&chain),
+        Some("top") => top(&chain, &args),
+        Some("today") => data::today(&chain, 10, &filter, &sort),
+        Some("completions") => match args.positional.first() {
+            Some(shell) => completions::generate(shell)
+                .map(|script| print!("{}", script))
+                .map_err(|e| usage(&e)),
+            None => Err(usage(
+                "usage: corona-stats completions bash|zsh|fish|powershell",
+            )),
+        },
+        Some("compare-waves") if args.positional.is_empty() => {
+            data::compare_waves(&chain, &config.countries, align.as_ref())
+        }
+        Some("compare-waves") => data::compare_waves(&chain, &args.positional, align.as_ref()),
+        Some("forecast") => forecast(&chain, &args, &config.countries),
+        Some("rt") => rt(&chain, &args, &config.countries),
+        Some("trends") => data::trends(&chain, &args.positional),
+        Some("lag") => lag(&chain, &args, &config.countries),
+        Some("anomalies") => anomalies(&chain, &args, &config.countries),
+        Some("reconcile") => reconcile(&chain, &args, &config.countries),
+        Some("validate") => match args.positional.as_slice() {
+            [] | [_] => data::validate(
+                &chain,
+                args.positional.first().map(String::as_str),
+                args.value("format").unwrap_or("table"),
+            ),
+            _ => Err(usage("usage: corona-stats validate [country]")),
+        },
+        Some("milestones") => match (args.positional.as_slice(), config.countries.first()) {
+            ([country], _) | ([], Some(country)) => data::milestones(&chain, country),
+            _ => Err(usage("usage: corona-stats milestones <country>")),
+        },
+        Some("waves") if args.positional.is_empty() => data::waves(&chain, &config.countries),
+        Some("waves") => data::waves(&chain, &args.positional),
+        Some(other) => Err(usage(&format!("unknown command '{}'", other))),
+    };
+    if let Err(e) = result {
+        fail(&*e);
+    }
+    // the cache is kept within its limits by every run, a daemon started
+    // over and over included
+    if let (Some(dir), false) = (cache_dir, config.cache_limits.is_empty()) {
+        if let Err(e) = cache::prune(&dir, &config.cache_limits) {
+            eprintln!("could not prune the cache: {}", e);
+        }
+    }
+    // what the cache holds now came from the last download or checkout
+    let downloads = chain.metrics().last().map(|m| m.hits).unwrap_or(0);
+    if let Some(path) = provenance_file.filter(|_| downloads > 0 || (git_synced && !offline)) {
+        if let Err(e) = chain.provenance().save(&path) {
+            eprintln!("could not record provenance: {}", e);
+        }
+    }
+}
+
+// --source, split at commas, then the config file, then every source the
+// precedence names, then jhu.
+fn sources(args: &cli::Args, config: &Config) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut sources: Vec<String> = match args.value("source") {
+        Some(names) => names.split(',').map(|s| s.trim().to_string()).collect(),
+        None => config.source.clone(),
+    };
+    if sources.is_empty() {
+        sources = precedence(&[], args, config)?.sources();
+    }
+    if sources.is_empty() {
+        sources.push("jhu".to_string());
+    }
+    for name in sources.iter() {
+        source::by_name(name).map_err(|e| usage(&e))?;
+    }
+    Ok(sources)
+}
+
+// The orders of the config file, then those of --precedence, with `default`
+// for the rest of the metrics.
+fn precedence(
+    default: &[&str],
+    args: &cli::Args,
+    config: &Config,
+) -> Result<Precedence, Box<dyn Error>> {
+    let mut precedence = Precedence::new(default);
+    for (metric, order) in config.precedence.iter() {
+        let order: Vec<&str> = order.iter().map(|s| s.as_str()).collect();
+        precedence = precedence.with_metric(metric, &order);
+    }
+    if let Some(spec) = args.value("precedence") {
+        precedence = precedence.with_spec(spec).map_err(|e| usage(&e))?;
+    }
+    for source in precedence.sources() {
+        source::by_name(&source).map_err(|e| usage(&e))?;
+    }
+    Ok(precedence)
+}
+
+fn merged(
+    names: &[String],
+    args: &cli::Args,
+    config: &Config,
+) -> Result<source::MergedSource, Box<dyn Error>> {
+    if args.flag("legacy-time-series") || args.value("data-dir").is_some() {
+        return Err(usage(
+            "--data-dir and --legacy-time-series only apply to the jhu source alone",
+        ));
+    }
+    let default: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+    let sources = names
+        .iter()
+        .map(|name| source::by_name(name).map_err(|e| usage(&e)))
+        .collect::<Result<_, _>>()?;
+    Ok(source::MergedSource::new(
+        sources,
+        precedence(&default, args, config)?,
+    ))
+}
+
+// Message on stderr, the documented exit code for the kind of failure.
+fn fail(error: &(dyn Error + 'static)) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(exit::code(error));
+}
+
+fn usage(message: &str) -> Box<dyn Error> {
+    Failure::Parse(message.to_string()).into()
+}
+
+fn config(args: &cli::Args, path: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let path = path.ok_or_else(|| usage("no config location, set HOME or use --config"))?;
+    match args.positional.first().map(|s| s.as_str()) {
+        Some("init") => {
+            Config::init(&path)?;
+            eprintln!("wrote {}", path.display());
+        }
+        Some("path") => println!("{}", path.display()),
+        _ => return Err(usage("usage: corona-stats config init|path")),
+    }
+    Ok(())
+}
+
+fn cache(args: &cli::Args, config: &Config) -> Result<(), Box<dyn Error>> {
+    let dir = config
+        .cache_dir()
+        .ok_or_else(|| usage("no cache directory, set HOME or cache_dir"))?;
+    match args.positional.first().map(|s| s.as_str()) {
+        Some("status") => {
+            println!("{}", dir.display());
+            print!("{}", cache::status(&dir));
+        }
+        Some("clear") => {
+            let removed = cache::clear(&dir)?;
+            eprintln!("removed {} files from {}", removed, dir.display());
+        }
+        Some("prune") => {
+            if config.cache_limits.is_empty() {
+                return Err(usage(
+                    "no limits to prune to, set max_size_mb or max_age_days in [cache]",
+                ));
+            }
+            let (files, bytes) = cache::prune(&dir, &config.cache_limits)?;
+            eprintln!(
+                "removed {} files ({}) from {}",
+                files,
+                cache::format_size(bytes),
+                dir.display()
+            );
+        }
+        _ => return Err(usage("usage: corona-stats cache status|clear|prune")),
+    }
+    Ok(())
+}
+
+// Where the data in the cache, or in the database of --db, came from.
+fn info(args: &cli::Args, config: &Config) -> Result<(), Box<dyn Error>> {
+    let provenance = match args.value("db") {
+        Some(db) => db_provenance(std::path::Path::new(db))?,
+        None => config
+            .cache_dir()
+            .and_then(|dir| Provenance::load(&dir.join("files").join(provenance::FILE))),
+    };
+    match provenance {
+        Some(provenance) => {
+            print!("{}", provenance);
+            Ok(())
+        }
+        None => Err(Failure::NoData("nothing has been fetched yet".to_string()).into()),
+    }
+}
+
+// The corrections of a daily report seen by runs with --keep-revisions.
+fn revisions(args: &cli::Args, config: &Config) -> Result<(), Box<dyn Error>> {
+    let date = match args.positional.first() {
+        Some(date) => NaiveDate::parse_from_str(date, "%m-%d-%Y")
+            .or_else(|_| date.parse())
+            .map_err(|_| usage("dates have to be given as MM-DD-YYYY or YYYY-MM-DD"))?,
+        None => return Err(usage("usage: corona-stats revisions <date>")),
+    };
+    let dir = config
+        .cache_dir()
+        .ok_or_else(|| usage("no cache directory, set HOME or cache_dir"))?;
+    data::revisions(&ReportStore::open(dir.join("reports")), &date)
+}
+
+#[cfg(feature = "sqlite")]
+fn db_provenance(db: &std::path::Path) -> Result<Option<Provenance>, Box<dyn Error>> {
+    corona_stats::db::provenance(db)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn db_provenance(_db: &std::path::Path) -> Result<Option<Provenance>, Box<dyn Error>> {
+    Err(usage("--db needs a build with the sqlite feature"))
+}
+
+#[cfg(feature = "sqlite")]
+fn ingest(chain: &Chain, args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    let db = args.value("db").ok_or_else(|| usage("--db is required"))?;
+    corona_stats::db::ingest(chain, std::path::Path::new(db))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn ingest(_chain: &Chain, _args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    Err(usage("ingest needs a build with the sqlite feature"))
+}
+
+#[cfg(feature = "sqlite")]
+fn query(args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    let db = args.value("db").ok_or_else(|| usage("--db is required"))?;
+    let sql = match args.positional.as_slice() {
+        [sql] => sql,
+        _ => return Err(usage("usage: corona-stats query --db <file> \"<sql>\"")),
+    };
+    let format = args.value("format").unwrap_or("table");
+    corona_stats::db::query(std::path::Path::new(db), sql, format)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn query(_args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    Err(usage("query needs a build with the sqlite feature"))
+}
+
+fn export(
+    chain: &Chain,
+    args: &cli::Args,
+    filter: &LocationFilter,
+    privacy: &PrivacyPolicy,
+) -> Result<(), Box<dyn Error>> {
+    if args.flag("json-schema") {
+        print!("{}", export::JSON_SCHEMA);
+        return Ok(());
+    }
+    let mut filter = filter.clone();
+    filter.country = args.value("country").map(String::from);
+    let metric = args.metric().map_err(|e| usage(&e))?;
+    let layout = args
+        .value("layout")
+        .unwrap_or("long")
+        .parse()
+        .map_err(|e: String| usage(&e))?;
+    // tables are for reading, the exports default to CSV
+    let format = match args.value("format") {
+        Some("table") | None => "csv",
+        Some(format) => format,
+    };
+    // binary output would only garble the terminal
+    let output = args.output();
+    if ["parquet", "xlsx"].contains(&format) && output.is_none() && std::io::stdout().is_terminal()
+    {
+        return Err(usage(&format!(
+            "{} is binary, write it with --output <file>",
+            format
+        )));
+    }
+    let transform = args.transform(metric).map_err(|e| usage(&e))?;
+    let target = export::Target {
+        format,
+        layout,
+        perturbation: privacy.for_target(format),
+    };
+    export::export(chain, &filter, metric, &transform, &target, output)
+}
+
+fn report(chain: &Chain, args: &cli::Args, configured: &[String]) -> Result<(), Box<dyn Error>> {
+    // --country Italy,Germany, the configured countries without
+    let mut countries: Vec<String> = args
+        .value("country")
+        .unwrap_or("")
+        .split(',')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+    if countries.is_empty() {
+        countries = configured.to_vec();
+    }
+    let format = match args.value("format") {
+        Some("table") | Some("md") | None => "markdown",
+        Some(format) => format,
+    };
+    let output = args.output();
+    let template = args.value("template").map(std::path::Path::new);
+    corona_stats::report::report(chain, &countries, format, template, output)
+}
+
+fn forecast(chain: &Chain, args: &cli::Args, configured: &[String]) -> Result<(), Box<dyn Error>> {
+    let country = match (args.positional.as_slice(), configured.first()) {
+        ([country], _) | ([], Some(country)) => country,
+        _ => {
+            return Err(usage(
+                "usage: corona-stats forecast <country> [--days 14] [--model logistic]",
+            ))
+        }
+    };
+    let model = args
+        .value("model")
+        .unwrap_or("exponential")
+        .parse()
+        .map_err(|e: String| usage(&e))?;
+    let days = args.days("days", 14).map_err(|e| usage(&e))?;
+    let fit_days = args
+        .days("fit-days", analytics::FIT_DAYS)
+        .map_err(|e| usage(&e))?;
+    data::forecast(chain, country, model, days, fit_days)
+}
+
+fn rt(chain: &Chain, args: &cli::Args, configured: &[String]) -> Result<(), Box<dyn Error>> {
+    let country = match (args.positional.as_slice(), configured.first()) {
+        ([country], _) | ([], Some(country)) => country,
+        _ => {
+            return Err(usage(
+                "usage: corona-stats rt <country> [--window 7] [--si-mean 4.7] [--si-sd 2.9]",
+            ))
+        }
+    };
+    let duration = |name: &str, default: f64| -> Result<f64, Box<dyn Error>> {
+        match args.value(name) {
+            Some(value) => match value.parse::<f64>() {
+                Ok(n) if n > 0.0 => Ok(n),
+                _ => Err(usage(&format!(
+                    "invalid --{} '{}', expected a positive number of days",
+                    name, value
+                ))),
+            },
+            None => Ok(default),
+        }
+    };
+    let serial = analytics::SerialInterval {
+        mean: duration("si-mean", analytics::SERIAL_INTERVAL.mean)?,
+        sd: duration("si-sd", analytics::SERIAL_INTERVAL.sd)?,
+    };
+    let window = args
+        .days("window", analytics::RT_WINDOW)
+        .map_err(|e| usage(&e))?;
+    let days = args.days("days", 28).map_err(|e| usage(&e))?;
+    data::rt(chain, country, serial, window, days)
+}
+
+fn lag(chain: &Chain, args: &cli::Args, configured: &[String]) -> Result<(), Box<dyn Error>> {
+    let country = match (args.positional.as_slice(), configured.first()) {
+        ([country], _) | ([], Some(country)) => country,
+        _ => return Err(usage("usage: corona-stats lag <country> [--max-lag 28]")),
+    };
+    let max_lag = match args.value("max-lag") {
+        Some(value) => value.parse().map_err(|_| {
+            usage(&format!(
+                "invalid --max-lag '{}', expected a number of days",
+                value
+            ))
+        })?,
+        None => analytics::MAX_LAG,
+    };
+    data::lag(chain, country, max_lag)
+}
+
+fn anomalies(chain: &Chain, args: &cli::Args, configured: &[String]) -> Result<(), Box<dyn Error>> {
+    let country = match (args.positional.as_slice(), configured.first()) {
+        ([country], _) | ([], Some(country)) => country,
+        _ => {
+            return Err(usage(
+                "usage: corona-stats anomalies <country> [--anomalies=3.5]",
+            ))
+        }
+    };
+    let threshold = args.anomalies().map_err(|e| usage(&e))?;
+    data::anomalies(chain, country, threshold.unwrap_or(analytics::ANOMALY_Z))
+}
+
+fn reconcile(chain: &Chain, args: &cli::Args, configured: &[String]) -> Result<(), Box<dyn Error>> {
+    let country = match (args.positional.as_slice(), configured.first()) {
+        ([country], _) | ([], Some(country)) => country,
+        _ => {
+            return Err(usage(
+                "usage: corona-stats reconcile <country> [--from <date>] [--to <date>] [--tolerance 1%]",
+            ))
+        }
+    };
+    let date = |name: &str, default: &str| args.value(name).unwrap_or(default).parse();
+    let (from, to) = match (date("from", "2020-01-22"), date("to", "9999-12-31")) {
+        (Ok(from), Ok(to)) => (from, to),
+        _ => return Err(usage("dates have to be given as YYYY-MM-DD")),
+    };
+    let tolerance = args
+        .value("tolerance")
+        .unwrap_or("0")
+        .parse()
+        .map_err(|e: String| usage(&e))?;
+    data::reconcile(chain, country, &from, &to, tolerance)
+}
+
+// The countries, or the members of --group, ranked by --by, 10 unless a
+// number is given, within the days from --from to --to.
+fn top(chain: &Chain, args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    let n = match args.positional.as_slice() {
+        [] => 10,
+        [n] => n
+            .parse()
+            .map_err(|_| usage(&format!("invalid number of countries '{}'", n)))?,
+        _ => {
+            return Err(usage(
+                "usage: corona-stats top [n] [--by <metric>] [--group <name>]",
+            ))
+        }
+    };
+    let date = |name: &str| -> Result<Option<NaiveDate>, Box<dyn Error>> {
+        match args.value(name) {
+            Some(date) => {
+                Ok(Some(date.parse().map_err(|_| {
+                    usage("dates have to be given as YYYY-MM-DD")
+                })?))
+            }
+            None => Ok(None),
+        }
+    };
+    let session = repl::Session::load(chain)?.with_range(date("from")?, date("to")?);
+    let by = args.value("by").unwrap_or("confirmed");
+    print!("{}", session.top(by, n, args.value("group"))?);
+    Ok(())
+}
+
+fn alert(chain: &Chain, args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    let country = args
+        .value("country")
+        .ok_or_else(|| usage("--country is required"))?;
+    let metric = args.value("metric").unwrap_or("new_cases");
+    let threshold = |name: &str| -> Result<Option<i64>, Box<dyn Error>> {
+        match args.value(name) {
+            Some(v) => Ok(Some(v.parse()?)),
+            None => Ok(None),
+        }
+    };
+    let (above, below) = (threshold("above")?, threshold("below")?);
+    if above.is_none() && below.is_none() {
+        return Err(usage("--above or --below is required"));
+    }
+
+    // the sentence is for people, scripts have the exit code
+    if let Some(message) = data::check_alert(chain, country, metric, above, below)? {
+        eprintln!("{}", message);
+        chain.hooks().alert(&message)?;
+        std::process::exit(exit::THRESHOLD);
+    }
+    Ok(())
 }
@@ -0,0 +1,294 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use crate::chain::{Download, FetchFuture, Fetcher};
+use crate::client::NotFound;
+use crate::data::{GITHUB_API, UPSTREAM};
+use crate::progress::Progress;
+use crate::source::{disease_sh, ecdc, owid};
+use crate::validators::Validators;
+use crate::{archive, deflate, inflate};
+
+// Fixture CSVs served by the mock upstream, keyed by their path below the
+// repository root, exactly as they are laid out upstream.
+pub const FIXTURES: &[(&str, &str)] = &[
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-14-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-14-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-15-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-15-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-16-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-16-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-17-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-17-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-18-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-18-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-19-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-19-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-20-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-20-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports/03-21-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports/03-21-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_confirmed_global.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_confirmed_global.csv"),
+    ),
+    (
+        "archived_data/archived_time_series/time_series_19-covid-Confirmed_archived_0325.csv",
+        include_str!("../fixtures/archived_data/archived_time_series/time_series_19-covid-Confirmed_archived_0325.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_deaths_global.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_deaths_global.csv"),
+    ),
+    (
+        "archived_data/archived_time_series/time_series_19-covid-Deaths_archived_0325.csv",
+        include_str!("../fixtures/archived_data/archived_time_series/time_series_19-covid-Deaths_archived_0325.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_recovered_global.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_recovered_global.csv"),
+    ),
+    (
+        "archived_data/archived_time_series/time_series_19-covid-Recovered_archived_0325.csv",
+        include_str!("../fixtures/archived_data/archived_time_series/time_series_19-covid-Recovered_archived_0325.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_daily_reports_us/04-12-2020.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_daily_reports_us/04-12-2020.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_confirmed_US.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_confirmed_US.csv"),
+    ),
+    (
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_deaths_US.csv",
+        include_str!("../fixtures/csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_deaths_US.csv"),
+    ),
+    (
+        "csse_covid_19_data/UID_ISO_FIPS_LookUp_Table.csv",
+        include_str!("../fixtures/csse_covid_19_data/UID_ISO_FIPS_LookUp_Table.csv"),
+    ),
+    (
+        "owid/owid-covid-data.csv",
+        include_str!("../fixtures/owid/owid-covid-data.csv"),
+    ),
+    (
+        "owid/vaccinations/vaccinations.csv",
+        include_str!("../fixtures/owid/vaccinations.csv"),
+    ),
+    (
+        "ecdc/casedistribution/csv",
+        include_str!("../fixtures/ecdc/casedistribution.csv"),
+    ),
+    (
+        "disease.sh/historical",
+        include_str!("../fixtures/disease.sh/historical.json"),
+    ),
+];
+
+// The query string is ignored, fixtures are served for any parameters.
+pub fn fixture(path: &str) -> Option<&'static str> {
+    let path = path.split('?').next().unwrap_or_default();
+    FIXTURES
+        .iter()
+        .find(|(p, _)| *p == path.trim_start_matches('/'))
+        .map(|(_, body)| *body)
+}
+
+// The fixtures for the real URLs without any server, for a chain in tests:
+// `chain.set_fetcher(Box::new(Fixtures))` runs the whole pipeline in process.
+pub struct Fixtures;
+
+impl Fixtures {
+    fn resolve(url: &str) -> Option<Vec<u8>> {
+        if let Some(path) = url.strip_prefix(UPSTREAM) {
+            return fixture(path).map(|body| body.as_bytes().to_vec());
+        }
+        if let Some(dir) = url.strip_prefix(GITHUB_API) {
+            return Some(listing(dir).into_bytes());
+        }
+        if url.strip_prefix(archive::BASE) == Some("zip/refs/heads/master") {
+            return Some(snapshot());
+        }
+        [
+            (owid::BASE, "owid"),
+            (ecdc::BASE, "ecdc"),
+            (disease_sh::BASE, "disease.sh"),
+        ]
+        .iter()
+        .find_map(|(base, name)| {
+            let path = url.strip_prefix(base)?;
+            fixture(&format!("{}/{}", name, path)).map(|body| body.as_bytes().to_vec())
+        })
+    }
+}
+
+impl Fetcher for Fixtures {
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        _validators: Option<&'a Validators>,
+        _progress: &'a Progress,
+    ) -> FetchFuture<'a> {
+        Box::pin(async move {
+            match Fixtures::resolve(url) {
+                Some(body) => Ok(Download::Body(body, Validators::default())),
+                None => Err(NotFound {
+                    url: url.to_string(),
+                }
+                .into()),
+            }
+        })
+    }
+}
+
+// A tiny HTTP server on localhost that answers the JHU paths with the bundled
+// fixtures and 404 for everything else, so the whole pipeline can run offline
+// and deterministically. It runs on a background thread for the lifetime of
+// the process.
+pub struct MockServer {
+    addr: SocketAddr,
+}
+
+impl MockServer {
+    pub fn start() -> io::Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || {
+                    let _ = respond(stream);
+                });
+            }
+        });
+        Ok(MockServer { addr })
+    }
+
+    // Base URL to use in place of the upstream repository root.
+    pub fn base_url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    // Base URL below which the fixtures of another source `name` are served.
+    pub fn source_url(&self, name: &str) -> String {
+        format!("http://{}/{}/", self.addr, name)
+    }
+
+    // Base URL to use in place of the GitHub contents API of the repository.
+    pub fn api_url(&self) -> String {
+        format!("http://{}/api/", self.addr)
+    }
+}
+
+// The fixtures of a directory in the shape of a GitHub contents API answer.
+fn listing(dir: &str) -> String {
+    let dir = format!("{}/", dir.trim_end_matches('/'));
+    let entries: Vec<String> = FIXTURES
+        .iter()
+        .filter_map(|(path, _)| path.strip_prefix(dir.as_str()))
+        .map(|name| format!("{{\"name\": \"{}\", \"type\": \"file\"}}", name))
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+fn respond(mut stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // drain the headers, answering gzipped when the client accepts it
+    let mut line = String::new();
+    let mut gzip = false;
+    while reader.read_line(&mut line)? > 2 {
+        let lower = line.to_ascii_lowercase();
+        gzip |= lower.starts_with("accept-encoding:") && lower.contains("gzip");
+        line.clear();
+    }
+
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = match (fixture(path), path.strip_prefix("/api/")) {
+        _ if path == "/archive/zip/refs/heads/master" => ("200 OK", "application/zip", snapshot()),
+        (Some(body), _) => ("200 OK", "text/plain", body.as_bytes().to_vec()),
+        (None, Some(dir)) => ("200 OK", "application/json", listing(dir).into_bytes()),
+        (None, None) => ("404 Not Found", "text/plain", b"404: Not Found".to_vec()),
+    };
+    let (encoding, body) = if gzip {
+        ("Content-Encoding: gzip\r\n", deflate::gzip(&body))
+    } else {
+        ("", body)
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        encoding,
+        body.len(),
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+// The fixtures as a snapshot zip of the repository like GitHub serves it, with
+// the files stored uncompressed below a top level directory.
+fn snapshot() -> Vec<u8> {
+    let mut zip = Vec::new();
+    let mut directory = Vec::new();
+    let files: Vec<(String, &str)> = FIXTURES
+        .iter()
+        .map(|(path, body)| (format!("COVID-19-master/{}", path), *body))
+        .collect();
+    for (name, body) in files.iter() {
+        let offset = zip.len() as u32;
+        let crc = inflate::crc32(body.as_bytes());
+        let size = body.len() as u32;
+        // version, flags, method, time and date, crc and sizes
+        let mut header = Vec::new();
+        header.extend_from_slice(&20u16.to_le_bytes());
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+
+        zip.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&header);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(body.as_bytes());
+
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&header);
+        // comment length, disk, attributes
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let start = zip.len() as u32;
+    zip.extend_from_slice(&directory);
+    zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    zip.extend_from_slice(&[0; 4]);
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&start.to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes());
+    zip
+}
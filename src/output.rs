@@ -0,0 +1,101 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::data::TimeSeries;
+use crate::dataset::Dataset;
+use crate::provenance::Provenance;
+
+// The series of an export, handed over one at a time.
+pub type SeriesStream<'a> = dyn Iterator<Item = TimeSeries> + 'a;
+
+// A format data can be written in. The exports are implemented with it, and
+// an application can hand its own to `export::export_with`.
+pub trait OutputWriter {
+    fn write(&mut self, dataset: &Dataset) -> Result<(), Box<dyn Error>>;
+
+    // The series of an export as they are selected. Formats that can write a
+    // series before the next one exists override this, all others get the
+    // series collected into a dataset.
+    fn write_stream(
+        &mut self,
+        series: &mut SeriesStream,
+        provenance: Option<Provenance>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.write(&Dataset::new(Vec::new(), series.collect(), provenance))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Confirmed,
+    Deaths,
+    Recovered,
+    NewCases,
+    Cfr,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortKey, String> {
+        match s {
+            "confirmed" => Ok(SortKey::Confirmed),
+            "deaths" => Ok(SortKey::Deaths),
+            "recovered" => Ok(SortKey::Recovered),
+            "new_cases" => Ok(SortKey::NewCases),
+            "cfr" => Ok(SortKey::Cfr),
+            _ => Err(format!(
+                "unknown sort key '{}', expected confirmed, deaths, recovered, new_cases or cfr",
+                s
+            )),
+        }
+    }
+}
+
+// Rows of a listing that can be ordered by `--sort`. Rows without a value for
+// the key (e.g. new cases in a listing of a single report) sort last.
+pub trait Sortable {
+    fn label(&self) -> &str;
+    fn sort_value(&self, key: SortKey) -> Option<f64>;
+}
+
+// How a listing should be ordered; without a key the command keeps its own order.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Sort {
+    pub key: Option<SortKey>,
+    pub desc: bool,
+}
+
+impl Sort {
+    pub fn apply<T: Sortable>(&self, rows: &mut [T]) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+        rows.sort_by(|a, b| {
+            let ordering = match (a.sort_value(key), b.sort_value(key)) {
+                (Some(x), Some(y)) => {
+                    let o = x.partial_cmp(&y).unwrap_or(Ordering::Equal);
+                    if self.desc {
+                        o.reverse()
+                    } else {
+                        o
+                    }
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            ordering.then_with(|| a.label().cmp(b.label()))
+        });
+    }
+}
+
+pub fn ratio(part: u64, total: u64) -> Option<f64> {
+    if total == 0 {
+        None
+    } else {
+        Some(part as f64 / total as f64)
+    }
+}
@@ -0,0 +1,259 @@
+// Writer for Apache Parquet files, the subset an export needs: required
+// columns of 32 bit integers, doubles, dates and UTF-8 strings, PLAIN
+// encoded and uncompressed, in a single row group with a page per column.
+// The metadata is Thrift's compact protocol, written by hand like the rest
+// of the formats this crate speaks.
+
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+const MAGIC: &[u8] = b"PAR1";
+
+// Thrift compact types
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+// Parquet enums
+const TYPE_INT32: i32 = 1;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REQUIRED: i32 = 0;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_DATE: i32 = 6;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const UNCOMPRESSED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+
+pub enum Column {
+    Int32(Vec<i32>),
+    Double(Vec<f64>),
+    // days since 1970-01-01
+    Date(Vec<i32>),
+    Utf8(Vec<String>),
+}
+
+impl Column {
+    fn len(&self) -> usize {
+        match self {
+            Column::Int32(values) | Column::Date(values) => values.len(),
+            Column::Double(values) => values.len(),
+            Column::Utf8(values) => values.len(),
+        }
+    }
+
+    fn physical_type(&self) -> i32 {
+        match self {
+            Column::Int32(_) | Column::Date(_) => TYPE_INT32,
+            Column::Double(_) => TYPE_DOUBLE,
+            Column::Utf8(_) => TYPE_BYTE_ARRAY,
+        }
+    }
+
+    fn converted_type(&self) -> Option<i32> {
+        match self {
+            Column::Int32(_) | Column::Double(_) => None,
+            Column::Date(_) => Some(CONVERTED_DATE),
+            Column::Utf8(_) => Some(CONVERTED_UTF8),
+        }
+    }
+
+    // PLAIN: little endian numbers, strings prefixed with their length.
+    // Required columns have no definition or repetition levels.
+    fn plain(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        match self {
+            Column::Int32(values) | Column::Date(values) => {
+                for v in values.iter() {
+                    data.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Column::Double(values) => {
+                for v in values.iter() {
+                    data.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Column::Utf8(values) => {
+                for v in values.iter() {
+                    data.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                    data.extend_from_slice(v.as_bytes());
+                }
+            }
+        }
+        data
+    }
+}
+
+// Thrift compact protocol, field ids are written as deltas to the previous
+// field of the same struct.
+struct Compact {
+    out: Vec<u8>,
+    last: Vec<i16>,
+}
+
+impl Compact {
+    fn new() -> Compact {
+        Compact {
+            out: Vec::new(),
+            last: vec![0],
+        }
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.out.push(value as u8);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let last = self.last.last_mut().expect("inside a struct");
+        let delta = id - *last;
+        *last = id;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            self.zigzag(i64::from(id));
+        }
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.zigzag(i64::from(value));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        self.zigzag(value);
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.out.extend_from_slice(value);
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, BINARY);
+        self.bytes(value);
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | kind);
+        } else {
+            self.out.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    // a struct as a field, or as an element of a list without `id`
+    fn begin(&mut self, id: Option<i16>) {
+        if let Some(id) = id {
+            self.field(id, STRUCT);
+        }
+        self.last.push(0);
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last.pop();
+    }
+}
+
+// Writes `columns`, all of the same length, as a Parquet file.
+pub fn write<W: Write>(mut out: W, columns: &[(&str, Column)]) -> io::Result<()> {
+    let rows = columns.first().map(|(_, c)| c.len()).unwrap_or(0);
+    if columns.iter().any(|(_, c)| c.len() != rows) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "columns of different lengths",
+        ));
+    }
+    out.write_all(MAGIC)?;
+    let mut offset = MAGIC.len() as i64;
+    // (offset of the page, size with its header) per column
+    let mut chunks = Vec::new();
+    for (_, column) in columns.iter() {
+        let data = column.plain();
+        let size = i32::try_from(data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "column too large"))?;
+        let mut header = Compact::new();
+        header.i32(1, DATA_PAGE);
+        header.i32(2, size);
+        header.i32(3, size);
+        header.begin(Some(5));
+        header.i32(1, rows as i32);
+        header.i32(2, ENCODING_PLAIN);
+        header.i32(3, ENCODING_RLE);
+        header.i32(4, ENCODING_RLE);
+        header.end();
+        header.out.push(0);
+        out.write_all(&header.out)?;
+        out.write_all(&data)?;
+        let total = (header.out.len() + data.len()) as i64;
+        chunks.push((offset, total));
+        offset += total;
+    }
+
+    let mut meta = Compact::new();
+    meta.i32(1, 1);
+    meta.list(2, STRUCT, columns.len() + 1);
+    meta.begin(None);
+    meta.binary(4, b"schema");
+    meta.i32(5, columns.len() as i32);
+    meta.end();
+    for (name, column) in columns.iter() {
+        meta.begin(None);
+        meta.i32(1, column.physical_type());
+        meta.i32(3, REQUIRED);
+        meta.binary(4, name.as_bytes());
+        if let Some(converted) = column.converted_type() {
+            meta.i32(6, converted);
+        }
+        meta.end();
+    }
+    meta.i64(3, rows as i64);
+    meta.list(4, STRUCT, 1);
+    meta.begin(None);
+    meta.list(1, STRUCT, columns.len());
+    for ((name, column), (page, total)) in columns.iter().zip(chunks.iter()) {
+        meta.begin(None);
+        meta.i64(2, *page);
+        meta.begin(Some(3));
+        meta.i32(1, column.physical_type());
+        meta.list(2, I32, 1);
+        meta.zigzag(i64::from(ENCODING_PLAIN));
+        meta.list(3, BINARY, 1);
+        meta.bytes(name.as_bytes());
+        meta.i32(4, UNCOMPRESSED);
+        meta.i64(5, rows as i64);
+        meta.i64(6, *total);
+        meta.i64(7, *total);
+        meta.i64(9, *page);
+        meta.end();
+        meta.end();
+    }
+    meta.i64(2, chunks.iter().map(|(_, total)| total).sum());
+    meta.i64(3, rows as i64);
+    meta.end();
+    meta.binary(
+        6,
+        format!("corona-stats version {}", env!("CARGO_PKG_VERSION")).as_bytes(),
+    );
+    meta.out.push(0);
+
+    out.write_all(&meta.out)?;
+    out.write_all(&(meta.out.len() as u32).to_le_bytes())?;
+    out.write_all(MAGIC)?;
+    out.flush()
+}
@@ -0,0 +1,84 @@
+use csv::ReaderBuilder;
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::chain::Chain;
+use crate::data::UPSTREAM;
+use crate::groups;
+
+// The UID/ISO/FIPS lookup table of the JHU repository, which also has the
+// population of every location in the time series.
+pub const LOOKUP_TABLE: &str = "csse_covid_19_data/UID_ISO_FIPS_LookUp_Table.csv";
+
+// Populations by country and province, keyed by the names of the time
+// series. The counties of the US are left out.
+#[derive(Debug, Clone, Default)]
+pub struct Populations {
+    locations: HashMap<(String, String), u64>,
+}
+
+impl Populations {
+    pub fn parse(body: &str) -> Result<Populations, Box<dyn Error>> {
+        let mut rdr = ReaderBuilder::new().from_reader(body.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| format!("lookup table without a {} column", name))
+        };
+        let (county, province, country, population) = (
+            column("Admin2")?,
+            column("Province_State")?,
+            column("Country_Region")?,
+            column("Population")?,
+        );
+        let mut locations = HashMap::new();
+        for record in rdr.records() {
+            let record = record?;
+            let get = |i: usize| record.get(i).unwrap_or("").trim();
+            if !get(county).is_empty() {
+                continue;
+            }
+            // some places have no population
+            if let Ok(population) = get(population).parse() {
+                locations.insert(
+                    (get(country).to_string(), get(province).to_string()),
+                    population,
+                );
+            }
+        }
+        Ok(Populations { locations })
+    }
+
+    // The population of `province`, of the whole country for an empty one.
+    // A group has the sum of its members, as long as all of them are known.
+    pub fn get(&self, country: &str, province: &str) -> Option<u64> {
+        if let (Some(members), "") = (groups::members(country), province) {
+            return members.iter().map(|m| self.get(m, "")).sum();
+        }
+        self.locations
+            .get(&(country.to_string(), province.to_string()))
+            .cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+// `value` per 100,000 people
+pub fn per_100k(value: u64, population: u64) -> Option<f64> {
+    if population == 0 {
+        None
+    } else {
+        Some(value as f64 / population as f64 * 100_000.0)
+    }
+}
+
+pub async fn fetch(chain: &Chain) -> Result<Populations, Box<dyn Error>> {
+    let body = chain
+        .fetch(&format!("{}{}", UPSTREAM, LOOKUP_TABLE))
+        .await?;
+    Populations::parse(&body)
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+// Decides which source wins for a metric when several sources report it, e.g.
+// cases from "jhu", vaccinations from "owid", hospitalizations from "ecdc".
+// Metrics without an explicit order fall back to the default order.
+#[derive(Debug, Clone, Default)]
+pub struct Precedence {
+    default: Vec<String>,
+    metrics: HashMap<String, Vec<String>>,
+}
+
+// A resolved value together with the source it was taken from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved<T> {
+    pub value: T,
+    pub source: String,
+    pub metric: String,
+}
+
+impl Precedence {
+    pub fn new(default: &[&str]) -> Precedence {
+        Precedence {
+            default: default.iter().map(|s| s.to_string()).collect(),
+            metrics: HashMap::new(),
+        }
+    }
+
+    pub fn with_metric(mut self, metric: &str, order: &[&str]) -> Precedence {
+        self.metrics.insert(
+            metric.to_string(),
+            order.iter().map(|s| s.to_string()).collect(),
+        );
+        self
+    }
+
+    // Parses "confirmed=jhu,owid;vaccinations=owid" as given on the command line
+    // or in the config file.
+    pub fn parse(spec: &str, default: &[&str]) -> Result<Precedence, String> {
+        Precedence::new(default).with_spec(spec)
+    }
+
+    // `parse` on top of the orders there are, replacing those of the metrics
+    // in `spec`.
+    pub fn with_spec(self, spec: &str) -> Result<Precedence, String> {
+        let mut precedence = self;
+        for part in spec.split(';').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let (metric, order) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected metric=source,... but got '{}'", part))?;
+            let order: Vec<&str> = order.split(',').map(|s| s.trim()).collect();
+            precedence = precedence.with_metric(metric.trim(), &order);
+        }
+        Ok(precedence)
+    }
+
+    // Every source named anywhere, the default order first.
+    pub fn sources(&self) -> Vec<String> {
+        let mut metrics: Vec<_> = self.metrics.iter().collect();
+        metrics.sort();
+        let mut sources: Vec<String> = Vec::new();
+        for source in self
+            .default
+            .iter()
+            .chain(metrics.into_iter().flat_map(|(_, o)| o))
+        {
+            if !sources.contains(source) {
+                sources.push(source.clone());
+            }
+        }
+        sources
+    }
+
+    pub fn order(&self, metric: &str) -> &[String] {
+        self.metrics.get(metric).unwrap_or(&self.default)
+    }
+
+    // `candidates` are (source, value) pairs; sources missing from the order
+    // are only used if none of the listed sources has a value.
+    pub fn resolve<T: Clone>(&self, metric: &str, candidates: &[(&str, T)]) -> Option<Resolved<T>> {
+        let order = self.order(metric);
+        let ranked = order
+            .iter()
+            .filter_map(|source| candidates.iter().find(|(s, _)| s == source))
+            .chain(candidates.iter())
+            .next()?;
+        Some(Resolved {
+            value: ranked.1.clone(),
+            source: ranked.0.to_string(),
+            metric: metric.to_string(),
+        })
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use crate::data::TimeSeries;
+use crate::derived;
+
+// Noise for re-published exports. Counts below `threshold` get Laplace noise
+// with scale `sensitivity / epsilon` added; larger counts are passed through.
+// The noise for a cell only depends on `seed` and the cell key, so publishing
+// the same export twice does not let readers average the noise away, with
+// this or any later build. Cumulative counts get the noise of their daily
+// increases, summed up again, so they keep rising and agree with the new
+// cases and deaths. Analytics never go through here and always work on the
+// exact numbers.
+//
+// Set per export format in the config file, e.g. `[privacy.csv]`:
+//   threshold    counts below it are perturbed, default 10
+//   epsilon      privacy budget, smaller is noisier, default 1.0; 0 turns
+//                the noise off
+//   sensitivity  how much one person changes a count, default 1
+//   seed         keys the noise, keep it secret and fixed, default 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perturbation {
+    pub threshold: u32,
+    pub epsilon: f64,
+    pub sensitivity: f64,
+    pub seed: u64,
+}
+
+// the series that count people, rates and ratios are never perturbed
+const COUNTS: &[&str] = &[
+    "Confirmed",
+    "Deaths",
+    "Recovered",
+    "Active",
+    derived::NEW_CONFIRMED,
+    derived::NEW_DEATHS,
+];
+
+// the cumulative counts and the series of their daily increases, whose keys
+// their increases are perturbed with
+const CUMULATIVE: &[(&str, &str)] = &[
+    ("Confirmed", derived::NEW_CONFIRMED),
+    ("Deaths", derived::NEW_DEATHS),
+    ("Recovered", "New recovered"),
+];
+
+impl Default for Perturbation {
+    fn default() -> Perturbation {
+        Perturbation {
+            threshold: 10,
+            epsilon: 1.0,
+            sensitivity: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Perturbation {
+    pub fn apply(&self, key: &str, value: u32) -> u32 {
+        if value >= self.threshold || self.epsilon <= 0.0 {
+            return value;
+        }
+        let noisy = f64::from(value) + self.laplace(key);
+        noisy.round().max(0.0) as u32
+    }
+
+    // Every small count of the counting series, keyed by location, series
+    // and date, or small daily increase of the cumulative ones. Negative
+    // corrections are left as they are.
+    pub fn perturb(&self, series: &mut [TimeSeries]) {
+        for s in series.iter_mut() {
+            self.perturb_series(s);
+        }
+    }
+
+    // The same for one series, in place.
+    pub fn perturb_series(&self, s: &mut TimeSeries) {
+        if !COUNTS.contains(&s.state.as_str()) {
+            return;
+        }
+        let daily = CUMULATIVE
+            .iter()
+            .find(|(cumulative, _)| *cumulative == s.state)
+            .map(|(_, daily)| *daily);
+        let prefix = format!(
+            "{}/{}/{}/",
+            s.country,
+            s.province,
+            daily.unwrap_or(&s.state)
+        );
+        let (mut previous, mut total) = (0, 0);
+        for (date, value) in s.data.iter_mut() {
+            let current = i64::from(*value);
+            let cell = match daily {
+                Some(_) => current - previous,
+                None => current,
+            };
+            previous = current;
+            let noisy = match u32::try_from(cell) {
+                Ok(cell) => i64::from(self.apply(&format!("{}{}", prefix, date), cell)),
+                Err(_) => cell,
+            };
+            total = match daily {
+                Some(_) => total + noisy,
+                None => noisy,
+            };
+            *value = total.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+        }
+    }
+
+    fn laplace(&self, key: &str) -> f64 {
+        let hash = siphash13(self.seed, 0, key.as_bytes());
+        // uniform in (-0.5, 0.5), never exactly at the edges
+        let u = ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64 - 0.5;
+        let scale = self.sensitivity / self.epsilon;
+        -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+    }
+}
+
+// SipHash-1-3 of `data` with the key `k0`, `k1`, written out rather than
+// std's hasher, whose algorithm may change with any release.
+fn siphash13(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    let round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+    let chunks = data.chunks_exact(8);
+    // the last bytes with the length in the top byte
+    let mut last = (data.len() as u64) << 56;
+    for (i, byte) in chunks.remainder().iter().enumerate() {
+        last |= u64::from(*byte) << (8 * i);
+    }
+    for chunk in chunks {
+        let m = u64::from_le_bytes(chunk.try_into().unwrap_or_default());
+        v[3] ^= m;
+        round(&mut v);
+        v[0] ^= m;
+    }
+    v[3] ^= last;
+    round(&mut v);
+    v[0] ^= last;
+    v[2] ^= 0xff;
+    for _ in 0..3 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+// Per export target settings, e.g. "csv" perturbed and "json" exact.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrivacyPolicy {
+    targets: HashMap<String, Perturbation>,
+}
+
+impl PrivacyPolicy {
+    pub fn new() -> PrivacyPolicy {
+        PrivacyPolicy::default()
+    }
+
+    pub fn with_target(mut self, target: &str, perturbation: Perturbation) -> PrivacyPolicy {
+        self.targets.insert(target.to_string(), perturbation);
+        self
+    }
+
+    pub fn for_target(&self, target: &str) -> Option<&Perturbation> {
+        self.targets.get(target)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    pub fn apply(&self, target: &str, key: &str, value: u32) -> u32 {
+        match self.for_target(target) {
+            Some(p) => p.apply(key, value),
+            None => value,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::sync::Mutex;
+
+const WIDTH: usize = 30;
+
+#[derive(Default)]
+struct State {
+    total: usize,
+    done: usize,
+    file: String,
+    file_bytes: u64,
+    file_length: Option<u64>,
+}
+
+// A single line progress display on stderr for downloads of many files: the
+// overall bar counts files, the suffix shows the file currently downloading.
+// Disabled instances do nothing, so callers never have to check.
+#[derive(Default)]
+pub struct Progress {
+    enabled: bool,
+    state: Mutex<State>,
+}
+
+impl Progress {
+    pub fn new(enabled: bool) -> Progress {
+        Progress {
+            enabled,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn start(&self, total: usize) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        *state = State {
+            total,
+            ..State::default()
+        };
+        draw(&state);
+    }
+
+    pub fn file_started(&self, url: &str, length: Option<u64>) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.file = url.rsplit('/').next().unwrap_or(url).to_string();
+        state.file_bytes = 0;
+        state.file_length = length;
+        draw(&state);
+    }
+
+    pub fn file_bytes(&self, bytes: u64) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.file_bytes = bytes;
+        draw(&state);
+    }
+
+    pub fn advance(&self) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.done += 1;
+        state.file.clear();
+        draw(&state);
+    }
+
+    pub fn finish(&self) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.total > 0 {
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
+        }
+        *state = State::default();
+    }
+}
+
+fn draw(state: &State) {
+    if state.total == 0 {
+        return;
+    }
+    let filled = (state.done * WIDTH / state.total).min(WIDTH);
+    let file = match (state.file.is_empty(), state.file_length) {
+        (true, _) => String::new(),
+        (false, Some(length)) if length > 0 => format!(
+            "  {} {:>3}%",
+            state.file,
+            (state.file_bytes * 100 / length).min(100)
+        ),
+        (false, _) => format!("  {} {} KiB", state.file, state.file_bytes / 1024),
+    };
+    eprint!(
+        "\r\x1b[2K[{}{}] {}/{} files{}",
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        state.done,
+        state.total,
+        file
+    );
+    let _ = std::io::stderr().flush();
+}
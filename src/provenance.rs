@@ -0,0 +1,82 @@
+use chrono::NaiveDateTime;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// The file in the cache directory with the provenance of the last download.
+pub const FILE: &str = "provenance";
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+// Where data came from and when, to tell later which upstream state an
+// analysis was based on: the source, the base URL it was downloaded from
+// (a mirror, the repository of a checkout) and the commit of a checkout.
+// Merged sources also record which of them each metric was taken from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub source: String,
+    pub url: String,
+    pub fetched: NaiveDateTime,
+    pub commit: Option<String>,
+    // (metric, "source,...") pairs
+    pub metrics: Vec<(String, String)>,
+}
+
+impl Provenance {
+    // The `key: value` lines of `Display`.
+    pub fn parse(text: &str) -> Option<Provenance> {
+        let (mut source, mut url, mut fetched, mut commit) = (None, None, None, None);
+        let mut metrics = Vec::new();
+        for line in text.lines() {
+            match line.split_once(": ") {
+                Some(("source", value)) => source = Some(value.to_string()),
+                Some(("url", value)) => url = Some(value.to_string()),
+                Some(("fetched", value)) => {
+                    fetched = NaiveDateTime::parse_from_str(value, TIME_FORMAT).ok()
+                }
+                Some(("commit", value)) => commit = Some(value.to_string()),
+                Some((key, value)) if key.starts_with("metric.") => {
+                    metrics.push((key["metric.".len()..].to_string(), value.to_string()))
+                }
+                _ => {}
+            }
+        }
+        Some(Provenance {
+            source: source?,
+            url: url?,
+            fetched: fetched?,
+            commit,
+            metrics,
+        })
+    }
+
+    pub fn load(path: &Path) -> Option<Provenance> {
+        Provenance::parse(&fs::read_to_string(path).ok()?)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())
+    }
+
+    pub fn fetched(&self) -> String {
+        self.fetched.format(TIME_FORMAT).to_string()
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "source: {}", self.source)?;
+        writeln!(f, "url: {}", self.url)?;
+        writeln!(f, "fetched: {}", self.fetched())?;
+        if let Some(commit) = &self.commit {
+            writeln!(f, "commit: {}", commit)?;
+        }
+        for (metric, sources) in self.metrics.iter() {
+            writeln!(f, "metric.{}: {}", metric, sources)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use chrono::NaiveDate;
+use std::str::FromStr;
+
+// How much a country's series can be trusted: when it was last updated, on how
+// many days of the covered period it reported at all, and how often the
+// cumulative numbers were revised downwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completeness {
+    pub last_date: Option<NaiveDate>,
+    pub coverage: f64,
+    pub revisions: usize,
+}
+
+impl Completeness {
+    // `expected` is the range of days a complete series would cover.
+    pub fn of(cumulative: &[(NaiveDate, i64)], expected: (NaiveDate, NaiveDate)) -> Completeness {
+        let days = ((expected.1 - expected.0).num_days() + 1).max(1) as f64;
+        let reported = cumulative
+            .iter()
+            .filter(|(d, _)| *d >= expected.0 && *d <= expected.1)
+            .count() as f64;
+        Completeness {
+            last_date: cumulative.iter().map(|(d, _)| *d).max(),
+            coverage: (reported / days).min(1.0),
+            revisions: decreases(cumulative).len(),
+        }
+    }
+
+    pub fn badges(&self, today: NaiveDate) -> Vec<Badge> {
+        let stale = self
+            .last_date
+            .map(|d| (today - d).num_days())
+            .unwrap_or(i64::MAX);
+        vec![
+            Badge {
+                label: "last data",
+                value: self
+                    .last_date
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| "none".to_string()),
+                level: match stale {
+                    0..=2 => Level::Good,
+                    3..=7 => Level::Warn,
+                    _ => Level::Bad,
+                },
+            },
+            Badge {
+                label: "coverage",
+                value: format!("{:.0}%", self.coverage * 100.0),
+                level: if self.coverage >= 0.95 {
+                    Level::Good
+                } else if self.coverage >= 0.75 {
+                    Level::Warn
+                } else {
+                    Level::Bad
+                },
+            },
+            Badge {
+                label: "revisions",
+                value: self.revisions.to_string(),
+                level: match self.revisions {
+                    0 => Level::Good,
+                    1..=5 => Level::Warn,
+                    _ => Level::Bad,
+                },
+            },
+        ]
+    }
+}
+
+// A day a cumulative count was lower than the day before, which only an
+// upstream correction explains.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decrease {
+    pub date: NaiveDate,
+    pub before: i64,
+    pub after: i64,
+}
+
+impl Decrease {
+    pub fn change(&self) -> i64 {
+        self.after - self.before
+    }
+}
+
+pub fn decreases(cumulative: &[(NaiveDate, i64)]) -> Vec<Decrease> {
+    cumulative
+        .windows(2)
+        .filter(|w| w[1].1 < w[0].1)
+        .map(|w| Decrease {
+            date: w[1].0,
+            before: w[0].1,
+            after: w[1].1,
+        })
+        .collect()
+}
+
+// How far two counts of the same thing may differ: by a number of cases, or
+// by a share of the larger count as with "1%".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    Absolute(u64),
+    Relative(f64),
+}
+
+impl FromStr for Tolerance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Tolerance, String> {
+        let invalid = || {
+            format!(
+                "invalid tolerance '{}', expected a count or a percentage",
+                s
+            )
+        };
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(p) if p >= 0.0 => Ok(Tolerance::Relative(p / 100.0)),
+                _ => Err(invalid()),
+            },
+            None => s.parse().map(Tolerance::Absolute).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl Tolerance {
+    pub fn exceeded(&self, a: i64, b: i64) -> bool {
+        let difference = a.abs_diff(b);
+        match self {
+            Tolerance::Absolute(n) => difference > *n,
+            Tolerance::Relative(share) => difference as f64 > a.max(b) as f64 * share,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Good,
+    Warn,
+    Bad,
+}
+
+impl Level {
+    fn color(self) -> &'static str {
+        match self {
+            Level::Good => "#4c1",
+            Level::Warn => "#dfb317",
+            Level::Bad => "#e05d44",
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Level::Good => "✔",
+            Level::Warn => "⚠",
+            Level::Bad => "✖",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge {
+    pub label: &'static str,
+    pub value: String,
+    pub level: Level,
+}
+
+impl Badge {
+    pub fn markdown(&self) -> String {
+        format!("`{} {}: {}`", self.level.symbol(), self.label, self.value)
+    }
+
+    pub fn html(&self) -> String {
+        format!(
+            "<span class=\"badge\" style=\"background:{}\">{}: {}</span>",
+            self.level.color(),
+            self.label,
+            self.value
+        )
+    }
+}
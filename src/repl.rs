@@ -0,0 +1,400 @@
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{BufRead, IsTerminal, Write};
+
+use crate::analytics::{doubling_time, growth_factor, DOUBLING_DAYS};
+use crate::chain::Chain;
+use crate::data::{country_series, get_populations, get_time_series};
+use crate::derived::incidence_of;
+use crate::exit::Failure;
+use crate::groups;
+use crate::locale;
+use crate::population::Populations;
+use crate::search;
+use crate::table::{format_change, format_count, format_decimal, Align, Table};
+use tracing::warn;
+
+const STATES: [&str; 3] = ["Confirmed", "Deaths", "Recovered"];
+
+const HELP: &str = "commands:
+  country <name>          daily values of a country or group within the range
+  top <metric> [n] [group]
+                          countries with the highest confirmed, deaths, recovered, new_cases,
+                          new_deaths, incidence or growth_factor, the shortest doubling_time,
+                          of all or of the members of a group
+  range <from>..<to>      limit the commands to a date range, `range all` resets it
+  help                    this text
+  quit                    leave";
+
+// The time series are downloaded and summed up per country once, every
+// command afterwards only works on memory.
+pub struct Session {
+    countries: BTreeMap<String, [Vec<(NaiveDate, i64)>; 3]>,
+    // the sums of the configured groups, apart so they are not ranked
+    groups: BTreeMap<String, [Vec<(NaiveDate, i64)>; 3]>,
+    // for the incidence, empty when the lookup table could not be loaded
+    populations: Populations,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+impl Session {
+    pub fn load(chain: &Chain) -> Result<Session, Box<dyn Error>> {
+        let series = get_time_series(chain)?;
+        let mut countries = BTreeMap::new();
+        for elem in series.iter() {
+            if !countries.contains_key(&elem.country) {
+                let values = STATES.map(|state| country_series(&series, &elem.country, state));
+                countries.insert(elem.country.clone(), values);
+            }
+        }
+        let groups = groups::names()
+            .into_iter()
+            .map(|name| {
+                let values = STATES.map(|state| country_series(&series, &name, state));
+                (name, values)
+            })
+            .collect();
+        let populations = get_populations(chain).unwrap_or_else(|e| {
+            warn!("no populations, top incidence is not available: {}", e);
+            Populations::default()
+        });
+        Ok(Session {
+            countries,
+            groups,
+            populations,
+            from: None,
+            to: None,
+        })
+    }
+
+    // Limits the commands to the days from `from` to `to`, both included.
+    pub fn with_range(mut self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Session {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<String, Box<dyn Error>> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] => Ok(format!("{}\n", HELP)),
+            ["country", name @ ..] if !name.is_empty() => self.country(&name.join(" ")),
+            ["top", metric, rest @ ..] if rest.len() <= 2 => {
+                let mut n = 10;
+                let mut group = None;
+                for word in rest {
+                    match word.parse() {
+                        Ok(number) => n = number,
+                        Err(_) if group.is_none() => group = Some(*word),
+                        Err(_) => return Err(usage("usage: top <metric> [n] [group]")),
+                    }
+                }
+                self.top(metric, n, group)
+            }
+            ["range"] => Ok(format!("{}\n", self.describe_range())),
+            ["range", "all"] => {
+                self.from = None;
+                self.to = None;
+                Ok(format!("{}\n", self.describe_range()))
+            }
+            ["range", range] => {
+                let (from, to) = range
+                    .split_once("..")
+                    .ok_or_else(|| usage("usage: range <from>..<to>"))?;
+                let date = |s: &str| -> Result<Option<NaiveDate>, Box<dyn Error>> {
+                    match s {
+                        "" => Ok(None),
+                        _ => {
+                            Ok(Some(s.parse().map_err(|_| {
+                                usage("dates have to be given as YYYY-MM-DD")
+                            })?))
+                        }
+                    }
+                };
+                self.from = date(from)?;
+                self.to = date(to)?;
+                Ok(format!("{}\n", self.describe_range()))
+            }
+            [command, ..] => Err(usage(&format!("unknown command '{}', try help", command))),
+        }
+    }
+
+    fn describe_range(&self) -> String {
+        let show = |d: Option<NaiveDate>| d.map(|d| d.to_string()).unwrap_or_default();
+        match (self.from, self.to) {
+            (None, None) => "range: all dates".to_string(),
+            (from, to) => format!("range: {}..{}", show(from), show(to)),
+        }
+    }
+
+    fn in_range(&self, date: &NaiveDate) -> bool {
+        self.from.map(|f| *date >= f).unwrap_or(true) && self.to.map(|t| *date <= t).unwrap_or(true)
+    }
+
+    fn country(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        let name = search::alias(name).unwrap_or(name);
+        let found = self
+            .countries
+            .iter()
+            .chain(self.groups.iter())
+            .find(|(country, _)| country.eq_ignore_ascii_case(name));
+        let (name, values) = match found {
+            Some(found) => found,
+            None => {
+                let known: Vec<&str> = self
+                    .countries
+                    .keys()
+                    .chain(self.groups.keys())
+                    .map(|c| c.as_str())
+                    .collect();
+                let message = match search::rank(name, &known, 1).first() {
+                    Some((best, _)) => format!("no data for {}, did you mean {}?", name, best),
+                    None => format!("no data for {}", name),
+                };
+                return Err(Failure::NoData(message).into());
+            }
+        };
+        let mut table = Table::new(&[
+            "Date",
+            "Confirmed",
+            "New cases",
+            "Deaths",
+            "New deaths",
+            "Recovered",
+        ]);
+        for column in 1..6 {
+            table = table.align(column, Align::Right);
+        }
+        let [confirmed, deaths, recovered] = values;
+        let mut rows = Vec::new();
+        for (i, (date, value)) in confirmed.iter().enumerate() {
+            if !self.in_range(date) {
+                continue;
+            }
+            let before = |series: &[(NaiveDate, i64)]| match i {
+                0 => 0,
+                _ => series.get(i - 1).map(|v| v.1).unwrap_or(0),
+            };
+            let at = |series: &[(NaiveDate, i64)]| series.get(i).map(|v| v.1).unwrap_or(0);
+            rows.push(vec![
+                locale::format_date(date),
+                format_count((*value).max(0) as u64),
+                format_change(value - before(confirmed)),
+                format_count(at(deaths).max(0) as u64),
+                format_change(at(deaths) - before(deaths)),
+                format_count(at(recovered).max(0) as u64),
+            ]);
+        }
+        if rows.is_empty() {
+            return Err(Failure::NoData(format!("no data for {} in this range", name)).into());
+        }
+        // without a range only the last week is of interest
+        let skip = match (self.from, self.to) {
+            (None, None) => rows.len().saturating_sub(7),
+            _ => 0,
+        };
+        for row in rows.into_iter().skip(skip) {
+            table.add_row(row);
+        }
+        Ok(format!("{}\n{}", name, table))
+    }
+
+    // The `n` countries, or members of `group`, ranked by `metric` within the
+    // range, as a table. The `top` command and the repl share it.
+    pub fn top(
+        &self,
+        metric: &str,
+        n: usize,
+        group: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        if let Some(group) = group.filter(|g| groups::members(g).is_none()) {
+            return Err(usage(&format!("unknown group '{}'", group)));
+        }
+        match metric {
+            "incidence" => return self.top_incidence(n, group),
+            "growth_factor" | "doubling_time" => return self.top_growth(metric, n, group),
+            _ => {}
+        }
+        let (state, daily) = match metric {
+            "confirmed" => (0, false),
+            "deaths" => (1, false),
+            "recovered" => (2, false),
+            "new_cases" | "new_confirmed" => (0, true),
+            "new_deaths" => (1, true),
+            _ => {
+                return Err(usage(&format!(
+                    "unknown metric '{}', expected confirmed, deaths, recovered, new_cases, new_deaths, incidence, growth_factor or doubling_time",
+                    metric
+                )))
+            }
+        };
+        let mut ranked: Vec<(&str, i64)> = self
+            .ranked(group)
+            .filter_map(|(country, values)| {
+                let values: Vec<i64> = values[state]
+                    .iter()
+                    .filter(|(date, _)| self.in_range(date))
+                    .map(|v| v.1)
+                    .collect();
+                let last = *values.last()?;
+                // growth since the start of the range, or of the last day without one
+                let value = match (daily, self.from) {
+                    (false, _) => last,
+                    (true, Some(_)) => last - values[0],
+                    (true, None) => {
+                        last - values.len().checked_sub(2).map(|i| values[i]).unwrap_or(0)
+                    }
+                };
+                Some((country.as_str(), value))
+            })
+            .collect();
+        if ranked.is_empty() {
+            return Err(Failure::NoData("no data in this range".to_string()).into());
+        }
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let mut table = Table::new(&["#", "Country", metric])
+            .align(0, Align::Right)
+            .align(2, Align::Right);
+        for (rank, (country, value)) in ranked.into_iter().take(n).enumerate() {
+            let value = if daily {
+                format_change(value)
+            } else {
+                format_count(value.max(0) as u64)
+            };
+            table.add_row(vec![(rank + 1).to_string(), country.to_string(), value]);
+        }
+        Ok(table.to_string())
+    }
+
+    // The countries to rank, only the members of `group` when one is given.
+    fn ranked<'a>(
+        &'a self,
+        group: Option<&'a str>,
+    ) -> impl Iterator<Item = (&'a String, &'a [Vec<(NaiveDate, i64)>; 3])> + 'a {
+        self.countries
+            .iter()
+            .filter(move |(country, _)| group.map(|g| groups::covers(g, country)).unwrap_or(true))
+    }
+
+    // The highest growth factors or shortest doubling times as of the last day
+    // of the range.
+    fn top_growth(
+        &self,
+        metric: &str,
+        n: usize,
+        group: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        let doubling = metric == "doubling_time";
+        let mut ranked: Vec<(&str, f64)> = self
+            .ranked(group)
+            .filter_map(|(country, values)| {
+                let confirmed: Vec<(NaiveDate, i64)> = values[0]
+                    .iter()
+                    .filter(|(date, _)| self.in_range(date))
+                    .cloned()
+                    .collect();
+                let value = if doubling {
+                    doubling_time(&confirmed, DOUBLING_DAYS)?
+                } else {
+                    growth_factor(&confirmed)?
+                };
+                Some((country.as_str(), value))
+            })
+            .collect();
+        if ranked.is_empty() {
+            return Err(Failure::NoData("no country grows in this range".to_string()).into());
+        }
+        if doubling {
+            ranked.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(b.0)));
+        } else {
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+        }
+
+        let mut table = Table::new(&["#", "Country", metric])
+            .align(0, Align::Right)
+            .align(2, Align::Right);
+        for (rank, (country, value)) in ranked.into_iter().take(n).enumerate() {
+            table.add_row(vec![
+                (rank + 1).to_string(),
+                country.to_string(),
+                format_decimal(value, 2),
+            ]);
+        }
+        Ok(table.to_string())
+    }
+
+    // The incidence of the last day of the range.
+    fn top_incidence(&self, n: usize, group: Option<&str>) -> Result<String, Box<dyn Error>> {
+        if self.populations.is_empty() {
+            return Err(Failure::NoData("no population data".to_string()).into());
+        }
+        let mut ranked: Vec<(&str, f64)> = self
+            .ranked(group)
+            .filter_map(|(country, values)| {
+                let population = self.populations.get(country, "")?;
+                let last = incidence_of(&values[0], population)
+                    .into_iter()
+                    .rev()
+                    .find(|(date, _)| self.in_range(date))?;
+                Some((country.as_str(), last.1))
+            })
+            .collect();
+        if ranked.is_empty() {
+            return Err(Failure::NoData("no data in this range".to_string()).into());
+        }
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let mut table = Table::new(&["#", "Country", "incidence"])
+            .align(0, Align::Right)
+            .align(2, Align::Right);
+        for (rank, (country, value)) in ranked.into_iter().take(n).enumerate() {
+            table.add_row(vec![
+                (rank + 1).to_string(),
+                country.to_string(),
+                format_decimal(value, 1),
+            ]);
+        }
+        Ok(table.to_string())
+    }
+}
+
+fn usage(message: &str) -> Box<dyn Error> {
+    Failure::Parse(message.to_string()).into()
+}
+
+// Reads commands from stdin until `quit` or the end of input. Failing commands
+// only print their message, the session goes on.
+pub fn run(chain: &Chain) -> Result<(), Box<dyn Error>> {
+    let mut session = Session::load(chain)?;
+    let interactive = std::io::stdin().is_terminal();
+    if interactive {
+        eprintln!(
+            "{} countries loaded, type help for the commands",
+            session.countries.len()
+        );
+    }
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            eprint!("> ");
+            let _ = std::io::stderr().flush();
+        }
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        match line.trim() {
+            "quit" | "exit" => break,
+            line => match session.execute(line) {
+                Ok(output) => print!("{}", output),
+                Err(e) => eprintln!("{}", e),
+            },
+        }
+    }
+    Ok(())
+}
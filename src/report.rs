@@ -0,0 +1,1022 @@
+// Summary documents of a few countries for wikis and issues: the latest
+// totals, the last week against the week before and the countries whose new
+// cases moved the most.
+
+use chrono::NaiveDate;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::chain::Chain;
+use crate::color::trend;
+use crate::data::{self, TimeSeries};
+use crate::exit::Failure;
+use crate::export;
+use crate::json::Value;
+use crate::provenance::Provenance;
+use crate::quality::Completeness;
+use crate::svg::{self, escape};
+use crate::table::{format_change, format_signed};
+use crate::template::Template;
+use crate::waves;
+use tracing::warn;
+
+pub const FORMATS: &[&str] = &["markdown", "html", "latex"];
+
+// days of a trend window
+const WEEK: usize = 7;
+// countries of a report without any given, by confirmed cases
+const DEFAULT_COUNTRIES: usize = 10;
+// rising and falling countries each
+const MOVERS: usize = 5;
+// days shown by charts
+const CHART_DAYS: usize = 180;
+const WORLD: &str = "World";
+
+// New cases and deaths of the last week and the one before.
+#[derive(Debug, Clone, Default)]
+pub struct Trend {
+    pub country: String,
+    pub cases: i64,
+    pub previous_cases: i64,
+    pub deaths: i64,
+    pub previous_deaths: i64,
+}
+
+impl Trend {
+    pub fn change(&self) -> i64 {
+        self.cases - self.previous_cases
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CountryReport {
+    pub country: String,
+    pub confirmed: i64,
+    pub deaths: i64,
+    pub recovered: i64,
+    pub trend: Trend,
+    // date, new cases and new deaths of every day but the first
+    pub daily: Vec<(NaiveDate, i64, i64)>,
+    // of the confirmed cases over the days of the report
+    pub completeness: Completeness,
+}
+
+impl CountryReport {
+    pub fn active(&self) -> i64 {
+        self.confirmed - self.deaths - self.recovered
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub date: NaiveDate,
+    pub provenance: Provenance,
+    pub world: CountryReport,
+    pub countries: Vec<CountryReport>,
+    pub rising: Vec<Trend>,
+    pub falling: Vec<Trend>,
+}
+
+// the cumulative value `days` before the last one
+fn back(cumulative: &[(NaiveDate, i64)], days: usize) -> i64 {
+    cumulative
+        .len()
+        .checked_sub(days + 1)
+        .map(|i| cumulative[i].1)
+        .unwrap_or(0)
+}
+
+fn last(cumulative: &[(NaiveDate, i64)]) -> i64 {
+    back(cumulative, 0)
+}
+
+// The cumulative `state` of a country, of the world without one.
+fn cumulative(series: &[TimeSeries], country: Option<&str>, state: &str) -> Vec<(NaiveDate, i64)> {
+    if let Some(country) = country {
+        return data::country_series(series, country, state);
+    }
+    let mut totals: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for elem in series.iter().filter(|s| s.state == state) {
+        for (date, value) in elem.data.iter() {
+            if let Ok(date) = date.parse::<NaiveDate>() {
+                *totals.entry(date).or_insert(0) += i64::from(*value);
+            }
+        }
+    }
+    totals.into_iter().collect()
+}
+
+fn trend_of(series: &[TimeSeries], country: Option<&str>) -> Trend {
+    let confirmed = cumulative(series, country, "Confirmed");
+    let deaths = cumulative(series, country, "Deaths");
+    let week = |cumulative: &[(NaiveDate, i64)], weeks: usize| {
+        back(cumulative, weeks * WEEK) - back(cumulative, (weeks + 1) * WEEK)
+    };
+    Trend {
+        country: country.unwrap_or(WORLD).to_string(),
+        cases: week(&confirmed, 0),
+        previous_cases: week(&confirmed, 1),
+        deaths: week(&deaths, 0),
+        previous_deaths: week(&deaths, 1),
+    }
+}
+
+// `expected` are the first and last day of any series.
+fn country_report(
+    series: &[TimeSeries],
+    country: Option<&str>,
+    expected: (NaiveDate, NaiveDate),
+) -> Option<CountryReport> {
+    let confirmed = cumulative(series, country, "Confirmed");
+    if confirmed.is_empty() {
+        return None;
+    }
+    let deaths = cumulative(series, country, "Deaths");
+    let recovered = cumulative(series, country, "Recovered");
+    let daily = (0..confirmed.len() - 1)
+        .rev()
+        .map(|days| {
+            let date = confirmed[confirmed.len() - 1 - days].0;
+            let new = |cumulative: &[(NaiveDate, i64)]| {
+                back(cumulative, days) - back(cumulative, days + 1)
+            };
+            (date, new(&confirmed), new(&deaths))
+        })
+        .collect();
+    Some(CountryReport {
+        country: country.unwrap_or(WORLD).to_string(),
+        confirmed: last(&confirmed),
+        deaths: last(&deaths),
+        recovered: last(&recovered),
+        trend: trend_of(series, country),
+        daily,
+        completeness: Completeness::of(&confirmed, expected),
+    })
+}
+
+impl Report {
+    // The report of `countries`, the ones with the most confirmed cases
+    // without any. Unknown countries are left out with a warning.
+    pub fn build(
+        series: &[TimeSeries],
+        countries: &[String],
+        provenance: Provenance,
+    ) -> Result<Report, Box<dyn Error>> {
+        let all: BTreeSet<&str> = series
+            .iter()
+            .filter(|s| s.state == "Confirmed")
+            .map(|s| s.country.as_str())
+            .collect();
+        let date = series
+            .iter()
+            .filter_map(|s| s.data.keys().next_back()?.parse::<NaiveDate>().ok())
+            .max()
+            .ok_or_else(|| Failure::NoData("no time series available".to_string()))?;
+        let first = series
+            .iter()
+            .filter_map(|s| s.data.keys().next()?.parse::<NaiveDate>().ok())
+            .min()
+            .unwrap_or(date);
+        let expected = (first, date);
+
+        let mut reports = Vec::new();
+        if countries.is_empty() {
+            reports = all
+                .iter()
+                .filter_map(|country| country_report(series, Some(country), expected))
+                .collect();
+            reports.sort_by_key(|r: &CountryReport| std::cmp::Reverse(r.confirmed));
+            reports.truncate(DEFAULT_COUNTRIES);
+        }
+        for country in countries.iter() {
+            match country_report(series, Some(country), expected) {
+                Some(report) => reports.push(report),
+                None => warn!("no data for {}", country),
+            }
+        }
+        if reports.is_empty() {
+            return Err(Failure::NoData("none of the countries has data".to_string()).into());
+        }
+
+        let mut trends: Vec<Trend> = all.iter().map(|c| trend_of(series, Some(c))).collect();
+        trends.sort_by(|a, b| b.change().cmp(&a.change()).then(a.country.cmp(&b.country)));
+        let rising = trends
+            .iter()
+            .filter(|t| t.change() > 0)
+            .take(MOVERS)
+            .cloned()
+            .collect();
+        let falling = trends
+            .iter()
+            .rev()
+            .filter(|t| t.change() < 0)
+            .take(MOVERS)
+            .cloned()
+            .collect();
+        Ok(Report {
+            date,
+            provenance,
+            world: country_report(series, None, expected)
+                .ok_or_else(|| Failure::NoData("no confirmed cases".to_string()))?,
+            countries: reports,
+            rising,
+            falling,
+        })
+    }
+}
+
+fn change(current: i64, previous: i64) -> String {
+    trend(
+        current.max(0) as u64,
+        Some(previous.max(0) as u64),
+        false,
+        false,
+    )
+}
+
+// A GitHub flavored table, `right` aligned columns by index.
+fn markdown_table(headers: &[&str], right: &[usize], rows: &[Vec<String>]) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+    let mut out = format!(
+        "| {} |\n",
+        headers
+            .iter()
+            .map(|h| cell(h))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    );
+    let rules: Vec<&str> = (0..headers.len())
+        .map(|i| if right.contains(&i) { "---:" } else { "---" })
+        .collect();
+    out.push_str(&format!("| {} |\n", rules.join(" | ")));
+    for row in rows.iter() {
+        let row: Vec<String> = row.iter().map(|c| cell(c)).collect();
+        out.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+    out
+}
+
+fn movers_table(trends: &[Trend]) -> String {
+    let rows: Vec<Vec<String>> = trends
+        .iter()
+        .map(|t| {
+            vec![
+                t.country.clone(),
+                format_signed(t.cases),
+                format_signed(t.previous_cases),
+                format_change(t.change()),
+                change(t.cases, t.previous_cases),
+            ]
+        })
+        .collect();
+    markdown_table(
+        &[
+            "Country",
+            "New cases",
+            "Week before",
+            "Difference",
+            "Change",
+        ],
+        &[1, 2, 3, 4],
+        &rows,
+    )
+}
+
+pub fn markdown(report: &Report) -> String {
+    let mut out = format!("# COVID-19 report of {}\n\n", report.date);
+    let provenance = &report.provenance;
+    out.push_str(&format!(
+        "Data from {} (<{}>), fetched {}",
+        provenance.source,
+        provenance.url,
+        provenance.fetched()
+    ));
+    if let Some(commit) = &provenance.commit {
+        out.push_str(&format!(" at commit `{}`", commit));
+    }
+    out.push_str(".\n\n## Latest totals\n\n");
+    let rows: Vec<Vec<String>> = report
+        .countries
+        .iter()
+        .chain(Some(&report.world))
+        .map(|c| {
+            vec![
+                c.country.clone(),
+                format_signed(c.confirmed),
+                format_signed(c.deaths),
+                format_signed(c.recovered),
+                format_signed(c.active()),
+            ]
+        })
+        .collect();
+    out.push_str(&markdown_table(
+        &["Country", "Confirmed", "Deaths", "Recovered", "Active"],
+        &[1, 2, 3, 4],
+        &rows,
+    ));
+
+    out.push_str(&format!(
+        "\n## {}-day trends\n\nNew cases and deaths of the last {} days against the {} days before.\n\n",
+        WEEK, WEEK, WEEK
+    ));
+    let rows: Vec<Vec<String>> = report
+        .countries
+        .iter()
+        .chain(Some(&report.world))
+        .map(|c| {
+            let t = &c.trend;
+            vec![
+                c.country.clone(),
+                format_signed(t.cases),
+                change(t.cases, t.previous_cases),
+                format_signed(t.deaths),
+                change(t.deaths, t.previous_deaths),
+            ]
+        })
+        .collect();
+    out.push_str(&markdown_table(
+        &["Country", "New cases", "Change", "New deaths", "Change"],
+        &[1, 2, 3, 4],
+        &rows,
+    ));
+
+    out.push_str(&format!(
+        "\n## Top movers\n\nCountries whose new cases of the last {} days changed the most against the {} days before.\n\n",
+        WEEK, WEEK
+    ));
+    if report.rising.is_empty() && report.falling.is_empty() {
+        out.push_str("No country changed.\n");
+    }
+    if !report.rising.is_empty() {
+        out.push_str("### Rising\n\n");
+        out.push_str(&movers_table(&report.rising));
+    }
+    if !report.falling.is_empty() {
+        if !report.rising.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("### Falling\n\n");
+        out.push_str(&movers_table(&report.falling));
+    }
+
+    for c in report.countries.iter() {
+        out.push_str(&format!("\n## {}\n\n", c.country));
+        let badges: Vec<String> = c
+            .completeness
+            .badges(report.date)
+            .iter()
+            .map(|b| b.markdown())
+            .collect();
+        out.push_str(&format!("{}\n\n", badges.join(" ")));
+        let rows: Vec<Vec<String>> = c.daily[c.daily.len().saturating_sub(WEEK)..]
+            .iter()
+            .map(|(date, cases, deaths)| {
+                vec![
+                    date.to_string(),
+                    format_change(*cases),
+                    format_change(*deaths),
+                ]
+            })
+            .collect();
+        out.push_str(&markdown_table(
+            &["Date", "New cases", "New deaths"],
+            &[1, 2],
+            &rows,
+        ));
+    }
+    out
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:60rem;margin:2rem auto;padding:0 1rem;color:#222}\
+h1{margin-bottom:.2rem}.source{color:#666;margin-top:0}\
+table{border-collapse:collapse;margin:1rem 0}th,td{padding:.3rem .7rem;border-bottom:1px solid #ddd}\
+th{text-align:left}.num{text-align:right;font-variant-numeric:tabular-nums}\
+.up{color:#b30000}.down{color:#006d2c}\
+.cards{display:flex;flex-wrap:wrap;gap:1rem}.card{border:1px solid #ddd;border-radius:6px;padding:.5rem 1rem}\
+.card b{display:block;font-size:1.4rem}svg{max-width:100%;height:auto}\
+.badge{color:#fff;border-radius:3px;padding:.1rem .4rem;margin-right:.3rem;font-size:.85rem}";
+
+fn html_table(headers: &[&str], right: &[usize], rows: &[Vec<String>]) -> String {
+    let class = |i: usize| {
+        if right.contains(&i) {
+            " class=\"num\""
+        } else {
+            ""
+        }
+    };
+    let mut out = String::from("<table>\n<tr>");
+    for (i, header) in headers.iter().enumerate() {
+        out.push_str(&format!("<th{}>{}</th>", class(i), escape(header)));
+    }
+    out.push_str("</tr>\n");
+    for row in rows.iter() {
+        out.push_str("<tr>");
+        for (i, cell) in row.iter().enumerate() {
+            out.push_str(&format!("<td{}>{}</td>", class(i), cell));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+// A change as text, colored by its direction; rising cases are bad news.
+fn html_change(current: i64, previous: i64) -> String {
+    let class = match current.cmp(&previous) {
+        Ordering::Greater => "up",
+        Ordering::Less => "down",
+        Ordering::Equal => "",
+    };
+    format!(
+        "<span class=\"{}\">{}</span>",
+        class,
+        escape(&change(current, previous))
+    )
+}
+
+// Staleness is judged against `date`, the last day of the report.
+fn html_country(c: &CountryReport, date: NaiveDate) -> String {
+    let badges: Vec<String> = c
+        .completeness
+        .badges(date)
+        .iter()
+        .map(|b| b.html())
+        .collect();
+    let mut out = format!(
+        "<section id=\"{}\">\n<h2>{}</h2>\n<p>{}</p>\n<div class=\"cards\">",
+        slug(&c.country),
+        escape(&c.country),
+        badges.join("")
+    );
+    let cards = [
+        ("Confirmed", format_signed(c.confirmed)),
+        ("Deaths", format_signed(c.deaths)),
+        ("Recovered", format_signed(c.recovered)),
+        ("Active", format_signed(c.active())),
+        (
+            "New cases, 7 days",
+            format!(
+                "{} {}",
+                format_signed(c.trend.cases),
+                html_change(c.trend.cases, c.trend.previous_cases)
+            ),
+        ),
+    ];
+    for (label, value) in cards.iter() {
+        out.push_str(&format!(
+            "<div class=\"card\">{}<b>{}</b></div>",
+            label, value
+        ));
+    }
+    out.push_str("</div>\n");
+    let days = &c.daily[c.daily.len().saturating_sub(CHART_DAYS)..];
+    let cases: Vec<(NaiveDate, i64)> = days
+        .iter()
+        .map(|(date, cases, _)| (*date, *cases))
+        .collect();
+    let deaths: Vec<(NaiveDate, i64)> = days
+        .iter()
+        .map(|(date, _, deaths)| (*date, *deaths))
+        .collect();
+    for (label, values) in [("New cases", cases), ("New deaths", deaths)].iter() {
+        out.push_str(&format!(
+            "<h3>{}</h3>\n{}\n",
+            label,
+            svg::daily_chart(&format!("{} in {}", label, c.country), values, WEEK)
+        ));
+    }
+    out.push_str("</section>\n");
+    out
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        STYLE,
+        body
+    )
+}
+
+fn html_source(provenance: &Provenance) -> String {
+    format!(
+        "<p class=\"source\">Data from {} (<a href=\"{}\">{}</a>), fetched {}{}. \
+         Lines are the mean of the last {} days.</p>\n",
+        escape(&provenance.source),
+        escape(&provenance.url),
+        escape(&provenance.url),
+        provenance.fetched(),
+        provenance
+            .commit
+            .as_ref()
+            .map(|c| format!(" at commit <code>{}</code>", escape(c)))
+            .unwrap_or_default(),
+        WEEK
+    )
+}
+
+// The totals and trends of `countries`, each linked to `link(country)`.
+fn html_totals(countries: &[CountryReport], link: &dyn Fn(&str) -> String) -> String {
+    let rows: Vec<Vec<String>> = countries
+        .iter()
+        .map(|c| {
+            let t = &c.trend;
+            vec![
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape(&link(&c.country)),
+                    escape(&c.country)
+                ),
+                format_signed(c.confirmed),
+                format_signed(c.deaths),
+                format_signed(c.recovered),
+                format_signed(c.active()),
+                format_signed(t.cases),
+                html_change(t.cases, t.previous_cases),
+                format_signed(t.deaths),
+                html_change(t.deaths, t.previous_deaths),
+            ]
+        })
+        .collect();
+    let mut out = String::from("<h2>Latest totals</h2>\n");
+    out.push_str(&html_table(
+        &[
+            "Country",
+            "Confirmed",
+            "Deaths",
+            "Recovered",
+            "Active",
+            "New cases, 7 days",
+            "Change",
+            "New deaths, 7 days",
+            "Change",
+        ],
+        &[1, 2, 3, 4, 5, 6, 7, 8],
+        &rows,
+    ));
+    out
+}
+
+fn html_movers(report: &Report) -> String {
+    let mut out = format!(
+        "<h2>Top movers</h2>\n<p>Countries whose new cases of the last {} days changed the most against the {} days before.</p>\n",
+        WEEK, WEEK
+    );
+    for (label, trends) in [("Rising", &report.rising), ("Falling", &report.falling)].iter() {
+        if trends.is_empty() {
+            continue;
+        }
+        let rows: Vec<Vec<String>> = trends
+            .iter()
+            .map(|t| {
+                vec![
+                    escape(&t.country),
+                    format_signed(t.cases),
+                    format_signed(t.previous_cases),
+                    format_change(t.change()),
+                    html_change(t.cases, t.previous_cases),
+                ]
+            })
+            .collect();
+        out.push_str(&format!("<h3>{}</h3>\n", label));
+        out.push_str(&html_table(
+            &[
+                "Country",
+                "New cases",
+                "Week before",
+                "Difference",
+                "Change",
+            ],
+            &[1, 2, 3, 4],
+            &rows,
+        ));
+    }
+    out
+}
+
+// A standalone page: styles inline, charts as SVG, nothing to load.
+pub fn html(report: &Report) -> String {
+    let title = format!("COVID-19 report of {}", report.date);
+    let mut body = format!("<h1>{}</h1>\n", escape(&title));
+    body.push_str(&html_source(&report.provenance));
+    body.push_str(&html_country(&report.world, report.date));
+    body.push_str(&html_totals(&report.countries, &|country| {
+        format!("#{}", slug(country))
+    }));
+    body.push_str(&html_movers(report));
+    for c in report.countries.iter() {
+        body.push_str(&html_country(c, report.date));
+    }
+    html_page(&title, &body)
+}
+
+// The index of a static site: the world, every country linked to its own
+// page and the movers.
+pub fn html_index(report: &Report) -> String {
+    let title = format!("COVID-19 dashboard of {}", report.date);
+    let mut body = format!("<h1>{}</h1>\n", escape(&title));
+    body.push_str(&html_source(&report.provenance));
+    body.push_str(&html_country(&report.world, report.date));
+    body.push_str(&html_totals(&report.countries, &|country| {
+        format!("{}.html", slug(country))
+    }));
+    body.push_str(&html_movers(report));
+    html_page(&title, &body)
+}
+
+// The page of a country on a static site, its data in `data/<slug>.json`.
+pub fn html_country_page(report: &Report, c: &CountryReport) -> String {
+    let title = format!("COVID-19 in {}", c.country);
+    let mut body = format!(
+        "<p><a href=\"index.html\">All countries</a></p>\n<h1>{}</h1>\n",
+        escape(&title)
+    );
+    body.push_str(&html_source(&report.provenance));
+    body.push_str(&html_country(c, report.date));
+    let rows: Vec<Vec<String>> = c
+        .daily
+        .iter()
+        .rev()
+        .take(CHART_DAYS)
+        .map(|(date, cases, deaths)| {
+            vec![
+                date.to_string(),
+                format_change(*cases),
+                format_change(*deaths),
+            ]
+        })
+        .collect();
+    body.push_str("<h2>Days</h2>\n");
+    body.push_str(&html_table(
+        &["Date", "New cases", "New deaths"],
+        &[1, 2],
+        &rows,
+    ));
+    body.push_str(&format!(
+        "<p>All data as <a href=\"data/{}.json\">JSON</a>.</p>\n",
+        slug(&c.country)
+    ));
+    html_page(&title, &body)
+}
+
+const LATEX_PREAMBLE: &str = "\\documentclass[a4paper]{article}\n\
+\\usepackage[utf8]{inputenc}\n\\usepackage[T1]{fontenc}\n\\usepackage[margin=2cm]{geometry}\n\
+\\usepackage{booktabs}\n\\usepackage{url}\n\\usepackage{xcolor}\n\\usepackage{pgfplots}\n\
+\\usepgfplotslibrary{dateplot}\n\\pgfplotsset{compat=1.16}\n";
+
+// Text for LaTeX, including the non-breaking spaces of some locales' numbers.
+fn latex_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '\u{a0}' => out.push('~'),
+            '\u{202f}' => out.push_str("\\,"),
+            '▲' => out.push_str("$\\uparrow$"),
+            '▼' => out.push_str("$\\downarrow$"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// A booktabs table, `right` aligned columns by index. The cells are escaped.
+fn latex_table(headers: &[&str], right: &[usize], rows: &[Vec<String>]) -> String {
+    let columns: String = (0..headers.len())
+        .map(|i| if right.contains(&i) { 'r' } else { 'l' })
+        .collect();
+    let line = |cells: Vec<String>| format!("{} \\\\\n", cells.join(" & "));
+    let mut out = format!("\\begin{{tabular}}{{{}}}\n\\toprule\n", columns);
+    out.push_str(&line(headers.iter().map(|h| latex_escape(h)).collect()));
+    out.push_str("\\midrule\n");
+    for row in rows.iter() {
+        out.push_str(&line(row.iter().map(|c| latex_escape(c)).collect()));
+    }
+    out.push_str("\\bottomrule\n\\end{tabular}\n");
+    out
+}
+
+// A pgfplots bar per day of `values` with a line through their trailing
+// `window` day mean, the counterpart of svg::daily_chart.
+fn latex_chart(values: &[(NaiveDate, i64)], window: usize) -> String {
+    let values: Vec<(NaiveDate, f64)> = values
+        .iter()
+        .map(|(date, value)| (*date, (*value).max(0) as f64))
+        .collect();
+    let coordinates = |values: &[(NaiveDate, f64)]| {
+        values
+            .iter()
+            .map(|(date, value)| format!("({},{:.1})", date, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    format!(
+        "\\begin{{tikzpicture}}\n\\begin{{axis}}[width=\\textwidth, height=5cm, date coordinates in=x, \
+         xticklabel={{\\year-\\month-\\day}}, xticklabel style={{font=\\small}}, \
+         yticklabel style={{font=\\small}}, scaled y ticks=false, ymin=0, enlarge x limits=0.01]\n\
+         \\addplot[ybar, bar width=0.6pt, draw=bars, fill=bars] coordinates {{{}}};\n\
+         \\addplot[sharp plot, no markers, thick, draw=line] coordinates {{{}}};\n\
+         \\end{{axis}}\n\\end{{tikzpicture}}\n",
+        coordinates(&values),
+        coordinates(&waves::smooth(&values, window))
+    )
+}
+
+// A standalone document for pdflatex with the sections of the markdown
+// report and charts of the daily numbers, nothing is compiled here.
+pub fn latex(report: &Report) -> String {
+    let mut out = LATEX_PREAMBLE.to_string();
+    out.push_str(&format!(
+        "\\definecolor{{bars}}{{HTML}}{{{}}}\n\\definecolor{{line}}{{HTML}}{{{}}}\n\n",
+        svg::BAR_COLOR.trim_start_matches('#').to_uppercase(),
+        svg::LINE_COLOR.trim_start_matches('#').to_uppercase()
+    ));
+    out.push_str(&format!(
+        "\\title{{COVID-19 report of {}}}\n\\date{{}}\n\\begin{{document}}\n\\maketitle\n\n",
+        report.date
+    ));
+    let provenance = &report.provenance;
+    out.push_str(&format!(
+        "Data from {} (\\url{{{}}}), fetched {}",
+        latex_escape(&provenance.source),
+        provenance.url,
+        latex_escape(&provenance.fetched())
+    ));
+    if let Some(commit) = &provenance.commit {
+        out.push_str(&format!(" at commit \\texttt{{{}}}", latex_escape(commit)));
+    }
+    out.push_str(".\n\n\\section*{Latest totals}\n\n");
+    let rows: Vec<Vec<String>> = report
+        .countries
+        .iter()
+        .chain(Some(&report.world))
+        .map(|c| {
+            vec![
+                c.country.clone(),
+                format_signed(c.confirmed),
+                format_signed(c.deaths),
+                format_signed(c.recovered),
+                format_signed(c.active()),
+            ]
+        })
+        .collect();
+    out.push_str(&latex_table(
+        &["Country", "Confirmed", "Deaths", "Recovered", "Active"],
+        &[1, 2, 3, 4],
+        &rows,
+    ));
+
+    out.push_str(&format!(
+        "\n\\section*{{{}-day trends}}\n\nNew cases and deaths of the last {} days against the {} days before.\n\n",
+        WEEK, WEEK, WEEK
+    ));
+    let rows: Vec<Vec<String>> = report
+        .countries
+        .iter()
+        .chain(Some(&report.world))
+        .map(|c| {
+            let t = &c.trend;
+            vec![
+                c.country.clone(),
+                format_signed(t.cases),
+                change(t.cases, t.previous_cases),
+                format_signed(t.deaths),
+                change(t.deaths, t.previous_deaths),
+            ]
+        })
+        .collect();
+    out.push_str(&latex_table(
+        &["Country", "New cases", "Change", "New deaths", "Change"],
+        &[1, 2, 3, 4],
+        &rows,
+    ));
+
+    out.push_str(&format!(
+        "\n\\section*{{Top movers}}\n\nCountries whose new cases of the last {} days changed the most against the {} days before.\n\n",
+        WEEK, WEEK
+    ));
+    if report.rising.is_empty() && report.falling.is_empty() {
+        out.push_str("No country changed.\n");
+    }
+    for (label, trends) in [("Rising", &report.rising), ("Falling", &report.falling)].iter() {
+        if trends.is_empty() {
+            continue;
+        }
+        let rows: Vec<Vec<String>> = trends
+            .iter()
+            .map(|t| {
+                vec![
+                    t.country.clone(),
+                    format_signed(t.cases),
+                    format_signed(t.previous_cases),
+                    format_change(t.change()),
+                    change(t.cases, t.previous_cases),
+                ]
+            })
+            .collect();
+        out.push_str(&format!("\n\\subsection*{{{}}}\n\n", label));
+        out.push_str(&latex_table(
+            &[
+                "Country",
+                "New cases",
+                "Week before",
+                "Difference",
+                "Change",
+            ],
+            &[1, 2, 3, 4],
+            &rows,
+        ));
+    }
+
+    for c in report.countries.iter() {
+        out.push_str(&format!("\n\\section*{{{}}}\n\n", latex_escape(&c.country)));
+        let days = &c.daily[c.daily.len().saturating_sub(CHART_DAYS)..];
+        let cases: Vec<(NaiveDate, i64)> = days
+            .iter()
+            .map(|(date, cases, _)| (*date, *cases))
+            .collect();
+        let deaths: Vec<(NaiveDate, i64)> = days
+            .iter()
+            .map(|(date, _, deaths)| (*date, *deaths))
+            .collect();
+        if !days.is_empty() {
+            for (label, values) in [("New cases", cases), ("New deaths", deaths)].iter() {
+                out.push_str(&format!(
+                    "\\subsection*{{{}}}\n\n{}\n",
+                    label,
+                    latex_chart(values, WEEK)
+                ));
+            }
+        }
+        let rows: Vec<Vec<String>> = c.daily[c.daily.len().saturating_sub(WEEK)..]
+            .iter()
+            .map(|(date, cases, deaths)| {
+                vec![
+                    date.to_string(),
+                    format_change(*cases),
+                    format_change(*deaths),
+                ]
+            })
+            .collect();
+        out.push_str(&latex_table(
+            &["Date", "New cases", "New deaths"],
+            &[1, 2],
+            &rows,
+        ));
+    }
+    out.push_str("\n\\end{document}\n");
+    out
+}
+
+// "Korea, South" becomes "korea-south", for anchors and file names
+pub fn slug(country: &str) -> String {
+    country
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn number(value: i64) -> Value {
+    Value::Number(value as f64)
+}
+
+fn trend_context(t: &Trend) -> Value {
+    Value::Object(vec![
+        ("country".to_string(), Value::String(t.country.clone())),
+        ("cases".to_string(), number(t.cases)),
+        ("previous_cases".to_string(), number(t.previous_cases)),
+        ("deaths".to_string(), number(t.deaths)),
+        ("previous_deaths".to_string(), number(t.previous_deaths)),
+        ("change".to_string(), number(t.change())),
+        (
+            "trend".to_string(),
+            Value::String(change(t.cases, t.previous_cases)),
+        ),
+    ])
+}
+
+fn country_context(c: &CountryReport) -> Value {
+    let daily = c
+        .daily
+        .iter()
+        .map(|(date, cases, deaths)| {
+            Value::Object(vec![
+                ("date".to_string(), Value::String(date.to_string())),
+                ("cases".to_string(), number(*cases)),
+                ("deaths".to_string(), number(*deaths)),
+            ])
+        })
+        .collect();
+    Value::Object(vec![
+        ("country".to_string(), Value::String(c.country.clone())),
+        ("confirmed".to_string(), number(c.confirmed)),
+        ("deaths".to_string(), number(c.deaths)),
+        ("recovered".to_string(), number(c.recovered)),
+        ("active".to_string(), number(c.active())),
+        ("trend".to_string(), trend_context(&c.trend)),
+        ("daily".to_string(), Value::Array(daily)),
+    ])
+}
+
+// What templates see: the computed report and, as `dataset`, the time series
+// of its countries in the layout of `export --format json`.
+pub fn context(report: &Report, dataset: Value) -> Value {
+    let provenance = &report.provenance;
+    let source = Value::Object(vec![
+        ("name".to_string(), Value::String(provenance.source.clone())),
+        ("url".to_string(), Value::String(provenance.url.clone())),
+        ("fetched".to_string(), Value::String(provenance.fetched())),
+        (
+            "commit".to_string(),
+            provenance
+                .commit
+                .clone()
+                .map(Value::String)
+                .unwrap_or(Value::Null),
+        ),
+    ]);
+    let trends = |trends: &[Trend]| Value::Array(trends.iter().map(trend_context).collect());
+    Value::Object(vec![
+        ("date".to_string(), Value::String(report.date.to_string())),
+        ("source".to_string(), source),
+        ("window".to_string(), number(WEEK as i64)),
+        ("world".to_string(), country_context(&report.world)),
+        (
+            "countries".to_string(),
+            Value::Array(report.countries.iter().map(country_context).collect()),
+        ),
+        ("rising".to_string(), trends(&report.rising)),
+        ("falling".to_string(), trends(&report.falling)),
+        ("dataset".to_string(), dataset),
+    ])
+}
+
+// Renders the report of `countries` in `format`, or with the `template` file,
+// to `output`, stdout without.
+pub fn report(
+    chain: &Chain,
+    countries: &[String],
+    format: &str,
+    template: Option<&Path>,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    // a template that does not parse fails before the download
+    let template = match template {
+        Some(path) => {
+            let source =
+                fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let template = Template::parse(&source)
+                .map_err(|e| Failure::Parse(format!("{}: {}", path.display(), e)))?;
+            Some((path, template))
+        }
+        None => None,
+    };
+    if template.is_none() && !FORMATS.contains(&format) {
+        return Err(Failure::Parse(format!(
+            "unknown report format '{}', expected {}",
+            format,
+            FORMATS.join(", ")
+        ))
+        .into());
+    }
+    let series = data::get_time_series(chain)?;
+    let report = Report::build(&series, countries, chain.provenance())?;
+    let text = match (template, format) {
+        (Some((path, template)), _) => {
+            let shown: BTreeSet<&str> = report
+                .countries
+                .iter()
+                .map(|c| c.country.as_str())
+                .collect();
+            let selected: Vec<TimeSeries> = series
+                .into_iter()
+                .filter(|s| shown.contains(s.country.as_str()))
+                .collect();
+            let dataset = export::to_json(&selected, &report.provenance);
+            template
+                .render(&context(&report, dataset))
+                .map_err(|e| Failure::Parse(format!("{}: {}", path.display(), e)))?
+        }
+        (None, "html") => html(&report),
+        (None, "latex") => latex(&report),
+        (None, _) => markdown(&report),
+    };
+    match output {
+        Some(path) => fs::write(path, text)?,
+        None => print!("{}", text),
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+// Fuzzy matching of user input against JHU's location names, which often
+// differ from what people type ("US" vs "United States", "Korea, South").
+
+const ALIASES: &[(&str, &str)] = &[
+    ("united states", "US"),
+    ("united states of america", "US"),
+    ("usa", "US"),
+    ("america", "US"),
+    ("south korea", "Korea, South"),
+    ("republic of korea", "Korea, South"),
+    ("uk", "United Kingdom"),
+    ("great britain", "United Kingdom"),
+    ("britain", "United Kingdom"),
+    ("czech republic", "Czechia"),
+    ("mainland china", "China"),
+    ("ivory coast", "Cote d'Ivoire"),
+    ("taiwan", "Taiwan*"),
+];
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// The JHU name for a common alternative name, if there is one.
+pub fn alias(query: &str) -> Option<&'static str> {
+    let query = normalize(query);
+    ALIASES
+        .iter()
+        .find(|(name, _)| *name == query)
+        .map(|(_, jhu)| *jhu)
+}
+
+// Higher is better, None means no match at all.
+pub fn score(query: &str, candidate: &str) -> Option<u32> {
+    let query = normalize(query);
+    let name = normalize(candidate);
+    if query.is_empty() || name.is_empty() {
+        return None;
+    }
+    if alias(&query).map(|a| normalize(a) == name).unwrap_or(false) || name == query {
+        return Some(100);
+    }
+    if name.starts_with(&query) {
+        return Some(80);
+    }
+    if name.split(' ').any(|word| word.starts_with(&query)) {
+        return Some(70);
+    }
+    if name.contains(&query) {
+        return Some(60);
+    }
+    if let Some(gaps) = subsequence_gaps(&query, &name) {
+        return Some(40u32.saturating_sub(gaps as u32).max(10));
+    }
+    let distance = levenshtein(&query, &name);
+    if distance <= query.len() / 4 + 1 {
+        return Some(30 - distance as u32);
+    }
+    None
+}
+
+// Number of skipped characters when `query` is found in order inside `name`.
+fn subsequence_gaps(query: &str, name: &str) -> Option<usize> {
+    let mut chars = name.chars();
+    let mut gaps = 0;
+    for q in query.chars() {
+        loop {
+            match chars.next() {
+                Some(c) if c == q => break,
+                Some(_) => gaps += 1,
+                None => return None,
+            }
+        }
+    }
+    Some(gaps)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+// Candidates sorted by score, best first, limited to `limit` results.
+pub fn rank<'a>(query: &str, candidates: &[&'a str], limit: usize) -> Vec<(&'a str, u32)> {
+    let mut matches: Vec<(&str, u32)> = candidates
+        .iter()
+        .filter_map(|c| score(query, c).map(|s| (*c, s)))
+        .collect();
+    matches.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    matches.truncate(limit);
+    matches
+}
@@ -0,0 +1,47 @@
+// A static dashboard for GitHub Pages or any web server: an index page, a
+// page per country and the data of every country as JSON. Pages link to each
+// other by relative paths, the directory can be served from anywhere.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::chain::Chain;
+use crate::data::{self, TimeSeries};
+use crate::export;
+use crate::report::{self, Report};
+
+// Writes the site to `dir`, replacing the files of an earlier run, and
+// returns the number of pages.
+pub fn site(chain: &Chain, dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let series = data::get_time_series(chain)?;
+    let countries: Vec<String> = series
+        .iter()
+        .filter(|s| s.state == "Confirmed")
+        .map(|s| s.country.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let report = Report::build(&series, &countries, chain.provenance())?;
+
+    fs::create_dir_all(dir.join("data"))?;
+    fs::write(dir.join("index.html"), report::html_index(&report))?;
+    for c in report.countries.iter() {
+        let slug = report::slug(&c.country);
+        fs::write(
+            dir.join(format!("{}.html", slug)),
+            report::html_country_page(&report, c),
+        )?;
+        let selected: Vec<TimeSeries> = series
+            .iter()
+            .filter(|s| s.country == c.country)
+            .cloned()
+            .collect();
+        fs::write(
+            dir.join("data").join(format!("{}.json", slug)),
+            format!("{}\n", export::to_json(&selected, &report.provenance)),
+        )?;
+    }
+    Ok(report.countries.len() + 1)
+}
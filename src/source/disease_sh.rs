@@ -0,0 +1,95 @@
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::error::Error;
+use tracing::{debug, trace};
+
+use super::{DataSource, SeriesFuture};
+use crate::chain::Chain;
+use crate::data::TimeSeries;
+use crate::exit::Failure;
+use crate::json::{self, Value};
+
+pub const BASE: &str = "https://disease.sh/v3/covid-19/";
+const HISTORICAL: &str = "historical?lastdays=all";
+
+// The timelines in the answer, with the state they are exposed as.
+const TIMELINES: &[(&str, &str)] = &[
+    ("cases", "Confirmed"),
+    ("deaths", "Deaths"),
+    ("recovered", "Recovered"),
+];
+
+// The disease.sh API, which serves the JHU time series already summed up per
+// country as one JSON document: a single small request instead of a CSV per
+// state. Countries are named by disease.sh ("USA", "S. Korea").
+pub struct DiseaseSh;
+
+impl DataSource for DiseaseSh {
+    fn name(&self) -> &'static str {
+        "disease.sh"
+    }
+
+    fn url(&self) -> String {
+        BASE.to_string()
+    }
+
+    fn time_series<'a>(&'a self, chain: &'a Chain) -> SeriesFuture<'a> {
+        Box::pin(time_series(chain))
+    }
+}
+
+async fn time_series(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let body = chain.fetch(&format!("{}{}", BASE, HISTORICAL)).await?;
+    parse(&body)
+}
+
+// An array of {"country", "province", "timeline": {"cases": {"1/22/20": 0,
+// ...}, "deaths": {...}, "recovered": {...}}}.
+fn parse(body: &str) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let answer = json::parse(body).map_err(Failure::Parse)?;
+    let locations = answer
+        .as_array()
+        .ok_or_else(|| Failure::Parse("disease.sh answer is not a list".to_string()))?;
+
+    let mut series = Vec::new();
+    for location in locations.iter() {
+        let country = match location.get("country").and_then(Value::as_str) {
+            Some(country) => country,
+            None => continue,
+        };
+        let province = location
+            .get("province")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        for (timeline, state) in TIMELINES.iter() {
+            let days = match location
+                .get("timeline")
+                .and_then(|t| t.get(timeline))
+                .and_then(Value::as_object)
+            {
+                Some(days) => days,
+                None => continue,
+            };
+            let mut data = BTreeMap::new();
+            for (day, value) in days.iter() {
+                match (NaiveDate::parse_from_str(day, "%m/%d/%y"), value.as_f64()) {
+                    (Ok(day), Some(value)) if value >= 0.0 && value <= f64::from(i32::MAX) => {
+                        data.insert(day.to_string(), value as i32);
+                    }
+                    _ => trace!(country, day = %day, "dropping unparseable value"),
+                }
+            }
+            series.push(TimeSeries {
+                province: province.to_string(),
+                country: country.to_string(),
+                lat: None,
+                long: None,
+                data,
+                state: state.to_string(),
+                scale: 1,
+            });
+        }
+    }
+    debug!(locations = locations.len(), "parsed disease.sh data");
+    Ok(series)
+}
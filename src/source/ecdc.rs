@@ -0,0 +1,105 @@
+use chrono::NaiveDate;
+use csv::ReaderBuilder;
+use std::collections::BTreeMap;
+use std::error::Error;
+use tracing::{debug, trace};
+
+use super::{DataSource, SeriesFuture};
+use crate::chain::Chain;
+use crate::data::TimeSeries;
+
+pub const BASE: &str = "https://opendata.ecdc.europa.eu/covid19/";
+const FILE: &str = "casedistribution/csv";
+
+// The European CDC's case distribution with one row per country and report
+// day. It only has new cases and deaths of each day, which are summed up into
+// the cumulative series the other sources provide. Countries are named with
+// underscores upstream ("United_States_of_America").
+pub struct EcdcSource;
+
+impl DataSource for EcdcSource {
+    fn name(&self) -> &'static str {
+        "ecdc"
+    }
+
+    fn url(&self) -> String {
+        BASE.to_string()
+    }
+
+    fn time_series<'a>(&'a self, chain: &'a Chain) -> SeriesFuture<'a> {
+        Box::pin(time_series(chain))
+    }
+}
+
+async fn time_series(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let body = chain.fetch(&format!("{}{}", BASE, FILE)).await?;
+    parse(&body)
+}
+
+fn parse(body: &str) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().from_reader(body.as_bytes());
+    let headers = rdr.headers()?.clone();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h));
+    let country =
+        column(&["countriesAndTerritories"]).ok_or("ECDC data without a country column")?;
+    let date = column(&["dateRep"]).ok_or("ECDC data without a dateRep column")?;
+    // the weekly edition of the file names its columns differently
+    let cases = column(&["cases", "cases_weekly"]).ok_or("ECDC data without a cases column")?;
+    let deaths = column(&["deaths", "deaths_weekly"]).ok_or("ECDC data without a deaths column")?;
+
+    let mut countries: BTreeMap<String, BTreeMap<NaiveDate, (i64, i64)>> = BTreeMap::new();
+    for row in rdr.records() {
+        let row = row?;
+        let (name, day) = match (row.get(country), row.get(date)) {
+            (Some(name), Some(day)) => (name, day),
+            _ => continue,
+        };
+        let day = match NaiveDate::parse_from_str(day, "%d/%m/%Y") {
+            Ok(day) => day,
+            Err(_) => {
+                trace!(country = name, day, "skipping row with an invalid date");
+                continue;
+            }
+        };
+        // corrections show up as negative days, they are kept so the sum is
+        // the reported total
+        let value = |index: usize| row.get(index).and_then(|v| v.parse::<i64>().ok());
+        let entry = countries
+            .entry(name.replace('_', " "))
+            .or_default()
+            .entry(day)
+            .or_insert((0, 0));
+        entry.0 += value(cases).unwrap_or(0);
+        entry.1 += value(deaths).unwrap_or(0);
+    }
+    debug!(countries = countries.len(), "parsed ECDC data");
+
+    let mut series = Vec::new();
+    for (country, days) in countries.into_iter() {
+        let (mut confirmed, mut dead) = (BTreeMap::new(), BTreeMap::new());
+        let (mut cases, mut deaths) = (0i64, 0i64);
+        for (day, (new_cases, new_deaths)) in days.into_iter() {
+            cases += new_cases;
+            deaths += new_deaths;
+            let key = day.format("%Y-%m-%d").to_string();
+            confirmed.insert(key.clone(), clamp(cases));
+            dead.insert(key, clamp(deaths));
+        }
+        for (data, state) in [(confirmed, "Confirmed"), (dead, "Deaths")] {
+            series.push(TimeSeries {
+                province: String::new(),
+                country: country.clone(),
+                lat: None,
+                long: None,
+                data,
+                state: state.to_string(),
+                scale: 1,
+            });
+        }
+    }
+    Ok(series)
+}
+
+fn clamp(value: i64) -> i32 {
+    value.clamp(0, i64::from(i32::MAX)) as i32
+}
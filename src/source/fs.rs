@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::jhu::{self, GLOBAL};
+use super::{DataSource, SeriesFuture};
+use crate::chain::{Chain, Layer};
+use crate::data::{TimeSeries, GITHUB_API, UPSTREAM};
+use crate::git;
+
+const DATA: &str = "csse_covid_19_data/";
+
+// The csse_covid_19_data directory of a JHU checkout on disk. The time series
+// are read without the chain, and as a layer it answers the daily reports and
+// directory listings too, so a run needs no network at all.
+#[derive(Debug, Clone)]
+pub struct FsSource {
+    dir: PathBuf,
+}
+
+impl FsSource {
+    // `path` may also be the root of the checkout.
+    pub fn new<P: AsRef<Path>>(path: P) -> FsSource {
+        let path = path.as_ref();
+        let nested = path.join(DATA.trim_end_matches('/'));
+        FsSource {
+            dir: if nested.is_dir() {
+                nested
+            } else {
+                path.to_path_buf()
+            },
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, file: &str) -> Option<PathBuf> {
+        Some(self.dir.join(file.strip_prefix(DATA)?))
+    }
+}
+
+async fn time_series(source: &FsSource) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let mut series = Vec::new();
+    for (state, file) in GLOBAL.iter() {
+        let path = source
+            .path(file)
+            .ok_or("time series outside of the data directory")?;
+        let body = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        series.append(&mut jhu::parse(&body, state)?);
+    }
+    Ok(series)
+}
+
+impl DataSource for FsSource {
+    fn name(&self) -> &'static str {
+        "fs"
+    }
+
+    fn url(&self) -> String {
+        format!("file://{}", self.dir.display())
+    }
+
+    fn time_series<'a>(&'a self, _chain: &'a Chain) -> SeriesFuture<'a> {
+        Box::pin(time_series(self))
+    }
+}
+
+impl Layer for FsSource {
+    fn name(&self) -> &'static str {
+        "fs"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        if let Some(file) = key.strip_prefix(UPSTREAM) {
+            return fs::read_to_string(self.path(file)?).ok();
+        }
+        git::listing(&self.path(key.strip_prefix(GITHUB_API)?)?)
+    }
+
+    fn put(&self, _key: &str, _body: &str) {}
+
+    fn covers(&self, key: &str) -> bool {
+        key.starts_with(&format!("{}{}", UPSTREAM, DATA))
+            || key.starts_with(&format!("{}{}", GITHUB_API, DATA))
+    }
+}
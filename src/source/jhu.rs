@@ -0,0 +1,141 @@
+use csv::{ReaderBuilder, StringRecord};
+use std::collections::BTreeMap;
+use std::error::Error;
+use tracing::trace;
+
+use super::{DataSource, SeriesFuture};
+use crate::chain::Chain;
+use crate::data::{ymd, TimeSeries, UPSTREAM};
+
+// File of each state below the repository root. JHU renamed the files on
+// 2020-03-23 and moved the old ones, which stop at that day, to the archive.
+pub type Files = &'static [(&'static str, &'static str)];
+
+pub const GLOBAL: Files = &[
+    (
+        "Confirmed",
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_confirmed_global.csv",
+    ),
+    (
+        "Deaths",
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_deaths_global.csv",
+    ),
+    (
+        "Recovered",
+        "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_recovered_global.csv",
+    ),
+];
+
+pub const LEGACY: Files = &[
+    (
+        "Confirmed",
+        "archived_data/archived_time_series/time_series_19-covid-Confirmed_archived_0325.csv",
+    ),
+    (
+        "Deaths",
+        "archived_data/archived_time_series/time_series_19-covid-Deaths_archived_0325.csv",
+    ),
+    (
+        "Recovered",
+        "archived_data/archived_time_series/time_series_19-covid-Recovered_archived_0325.csv",
+    ),
+];
+
+// The Johns Hopkins CSSE repository, one CSV per state with a column per day.
+pub struct JhuSource {
+    files: Files,
+}
+
+impl Default for JhuSource {
+    fn default() -> JhuSource {
+        JhuSource { files: GLOBAL }
+    }
+}
+
+impl JhuSource {
+    // The retired time_series_19-covid-* files, for runs that have to
+    // reproduce results from before the rename.
+    pub fn legacy() -> JhuSource {
+        JhuSource { files: LEGACY }
+    }
+}
+
+impl DataSource for JhuSource {
+    fn name(&self) -> &'static str {
+        "jhu"
+    }
+
+    fn url(&self) -> String {
+        UPSTREAM.to_string()
+    }
+
+    fn time_series<'a>(&'a self, chain: &'a Chain) -> SeriesFuture<'a> {
+        Box::pin(time_series(chain, self.files))
+    }
+}
+
+pub async fn time_series(chain: &Chain, files: Files) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let mut series = Vec::new();
+
+    for (state, file) in files.iter() {
+        let url = format!("{}{}", UPSTREAM, file);
+
+        let body = chain.fetch(&url).await?;
+        series.append(&mut parse(&body, state)?);
+    }
+
+    Ok(series)
+}
+
+// One time series file, `state` is what its values count.
+pub fn parse(body: &str, state: &str) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let mut series = Vec::new();
+    let mut rdr = ReaderBuilder::new()
+        .delimiter(b',')
+        .from_reader(body.as_bytes());
+
+    for rlt in rdr.records() {
+        let result: StringRecord = rlt?;
+        let mut record = TimeSeries {
+            province: match result.get(0) {
+                Some(t) => t.to_string(),
+                None => "".to_string(),
+            },
+            country: match result.get(1) {
+                Some(t) => t.to_string(),
+                None => "".to_string(),
+            },
+            lat: match result.get(2) {
+                Some(t) => t.parse::<f32>().ok(),
+                None => None::<f32>,
+            },
+            long: match result.get(3) {
+                Some(t) => t.parse::<f32>().ok(),
+                None => None::<f32>,
+            },
+            data: BTreeMap::new(),
+            state: state.to_string(),
+            scale: 1,
+        };
+        let mut index = 4;
+        let mut date = ymd(2020, 1, 22);
+        loop {
+            record.data.insert(
+                date.to_string(),
+                match result.get(index) {
+                    Some(t) => t.parse::<i32>().unwrap_or(-1),
+                    None => break,
+                },
+            );
+            if *record.data.get(&date.to_string()).unwrap() < 0 {
+                trace!(country = %record.country, province = %record.province, %date, "dropping unparseable value");
+                record.data.remove(&date.to_string());
+            }
+            index += 1;
+            date = date.succ_opt().unwrap();
+        }
+        series.push(record);
+    }
+
+    Ok(series)
+}
@@ -0,0 +1,108 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::sync::Mutex;
+
+use super::{DataSource, SeriesFuture};
+use crate::chain::Chain;
+use crate::data::TimeSeries;
+use crate::precedence::Precedence;
+
+// Several sources at once. Where more than one has a series for the same
+// location and state, the precedence of the metric (the lower case state,
+// "confirmed", "vaccinations", ...) decides which one is kept; the others
+// only fill in locations and states the preferred ones lack.
+pub struct MergedSource {
+    sources: Vec<Box<dyn DataSource>>,
+    precedence: Precedence,
+    // the sources that won for each metric in the last fetch
+    winners: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl MergedSource {
+    pub fn new(sources: Vec<Box<dyn DataSource>>, precedence: Precedence) -> MergedSource {
+        MergedSource {
+            sources,
+            precedence,
+            winners: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+async fn time_series(
+    merged: &MergedSource,
+    chain: &Chain,
+) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let mut fetched = Vec::new();
+    for source in merged.sources.iter() {
+        fetched.push((source.name(), source.time_series(chain).await?));
+    }
+    let (series, winners) = merge(fetched, &merged.precedence);
+    if let Ok(mut current) = merged.winners.lock() {
+        *current = winners;
+    }
+    Ok(series)
+}
+
+type Key = (String, String, String);
+
+// One series per location and state, taken from the source `precedence`
+// ranks first for the metric, along with the sources that won per metric.
+pub fn merge(
+    fetched: Vec<(&str, Vec<TimeSeries>)>,
+    precedence: &Precedence,
+) -> (Vec<TimeSeries>, BTreeMap<String, BTreeSet<String>>) {
+    let mut candidates: BTreeMap<Key, Vec<(&str, TimeSeries)>> = BTreeMap::new();
+    for (name, series) in fetched {
+        for s in series {
+            let key = (s.country.clone(), s.province.clone(), s.state.clone());
+            candidates.entry(key).or_default().push((name, s));
+        }
+    }
+    let mut winners: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut series = Vec::new();
+    for ((_, _, state), mut candidates) in candidates {
+        let ranked: Vec<(&str, usize)> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (*name, i))
+            .collect();
+        if let Some(resolved) = precedence.resolve(&state.to_lowercase(), &ranked) {
+            winners
+                .entry(resolved.metric)
+                .or_default()
+                .insert(resolved.source);
+            series.push(candidates.swap_remove(resolved.value).1);
+        }
+    }
+    (series, winners)
+}
+
+impl DataSource for MergedSource {
+    fn name(&self) -> &'static str {
+        "merged"
+    }
+
+    // the URL of the first source
+    fn url(&self) -> String {
+        self.sources.first().map(|s| s.url()).unwrap_or_default()
+    }
+
+    fn time_series<'a>(&'a self, chain: &'a Chain) -> SeriesFuture<'a> {
+        Box::pin(time_series(self, chain))
+    }
+
+    fn resolved(&self) -> Vec<(String, String)> {
+        self.winners
+            .lock()
+            .map(|winners| {
+                winners
+                    .iter()
+                    .map(|(metric, sources)| {
+                        let sources: Vec<&str> = sources.iter().map(|s| s.as_str()).collect();
+                        (metric.clone(), sources.join(","))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::chain::Chain;
+use crate::data::TimeSeries;
+
+pub mod disease_sh;
+pub mod ecdc;
+pub mod fs;
+pub mod jhu;
+pub mod merged;
+pub mod owid;
+pub mod vaccinations;
+
+pub use disease_sh::DiseaseSh;
+pub use ecdc::EcdcSource;
+pub use fs::FsSource;
+pub use jhu::JhuSource;
+pub use merged::MergedSource;
+pub use owid::OwidSource;
+pub use vaccinations::VaccinationSource;
+
+pub type SeriesFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<TimeSeries>, Box<dyn Error>>> + 'a>>;
+
+// A provider of cumulative time series per location, one `TimeSeries` per
+// location and state ("Confirmed", "Deaths", ...). Downloads go through the
+// chain so sources share its layers, mirrors and retries.
+pub trait DataSource: Send + Sync {
+    fn name(&self) -> &'static str;
+    // Where the files come from, before any rewrite or mirror.
+    fn url(&self) -> String;
+    fn time_series<'a>(&'a self, chain: &'a Chain) -> SeriesFuture<'a>;
+    // The sources the last fetch took each metric from, as (metric,
+    // "source,...") pairs, for sources made of others.
+    fn resolved(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+}
+
+pub const NAMES: &[&str] = &["jhu", "owid", "ecdc", "disease.sh"];
+
+// `--source` values, `mock` is the JHU source served from the fixtures.
+pub fn by_name(name: &str) -> Result<Box<dyn DataSource>, String> {
+    match name {
+        "jhu" | "mock" => Ok(Box::new(JhuSource::default())),
+        "owid" => Ok(Box::new(OwidSource)),
+        "ecdc" => Ok(Box::new(EcdcSource)),
+        "disease.sh" => Ok(Box::new(DiseaseSh)),
+        _ => Err(format!(
+            "unknown source '{}', expected {} or mock",
+            name,
+            NAMES.join(", ")
+        )),
+    }
+}
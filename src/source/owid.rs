@@ -0,0 +1,115 @@
+use csv::ReaderBuilder;
+use std::collections::BTreeMap;
+use std::error::Error;
+use tracing::{debug, trace};
+
+use super::{DataSource, SeriesFuture};
+use crate::chain::Chain;
+use crate::data::TimeSeries;
+
+pub const BASE: &str = "https://covid.ourworldindata.org/data/";
+const FILE: &str = "owid-covid-data.csv";
+
+// Columns of the OWID file that become time series, with the state they are
+// exposed as. Testing, hospital and vaccination figures are not part of the
+// JHU data.
+const COLUMNS: &[(&str, &str)] = &[
+    ("total_cases", "Confirmed"),
+    ("total_deaths", "Deaths"),
+    ("total_tests", "Tests"),
+    ("hosp_patients", "Hospitalized"),
+    ("icu_patients", "ICU"),
+    ("people_vaccinated", "Vaccinated"),
+    ("people_fully_vaccinated", "Fully vaccinated"),
+];
+
+// Our World in Data's single CSV with one row per country and day. Countries
+// have no provinces and are named by OWID ("United States", not "US").
+pub struct OwidSource;
+
+impl DataSource for OwidSource {
+    fn name(&self) -> &'static str {
+        "owid"
+    }
+
+    fn url(&self) -> String {
+        BASE.to_string()
+    }
+
+    fn time_series<'a>(&'a self, chain: &'a Chain) -> SeriesFuture<'a> {
+        Box::pin(time_series(chain))
+    }
+}
+
+async fn time_series(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let body = chain.fetch(&format!("{}{}", BASE, FILE)).await?;
+    parse(&body, COLUMNS)
+}
+
+// The OWID files share the location, iso_code and date columns, `columns`
+// names the ones to read and the state they become.
+pub(crate) fn parse(
+    body: &str,
+    columns: &[(&str, &str)],
+) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().from_reader(body.as_bytes());
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let iso_code = column("iso_code");
+    let location = column("location").ok_or("OWID data without a location column")?;
+    let date = column("date").ok_or("OWID data without a date column")?;
+    let wanted: Vec<(usize, &str)> = columns
+        .iter()
+        .filter_map(|(name, state)| Some((column(name)?, *state)))
+        .collect();
+
+    let mut locations: BTreeMap<String, Vec<BTreeMap<String, i32>>> = BTreeMap::new();
+    for row in rdr.records() {
+        let row = row?;
+        // OWID_WRL, OWID_EUR, ... are aggregates of the countries
+        if iso_code
+            .and_then(|c| row.get(c))
+            .map(|c| c.starts_with("OWID_"))
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let (name, day) = match (row.get(location), row.get(date)) {
+            (Some(name), Some(day)) => (name, day),
+            _ => continue,
+        };
+        let data = locations
+            .entry(name.to_string())
+            .or_insert_with(|| vec![BTreeMap::new(); wanted.len()]);
+        for (series, (index, _)) in data.iter_mut().zip(wanted.iter()) {
+            // empty cells are days without a figure
+            if let Some(value) = row.get(*index).and_then(|v| v.parse::<f64>().ok()) {
+                if value >= 0.0 && value <= f64::from(i32::MAX) {
+                    series.insert(day.to_string(), value as i32);
+                } else {
+                    trace!(location = name, day, value, "dropping out of range value");
+                }
+            }
+        }
+    }
+    debug!(locations = locations.len(), "parsed OWID data");
+
+    let mut series = Vec::new();
+    for (country, data) in locations.into_iter() {
+        for (data, (_, state)) in data.into_iter().zip(wanted.iter()) {
+            if data.is_empty() {
+                continue;
+            }
+            series.push(TimeSeries {
+                province: String::new(),
+                country: country.clone(),
+                lat: None,
+                long: None,
+                data,
+                state: state.to_string(),
+                scale: 1,
+            });
+        }
+    }
+    Ok(series)
+}
@@ -0,0 +1,34 @@
+use std::error::Error;
+
+use super::{owid, DataSource, SeriesFuture};
+use crate::chain::Chain;
+use crate::data::TimeSeries;
+
+const FILE: &str = "vaccinations/vaccinations.csv";
+
+// OWID's vaccination figures as reported by the countries, kept apart from
+// the main OWID file as it is updated more often and much smaller.
+const COLUMNS: &[(&str, &str)] = &[("total_vaccinations", "Vaccinations")];
+
+// Administered doses per country. No other source has them, so the
+// "Vaccinations" series are fetched from here whichever source is selected.
+pub struct VaccinationSource;
+
+impl DataSource for VaccinationSource {
+    fn name(&self) -> &'static str {
+        "vaccinations"
+    }
+
+    fn url(&self) -> String {
+        owid::BASE.to_string()
+    }
+
+    fn time_series<'a>(&'a self, chain: &'a Chain) -> SeriesFuture<'a> {
+        Box::pin(time_series(chain))
+    }
+}
+
+pub async fn time_series(chain: &Chain) -> Result<Vec<TimeSeries>, Box<dyn Error>> {
+    let body = chain.fetch(&format!("{}{}", owid::BASE, FILE)).await?;
+    owid::parse(&body, COLUMNS)
+}
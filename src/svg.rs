@@ -0,0 +1,139 @@
+// Charts as inline SVG for HTML pages, drawn without any script so they show
+// in every browser and in mail clients.
+
+use chrono::NaiveDate;
+
+use crate::table::format_count;
+use crate::waves;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 220.0;
+// room for the axis labels
+const LEFT: f64 = 64.0;
+const BOTTOM: f64 = 24.0;
+const TOP: f64 = 8.0;
+
+pub const BAR_COLOR: &str = "#9ecae1";
+pub const LINE_COLOR: &str = "#08519c";
+
+// Text and attribute values for XML and HTML.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// A bar per day of `values` with a line through their trailing `window` day
+// mean. Hovering a bar shows its date and value. Negative values, corrections
+// of earlier days, are drawn as 0.
+pub fn daily_chart(title: &str, values: &[(NaiveDate, i64)], window: usize) -> String {
+    let values: Vec<(NaiveDate, f64)> = values
+        .iter()
+        .map(|(date, value)| (*date, (*value).max(0) as f64))
+        .collect();
+    let mean = waves::smooth(&values, window);
+    let max = values.iter().map(|v| v.1).fold(0.0, f64::max).max(1.0);
+    let (plot_width, plot_height) = (WIDTH - LEFT, HEIGHT - BOTTOM - TOP);
+    let step = plot_width / values.len().max(1) as f64;
+    let y = |value: f64| TOP + plot_height - value / max * plot_height;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {w} {h}\" width=\"{w}\" height=\"{h}\" role=\"img\" aria-label=\"{t}\">\n<title>{t}</title>\n",
+        w = WIDTH,
+        h = HEIGHT,
+        t = escape(title)
+    );
+    // axis with the highest value, half of it and 0
+    for value in [max, max / 2.0, 0.0].iter() {
+        svg.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{y:.1}\" x2=\"{}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>\
+             <text x=\"{}\" y=\"{:.1}\" font-size=\"11\" text-anchor=\"end\" fill=\"#555\">{}</text>\n",
+            LEFT,
+            WIDTH,
+            LEFT - 6.0,
+            y(*value) + 4.0,
+            format_count(value.round() as u64),
+            y = y(*value)
+        ));
+    }
+    for (i, (date, value)) in values.iter().enumerate() {
+        svg.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"{:.1}\" width=\"{:.2}\" height=\"{:.1}\" fill=\"{}\"><title>{}: {}</title></rect>\n",
+            LEFT + i as f64 * step,
+            y(*value),
+            (step * 0.8).max(0.5),
+            TOP + plot_height - y(*value),
+            BAR_COLOR,
+            date,
+            format_count(*value as u64)
+        ));
+    }
+    let points: Vec<String> = mean
+        .iter()
+        .enumerate()
+        .map(|(i, (_, value))| format!("{:.1},{:.1}", LEFT + (i as f64 + 0.4) * step, y(*value)))
+        .collect();
+    if !points.is_empty() {
+        svg.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+            points.join(" "),
+            LINE_COLOR
+        ));
+    }
+    if let (Some(first), Some(last)) = (values.first(), values.last()) {
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"11\" fill=\"#555\">{}</text>\
+             <text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\" fill=\"#555\">{}</text>\n",
+            LEFT,
+            HEIGHT - 6.0,
+            first.0,
+            WIDTH,
+            HEIGHT - 6.0,
+            last.0
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+// Width of `text` in 11px Verdana, the font of shields.io badges, from an
+// average character width plus padding.
+fn text_width(text: &str) -> f64 {
+    (text.chars().count() as f64 * 6.8 + 12.0).round()
+}
+
+// A badge in the flat style of shields.io: `label` on grey, `message` on
+// `color`.
+pub fn badge(label: &str, message: &str, color: &str) -> String {
+    let (left, right) = (text_width(label), text_width(message));
+    let width = left + right;
+    let (label, message) = (escape(label), escape(message));
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"20\" role=\"img\" aria-label=\"{l}: {m}\">\
+         <title>{l}: {m}</title>\
+         <linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\
+         <clipPath id=\"r\"><rect width=\"{w}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\
+         <g clip-path=\"url(#r)\"><rect width=\"{lw}\" height=\"20\" fill=\"#555\"/><rect x=\"{lw}\" width=\"{rw}\" height=\"20\" fill=\"{c}\"/><rect width=\"{w}\" height=\"20\" fill=\"url(#s)\"/></g>\
+         <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\
+         <text x=\"{lx}\" y=\"15\" fill=\"#010101\" fill-opacity=\".3\">{l}</text><text x=\"{lx}\" y=\"14\">{l}</text>\
+         <text x=\"{rx}\" y=\"15\" fill=\"#010101\" fill-opacity=\".3\">{m}</text><text x=\"{rx}\" y=\"14\">{m}</text>\
+         </g></svg>\n",
+        w = width,
+        lw = left,
+        rw = right,
+        lx = left / 2.0,
+        rx = left + right / 2.0,
+        c = escape(color),
+        l = label,
+        m = message
+    )
+}
@@ -0,0 +1,231 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::cache;
+use crate::chain::Layer;
+use crate::data::{URL_DAILY_REPORT, URL_REPORT_LISTING};
+use crate::inflate;
+
+// JHU still corrects a report in the days after it was published, a copy
+// fetched within this many days of its date is fetched again.
+const REVALIDATE_DAYS: i64 = 3;
+
+const MANIFEST: &str = "manifest";
+// earlier versions of reports that were corrected, with --keep-revisions
+const REVISIONS: &str = "revisions";
+const REVISION_TIME: &str = "%Y%m%dT%H%M%S";
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    fetched: NaiveDateTime,
+    // missing in the lines of older versions
+    crc: Option<u32>,
+}
+
+// The daily reports downloaded so far, kept on disk so a run only downloads
+// the days it has not seen yet. The manifest has a line "<date> <fetched at>
+// <CRC-32>" per ingested report, later lines win. A report that does not
+// match its checksum is downloaded again.
+pub struct ReportStore {
+    dir: PathBuf,
+    manifest: Mutex<BTreeMap<NaiveDate, Entry>>,
+    offline: bool,
+    revisions: bool,
+}
+
+impl ReportStore {
+    // A store without a readable manifest starts out empty.
+    pub fn open(dir: PathBuf) -> ReportStore {
+        let mut manifest = BTreeMap::new();
+        for line in fs::read_to_string(dir.join(MANIFEST))
+            .unwrap_or_default()
+            .lines()
+        {
+            let mut fields = line.split_whitespace();
+            if let (Some(Ok(date)), Some(Ok(fetched))) = (
+                fields.next().map(|d| d.parse::<NaiveDate>()),
+                fields.next().map(|f| f.parse::<NaiveDateTime>()),
+            ) {
+                let crc = fields.next().and_then(|c| u32::from_str_radix(c, 16).ok());
+                manifest.insert(date, Entry { fetched, crc });
+            }
+        }
+        debug!(dir = %dir.display(), reports = manifest.len(), "opened report store");
+        ReportStore {
+            dir,
+            manifest: Mutex::new(manifest),
+            offline: false,
+            revisions: false,
+        }
+    }
+
+    // Without network the stored reports are all there are: recent ones are
+    // used as they are and the listing of the daily reports is answered with
+    // the stored ones, so only those are asked for.
+    pub fn offline(mut self, offline: bool) -> ReportStore {
+        self.offline = offline;
+        self
+    }
+
+    // JHU corrects reports long after they were published. Keeping revisions,
+    // every report is fetched again once its copy is older than the cache's
+    // max age, and a version that changed is kept below `revisions` with the
+    // time it was fetched.
+    pub fn keep_revisions(mut self, keep: bool) -> ReportStore {
+        self.revisions = keep;
+        self
+    }
+
+    // Every stored version of the report of `date` with the time it was
+    // fetched, oldest first.
+    pub fn revisions(&self, date: &NaiveDate) -> Vec<(NaiveDateTime, String)> {
+        let prefix = format!("{}.", date.format("%m-%d-%Y"));
+        let mut versions: Vec<(NaiveDateTime, String)> = fs::read_dir(self.dir.join(REVISIONS))
+            .map(|entries| entries.flatten().collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let time = name.strip_prefix(&prefix)?.strip_suffix(".csv.gz")?;
+                let fetched = NaiveDateTime::parse_from_str(time, REVISION_TIME).ok()?;
+                Some((fetched, cache::read(&entry.path())?))
+            })
+            .collect();
+        versions.sort_by_key(|(fetched, _)| *fetched);
+        let current = self.manifest.lock().unwrap().get(date).copied();
+        if let (Some(entry), Some(body)) = (current, cache::read(&self.path(date))) {
+            versions.push((entry.fetched, body));
+        }
+        versions
+    }
+
+    fn listing(&self) -> String {
+        let entries: Vec<String> = self
+            .manifest
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|date| self.path(date).exists())
+            .map(|date| {
+                format!(
+                    "{{\"name\": \"{}.csv\", \"type\": \"file\"}}",
+                    date.format("%m-%d-%Y")
+                )
+            })
+            .collect();
+        format!("[{}]", entries.join(", "))
+    }
+
+    fn date_of(key: &str) -> Option<NaiveDate> {
+        let name = key.strip_prefix(URL_DAILY_REPORT)?.strip_suffix(".csv")?;
+        NaiveDate::parse_from_str(name, "%m-%d-%Y").ok()
+    }
+
+    fn path(&self, date: &NaiveDate) -> PathBuf {
+        self.dir.join(format!("{}.csv.gz", date.format("%m-%d-%Y")))
+    }
+
+    // Moves the stored report of `date` out of the way when the new version
+    // with `crc` differs from it.
+    fn keep_revision(&self, date: &NaiveDate, crc: Option<u32>) {
+        let previous = match self.manifest.lock().unwrap().get(date) {
+            Some(previous) => *previous,
+            None => return,
+        };
+        let path = self.path(date);
+        // entries of older versions have no checksum to compare
+        let stored = previous
+            .crc
+            .or_else(|| cache::read(&path).map(|body| inflate::crc32(body.as_bytes())));
+        if stored == crc || !path.exists() {
+            return;
+        }
+        let revision = self.dir.join(REVISIONS).join(format!(
+            "{}.{}.csv.gz",
+            date.format("%m-%d-%Y"),
+            previous.fetched.format(REVISION_TIME)
+        ));
+        let kept =
+            fs::create_dir_all(self.dir.join(REVISIONS)).and_then(|_| fs::rename(&path, &revision));
+        match kept {
+            Ok(()) => info!(%date, "report was corrected, kept the earlier version"),
+            Err(e) => warn!("could not keep the earlier report of {}: {}", date, e),
+        }
+    }
+
+    fn record(&self, date: NaiveDate, entry: Entry) -> std::io::Result<()> {
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(MANIFEST))?;
+        write!(
+            manifest,
+            "{} {}",
+            date,
+            entry.fetched.format("%Y-%m-%dT%H:%M:%S")
+        )?;
+        if let Some(crc) = entry.crc {
+            write!(manifest, " {:08x}", crc)?;
+        }
+        writeln!(manifest)
+    }
+}
+
+impl Layer for ReportStore {
+    fn name(&self) -> &'static str {
+        "reports"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        if self.offline && key == URL_REPORT_LISTING {
+            return Some(self.listing());
+        }
+        let date = ReportStore::date_of(key)?;
+        let entry = *self.manifest.lock().unwrap().get(&date)?;
+        if !self.offline && entry.fetched.date() < date + Duration::days(REVALIDATE_DAYS) {
+            debug!(%date, fetched = %entry.fetched, "revalidating recent report");
+            return None;
+        }
+        let age = Utc::now().naive_utc() - entry.fetched;
+        if !self.offline && self.revisions && age.to_std().is_ok_and(|a| a > cache::MAX_AGE) {
+            debug!(%date, fetched = %entry.fetched, "revalidating for revisions");
+            return None;
+        }
+        let path = self.path(&date);
+        let body = cache::read(&path)?;
+        match entry.crc {
+            Some(crc) if crc != inflate::crc32(body.as_bytes()) => {
+                cache::remove_damaged(&path, "checksum mismatch")
+            }
+            _ => Some(body),
+        }
+    }
+
+    fn put(&self, key: &str, body: &str) {
+        let date = match ReportStore::date_of(key) {
+            Some(date) => date,
+            None => return,
+        };
+        let entry = Entry {
+            fetched: Utc::now().naive_utc(),
+            crc: Some(inflate::crc32(body.as_bytes())),
+        };
+        if self.revisions {
+            self.keep_revision(&date, entry.crc);
+        }
+        let stored = fs::create_dir_all(&self.dir)
+            .and_then(|_| cache::write(&self.path(&date), body))
+            .and_then(|_| self.record(date, entry));
+        match stored {
+            Ok(()) => {
+                self.manifest.lock().unwrap().insert(date, entry);
+            }
+            Err(e) => warn!("could not store the report of {}: {}", date, e),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::color::visible_width;
+use crate::i18n;
+use crate::locale;
+
+// --format tsv: cells separated by tabs without padding or rules, and
+// counts without thousands separators
+static TSV: AtomicBool = AtomicBool::new(false);
+// cleared by --no-header
+static HEADER: AtomicBool = AtomicBool::new(true);
+
+// Set once from the command line, for every table of the run.
+pub fn set_style(tsv: bool, header: bool) {
+    TSV.store(tsv, Ordering::Relaxed);
+    HEADER.store(header, Ordering::Relaxed);
+}
+
+// Captions and charts around the tables are left out in TSV.
+pub fn is_tsv() -> bool {
+    TSV.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    align: Vec<Align>,
+    rows: Vec<Vec<String>>,
+    footer: Option<Vec<String>>,
+}
+
+impl Table {
+    // The headers are translated to the language of --lang.
+    pub fn new(headers: &[&str]) -> Table {
+        Table {
+            headers: headers.iter().map(|h| i18n::label(h).to_string()).collect(),
+            align: headers.iter().map(|_| Align::Left).collect(),
+            rows: Vec::new(),
+            footer: None,
+        }
+    }
+
+    pub fn align(mut self, column: usize, align: Align) -> Table {
+        if let Some(a) = self.align.get_mut(column) {
+            *a = align;
+        }
+        self
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    // Rendered below a separator line, used for totals.
+    pub fn set_footer(&mut self, row: Vec<String>) {
+        self.footer = Some(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| visible_width(h)).collect();
+        for row in self.rows.iter().chain(self.footer.iter()) {
+            for (i, cell) in row.iter().enumerate() {
+                if i < widths.len() {
+                    widths[i] = widths[i].max(visible_width(cell));
+                }
+            }
+        }
+        widths
+    }
+
+    fn write_row(&self, f: &mut fmt::Formatter, row: &[String], widths: &[usize]) -> fmt::Result {
+        let mut line = String::new();
+        for (i, width) in widths.iter().enumerate() {
+            let cell = row.get(i).map(|c| c.as_str()).unwrap_or("");
+            let pad = " ".repeat(width - visible_width(cell).min(*width));
+            if i > 0 {
+                line.push_str("  ");
+            }
+            match self.align[i] {
+                Align::Left => {
+                    line.push_str(cell);
+                    line.push_str(&pad);
+                }
+                Align::Right => {
+                    line.push_str(&pad);
+                    line.push_str(cell);
+                }
+            }
+        }
+        writeln!(f, "{}", line.trim_end())
+    }
+
+    // A line of tab separated cells, tabs and newlines within them replaced
+    // by spaces.
+    fn write_tsv_row(&self, f: &mut fmt::Formatter, row: &[String]) -> fmt::Result {
+        let cells: Vec<String> = (0..self.headers.len())
+            .map(|i| {
+                row.get(i)
+                    .map(|c| c.replace(['\t', '\n'], " "))
+                    .unwrap_or_default()
+            })
+            .collect();
+        writeln!(f, "{}", cells.join("\t"))
+    }
+
+    fn write_tsv(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if HEADER.load(Ordering::Relaxed) {
+            self.write_tsv_row(f, &self.headers)?;
+        }
+        for row in self.rows.iter().chain(self.footer.iter()) {
+            self.write_tsv_row(f, row)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if is_tsv() {
+            return self.write_tsv(f);
+        }
+        let widths = self.widths();
+        let rule = "-".repeat(widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1));
+
+        if HEADER.load(Ordering::Relaxed) {
+            self.write_row(f, &self.headers, &widths)?;
+            writeln!(f, "{}", rule)?;
+        }
+        for row in self.rows.iter() {
+            self.write_row(f, row, &widths)?;
+        }
+        if let Some(footer) = &self.footer {
+            writeln!(f, "{}", rule)?;
+            self.write_row(f, footer, &widths)?;
+        }
+        Ok(())
+    }
+}
+
+// 1234567 -> "1,234,567", or "1.234.567" and so on by the locale, and
+// "1234567" in TSV
+pub fn format_count(value: u64) -> String {
+    if is_tsv() {
+        return value.to_string();
+    }
+    let separator = locale::current().thousands;
+    let digits = value.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push_str(separator);
+        }
+        out.push(c);
+    }
+    out
+}
+
+// -1234 -> "-1,234", 1234 -> "1,234", for counts that corrections can make
+// negative
+pub fn format_signed(value: i64) -> String {
+    if value < 0 {
+        format_change(value)
+    } else {
+        format_count(value as u64)
+    }
+}
+
+// -1234 -> "-1,234", 1234 -> "+1,234"
+pub fn format_change(value: i64) -> String {
+    let sign = if value < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_count(value.unsigned_abs()))
+}
+
+// 12.345 with 1 decimal -> "12.3", or "12,3" by the locale
+pub fn format_decimal(value: f64, decimals: usize) -> String {
+    let text = format!("{:.*}", decimals, value);
+    match locale::current().decimal {
+        '.' => text,
+        decimal => text.replace('.', &decimal.to_string()),
+    }
+}
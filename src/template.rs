@@ -0,0 +1,488 @@
+// Templates for custom reports in a small subset of the Jinja2/Tera syntax:
+//
+//   {{ countries.0.confirmed | count }}
+//   {% for c in countries %}...{% endfor %}   with loop.index, loop.first, loop.last
+//   {% if c.trend.change > 0 %}...{% elif ... %}...{% else %}...{% endif %}
+//   {# a comment #}
+//
+// A `-` next to a delimiter, `{{-` or `-%}`, trims the whitespace on that
+// side. The context is a JSON value, an unknown variable is an error.
+
+use std::cmp::Ordering;
+
+use crate::json::Value;
+use crate::svg::escape;
+use crate::table::{format_change, format_signed};
+
+const FILTERS: &[&str] = &[
+    "count", "change", "round", "upper", "lower", "escape", "json", "length", "first", "last",
+    "reverse",
+];
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Path(Vec<String>),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    operand: Operand,
+    filters: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    negate: bool,
+    left: Expr,
+    compare: Option<(&'static str, Expr)>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Print(Expr),
+    For {
+        name: String,
+        list: Expr,
+        body: Vec<Node>,
+    },
+    If {
+        branches: Vec<(Condition, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+enum Token {
+    Text(String),
+    Print(String, usize),
+    Tag(String, usize),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let line = |pos: usize| source[..pos].matches('\n').count() + 1;
+    // (token, trim before, trim after)
+    let mut raw: Vec<(Token, bool, bool)> = Vec::new();
+    let mut pos = 0;
+    while pos < source.len() {
+        let start = match source[pos..].find('{').map(|i| pos + i) {
+            Some(start) => start,
+            None => {
+                raw.push((Token::Text(source[pos..].to_string()), false, false));
+                break;
+            }
+        };
+        let close = match source[start + 1..].chars().next() {
+            Some('{') => "}}",
+            Some('%') => "%}",
+            Some('#') => "#}",
+            _ => {
+                raw.push((Token::Text(source[pos..=start].to_string()), false, false));
+                pos = start + 1;
+                continue;
+            }
+        };
+        if start > pos {
+            raw.push((Token::Text(source[pos..start].to_string()), false, false));
+        }
+        let end = source[start + 2..]
+            .find(close)
+            .map(|i| start + 2 + i)
+            .ok_or_else(|| {
+                format!(
+                    "line {}: unclosed '{}'",
+                    line(start),
+                    &source[start..start + 2]
+                )
+            })?;
+        let inner = &source[start + 2..end];
+        let (trim_before, inner) = match inner.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, inner),
+        };
+        let (trim_after, inner) = match inner.strip_suffix('-') {
+            Some(rest) => (true, rest),
+            None => (false, inner),
+        };
+        let inner = inner.trim().to_string();
+        let token = match close {
+            "}}" => Token::Print(inner, line(start)),
+            "%}" => Token::Tag(inner, line(start)),
+            _ => Token::Text(String::new()),
+        };
+        raw.push((token, trim_before, trim_after));
+        pos = end + 2;
+    }
+
+    let mut tokens = Vec::new();
+    for i in 0..raw.len() {
+        let trim_start = i > 0 && raw[i - 1].2;
+        let trim_end = raw.get(i + 1).map(|t| t.1).unwrap_or(false);
+        match &raw[i].0 {
+            Token::Text(text) => {
+                let mut text = text.as_str();
+                if trim_start {
+                    text = text.trim_start();
+                }
+                if trim_end {
+                    text = text.trim_end();
+                }
+                if !text.is_empty() {
+                    tokens.push(Token::Text(text.to_string()));
+                }
+            }
+            Token::Print(expr, line) => tokens.push(Token::Print(expr.clone(), *line)),
+            Token::Tag(tag, line) => tokens.push(Token::Tag(tag.clone(), *line)),
+        }
+    }
+    Ok(tokens)
+}
+
+// Splits at `separator` outside of quoted strings.
+fn split_outside_quotes<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let (mut quote, mut start) = (None, 0);
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, _) if text[i..].starts_with(separator) && i >= start => {
+                parts.push(&text[start..i]);
+                start = i + separator.len();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let text = text.trim();
+    if text.len() >= 2
+        && ((text.starts_with('"') && text.ends_with('"'))
+            || (text.starts_with('\'') && text.ends_with('\'')))
+    {
+        return Ok(Operand::Literal(Value::String(
+            text[1..text.len() - 1].to_string(),
+        )));
+    }
+    if let Ok(n) = text.parse::<f64>() {
+        return Ok(Operand::Literal(Value::Number(n)));
+    }
+    match text {
+        "true" => return Ok(Operand::Literal(Value::Bool(true))),
+        "false" => return Ok(Operand::Literal(Value::Bool(false))),
+        _ => {}
+    }
+    let path: Vec<String> = text.split('.').map(|p| p.trim().to_string()).collect();
+    if path.iter().any(|p| {
+        p.is_empty()
+            || !p
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    }) {
+        return Err(format!("invalid expression '{}'", text));
+    }
+    Ok(Operand::Path(path))
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let mut parts = split_outside_quotes(text, "|").into_iter();
+    let operand = parse_operand(parts.next().unwrap_or(""))?;
+    let mut filters = Vec::new();
+    for filter in parts {
+        let filter = filter.trim();
+        if !FILTERS.contains(&filter) {
+            return Err(format!(
+                "unknown filter '{}', expected one of {}",
+                filter,
+                FILTERS.join(", ")
+            ));
+        }
+        filters.push(filter.to_string());
+    }
+    Ok(Expr { operand, filters })
+}
+
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    let (negate, text) = match text.trim().strip_prefix("not ") {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    for op in ["==", "!=", ">=", "<=", ">", "<"].iter() {
+        if let [left, right] = split_outside_quotes(text, op).as_slice() {
+            return Ok(Condition {
+                negate,
+                left: parse_expr(left)?,
+                compare: Some((*op, parse_expr(right)?)),
+            });
+        }
+    }
+    Ok(Condition {
+        negate,
+        left: parse_expr(text)?,
+        compare: None,
+    })
+}
+
+// a tag with its line
+type Tag = (String, usize);
+
+struct Parser {
+    tokens: std::vec::IntoIter<Token>,
+}
+
+impl Parser {
+    // Nodes up to one of the tags `until`, returned with the nodes.
+    fn block(&mut self, until: &[&str]) -> Result<(Vec<Node>, Option<Tag>), String> {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.tokens.next() {
+            match token {
+                Token::Text(text) => nodes.push(Node::Text(text)),
+                Token::Print(expr, line) => nodes.push(Node::Print(
+                    parse_expr(&expr).map_err(|e| format!("line {}: {}", line, e))?,
+                )),
+                Token::Tag(tag, line) => {
+                    let keyword = tag.split_whitespace().next().unwrap_or("");
+                    if until.contains(&keyword) {
+                        return Ok((nodes, Some((tag, line))));
+                    }
+                    let node = self
+                        .tag(&tag)
+                        .map_err(|e| format!("line {}: {}", line, e))?;
+                    nodes.push(node);
+                }
+            }
+        }
+        match until.last() {
+            Some(end) => Err(format!("missing {{% {} %}}", end)),
+            None => Ok((nodes, None)),
+        }
+    }
+
+    fn tag(&mut self, tag: &str) -> Result<Node, String> {
+        let (keyword, rest) = tag.split_once(' ').unwrap_or((tag, ""));
+        match keyword {
+            "for" => {
+                let (name, list) = rest
+                    .split_once(" in ")
+                    .ok_or("expected {% for <name> in <list> %}")?;
+                let (body, _) = self.block(&["endfor"])?;
+                Ok(Node::For {
+                    name: name.trim().to_string(),
+                    list: parse_expr(list)?,
+                    body,
+                })
+            }
+            "if" => {
+                let mut branches = Vec::new();
+                let mut condition = parse_condition(rest)?;
+                loop {
+                    let (body, end) = self.block(&["elif", "else", "endif"])?;
+                    branches.push((condition, body));
+                    let (end, line) = end.unwrap_or_default();
+                    match end.split_once(' ') {
+                        Some(("elif", rest)) => {
+                            condition = parse_condition(rest)
+                                .map_err(|e| format!("line {}: {}", line, e))?
+                        }
+                        _ if end == "else" => {
+                            let (otherwise, _) = self.block(&["endif"])?;
+                            return Ok(Node::If {
+                                branches,
+                                otherwise,
+                            });
+                        }
+                        _ => {
+                            return Ok(Node::If {
+                                branches,
+                                otherwise: Vec::new(),
+                            })
+                        }
+                    }
+                }
+            }
+            _ => Err(format!("unknown tag '{}'", keyword)),
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => *n != 0.0,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(values) => !values.is_empty(),
+        Value::Object(members) => !members.is_empty(),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn apply(filter: &str, value: Value) -> Result<Value, String> {
+    let number = |value: &Value| {
+        value
+            .as_f64()
+            .ok_or_else(|| format!("'{}' needs a number, got {}", filter, value))
+    };
+    let list = |value: Value| match value {
+        Value::Array(values) => Ok(values),
+        value => Err(format!("'{}' needs a list, got {}", filter, value)),
+    };
+    Ok(match filter {
+        "count" => Value::String(format_signed(number(&value)?.round() as i64)),
+        "change" => Value::String(format_change(number(&value)?.round() as i64)),
+        "round" => Value::Number(number(&value)?.round()),
+        "upper" => Value::String(text(&value).to_uppercase()),
+        "lower" => Value::String(text(&value).to_lowercase()),
+        "escape" => Value::String(escape(&text(&value))),
+        "json" => Value::String(value.to_string()),
+        "length" => Value::Number(match &value {
+            Value::String(s) => s.chars().count(),
+            Value::Array(values) => values.len(),
+            Value::Object(members) => members.len(),
+            _ => return Err(format!("'length' needs a list, got {}", value)),
+        } as f64),
+        "first" => list(value)?.into_iter().next().unwrap_or(Value::Null),
+        "last" => list(value)?.pop().unwrap_or(Value::Null),
+        _ => Value::Array(list(value)?.into_iter().rev().collect()),
+    })
+}
+
+struct Scope<'a> {
+    context: &'a Value,
+    // loop variables, innermost last
+    locals: Vec<(String, Value)>,
+}
+
+impl<'a> Scope<'a> {
+    fn lookup(&self, path: &[String]) -> Result<Value, String> {
+        let first = &path[0];
+        let mut value = match self.locals.iter().rev().find(|(name, _)| name == first) {
+            Some((_, value)) => value,
+            None => self
+                .context
+                .get(first)
+                .ok_or_else(|| format!("unknown variable '{}'", first))?,
+        };
+        for key in path[1..].iter() {
+            let next = match (value, key.parse::<usize>()) {
+                (Value::Array(values), Ok(index)) => values.get(index),
+                (value, _) => value.get(key),
+            };
+            value = next.ok_or_else(|| format!("'{}' has no '{}'", path.join("."), key))?;
+        }
+        Ok(value.clone())
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Value, String> {
+        let mut value = match &expr.operand {
+            Operand::Path(path) => self.lookup(path)?,
+            Operand::Literal(value) => value.clone(),
+        };
+        for filter in expr.filters.iter() {
+            value = apply(filter, value)?;
+        }
+        Ok(value)
+    }
+
+    fn test(&self, condition: &Condition) -> Result<bool, String> {
+        let left = self.eval(&condition.left)?;
+        let result = match &condition.compare {
+            None => truthy(&left),
+            Some((op, right)) => {
+                let right = self.eval(right)?;
+                let order = match (&left, &right) {
+                    (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                    _ => None,
+                };
+                match *op {
+                    "==" => left == right,
+                    "!=" => left != right,
+                    ">" => order == Some(Ordering::Greater),
+                    "<" => order == Some(Ordering::Less),
+                    ">=" => matches!(order, Some(Ordering::Greater) | Some(Ordering::Equal)),
+                    _ => matches!(order, Some(Ordering::Less) | Some(Ordering::Equal)),
+                }
+            }
+        };
+        Ok(result != condition.negate)
+    }
+
+    fn render(&mut self, nodes: &[Node], out: &mut String) -> Result<(), String> {
+        for node in nodes.iter() {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Print(expr) => out.push_str(&text(&self.eval(expr)?)),
+                Node::For { name, list, body } => {
+                    let items = match self.eval(list)? {
+                        Value::Array(items) => items,
+                        Value::Null => Vec::new(),
+                        value => return Err(format!("cannot loop over {}", value)),
+                    };
+                    let count = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let state = Value::Object(vec![
+                            ("index".to_string(), Value::Number((i + 1) as f64)),
+                            ("first".to_string(), Value::Bool(i == 0)),
+                            ("last".to_string(), Value::Bool(i + 1 == count)),
+                        ]);
+                        self.locals.push(("loop".to_string(), state));
+                        self.locals.push((name.clone(), item));
+                        let rendered = self.render(body, out);
+                        self.locals.truncate(self.locals.len() - 2);
+                        rendered?;
+                    }
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                } => {
+                    let mut taken = None;
+                    for (condition, body) in branches.iter() {
+                        if self.test(condition)? {
+                            taken = Some(body);
+                            break;
+                        }
+                    }
+                    self.render(taken.unwrap_or(otherwise), out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?.into_iter(),
+        };
+        let (nodes, _) = parser.block(&[])?;
+        Ok(Template { nodes })
+    }
+
+    pub fn render(&self, context: &Value) -> Result<String, String> {
+        let mut scope = Scope {
+            context,
+            locals: Vec::new(),
+        };
+        let mut out = String::new();
+        scope.render(&self.nodes, &mut out)?;
+        Ok(out)
+    }
+}
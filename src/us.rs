@@ -0,0 +1,386 @@
+use chrono::NaiveDate;
+use csv::ReaderBuilder;
+use std::collections::BTreeMap;
+use std::error::Error;
+use tracing::debug;
+
+use crate::chain::Chain;
+use crate::client::NotFound;
+use crate::data::UPSTREAM;
+use crate::exit::Failure;
+use crate::i18n;
+use crate::locale;
+use crate::search;
+use crate::table::{self, format_change, format_count, format_decimal, Align, Table};
+
+const DAILY_REPORTS: &str = "csse_covid_19_data/csse_covid_19_daily_reports_us/";
+const TIME_SERIES: &str = "csse_covid_19_data/csse_covid_19_time_series/time_series_covid19_";
+
+// A row of the US daily reports, one per state and day. The testing figures
+// are only known for some states.
+#[derive(Debug, Clone, Default)]
+pub struct StateReport {
+    pub state: String,
+    pub confirmed: u64,
+    pub deaths: u64,
+    pub recovered: Option<u64>,
+    pub active: Option<u64>,
+    pub fips: Option<u32>,
+    pub incident_rate: Option<f64>,
+    pub tested: Option<u64>,
+}
+
+// Confirmed cases and deaths of a county from the US time series, cumulative
+// and oldest first. Some rows are not counties but e.g. "Unassigned" cases of
+// a state.
+#[derive(Debug, Clone, Default)]
+pub struct CountySeries {
+    pub fips: Option<u32>,
+    pub county: String,
+    pub state: String,
+    pub population: Option<u64>,
+    pub confirmed: Vec<(NaiveDate, i64)>,
+    pub deaths: Vec<(NaiveDate, i64)>,
+}
+
+// The US daily report of `date`, empty if it was not published.
+pub async fn fetch_report(
+    chain: &Chain,
+    date: &NaiveDate,
+) -> Result<Vec<StateReport>, Box<dyn Error>> {
+    let url = format!(
+        "{}{}{}.csv",
+        UPSTREAM,
+        DAILY_REPORTS,
+        date.format("%m-%d-%Y")
+    );
+    match chain.fetch(&url).await {
+        Ok(body) => parse_report(&body),
+        Err(e) if e.is::<NotFound>() => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn parse_report(body: &str) -> Result<Vec<StateReport>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().from_reader(body.as_bytes());
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let state = column("Province_State")
+        .ok_or_else(|| Failure::Parse("US daily report without Province_State".to_string()))?;
+    let (confirmed, deaths, recovered, active) = (
+        column("Confirmed"),
+        column("Deaths"),
+        column("Recovered"),
+        column("Active"),
+    );
+    let (fips, incident_rate, tested) = (
+        column("FIPS"),
+        column("Incident_Rate"),
+        column("People_Tested"),
+    );
+
+    let mut reports = Vec::new();
+    for row in rdr.records() {
+        let row = row?;
+        // counts are written as floats ("461601.0") in some of the reports
+        let number = |index: Option<usize>| {
+            index
+                .and_then(|i| row.get(i))
+                .and_then(|v| v.parse::<f64>().ok())
+        };
+        let count = |index: Option<usize>| number(index).map(|v| v.max(0.0) as u64);
+        reports.push(StateReport {
+            state: row.get(state).unwrap_or_default().to_string(),
+            confirmed: count(confirmed).unwrap_or(0),
+            deaths: count(deaths).unwrap_or(0),
+            recovered: count(recovered),
+            active: count(active),
+            fips: number(fips).map(|v| v as u32),
+            incident_rate: number(incident_rate),
+            tested: count(tested),
+        });
+    }
+    debug!(rows = reports.len(), "parsed US daily report");
+    Ok(reports)
+}
+
+// The county level time series of confirmed cases and deaths.
+pub async fn fetch_time_series(chain: &Chain) -> Result<Vec<CountySeries>, Box<dyn Error>> {
+    let confirmed = chain
+        .fetch(&format!("{}{}confirmed_US.csv", UPSTREAM, TIME_SERIES))
+        .await?;
+    let deaths = chain
+        .fetch(&format!("{}{}deaths_US.csv", UPSTREAM, TIME_SERIES))
+        .await?;
+    parse_time_series(&confirmed, &deaths)
+}
+
+// Both files have a row per UID with the metadata columns first and a column
+// per day after them, the deaths file also has the population.
+pub fn parse_time_series(
+    confirmed: &str,
+    deaths: &str,
+) -> Result<Vec<CountySeries>, Box<dyn Error>> {
+    let mut counties: BTreeMap<String, CountySeries> = BTreeMap::new();
+    for (body, is_deaths) in [(confirmed, false), (deaths, true)] {
+        let mut rdr = ReaderBuilder::new().from_reader(body.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|h| h == name);
+        let (uid, fips, county, state, population) = (
+            column("UID"),
+            column("FIPS"),
+            column("Admin2"),
+            column("Province_State").ok_or_else(|| {
+                Failure::Parse("US time series without Province_State".to_string())
+            })?,
+            column("Population"),
+        );
+        let days: Vec<(usize, NaiveDate)> = headers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, h)| Some((i, NaiveDate::parse_from_str(h, "%m/%d/%y").ok()?)))
+            .collect();
+
+        for row in rdr.records() {
+            let row = row?;
+            let get = |index: Option<usize>| index.and_then(|i| row.get(i)).unwrap_or_default();
+            let (county, state) = (get(county), row.get(state).unwrap_or_default());
+            // the UID is unique, county and state are the fallback
+            let key = match get(uid) {
+                "" => format!("{}/{}", state, county),
+                uid => uid.to_string(),
+            };
+            let entry = counties.entry(key).or_insert_with(|| CountySeries {
+                fips: get(fips).parse::<f64>().ok().map(|v| v as u32),
+                county: county.to_string(),
+                state: state.to_string(),
+                ..CountySeries::default()
+            });
+            let values = days
+                .iter()
+                .filter_map(|(i, day)| Some((*day, row.get(*i)?.parse::<f64>().ok()? as i64)))
+                .collect();
+            if is_deaths {
+                entry.population = get(population).parse().ok();
+                entry.deaths = values;
+            } else {
+                entry.confirmed = values;
+            }
+        }
+    }
+    debug!(counties = counties.len(), "parsed US time series");
+    Ok(counties.into_values().collect())
+}
+
+// Newest value and the change to the day before.
+fn last(series: &[(NaiveDate, i64)]) -> (i64, i64) {
+    match series {
+        [.., before, last] => (last.1, last.1 - before.1),
+        [last] => (last.1, last.1),
+        [] => (0, 0),
+    }
+}
+
+fn per_100k(value: i64, population: u64) -> String {
+    if population == 0 {
+        return "-".to_string();
+    }
+    format_decimal(value as f64 / population as f64 * 100_000.0, 1)
+}
+
+// The name as it is spelled in the data, matching is case insensitive. A
+// close match is suggested for typos.
+fn resolve<'a>(name: &str, known: &[&'a str], what: &str) -> Result<&'a str, Box<dyn Error>> {
+    if let Some(found) = known.iter().find(|k| k.eq_ignore_ascii_case(name)) {
+        return Ok(found);
+    }
+    let message = match search::rank(name, known, 1).first() {
+        Some((best, _)) => format!("no {} {}, did you mean {}?", what, name, best),
+        None => format!("no {} {}", what, name),
+    };
+    Err(Failure::NoData(message).into())
+}
+
+// `corona-stats us`: all states, the counties of `state` or the last two weeks
+// of a single county.
+#[tokio::main]
+pub async fn us(
+    chain: &Chain,
+    state: Option<&str>,
+    county: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let series = fetch_time_series(chain).await?;
+    let mut states: Vec<&str> = series.iter().map(|c| c.state.as_str()).collect();
+    states.sort_unstable();
+    states.dedup();
+    match (state, county) {
+        (None, None) => print_states(chain, &series).await,
+        (Some(state), county) => {
+            let state = resolve(state, &states, "state")?;
+            let counties: Vec<&CountySeries> = series.iter().filter(|c| c.state == state).collect();
+            match county {
+                None => print_counties(state, &counties),
+                Some(county) => {
+                    let names: Vec<&str> = counties.iter().map(|c| c.county.as_str()).collect();
+                    let county = resolve(county, &names, &format!("county in {}", state))?;
+                    match counties.iter().find(|c| c.county == county) {
+                        Some(county) => print_county(county),
+                        None => Err(Failure::NoData(format!("no county {}", county)).into()),
+                    }
+                }
+            }
+        }
+        (None, Some(_)) => Err(Failure::Parse("--county needs a --state".to_string()).into()),
+    }
+}
+
+// Totals per state from the time series, with the testing figures of the US
+// daily report of the same day where it was published.
+async fn print_states(chain: &Chain, series: &[CountySeries]) -> Result<(), Box<dyn Error>> {
+    let mut totals: BTreeMap<&str, (i64, i64, i64)> = BTreeMap::new();
+    for county in series.iter() {
+        let (confirmed, new) = last(&county.confirmed);
+        let entry = totals.entry(county.state.as_str()).or_insert((0, 0, 0));
+        entry.0 += confirmed;
+        entry.1 += new;
+        entry.2 += last(&county.deaths).0;
+    }
+    let date = match series
+        .iter()
+        .filter_map(|c| c.confirmed.last())
+        .map(|v| v.0)
+        .max()
+    {
+        Some(date) => date,
+        None => return Err(Failure::NoData("no US data available".to_string()).into()),
+    };
+    let reports = fetch_report(chain, &date).await?;
+
+    let mut rows: Vec<(&str, (i64, i64, i64))> = totals.into_iter().collect();
+    rows.sort_by(|a, b| (b.1).0.cmp(&(a.1).0).then(a.0.cmp(b.0)));
+    let mut table = Table::new(&[
+        "State",
+        "Confirmed",
+        "New cases",
+        "Deaths",
+        "Tested",
+        "Per 100k",
+    ]);
+    for column in 1..6 {
+        table = table.align(column, Align::Right);
+    }
+    let (mut confirmed, mut new, mut deaths) = (0, 0, 0);
+    for (state, (c, n, d)) in rows.into_iter() {
+        let report = reports.iter().find(|r| r.state == state);
+        table.add_row(vec![
+            state.to_string(),
+            format_count(c.max(0) as u64),
+            format_change(n),
+            format_count(d.max(0) as u64),
+            report
+                .and_then(|r| r.tested)
+                .map(format_count)
+                .unwrap_or_else(|| "-".to_string()),
+            report
+                .and_then(|r| r.incident_rate)
+                .map(|r| format_decimal(r, 1))
+                .unwrap_or_else(|| "-".to_string()),
+        ]);
+        confirmed += c;
+        new += n;
+        deaths += d;
+    }
+    table.set_footer(vec![
+        "US".to_string(),
+        format_count(confirmed.max(0) as u64),
+        format_change(new),
+        format_count(deaths.max(0) as u64),
+        String::new(),
+        String::new(),
+    ]);
+    if !table::is_tsv() {
+        println!("{} {}", i18n::label("US as of"), locale::format_date(&date));
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+fn print_counties(state: &str, counties: &[&CountySeries]) -> Result<(), Box<dyn Error>> {
+    let mut counties = counties.to_vec();
+    counties.sort_by(|a, b| {
+        last(&b.confirmed)
+            .0
+            .cmp(&last(&a.confirmed).0)
+            .then(a.county.cmp(&b.county))
+    });
+    let mut table = Table::new(&[
+        "County",
+        "FIPS",
+        "Confirmed",
+        "New cases",
+        "Deaths",
+        "Per 100k",
+    ]);
+    for column in 1..6 {
+        table = table.align(column, Align::Right);
+    }
+    let (mut confirmed, mut new, mut deaths) = (0, 0, 0);
+    for county in counties.iter() {
+        let (c, n) = last(&county.confirmed);
+        let d = last(&county.deaths).0;
+        table.add_row(vec![
+            county.county.clone(),
+            county.fips.map(|f| format!("{:05}", f)).unwrap_or_default(),
+            format_count(c.max(0) as u64),
+            format_change(n),
+            format_count(d.max(0) as u64),
+            per_100k(c, county.population.unwrap_or(0)),
+        ]);
+        confirmed += c;
+        new += n;
+        deaths += d;
+    }
+    table.set_footer(vec![
+        state.to_string(),
+        String::new(),
+        format_count(confirmed.max(0) as u64),
+        format_change(new),
+        format_count(deaths.max(0) as u64),
+        per_100k(
+            confirmed,
+            counties.iter().filter_map(|c| c.population).sum(),
+        ),
+    ]);
+    print!("{}", table);
+    Ok(())
+}
+
+fn print_county(county: &CountySeries) -> Result<(), Box<dyn Error>> {
+    let mut table = Table::new(&["Date", "Confirmed", "New cases", "Deaths", "New deaths"]);
+    for column in 1..5 {
+        table = table.align(column, Align::Right);
+    }
+    let skip = county.confirmed.len().saturating_sub(14);
+    for (i, (date, confirmed)) in county.confirmed.iter().enumerate().skip(skip) {
+        let before = |series: &[(NaiveDate, i64)]| match i {
+            0 => 0,
+            _ => series.get(i - 1).map(|v| v.1).unwrap_or(0),
+        };
+        let deaths = county.deaths.get(i).map(|v| v.1).unwrap_or(0);
+        table.add_row(vec![
+            locale::format_date(date),
+            format_count((*confirmed).max(0) as u64),
+            format_change(confirmed - before(&county.confirmed)),
+            format_count(deaths.max(0) as u64),
+            format_change(deaths - before(&county.deaths)),
+        ]);
+    }
+    if table.is_empty() {
+        return Err(Failure::NoData(format!("no data for {}", county.county)).into());
+    }
+    if !table::is_tsv() {
+        println!("{} County, {}", county.county, county.state);
+    }
+    print!("{}", table);
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use std::fs;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+use crate::cache::{self, file_name};
+use crate::data::URL_DAILY_REPORT;
+
+// What the server said identifies the version of a file, sent back with the
+// next request so an unchanged file is answered with 304 and no body.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_headers(headers: &HeaderMap) -> Validators {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        Validators {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    // The conditional request headers, If-None-Match wins on the server side
+    // when both are sent.
+    pub fn headers(&self) -> Vec<(reqwest::header::HeaderName, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push((IF_NONE_MATCH, etag.clone()));
+        }
+        if let Some(date) = &self.last_modified {
+            headers.push((IF_MODIFIED_SINCE, date.clone()));
+        }
+        headers
+    }
+}
+
+// The last version of every downloaded file that came with validators, the
+// time series above all, in a directory with a body and a meta file per URL.
+// The bodies are those of the disk cache when both share a directory. Daily
+// reports are left to the report store.
+pub struct ValidatorStore {
+    dir: PathBuf,
+}
+
+impl ValidatorStore {
+    pub fn new(dir: PathBuf) -> ValidatorStore {
+        ValidatorStore { dir }
+    }
+
+    fn path(&self, url: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", file_name(url), extension))
+    }
+
+    fn applies(url: &str) -> bool {
+        !url.starts_with(URL_DAILY_REPORT)
+    }
+
+    pub fn load(&self, url: &str) -> Option<(Validators, String)> {
+        if !ValidatorStore::applies(url) {
+            return None;
+        }
+        let meta = fs::read_to_string(self.path(url, "meta")).ok()?;
+        let mut validators = Validators::default();
+        for line in meta.lines() {
+            match line.split_once(": ") {
+                Some(("etag", value)) => validators.etag = Some(value.to_string()),
+                Some(("last-modified", value)) => {
+                    validators.last_modified = Some(value.to_string())
+                }
+                _ => {}
+            }
+        }
+        let body = cache::read(&self.path(url, "body.gz"))?;
+        Some((validators, body))
+    }
+
+    pub fn save(&self, url: &str, validators: &Validators, body: &str) {
+        if validators.is_empty() || !ValidatorStore::applies(url) {
+            return;
+        }
+        let mut meta = String::new();
+        if let Some(etag) = &validators.etag {
+            meta.push_str(&format!("etag: {}\n", etag));
+        }
+        if let Some(date) = &validators.last_modified {
+            meta.push_str(&format!("last-modified: {}\n", date));
+        }
+        // the body first, a meta file without its body is never used
+        let saved = fs::create_dir_all(&self.dir)
+            .and_then(|_| cache::write(&self.path(url, "body.gz"), body))
+            .and_then(|_| fs::write(self.path(url, "meta"), meta));
+        match saved {
+            Ok(()) => debug!(url, "stored validators"),
+            Err(e) => warn!("could not store {}: {}", url, e),
+        }
+    }
+}
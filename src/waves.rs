@@ -0,0 +1,353 @@
+use chrono::NaiveDate;
+use std::str::FromStr;
+
+use crate::locale;
+use crate::table::{format_count, format_decimal, Align, Table};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wave {
+    pub start: NaiveDate,
+    pub peak: NaiveDate,
+    pub end: NaiveDate,
+    pub peak_value: f64,
+    pub end_value: f64,
+    pub total: f64,
+}
+
+impl Wave {
+    pub fn duration(&self) -> i64 {
+        (self.end - self.start).num_days() + 1
+    }
+
+    pub fn days_to_peak(&self) -> i64 {
+        (self.peak - self.start).num_days()
+    }
+
+    // Average relative decline per day between the peak and the end of the wave.
+    pub fn decay_rate(&self) -> Option<f64> {
+        let days = (self.end - self.peak).num_days();
+        if days <= 0 || self.peak_value <= 0.0 {
+            return None;
+        }
+        let ratio = (self.end_value / self.peak_value).max(1e-9);
+        Some(1.0 - ratio.powf(1.0 / days as f64))
+    }
+}
+
+// Cumulative totals to new cases per day. Downward corrections are clamped to 0.
+pub fn daily_new(cumulative: &[(NaiveDate, i64)]) -> Vec<(NaiveDate, f64)> {
+    cumulative
+        .windows(2)
+        .map(|w| (w[1].0, (w[1].1 - w[0].1).max(0) as f64))
+        .collect()
+}
+
+// Trailing mean; the first values average over what is available so far.
+pub fn smooth(values: &[(NaiveDate, f64)], window: usize) -> Vec<(NaiveDate, f64)> {
+    let window = window.max(1);
+    (0..values.len())
+        .map(|i| {
+            let from = (i + 1).saturating_sub(window);
+            let slice = &values[from..=i];
+            (
+                values[i].0,
+                slice.iter().map(|v| v.1).sum::<f64>() / slice.len() as f64,
+            )
+        })
+        .collect()
+}
+
+// Splits a smoothed new-case curve into waves. A peak has to be the maximum of
+// the surrounding `radius` days and reach `min_share` of the highest peak; a
+// wave runs from the low point before its peak to the low point after it.
+pub fn detect(smoothed: &[(NaiveDate, f64)], radius: usize, min_share: f64) -> Vec<Wave> {
+    let max = smoothed.iter().map(|v| v.1).fold(0.0, f64::max);
+    if max <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut peaks: Vec<usize> = Vec::new();
+    for i in 0..smoothed.len() {
+        let from = i.saturating_sub(radius);
+        let to = (i + radius + 1).min(smoothed.len());
+        let value = smoothed[i].1;
+        let is_max = smoothed[from..to].iter().all(|v| v.1 <= value);
+        if is_max && value > 0.0 && value >= max * min_share {
+            // plateaus produce several equal maxima, keep the first one
+            if peaks.last().map(|p| i - p > radius).unwrap_or(true) {
+                peaks.push(i);
+            }
+        }
+    }
+
+    let first = smoothed.iter().position(|v| v.1 > 0.0).unwrap_or(0);
+    let mut waves = Vec::new();
+    let mut start = first;
+    for (n, peak) in peaks.iter().enumerate() {
+        let end = match peaks.get(n + 1) {
+            Some(next) => lowest(smoothed, *peak, *next),
+            None => smoothed.len() - 1,
+        };
+        waves.push(Wave {
+            start: smoothed[start].0,
+            peak: smoothed[*peak].0,
+            end: smoothed[end].0,
+            peak_value: smoothed[*peak].1,
+            end_value: smoothed[end].1,
+            total: smoothed[start..=end].iter().map(|v| v.1).sum(),
+        });
+        start = end;
+    }
+    waves
+}
+
+fn lowest(values: &[(NaiveDate, f64)], from: usize, to: usize) -> usize {
+    (from..=to)
+        .min_by(|a, b| values[*a].1.partial_cmp(&values[*b].1).unwrap())
+        .unwrap_or(from)
+}
+
+// Local maxima whose prominence, the height above the higher of the lowest
+// points separating them from a higher peak on either side, reaches
+// `min_prominence` of the highest value. Plateaus count once, at their first
+// day; the first and the last day are never peaks.
+pub fn peaks(values: &[(NaiveDate, f64)], min_prominence: f64) -> Vec<(NaiveDate, f64)> {
+    let max = values.iter().map(|v| v.1).fold(0.0, f64::max);
+    let mut found = Vec::new();
+    let mut i = 1;
+    while i + 1 < values.len() {
+        let value = values[i].1;
+        let mut end = i;
+        while end + 1 < values.len() && values[end + 1].1 == value {
+            end += 1;
+        }
+        let is_max = values[i - 1].1 < value && end + 1 < values.len() && values[end + 1].1 < value;
+        if is_max {
+            let base = |range: &mut dyn Iterator<Item = usize>| {
+                let mut lowest = value;
+                for k in range {
+                    if values[k].1 > value {
+                        break;
+                    }
+                    lowest = lowest.min(values[k].1);
+                }
+                lowest
+            };
+            let left = base(&mut (0..i).rev());
+            let right = base(&mut (end + 1..values.len()));
+            if value - left.max(right) >= max * min_prominence && value > 0.0 {
+                found.push(values[i]);
+            }
+        }
+        i = end + 1;
+    }
+    found
+}
+
+// One country's new-case curve shifted so that day 0 is the start of its
+// first wave, which makes curves of countries hit at different times comparable.
+#[derive(Debug, Clone)]
+pub struct Synchronized {
+    pub country: String,
+    pub onset: NaiveDate,
+    pub waves: Vec<Wave>,
+    pub curve: Vec<f64>,
+}
+
+// The waves of the 7-day mean of new cases from cumulative counts: a peak
+// has to top the two weeks around it and reach a tenth of the highest one.
+pub fn segment(cumulative: &[(NaiveDate, i64)]) -> Vec<Wave> {
+    detect(&smooth(&daily_new(cumulative), 7), 14, 0.1)
+}
+
+pub fn synchronize(country: &str, cumulative: &[(NaiveDate, i64)]) -> Option<Synchronized> {
+    let onset = segment(cumulative).first()?.start;
+    Some(synchronize_at(country, cumulative, onset))
+}
+
+// Same as `synchronize`, with day 0 given instead of taken from the first wave.
+pub fn synchronize_at(
+    country: &str,
+    cumulative: &[(NaiveDate, i64)],
+    onset: NaiveDate,
+) -> Synchronized {
+    let smoothed = smooth(&daily_new(cumulative), 7);
+    let waves = segment(cumulative);
+    let curve = smoothed
+        .iter()
+        .filter(|v| v.0 >= onset)
+        .map(|v| v.1)
+        .collect();
+    Synchronized {
+        country: country.to_string(),
+        onset,
+        waves,
+        curve,
+    }
+}
+
+// `--align-on 100cases` or `10deaths`: day 0 is the first day a cumulative
+// count reached the threshold, the usual way to line up epidemic curves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alignment {
+    pub threshold: i64,
+    // time series holding the count, Confirmed or Deaths
+    pub state: &'static str,
+}
+
+impl FromStr for Alignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Alignment, String> {
+        let error = || {
+            format!(
+                "invalid alignment '{}', expected a count followed by cases or deaths, e.g. 100cases",
+                s
+            )
+        };
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(error)?;
+        let (number, unit) = s.split_at(split);
+        let state = match unit {
+            "cases" => "Confirmed",
+            "deaths" => "Deaths",
+            _ => return Err(error()),
+        };
+        Ok(Alignment {
+            threshold: number.parse().map_err(|_| error())?,
+            state,
+        })
+    }
+}
+
+impl Alignment {
+    pub fn day_zero(&self, cumulative: &[(NaiveDate, i64)]) -> Option<NaiveDate> {
+        cumulative
+            .iter()
+            .find(|(_, value)| *value >= self.threshold)
+            .map(|(date, _)| *date)
+    }
+}
+
+pub fn comparison_table(countries: &[Synchronized]) -> Table {
+    let mut table = Table::new(&[
+        "Country",
+        "Wave",
+        "Start",
+        "Day",
+        "Days to peak",
+        "Peak (7d avg)",
+        "Duration",
+        "Decay/day",
+    ]);
+    for column in 1..8 {
+        if column != 2 {
+            table = table.align(column, Align::Right);
+        }
+    }
+    for c in countries.iter() {
+        for (n, wave) in c.waves.iter().enumerate() {
+            table.add_row(vec![
+                c.country.clone(),
+                (n + 1).to_string(),
+                wave.start.to_string(),
+                (wave.start - c.onset).num_days().to_string(),
+                wave.days_to_peak().to_string(),
+                format_count(wave.peak_value.round() as u64),
+                format!("{} d", wave.duration()),
+                match wave.decay_rate() {
+                    Some(rate) => format!("{}%", format_decimal(rate * 100.0, 1)),
+                    None => "-".to_string(),
+                },
+            ]);
+        }
+    }
+    table
+}
+
+// Every wave of every country, with the new cases of the whole wave.
+pub fn wave_table(countries: &[(String, Vec<Wave>)]) -> Table {
+    let mut table = Table::new(&[
+        "Country",
+        "Wave",
+        "Start",
+        "Peak",
+        "End",
+        "Peak (7d avg)",
+        "Total",
+        "Duration",
+    ]);
+    for column in [1, 5, 6, 7].iter() {
+        table = table.align(*column, Align::Right);
+    }
+    for (country, waves) in countries.iter() {
+        for (n, wave) in waves.iter().enumerate() {
+            table.add_row(vec![
+                country.clone(),
+                (n + 1).to_string(),
+                locale::format_date(&wave.start),
+                locale::format_date(&wave.peak),
+                locale::format_date(&wave.end),
+                format_count(wave.peak_value.round() as u64),
+                format_count(wave.total.round() as u64),
+                format!("{} d", wave.duration()),
+            ]);
+        }
+    }
+    table
+}
+
+// Plots the synchronized curves on top of each other, each normalized to its
+// own highest peak, one letter per country.
+pub fn overlay_chart(countries: &[Synchronized], width: usize, height: usize) -> String {
+    let days = countries.iter().map(|c| c.curve.len()).max().unwrap_or(0);
+    if days == 0 || width == 0 || height == 0 {
+        return String::new();
+    }
+    let columns = width.min(days);
+    let mut grid = vec![vec![' '; columns]; height];
+
+    for (n, c) in countries.iter().enumerate() {
+        let mark = (b'A' + (n % 26) as u8) as char;
+        let max = c.curve.iter().cloned().fold(0.0, f64::max);
+        if max <= 0.0 {
+            continue;
+        }
+        for (column, cell) in (0..columns).map(|col| (col, col * days / columns)) {
+            if let Some(value) = c.curve.get(cell) {
+                let row = ((value / max) * (height - 1) as f64).round() as usize;
+                grid[height - 1 - row][column] = mark;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for (i, row) in grid.iter().enumerate() {
+        let label = if i == 0 {
+            "100%"
+        } else if i == height - 1 {
+            "  0%"
+        } else {
+            "    "
+        };
+        out.push_str(&format!(
+            "{} |{}\n",
+            label,
+            row.iter().collect::<String>().trim_end()
+        ));
+    }
+    out.push_str(&format!("     +{}\n", "-".repeat(columns)));
+    out.push_str(&format!(
+        "      day 0{:>width$}\n",
+        format!("day {}", days - 1),
+        width = columns.saturating_sub(5)
+    ));
+    for (n, c) in countries.iter().enumerate() {
+        out.push_str(&format!(
+            "      {} = {} (onset {})\n",
+            (b'A' + (n % 26) as u8) as char,
+            c.country,
+            c.onset
+        ));
+    }
+    out
+}
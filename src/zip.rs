@@ -0,0 +1,106 @@
+// Writer for ZIP archives (APPNOTE 6.3), the container of XLSX workbooks.
+// Entries are deflated with `deflate`, there is no encryption, no ZIP64 and
+// every entry has the same timestamp.
+
+use crate::deflate;
+use crate::inflate::crc32;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+const VERSION: u16 = 20;
+const DEFLATED: u16 = 8;
+// 1980-01-01 00:00 in MS-DOS format
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+    offset: u32,
+}
+
+#[derive(Default)]
+pub struct Zip {
+    out: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+fn u16le(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn u32le(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+impl Zip {
+    pub fn new() -> Zip {
+        Zip::default()
+    }
+
+    pub fn add(&mut self, name: &str, data: &[u8]) {
+        let compressed = deflate::deflate(data);
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32(data),
+            compressed: compressed.len() as u32,
+            size: data.len() as u32,
+            offset: self.out.len() as u32,
+        };
+        let out = &mut self.out;
+        u32le(out, LOCAL_HEADER);
+        u16le(out, VERSION);
+        u16le(out, 0);
+        u16le(out, DEFLATED);
+        u16le(out, DOS_TIME);
+        u16le(out, DOS_DATE);
+        u32le(out, entry.crc);
+        u32le(out, entry.compressed);
+        u32le(out, entry.size);
+        u16le(out, name.len() as u16);
+        u16le(out, 0);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&compressed);
+        self.entries.push(entry);
+    }
+
+    // The archive with its central directory.
+    pub fn finish(mut self) -> Vec<u8> {
+        let start = self.out.len() as u32;
+        let out = &mut self.out;
+        for entry in self.entries.iter() {
+            u32le(out, CENTRAL_HEADER);
+            u16le(out, VERSION);
+            u16le(out, VERSION);
+            u16le(out, 0);
+            u16le(out, DEFLATED);
+            u16le(out, DOS_TIME);
+            u16le(out, DOS_DATE);
+            u32le(out, entry.crc);
+            u32le(out, entry.compressed);
+            u32le(out, entry.size);
+            u16le(out, entry.name.len() as u16);
+            // extra field, comment, disk, internal and external attributes
+            u16le(out, 0);
+            u16le(out, 0);
+            u16le(out, 0);
+            u16le(out, 0);
+            u32le(out, 0);
+            u32le(out, entry.offset);
+            out.extend_from_slice(entry.name.as_bytes());
+        }
+        let size = out.len() as u32 - start;
+        u32le(out, END_OF_DIRECTORY);
+        u16le(out, 0);
+        u16le(out, 0);
+        u16le(out, self.entries.len() as u16);
+        u16le(out, self.entries.len() as u16);
+        u32le(out, size);
+        u32le(out, start);
+        u16le(out, 0);
+        self.out
+    }
+}
@@ -0,0 +1,300 @@
+use chrono::{Duration, NaiveDate};
+
+use corona_stats::analytics::{self, AnomalyKind, Model, Trend};
+
+fn day(n: usize) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2020, 3, 1).unwrap() + Duration::days(n as i64)
+}
+
+// Cumulative counts of consecutive days from 2020-03-01.
+fn series(values: &[i64]) -> Vec<(NaiveDate, i64)> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(n, v)| (day(n), *v))
+        .collect()
+}
+
+// The cumulative counts of `new` cases a day.
+fn accumulate(new: &[f64]) -> Vec<(NaiveDate, i64)> {
+    let mut total = 0.0;
+    let values: Vec<i64> = new
+        .iter()
+        .map(|n| {
+            total += n;
+            total.round() as i64
+        })
+        .collect();
+    series(&values)
+}
+
+fn close(a: f64, b: f64, tolerance: f64) -> bool {
+    (a - b).abs() <= tolerance
+}
+
+#[test]
+fn growth_factor_of_the_new_cases() {
+    assert_eq!(
+        analytics::growth_factor(&series(&[0, 10, 20, 40])),
+        Some(2.0)
+    );
+    assert_eq!(analytics::growth_factor(&series(&[10, 20, 25])), Some(0.5));
+    // a correction counts as no new cases
+    assert_eq!(analytics::growth_factor(&series(&[10, 20, 15])), Some(0.0));
+    assert_eq!(analytics::growth_factor(&series(&[10, 10, 20])), None);
+    assert_eq!(analytics::growth_factor(&series(&[10, 20])), None);
+}
+
+#[test]
+fn doubling_time_of_an_exponential() {
+    let values: Vec<i64> = (0..20)
+        .map(|d| (1000.0 * 2f64.powf(d as f64 / 3.0)).round() as i64)
+        .collect();
+    let days = analytics::doubling_time(&series(&values), analytics::DOUBLING_DAYS).unwrap();
+    assert!(close(days, 3.0, 0.01), "{}", days);
+}
+
+#[test]
+fn no_doubling_time_without_growth() {
+    let doubling = |values: &[i64]| analytics::doubling_time(&series(values), 7);
+    assert_eq!(doubling(&[5; 10]), None);
+    assert_eq!(doubling(&[9, 8, 7, 6]), None);
+    // nothing to take the logarithm of
+    assert_eq!(doubling(&[0, 0, 1, 2]), None);
+    assert_eq!(doubling(&[1]), None);
+    assert_eq!(doubling(&[]), None);
+    // only the last days count
+    assert!(doubling(&[0, 0, 0, 1, 2, 4, 8, 16, 32, 64, 128]).is_some());
+}
+
+#[test]
+fn fit_an_exponential() {
+    let mut values = vec![0, 0];
+    values.extend((0..28).map(|d| (500.0 * (0.2 * d as f64).exp()).round() as i64));
+    let fit = analytics::fit(&series(&values), Model::Exponential).unwrap();
+    // leading days without cases are left out
+    assert_eq!(fit.start, day(2));
+    assert!(close(fit.rate, 0.2, 0.001), "{:?}", fit);
+    assert!(close(fit.scale, 500.0, 5.0), "{:?}", fit);
+    assert!(fit.rmse < fit.value(27.0) * 0.001, "{:?}", fit);
+    assert_eq!(fit.parameters()[1], ("rate", fit.rate));
+}
+
+#[test]
+fn fit_a_logistic() {
+    let values: Vec<i64> = (0..40)
+        .map(|d| (10_000.0 / (1.0 + (-0.3 * (d as f64 - 15.0)).exp())).round() as i64)
+        .collect();
+    let fit = analytics::fit(&series(&values), Model::Logistic).unwrap();
+    assert!(close(fit.scale, 10_000.0, 200.0), "{:?}", fit);
+    assert!(close(fit.rate, 0.3, 0.03), "{:?}", fit);
+    assert!(close(fit.midpoint, 15.0, 1.0), "{:?}", fit);
+    // the final size is never below the last count
+    assert!(fit.scale >= *values.last().unwrap() as f64);
+}
+
+#[test]
+fn no_fit_of_too_few_days() {
+    assert!(analytics::fit(&series(&[0, 0, 5, 10]), Model::Exponential).is_none());
+    assert!(analytics::fit(&series(&[]), Model::Logistic).is_none());
+}
+
+#[test]
+fn projections_continue_after_the_last_day() {
+    let values: Vec<i64> = (0..10).map(|d| 100 << d).collect();
+    let fit = analytics::fit(&series(&values), Model::Exponential).unwrap();
+    let projected = analytics::project(&fit, &day(9), 3);
+    let dates: Vec<NaiveDate> = projected.iter().map(|p| p.0).collect();
+    assert_eq!(dates, vec![day(10), day(11), day(12)]);
+    for (date, value, new) in projected.iter() {
+        assert!(close(*value, fit.value_at(date), 1e-6));
+        let before = fit.value_at(&(*date - Duration::days(1)));
+        assert!(close(*new, value - before, 1e-6));
+    }
+    assert!(close(projected[0].1, 102_400.0, 1024.0), "{:?}", projected);
+}
+
+#[test]
+fn models_by_name() {
+    assert_eq!("exponential".parse(), Ok(Model::Exponential));
+    assert_eq!("logistic".parse(), Ok(Model::Logistic));
+    assert!("linear".parse::<Model>().is_err());
+}
+
+fn rt_of(new: &[f64]) -> Vec<analytics::Rt> {
+    let new: Vec<(NaiveDate, f64)> = new.iter().enumerate().map(|(n, v)| (day(n), *v)).collect();
+    analytics::rt(&new, analytics::SERIAL_INTERVAL, analytics::RT_WINDOW)
+}
+
+#[test]
+fn rt_of_constant_cases_is_one() {
+    let estimates = rt_of(&[100.0; 90]);
+    let last = estimates.last().unwrap();
+    assert_eq!(last.date, day(89));
+    assert!(close(last.mean, 1.0, 0.02), "{:?}", last);
+    assert!(last.low < last.mean && last.mean < last.high, "{:?}", last);
+    assert!(last.low > 0.9 && last.high < 1.1, "{:?}", last);
+}
+
+#[test]
+fn rt_follows_growth() {
+    let growing: Vec<f64> = (0..60).map(|d| 10.0 * 1.05f64.powi(d)).collect();
+    assert!(rt_of(&growing).last().unwrap().low > 1.0);
+    let shrinking: Vec<f64> = (0..60).map(|d| 10_000.0 * 0.95f64.powi(d)).collect();
+    assert!(rt_of(&shrinking).last().unwrap().high < 1.0);
+}
+
+#[test]
+fn rt_needs_cases() {
+    assert!(rt_of(&[0.0; 30]).is_empty());
+    assert!(rt_of(&[5.0; 7]).is_empty());
+    // days are only estimated once there were cases before the window
+    let mut late = vec![0.0; 20];
+    late.extend(vec![10.0; 20]);
+    assert!(rt_of(&late).iter().all(|r| r.date > day(20)));
+}
+
+fn trend_of(first: f64, second: f64) -> Option<(Trend, f64, f64, Option<f64>)> {
+    let mut new = vec![0.0];
+    new.extend(vec![first; analytics::TREND_DAYS]);
+    new.extend(vec![second; analytics::TREND_DAYS]);
+    analytics::trend(&accumulate(&new))
+}
+
+#[test]
+fn trends_compare_two_weeks() {
+    assert_eq!(
+        trend_of(10.0, 20.0),
+        Some((Trend::Rising, 20.0, 10.0, Some(100.0)))
+    );
+    assert_eq!(
+        trend_of(20.0, 10.0),
+        Some((Trend::Falling, 10.0, 20.0, Some(-50.0)))
+    );
+    let (trend, _, _, change) = trend_of(100.0, 105.0).unwrap();
+    assert_eq!(trend, Trend::Flat);
+    assert!(close(change.unwrap(), 5.0, 1e-9));
+}
+
+#[test]
+fn trends_after_a_week_without_cases() {
+    assert_eq!(trend_of(0.0, 3.0), Some((Trend::Rising, 3.0, 0.0, None)));
+    assert_eq!(trend_of(0.0, 0.0), Some((Trend::Flat, 0.0, 0.0, None)));
+}
+
+#[test]
+fn trends_need_two_weeks() {
+    let new = vec![1.0; 2 * analytics::TREND_DAYS];
+    assert_eq!(analytics::trend(&accumulate(&new)), None);
+}
+
+#[test]
+fn trend_labels() {
+    assert!(Trend::Rising < Trend::Flat && Trend::Flat < Trend::Falling);
+    assert_eq!(Trend::Falling.label(), "Falling");
+}
+
+#[test]
+fn deaths_follow_cases_by_the_best_lag() {
+    // waves of new cases, and a tenth of them dying 12 days later
+    let wave = |d: f64| 1000.0 + 800.0 * (d / 9.0).sin() + 300.0 * (d / 4.0).cos();
+    let cases: Vec<f64> = (0..150).map(|d| wave(d as f64)).collect();
+    let deaths: Vec<f64> = (0..150).map(|d| wave(d as f64 - 12.0) * 0.1).collect();
+    let correlations = analytics::lag_correlations(&accumulate(&cases), &accumulate(&deaths), 20);
+    assert_eq!(correlations.len(), 21);
+    let (lag, r) = analytics::best_lag(&correlations).unwrap();
+    assert_eq!(lag, 12);
+    assert!(r > 0.99, "{}", r);
+    assert!(correlations
+        .iter()
+        .all(|(_, r)| (-1.0..=1.0001).contains(r)));
+}
+
+#[test]
+fn lags_need_overlapping_days() {
+    let cases = accumulate(&(0..20).map(|d| d as f64).collect::<Vec<_>>());
+    let deaths = accumulate(&(0..20).map(|d| (d % 3) as f64).collect::<Vec<_>>());
+    // 14 days from 20 leave lags up to 6 minus the days the means need
+    let correlations = analytics::lag_correlations(&cases, &deaths, 10);
+    assert!(correlations.iter().all(|(lag, _)| *lag <= 6));
+    assert_eq!(analytics::best_lag(&[]), None);
+}
+
+#[test]
+fn constant_series_do_not_correlate() {
+    let flat = accumulate(&[5.0; 60]);
+    let cases = accumulate(&(0..60).map(|d| d as f64).collect::<Vec<_>>());
+    assert!(analytics::lag_correlations(&cases, &flat, 5).is_empty());
+}
+
+#[test]
+fn spikes_drops_and_corrections() {
+    // far enough apart not to be in each other's baseline
+    let mut new = vec![100.0; 80];
+    new[10] = 1000.0;
+    new[40] = 0.0;
+    new[70] = -50.0;
+    let anomalies = analytics::anomalies(&accumulate(&new), analytics::ANOMALY_Z);
+    let found: Vec<(NaiveDate, AnomalyKind, i64)> = anomalies
+        .iter()
+        .map(|a| (a.date, a.kind, a.change))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (day(10), AnomalyKind::Spike, 1000),
+            (day(40), AnomalyKind::Drop, 0),
+            (day(70), AnomalyKind::Correction, -50),
+        ]
+    );
+    assert!(anomalies.iter().all(|a| a.median == 100.0));
+    assert!(anomalies[0].z > analytics::ANOMALY_Z);
+    assert!(anomalies[1].z < -analytics::ANOMALY_Z);
+}
+
+#[test]
+fn steady_and_noisy_series_have_no_anomalies() {
+    assert!(analytics::anomalies(&accumulate(&[100.0; 40]), analytics::ANOMALY_Z).is_empty());
+    let noisy: Vec<f64> = (0..60).map(|d| 100.0 + ((d * 37) % 21) as f64).collect();
+    assert!(analytics::anomalies(&accumulate(&noisy), analytics::ANOMALY_Z).is_empty());
+    assert!(analytics::anomalies(&series(&[5]), analytics::ANOMALY_Z).is_empty());
+}
+
+#[test]
+fn the_threshold_decides() {
+    let mut new: Vec<f64> = (0..40).map(|d| 100.0 + (d % 5) as f64 * 10.0).collect();
+    new[20] = 200.0;
+    let cumulative = accumulate(&new);
+    let z = analytics::anomalies(&cumulative, 0.0)
+        .into_iter()
+        .find(|a| a.date == day(20))
+        .unwrap()
+        .z;
+    assert_eq!(analytics::anomalies(&cumulative, z + 0.1).len(), 0);
+    assert_eq!(analytics::anomalies(&cumulative, z - 0.1).len(), 1);
+}
+
+#[test]
+fn first_days_at_each_milestone() {
+    let cumulative = series(&[50, 150, 999, 1000, 20_000, 20_000]);
+    assert_eq!(
+        analytics::milestones(&cumulative),
+        vec![(100, day(1)), (1_000, day(3)), (10_000, day(4))]
+    );
+    assert!(analytics::milestones(&series(&[0, 99])).is_empty());
+    assert_eq!(
+        analytics::milestones(&series(&[2_000_000])).len(),
+        analytics::MILESTONES.len()
+    );
+}
+
+#[test]
+fn outcome_shares_of_the_resolved_cases() {
+    let deaths = series(&[0, 1, 2, 3]);
+    let recovered = vec![(day(0), 0), (day(1), 3), (day(3), 1)];
+    assert_eq!(
+        analytics::outcome_shares(&deaths, &recovered),
+        vec![(day(1), 0.25, 0.75), (day(3), 0.75, 0.25)]
+    );
+    assert!(analytics::outcome_shares(&deaths, &[]).is_empty());
+}
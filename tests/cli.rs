@@ -0,0 +1,172 @@
+// The command line of the binary, run on the bundled sample data.
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    // no config file or cache of the user
+    run_in(&std::env::temp_dir().join("corona-stats-cli"), args)
+}
+
+fn run_in(home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_corona-stats"))
+        .args(["--demo", "-q"])
+        .args(args)
+        .env("XDG_CONFIG_HOME", home)
+        .env("XDG_CACHE_HOME", home)
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn no_colors_when_piped() {
+    let text = stdout(&run(&["summary"]));
+    assert!(text.contains("Italy"));
+    assert!(!text.contains('\u{1b}'));
+}
+
+// the same output for `--flag value` and `--flag=value`
+fn same_as_joined(args: &[&str], flag: &str, value: &str) {
+    let mut separate = args.to_vec();
+    separate.extend_from_slice(&[flag, value]);
+    let joined = format!("{}={}", flag, value);
+    let mut together = args.to_vec();
+    together.push(&joined);
+    assert_eq!(stdout(&run(&separate)), stdout(&run(&together)));
+}
+
+#[test]
+fn per_capita_takes_the_next_number() {
+    same_as_joined(&["series", "Italy"], "--per-capita", "1000000");
+    // not a number, so a country
+    let text = stdout(&run(&["series", "--per-capita", "Germany"]));
+    assert!(text.starts_with("\"Germany\""));
+}
+
+#[test]
+fn per_capita_keeps_the_decimals() {
+    let text = stdout(&run(&[
+        "series",
+        "Germany",
+        "--metric",
+        "deaths",
+        "--per-capita",
+    ]));
+    assert!(text.ends_with("(\"2020-03-21\", 0.038)\n"));
+    // rates are not divided once more
+    for metric in ["incidence", "cfr", "outcomes"].iter() {
+        let output = run(&["series", "Italy", "--metric", metric, "--per-capita"]);
+        assert_eq!(output.status.code(), Some(2));
+    }
+}
+
+#[test]
+fn peaks_take_the_next_number() {
+    same_as_joined(
+        &["series", "China", "--metric", "confirmed"],
+        "--peaks",
+        "0.9",
+    );
+    let text = stdout(&run(&["series", "--peaks", "China"]));
+    assert!(text.starts_with("\"China\""));
+}
+
+#[test]
+fn anomalies_take_the_next_number() {
+    same_as_joined(&["anomalies", "China"], "--anomalies", "1");
+}
+
+#[test]
+fn unknown_options_are_usage_errors() {
+    let output = run(&["series", "Italy", "--per-capta", "1000"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--per-capta"));
+}
+
+#[test]
+fn nothing_found_goes_to_stderr() {
+    for (args, message) in [
+        (["anomalies", "Italy"], "No anomalies"),
+        (["reconcile", "Italy"], "agree"),
+    ]
+    .iter()
+    {
+        let output = run(args);
+        assert_eq!(stdout(&output), "");
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message));
+    }
+}
+
+#[test]
+fn days_have_to_be_positive() {
+    for args in [
+        ["forecast", "Italy", "--days", "0"],
+        ["rt", "Italy", "--window", "x"],
+    ]
+    .iter()
+    {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("expected a number of days"));
+    }
+}
+
+// watch would otherwise never return
+#[test]
+fn intervals_are_checked() {
+    for interval in ["0", "0s", "99999999999999999d"].iter() {
+        let output = run(&["watch", "--interval", interval]);
+        assert_eq!(output.status.code(), Some(2));
+    }
+}
+
+#[test]
+fn top_ranks_by_a_metric() {
+    let text = stdout(&run(&["top", "2", "--by", "doubling_time"]));
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].contains("doubling_time"));
+    // a header, its rule and two countries
+    assert_eq!(lines.len(), 4);
+    assert!(lines[2].starts_with("1  US"));
+    assert_eq!(run(&["top", "--by", "nothing"]).status.code(), Some(2));
+}
+
+// a config file with the group DACH
+fn grouped(args: &[&str]) -> Output {
+    let home = std::env::temp_dir().join("corona-stats-cli-groups");
+    std::fs::create_dir_all(home.join("corona-stats")).unwrap();
+    std::fs::write(
+        home.join("corona-stats").join("config.toml"),
+        "[groups]\nDACH = [\"Germany\", \"Austria\", \"Switzerland\"]\n",
+    )
+    .unwrap();
+    run_in(&home, args)
+}
+
+#[test]
+fn groups_work_wherever_countries_do() {
+    let text = stdout(&grouped(&["top", "--group", "DACH", "--by", "deaths"]));
+    let countries: Vec<&str> = text
+        .lines()
+        .skip(2)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .collect();
+    assert_eq!(countries, ["Germany", "Austria"]);
+    assert_eq!(grouped(&["top", "--group", "EU"]).status.code(), Some(2));
+
+    let badge = stdout(&grouped(&["badge", "--country", "DACH"]));
+    assert!(badge.contains("DACH new cases: 2,113"));
+    // a crossed threshold is exit code 4, the message is on stderr
+    let alert = grouped(&["alert", "--country", "DACH", "--above", "2000"]);
+    assert_eq!(alert.status.code(), Some(4));
+    assert!(alert.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&alert.stderr).contains("DACH new_cases on 2020-03-21 is 2113"));
+    let feed = stdout(&grouped(&["feed", "--country", "DACH"]));
+    assert!(feed.contains("<title>DACH on 2020-03-21: +2,113 cases, +7 deaths</title>"));
+}
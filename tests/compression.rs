@@ -0,0 +1,144 @@
+// The DEFLATE and gzip codecs against streams written by zlib, and against
+// each other.
+
+use corona_stats::deflate::{deflate, gzip};
+use corona_stats::inflate::{crc32, gunzip, gunzip_stream, inflate, is_gzip};
+
+const HELLO: &[u8] = b"Hello, hello, hello!\n";
+
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+// 30 lines of a long CSV export
+fn csv() -> Vec<u8> {
+    (1..=30)
+        .map(|d| format!("2020-03-{:02},Italy,,confirmed,{}\n", d, d * d * 37))
+        .collect::<String>()
+        .into_bytes()
+}
+
+#[test]
+fn zlib_stored_block() {
+    let data = hex("011500eaff48656c6c6f2c2068656c6c6f2c2068656c6c6f210a");
+    assert_eq!(inflate(&data).unwrap(), (HELLO.to_vec(), data.len()));
+}
+
+#[test]
+fn zlib_fixed_codes() {
+    let data = hex("f348cdc9c9d751c840a214b900");
+    assert_eq!(inflate(&data).unwrap(), (HELLO.to_vec(), data.len()));
+}
+
+#[test]
+fn zlib_dynamic_codes() {
+    let data = hex(concat!(
+        "6dd34b6ac4400c04d07dcee20155a9d5dd3a428e11f281406606866c72fb64132470ad4b96a5679946bb98",
+        "5f0cc7f3f7cbd7cf71bcde6f1f9f8febfbdbe1eb89ff31cf31c6aedcc5e3ee958f731ec9cae39c27a3f229",
+        "deefde1a2c51b0d126d8e702fa6c2ba428c82c03982232ab026138c66a1d8462386b0608c6c9a82d201c17",
+        "a31c2020b7374908c91cab75509236b30d212881dc6d8f949f2b8a82260faa695268623a5a0f75942bade6",
+        "a0f044c6aa5d284009477930d4e1a091529032acf710a69cd9e750e799d67711a60e340f37f913fe99fe02"
+    ));
+    assert_eq!(inflate(&data).unwrap(), (csv(), data.len()));
+}
+
+#[test]
+fn trailing_bytes_are_not_consumed() {
+    let mut data = hex("f348cdc9c9d751c840a214b900");
+    let used = data.len();
+    data.extend_from_slice(b"trailer");
+    assert_eq!(inflate(&data).unwrap().1, used);
+}
+
+#[test]
+fn broken_streams_are_errors() {
+    let data = hex("f348cdc9c9d751c840a214b900");
+    assert!(inflate(&data[..data.len() / 2]).is_err());
+    // block type 3 does not exist
+    assert!(inflate(&[0x07]).is_err());
+    // stored block whose length and its complement disagree
+    assert!(inflate(&hex("0115001500")).is_err());
+}
+
+#[test]
+fn gzip_of_zlib() {
+    let data = hex("1f8b0800000000000203f348cdc9c9d751c840a214b900bbc753ed15000000");
+    assert!(is_gzip(&data));
+    assert_eq!(gunzip(&data).unwrap(), HELLO);
+}
+
+#[test]
+fn gzip_with_file_name() {
+    let mut data = hex("1f8b08080000000000ff");
+    data.extend_from_slice(b"hello.txt\0");
+    data.extend(hex("f348cdc9c9d751c840a214b900bbc753ed15000000"));
+    assert_eq!(gunzip(&data).unwrap(), HELLO);
+}
+
+#[test]
+fn gzip_checks() {
+    assert!(!is_gzip(HELLO));
+    assert!(gunzip(HELLO).is_err());
+    let mut data = hex("1f8b0800000000000203f348cdc9c9d751c840a214b900bbc753ed15000000");
+    // a flipped bit of the checksum
+    data[23] ^= 1;
+    assert!(gunzip(&data).is_err());
+    let data = hex("1f8b0800000000000203f348cdc9c9d751c840a214b900");
+    assert!(gunzip(&data).is_err());
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
+
+#[test]
+fn round_trips() {
+    let mut pseudo_random = Vec::new();
+    let mut state = 1u32;
+    for _ in 0..5000 {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        pseudo_random.push((state >> 16) as u8);
+    }
+    let samples: Vec<Vec<u8>> = vec![
+        Vec::new(),
+        b"a".to_vec(),
+        b"ab".to_vec(),
+        HELLO.to_vec(),
+        csv(),
+        vec![0; 100_000],
+        csv().repeat(200),
+        pseudo_random,
+    ];
+    for data in samples.iter() {
+        let compressed = deflate(data);
+        assert_eq!(
+            inflate(&compressed).unwrap(),
+            (data.clone(), compressed.len())
+        );
+        assert_eq!(&gunzip(&gzip(data)).unwrap(), data);
+    }
+}
+
+// A download arrives in chunks of any size, the inflater only sees the bytes.
+#[test]
+fn gunzip_of_chunks() {
+    let data = csv().repeat(200);
+    let compressed = gzip(&data);
+    for size in [1, 7, 1024, compressed.len()].iter() {
+        let chunks = compressed.chunks(*size).flat_map(|c| c.iter().copied());
+        assert_eq!(gunzip_stream(chunks).unwrap(), data);
+    }
+    let truncated = compressed[..compressed.len() - 3].iter().copied();
+    assert!(gunzip_stream(truncated).is_err());
+}
+
+#[test]
+fn repetitive_data_shrinks() {
+    let data = csv().repeat(200);
+    assert!(deflate(&data).len() < data.len() / 10);
+}
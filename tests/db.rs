@@ -0,0 +1,29 @@
+// The SQL of `query` before it reaches the sqlite3 shell.
+#![cfg(feature = "sqlite")]
+
+use std::path::Path;
+
+use corona_stats::db;
+use corona_stats::exit::Failure;
+
+#[test]
+fn shell_commands_are_refused() {
+    let db = Path::new("never-created.db");
+    for sql in [
+        ".shell touch owned",
+        "SELECT 1;\n.system touch owned",
+        "SELECT 1;\n  .output owned",
+    ]
+    .iter()
+    {
+        let error = db::query(db, sql, "csv").unwrap_err();
+        assert!(
+            matches!(error.downcast_ref(), Some(Failure::Parse(_))),
+            "{}",
+            sql
+        );
+    }
+    // plain SQL only misses the database
+    let error = db::query(db, "SELECT 1.5", "csv").unwrap_err();
+    assert!(matches!(error.downcast_ref(), Some(Failure::NoData(_))));
+}
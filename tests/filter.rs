@@ -0,0 +1,120 @@
+use corona_stats::filter::{Glob, LocationFilter, Regex};
+
+fn regex(pattern: &str) -> Regex {
+    Regex::new(pattern).unwrap()
+}
+
+#[test]
+fn literals_match_anywhere() {
+    assert!(regex("land").is_match("Switzerland"));
+    assert!(regex("").is_match("Italy"));
+    assert!(!regex("land").is_match("Italy"));
+    // no case folding
+    assert!(!regex("italy").is_match("Italy"));
+}
+
+#[test]
+fn anchors() {
+    assert!(regex("^Ger").is_match("Germany"));
+    assert!(!regex("^many").is_match("Germany"));
+    assert!(regex("many$").is_match("Germany"));
+    assert!(!regex("^Ger$").is_match("Germany"));
+    assert!(regex("^$").is_match(""));
+}
+
+#[test]
+fn classes() {
+    assert!(regex("^[A-C]").is_match("Brazil"));
+    assert!(!regex("^[A-C]").is_match("Denmark"));
+    assert!(regex("^[^,]+$").is_match("Korea South"));
+    assert!(!regex("^[^,]+$").is_match("Korea, South"));
+    assert!(regex("[-x]").is_match("Guinea-Bissau"));
+    assert!(regex("[]]").is_match("a]"));
+    assert!(regex(r"^\d+$").is_match("2020"));
+    assert!(!regex(r"^\d+$").is_match("20a0"));
+    assert!(regex(r"^\w+\s\w+$").is_match("New Zealand"));
+    assert!(regex(r"[\d.]").is_match("3.5"));
+    assert!(regex(r"^\.$").is_match("."));
+    assert!(!regex(r"^\.$").is_match("x"));
+}
+
+#[test]
+fn quantifiers() {
+    assert!(regex("^ab*c$").is_match("ac"));
+    assert!(regex("^ab*c$").is_match("abbbc"));
+    assert!(!regex("^ab+c$").is_match("ac"));
+    assert!(regex("^ab+c$").is_match("abc"));
+    assert!(regex("^colou?r$").is_match("color"));
+    assert!(regex("^colou?r$").is_match("colour"));
+    assert!(!regex("^colou?r$").is_match("colouur"));
+    // greedy, but backtracks
+    assert!(regex("^.*a$").is_match("Canada"));
+    assert!(regex("^(ab)+$").is_match("ababab"));
+    assert!(!regex("^(ab)+$").is_match("ababa"));
+}
+
+#[test]
+fn alternation_and_groups() {
+    let dach = regex("^(Germany|Austria|Switzerland)$");
+    assert!(dach.is_match("Austria"));
+    assert!(!dach.is_match("Australia"));
+    assert!(regex("^Korea, (North|South)$").is_match("Korea, South"));
+    assert!(regex("a|^b").is_match("xa"));
+    assert!(regex("(|x)y").is_match("y"));
+}
+
+#[test]
+fn invalid_patterns() {
+    for pattern in ["(a", "a)", "[a-", "*a", "a|+", "\\"].iter() {
+        assert!(Regex::new(pattern).is_err(), "{}", pattern);
+    }
+}
+
+#[test]
+fn long_texts_do_not_hang() {
+    let text = "a".repeat(200);
+    assert!(regex("^(a|aa)*$").is_match(&text));
+    assert!(regex("^a*a*a*b?$").is_match(&text));
+}
+
+#[test]
+fn globs_match_whole_names() {
+    let glob = |pattern: &str| Glob::new(pattern).unwrap();
+    assert!(glob("New*").is_match("New York"));
+    assert!(!glob("New*").is_match("Renew"));
+    assert!(glob("*").is_match(""));
+    assert!(glob("?ouisiana").is_match("Louisiana"));
+    assert!(!glob("?ouisiana").is_match("ouisiana"));
+    assert!(glob("[A-C]*").is_match("California"));
+    assert!(!glob("[!A-C]*").is_match("California"));
+    assert!(glob("[!A-C]*").is_match("Texas"));
+    // regex characters are literals
+    assert!(glob("St. Martin").is_match("St. Martin"));
+    assert!(!glob("St. Martin").is_match("Sta Martin"));
+    assert!(glob("(a)+").is_match("(a)+"));
+    assert!(glob("a|b").is_match("a|b"));
+    assert!(!glob("a|b").is_match("a"));
+}
+
+#[test]
+fn filters_combine_all_parts() {
+    let filter = LocationFilter {
+        country_regex: Some(regex("^(Canada|US)$")),
+        province_glob: Some(Glob::new("N*").unwrap()),
+        ..LocationFilter::default()
+    };
+    assert!(filter.matches("US", "New York"));
+    assert!(filter.matches("Canada", "Nova Scotia"));
+    assert!(!filter.matches("US", "Texas"));
+    assert!(!filter.matches("Mexico", "Nuevo Leon"));
+    assert!(LocationFilter::default().matches("anything", ""));
+    assert!(LocationFilter::default().is_empty());
+    assert!(!filter.is_empty());
+
+    let group = LocationFilter {
+        group: Some(vec!["Germany".to_string(), "Austria".to_string()]),
+        ..LocationFilter::default()
+    };
+    assert!(group.matches("Austria", ""));
+    assert!(!group.matches("Switzerland", ""));
+}
@@ -0,0 +1,399 @@
+// The pipeline in process, the chain's fetcher answering from the fixtures
+// without any server or socket.
+
+use chrono::NaiveDate;
+use std::sync::{Arc, Mutex};
+
+use corona_stats::analytics;
+use corona_stats::chain::{Chain, Layer};
+use corona_stats::client::NotFound;
+use corona_stats::data::{self, Metric};
+use corona_stats::dataset::Dataset;
+use corona_stats::derived::Transform;
+use corona_stats::exit::{self, Failure};
+use corona_stats::export::{self, CsvWriter, Layout, NdjsonWriter, PerturbedWriter};
+use corona_stats::filter::LocationFilter;
+use corona_stats::hooks::Hooks;
+use corona_stats::mock::{self, Fixtures, MockServer};
+use corona_stats::output::{OutputWriter, SeriesStream};
+use corona_stats::precedence::Precedence;
+use corona_stats::privacy::Perturbation;
+use corona_stats::provenance::Provenance;
+use corona_stats::source::{self, MergedSource};
+
+fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+fn fixtures() -> Chain {
+    let mut chain = Chain::default();
+    chain.set_fetcher(Box::new(Fixtures));
+    chain
+}
+
+#[tokio::test]
+async fn upstream_urls_are_answered() {
+    let chain = fixtures();
+    let url = format!("{}{}", data::UPSTREAM, source::jhu::GLOBAL[0].1);
+    let body = chain.fetch(&url).await.unwrap();
+    assert!(body.starts_with("Province/State,Country/Region"));
+    assert_eq!(Some(body.as_str()), mock::fixture(source::jhu::GLOBAL[0].1));
+}
+
+#[tokio::test]
+async fn unknown_urls_are_not_found() {
+    let chain = fixtures();
+    let error = chain
+        .fetch(&format!("{}missing.csv", data::UPSTREAM))
+        .await
+        .unwrap_err();
+    assert!(error.is::<NotFound>());
+}
+
+#[test]
+fn same_series_as_the_mock_server() {
+    let server = MockServer::start().unwrap();
+    let mut served = Chain::default().with_rewrite(data::UPSTREAM, &server.base_url());
+    served.set_rate_limit(0.0);
+    let served = data::get_time_series(&served).unwrap();
+    let local = data::get_time_series(&fixtures()).unwrap();
+    assert_eq!(served.len(), local.len());
+    for country in ["Italy", "Germany", "US"].iter() {
+        for state in ["Confirmed", "Deaths", "Recovered"].iter() {
+            assert_eq!(
+                data::country_series(&served, country, state),
+                data::country_series(&local, country, state)
+            );
+        }
+    }
+}
+
+#[test]
+fn fetch_analyze_export() {
+    let chain = fixtures();
+    let reports = data::latest_reports(&chain, 1)
+        .unwrap()
+        .into_reports()
+        .unwrap();
+    assert_eq!(reports[0].0, ymd(2020, 3, 21));
+
+    let series = data::get_time_series(&chain).unwrap();
+    let deaths = data::country_series(&series, "Italy", "Deaths");
+    assert_eq!(deaths.last().map(|(d, _)| *d), Some(ymd(2020, 3, 21)));
+    assert!(analytics::anomalies(&deaths, analytics::ANOMALY_Z)
+        .iter()
+        .all(|a| a.date <= ymd(2020, 3, 21)));
+
+    let filter = LocationFilter {
+        country: Some("Italy".to_string()),
+        ..LocationFilter::default()
+    };
+    let mut out = Vec::new();
+    export::export_with(
+        &chain,
+        &filter,
+        Metric::NewDeaths,
+        &Transform::default(),
+        &mut CsvWriter {
+            out: &mut out,
+            layout: Layout::Wide,
+        },
+    )
+    .unwrap();
+    let csv = String::from_utf8(out).unwrap();
+    let last = csv.lines().last().unwrap();
+    let expected = deaths[deaths.len() - 1].1 - deaths[deaths.len() - 2].1;
+    assert!(last.starts_with("2020-03-21,Italy,"));
+    assert!(last.ends_with(&format!(",{}", expected)));
+}
+
+#[test]
+fn merged_sources_record_the_winners() {
+    let mut chain = fixtures();
+    let precedence = Precedence::new(&["jhu", "owid"]).with_metric("confirmed", &["owid", "jhu"]);
+    chain.set_source(Box::new(MergedSource::new(
+        vec![
+            Box::new(source::JhuSource::default()),
+            Box::new(source::OwidSource),
+        ],
+        precedence,
+    )));
+    let series = data::get_time_series(&chain).unwrap();
+    let confirmed: Vec<_> = series
+        .iter()
+        .filter(|s| s.country == "Italy" && s.state == "Confirmed")
+        .collect();
+    assert_eq!(confirmed.len(), 1);
+    let metrics = chain.provenance().metrics;
+    let winners = |metric: &str| {
+        metrics
+            .iter()
+            .find(|(m, _)| m == metric)
+            .map(|(_, s)| s.clone())
+    };
+    assert_eq!(winners("deaths"), Some("jhu".to_string()));
+    assert!(winners("confirmed").unwrap().contains("owid"));
+}
+
+#[test]
+fn daily_reports_pass_the_transform_hook() {
+    let mut chain = fixtures();
+    chain.set_hooks(Hooks {
+        transform_record: Some("grep -v ',Italy,'".to_string()),
+        ..Hooks::default()
+    });
+    let date = ymd(2020, 3, 21);
+    let records = data::get_data_from(&chain, &date).unwrap();
+    assert!(!records.is_empty());
+    assert!(records.iter().all(|r| r.country != "Italy"));
+    let all = data::get_data_from(&fixtures(), &date).unwrap();
+    assert!(all.iter().any(|r| r.country == "Italy"));
+}
+
+#[test]
+fn hooks_may_answer_before_reading_their_input() {
+    // more than a pipe holds in both directions
+    let hooks = Hooks {
+        on_alert: Some("head -c 1000000 /dev/zero; cat > /dev/null".to_string()),
+        ..Hooks::default()
+    };
+    hooks.alert(&"x".repeat(1_000_000)).unwrap();
+    let failing = Hooks {
+        on_alert: Some("exit 3".to_string()),
+        ..Hooks::default()
+    };
+    assert!(failing.alert("message").is_err());
+}
+
+// The first 1000 entries of the report listing, as the GitHub API cuts it off.
+struct TruncatedListing;
+
+impl Layer for TruncatedListing {
+    fn name(&self) -> &'static str {
+        "truncated"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        if !key.starts_with(data::GITHUB_API) {
+            return None;
+        }
+        let entries: Vec<String> = (1..=1000)
+            .map(|n| {
+                let date = ymd(2020, 3, 21) + chrono::Duration::days(n);
+                format!("{{\"name\": \"{}.csv\"}}", date.format("%m-%d-%Y"))
+            })
+            .collect();
+        Some(format!("[{}]", entries.join(",")))
+    }
+
+    fn put(&self, _key: &str, _body: &str) {}
+}
+
+#[test]
+fn truncated_listings_are_not_trusted() {
+    let mut chain = fixtures().with_layer(TruncatedListing);
+    chain.set_discovery(true);
+    let reports = data::latest_reports(&chain, 1)
+        .unwrap()
+        .into_reports()
+        .unwrap();
+    assert_eq!(reports[0].0, ymd(2020, 3, 21));
+}
+
+// Every key the chain asks for below the memory layer.
+struct Recording(Arc<Mutex<Vec<String>>>);
+
+impl Layer for Recording {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.lock().unwrap().push(key.to_string());
+        None
+    }
+
+    fn put(&self, _key: &str, _body: &str) {}
+}
+
+#[test]
+fn history_stops_at_the_last_published_day() {
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let chain = fixtures().with_layer(Recording(asked.clone()));
+    let reports = data::get_history(&chain, &ymd(2020, 3, 15), &ymd(2023, 12, 31))
+        .unwrap()
+        .into_reports()
+        .unwrap();
+    assert_eq!(reports.last().map(|r| r.0), Some(ymd(2020, 3, 21)));
+    let reports: Vec<String> = asked
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|key| key.contains("csse_covid_19_daily_reports/"))
+        .cloned()
+        .collect();
+    assert_eq!(reports.len(), 7, "{:?}", reports);
+}
+
+#[tokio::test]
+async fn offline_misses_are_failures() {
+    let mut chain = Chain::default();
+    chain.set_enabled("network", false);
+    let url = format!("{}{}", data::UPSTREAM, source::jhu::GLOBAL[0].1);
+    let error = chain.fetch(&url).await.unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<Failure>(),
+        Some(&Failure::Offline(url))
+    );
+    assert_eq!(exit::code(&*error), exit::NO_DATA);
+}
+
+// Takes the series one by one, they must not be collected first.
+struct Streamed;
+
+impl OutputWriter for Streamed {
+    fn write(&mut self, _dataset: &Dataset) -> Result<(), Box<dyn std::error::Error>> {
+        panic!("the series were collected");
+    }
+
+    fn write_stream(
+        &mut self,
+        series: &mut SeriesStream,
+        _provenance: Option<Provenance>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        series.for_each(drop);
+        Ok(())
+    }
+}
+
+#[test]
+fn exports_stream_through_the_noise() {
+    let chain = fixtures();
+    let filter = LocationFilter {
+        country: Some("Italy".to_string()),
+        ..LocationFilter::default()
+    };
+    let perturbation = Perturbation {
+        threshold: 1000,
+        ..Perturbation::default()
+    };
+    let mut streamed = PerturbedWriter {
+        inner: Box::new(Streamed),
+        perturbation: perturbation.clone(),
+    };
+    let transform = Transform::default();
+    export::export_with(&chain, &filter, Metric::All, &transform, &mut streamed).unwrap();
+
+    let mut out = Vec::new();
+    let mut ndjson = PerturbedWriter {
+        inner: Box::new(NdjsonWriter { out: &mut out }),
+        perturbation: perturbation.clone(),
+    };
+    export::export_with(&chain, &filter, Metric::All, &transform, &mut ndjson).unwrap();
+    drop(ndjson);
+    let mut series: Vec<_> = export::select(&chain, &filter, Metric::All, &transform)
+        .unwrap()
+        .collect();
+    perturbation.perturb(&mut series);
+    let mut expected = Vec::new();
+    export::write_ndjson(&mut expected, series.iter()).unwrap();
+    assert_eq!(out, expected);
+    assert_eq!(
+        String::from_utf8(out).unwrap().lines().count(),
+        series.iter().map(|s| s.data.len()).sum::<usize>()
+    );
+}
+
+// Kept in hundred-thousandths, the CFR is written as the ratio it is.
+#[test]
+fn cfr_is_written_as_a_ratio() {
+    let chain = fixtures();
+    let filter = LocationFilter {
+        country: Some("Italy".to_string()),
+        ..LocationFilter::default()
+    };
+    let transform = Transform::default();
+    let series: Vec<_> = export::select(&chain, &filter, Metric::Cfr, &transform)
+        .unwrap()
+        .collect();
+    let cfr = &series[0];
+    assert_eq!(cfr.data["2020-03-21"], 5669);
+    assert_eq!(cfr.value(5669), 0.05669);
+
+    let mut out = Vec::new();
+    export::write_csv(&mut out, &series, Layout::Long).unwrap();
+    let csv = String::from_utf8(out).unwrap();
+    assert!(csv.ends_with("2020-03-21,Italy,,cfr,0.05669\n"));
+
+    let provenance = chain.provenance();
+    let json = export::to_json(&series, &provenance).to_string();
+    assert!(json.contains("\"2020-03-21\":0.05669"));
+
+    // and it stays one in a snapshot
+    let dataset = Dataset::new(Vec::new(), series.clone(), None);
+    let decoded = Dataset::decode(&dataset.encode()).unwrap();
+    assert_eq!(decoded.series[0].scale, cfr.scale);
+    assert_eq!(decoded.series[0].data, cfr.data);
+}
+
+#[test]
+fn outcome_shares_are_written_as_ratios() {
+    let chain = fixtures();
+    let filter = LocationFilter {
+        country: Some("Italy".to_string()),
+        ..LocationFilter::default()
+    };
+    let transform = Transform::default();
+    let series: Vec<_> = export::select(&chain, &filter, Metric::Outcomes, &transform)
+        .unwrap()
+        .collect();
+    assert_eq!(series.len(), 2);
+    let day = |s: &data::TimeSeries| s.value(s.data["2020-03-21"]);
+    assert!((day(&series[0]) + day(&series[1]) - 1.0).abs() < 1e-4);
+
+    let mut out = Vec::new();
+    export::write_ndjson(&mut out, series.iter()).unwrap();
+    let ndjson = String::from_utf8(out).unwrap();
+    assert!(ndjson.contains(
+        "{\"date\":\"2020-03-21\",\"country\":\"Italy\",\"province\":\"\",\"metric\":\"recovered_share\",\"value\":0.3052}"
+    ));
+}
+
+// The noise of a seed is the same with every build, and cumulative counts
+// take the noise of their daily increases.
+#[test]
+fn noise_is_stable_and_keeps_counts_consistent() {
+    let perturbation = Perturbation {
+        threshold: 1_000_000,
+        ..Perturbation::default()
+    };
+    let noisy: Vec<u32> = (0..8)
+        .map(|day| perturbation.apply(&format!("Italy//Confirmed/2020-03-0{}", day + 1), 5))
+        .collect();
+    assert_eq!(noisy, [6, 6, 4, 7, 4, 5, 3, 5]);
+
+    let chain = fixtures();
+    let filter = LocationFilter {
+        country: Some("Italy".to_string()),
+        ..LocationFilter::default()
+    };
+    let transform = Transform::default();
+    let perturbed = |metric| {
+        let mut series: Vec<_> = export::select(&chain, &filter, metric, &transform)
+            .unwrap()
+            .collect();
+        perturbation.perturb(&mut series);
+        series.remove(0)
+    };
+    let confirmed = perturbed(Metric::Confirmed);
+    let new = perturbed(Metric::NewConfirmed);
+    let values: Vec<i32> = confirmed.data.values().copied().collect();
+    assert!(values.windows(2).all(|w| w[0] <= w[1]));
+    let increases: Vec<i32> = values.windows(2).map(|w| w[1] - w[0]).collect();
+    assert_eq!(increases, new.data.values().copied().collect::<Vec<_>>());
+    let exact = export::select(&chain, &filter, Metric::Confirmed, &transform)
+        .unwrap()
+        .next()
+        .unwrap();
+    assert_ne!(confirmed.data, exact.data);
+}
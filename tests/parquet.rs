@@ -0,0 +1,281 @@
+// Parquet files read back with a small Thrift compact protocol decoder: the
+// footer, the schema and every column chunk the metadata points to.
+#![cfg(feature = "arrow")]
+
+use corona_stats::parquet::{self, Column};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i64),
+    Binary(Vec<u8>),
+    List(Vec<Value>),
+    Struct(Vec<(i16, Value)>),
+}
+
+impl Value {
+    fn field(&self, id: i16) -> &Value {
+        match self {
+            Value::Struct(fields) => fields
+                .iter()
+                .find(|(i, _)| *i == id)
+                .map(|(_, v)| v)
+                .unwrap_or_else(|| panic!("no field {} in {:?}", id, self)),
+            _ => panic!("not a struct: {:?}", self),
+        }
+    }
+
+    fn has(&self, id: i16) -> bool {
+        matches!(self, Value::Struct(fields) if fields.iter().any(|(i, _)| *i == id))
+    }
+
+    fn int(&self) -> i64 {
+        match self {
+            Value::Int(n) => *n,
+            _ => panic!("not an integer: {:?}", self),
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Value::Binary(b) => String::from_utf8(b.clone()).unwrap(),
+            _ => panic!("not binary: {:?}", self),
+        }
+    }
+
+    fn list(&self) -> &[Value] {
+        match self {
+            Value::List(items) => items,
+            _ => panic!("not a list: {:?}", self),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> u8 {
+        self.pos += 1;
+        self.data[self.pos - 1]
+    }
+
+    fn varint(&mut self) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte();
+            value |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn zigzag(&mut self) -> i64 {
+        let n = self.varint();
+        (n >> 1) as i64 ^ -((n & 1) as i64)
+    }
+
+    fn value(&mut self, kind: u8) -> Value {
+        match kind {
+            5 | 6 => Value::Int(self.zigzag()),
+            8 => {
+                let len = self.varint() as usize;
+                self.pos += len;
+                Value::Binary(self.data[self.pos - len..self.pos].to_vec())
+            }
+            9 => {
+                let header = self.byte();
+                let len = match header >> 4 {
+                    15 => self.varint() as usize,
+                    n => usize::from(n),
+                };
+                Value::List((0..len).map(|_| self.value(header & 0x0f)).collect())
+            }
+            12 => {
+                let mut fields = Vec::new();
+                let mut last = 0i16;
+                loop {
+                    let header = self.byte();
+                    if header == 0 {
+                        return Value::Struct(fields);
+                    }
+                    let id = match header >> 4 {
+                        0 => self.zigzag() as i16,
+                        delta => last + i16::from(delta),
+                    };
+                    last = id;
+                    fields.push((id, self.value(header & 0x0f)));
+                }
+            }
+            _ => panic!("unexpected type {}", kind),
+        }
+    }
+}
+
+fn decode(data: &[u8], at: usize) -> (Value, usize) {
+    let mut r = Reader { data, pos: at };
+    let value = r.value(12);
+    (value, r.pos)
+}
+
+fn write(columns: &[(&str, Column)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    parquet::write(&mut out, columns).unwrap();
+    out
+}
+
+// The metadata from the footer.
+fn footer(data: &[u8]) -> Value {
+    assert_eq!(&data[..4], b"PAR1");
+    assert_eq!(&data[data.len() - 4..], b"PAR1");
+    let len = u32::from_le_bytes([
+        data[data.len() - 8],
+        data[data.len() - 7],
+        data[data.len() - 6],
+        data[data.len() - 5],
+    ]) as usize;
+    let start = data.len() - 8 - len;
+    let (meta, end) = decode(data, start);
+    assert_eq!(end, data.len() - 8);
+    meta
+}
+
+// The PLAIN data of every column chunk, checking the sizes on the way.
+fn pages(data: &[u8], meta: &Value) -> Vec<Vec<u8>> {
+    let groups = meta.field(4).list();
+    assert_eq!(groups.len(), 1);
+    let mut pages = Vec::new();
+    let mut previous = 4;
+    for chunk in groups[0].field(1).list() {
+        let offset = chunk.field(2).int() as usize;
+        assert_eq!(offset, previous);
+        let column = chunk.field(3);
+        assert_eq!(column.field(9).int() as usize, offset);
+        let (header, start) = decode(data, offset);
+        let size = header.field(2).int() as usize;
+        assert_eq!(header.field(3).int() as usize, size);
+        assert_eq!(header.field(5).field(1).int(), meta.field(3).int());
+        assert_eq!(start - offset + size, column.field(6).int() as usize);
+        pages.push(data[start..start + size].to_vec());
+        previous = start + size;
+    }
+    assert_eq!(
+        groups[0].field(2).int() as usize,
+        previous - 4,
+        "total byte size"
+    );
+    pages
+}
+
+fn int32s(page: &[u8]) -> Vec<i32> {
+    page.chunks(4)
+        .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn strings(page: &[u8]) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut at = 0;
+    while at < page.len() {
+        let len = int32s(&page[at..at + 4])[0] as usize;
+        values.push(String::from_utf8(page[at + 4..at + 4 + len].to_vec()).unwrap());
+        at += 4 + len;
+    }
+    values
+}
+
+#[test]
+fn columns_round_trip() {
+    let countries: Vec<String> = ["Italy", "Germany", "Côte d'Ivoire"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let data = write(&[
+        // 2020-03-21 and the days after
+        ("date", Column::Date(vec![18342, 18343, 18344])),
+        ("country", Column::Utf8(countries.clone())),
+        ("confirmed", Column::Int32(vec![53578, -1, i32::MAX])),
+    ]);
+    let meta = footer(&data);
+    assert_eq!(meta.field(1).int(), 1, "version");
+    assert_eq!(meta.field(3).int(), 3, "rows");
+    assert!(meta.field(6).text().starts_with("corona-stats"));
+
+    let schema = meta.field(2).list();
+    assert_eq!(schema.len(), 4);
+    assert_eq!(schema[0].field(4).text(), "schema");
+    assert_eq!(schema[0].field(5).int(), 3);
+    let names: Vec<String> = schema[1..].iter().map(|s| s.field(4).text()).collect();
+    assert_eq!(names, vec!["date", "country", "confirmed"]);
+    // INT32 as DATE, BYTE_ARRAY as UTF8, a plain INT32
+    assert_eq!((schema[1].field(1).int(), schema[1].field(6).int()), (1, 6));
+    assert_eq!((schema[2].field(1).int(), schema[2].field(6).int()), (6, 0));
+    assert_eq!(schema[3].field(1).int(), 1);
+    assert!(!schema[3].has(6));
+    assert!(
+        schema[1..].iter().all(|s| s.field(3).int() == 0),
+        "required"
+    );
+
+    let pages = pages(&data, &meta);
+    assert_eq!(int32s(&pages[0]), vec![18342, 18343, 18344]);
+    assert_eq!(strings(&pages[1]), countries);
+    assert_eq!(int32s(&pages[2]), vec![53578, -1, i32::MAX]);
+}
+
+#[test]
+fn doubles_for_ratios() {
+    let data = write(&[("cfr", Column::Double(vec![0.05669, 0.0, 1.0]))]);
+    let meta = footer(&data);
+    // DOUBLE without a converted type
+    let schema = meta.field(2).list();
+    assert_eq!(schema[1].field(1).int(), 5);
+    assert!(!schema[1].has(6));
+    let values: Vec<f64> = pages(&data, &meta)[0]
+        .chunks(8)
+        .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .collect();
+    assert_eq!(values, vec![0.05669, 0.0, 1.0]);
+}
+
+#[test]
+fn many_columns_use_long_lists() {
+    let names: Vec<String> = (0..20).map(|i| format!("c{}", i)).collect();
+    let columns: Vec<(&str, Column)> = names
+        .iter()
+        .zip(0..)
+        .map(|(name, i)| (name.as_str(), Column::Int32(vec![i])))
+        .collect();
+    let data = write(&columns);
+    let meta = footer(&data);
+    assert_eq!(meta.field(2).list().len(), 21);
+    let pages = pages(&data, &meta);
+    assert_eq!(pages.len(), 20);
+    assert_eq!(int32s(&pages[19]), vec![19]);
+}
+
+#[test]
+fn no_rows() {
+    let data = write(&[("country", Column::Utf8(Vec::new()))]);
+    let meta = footer(&data);
+    assert_eq!(meta.field(3).int(), 0);
+    assert!(pages(&data, &meta)[0].is_empty());
+}
+
+#[test]
+fn columns_of_different_lengths() {
+    let mut out = Vec::new();
+    let result = parquet::write(
+        &mut out,
+        &[
+            ("a", Column::Int32(vec![1, 2])),
+            ("b", Column::Int32(vec![1])),
+        ],
+    );
+    assert!(result.is_err());
+    assert!(out.is_empty());
+}
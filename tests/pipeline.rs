@@ -0,0 +1,174 @@
+// The whole pipeline against the bundled fixtures served by a MockServer on
+// localhost: fetch, parse, analyze and export, without leaving the machine.
+
+use chrono::NaiveDate;
+
+use corona_stats::analytics;
+use corona_stats::chain::Chain;
+use corona_stats::data::{self, Metric};
+use corona_stats::derived::Transform;
+use corona_stats::export::{self, CsvWriter, JsonWriter, Layout};
+use corona_stats::filter::LocationFilter;
+use corona_stats::json;
+use corona_stats::mock::MockServer;
+use corona_stats::source;
+
+fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+fn served(server: &MockServer) -> Chain {
+    let mut chain = Chain::default()
+        .with_rewrite(data::UPSTREAM, &server.base_url())
+        .with_rewrite(data::GITHUB_API, &server.api_url())
+        .with_rewrite(source::owid::BASE, &server.source_url("owid"));
+    chain.set_rate_limit(0.0);
+    chain.set_retries(0);
+    chain
+}
+
+fn italy() -> LocationFilter {
+    LocationFilter {
+        country: Some("Italy".to_string()),
+        ..LocationFilter::default()
+    }
+}
+
+#[test]
+fn time_series_are_served() {
+    let server = MockServer::start().unwrap();
+    let series = data::get_time_series(&served(&server)).unwrap();
+    let confirmed = data::country_series(&series, "Italy", "Confirmed");
+    assert_eq!(confirmed.first().map(|(d, _)| *d), Some(ymd(2020, 1, 22)));
+    assert!(confirmed.windows(2).all(|w| w[0].0 < w[1].0));
+    assert!(confirmed.last().unwrap().1 > 0);
+    for state in ["Deaths", "Recovered"].iter() {
+        assert_eq!(
+            data::country_series(&series, "Italy", state).len(),
+            confirmed.len()
+        );
+    }
+}
+
+#[test]
+fn daily_reports_are_served() {
+    let server = MockServer::start().unwrap();
+    let chain = served(&server);
+    let records = data::get_data_from(&chain, &ymd(2020, 3, 15)).unwrap();
+    let italy = data::summarize(&records)
+        .into_iter()
+        .find(|s| s.country == "Italy")
+        .unwrap();
+    assert!(italy.confirmed > 0 && italy.deaths > 0);
+    // a day without a report is empty, not an error
+    assert!(data::get_data_from(&chain, &ymd(2019, 1, 1))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn latest_reports_follow_the_time_series() {
+    let server = MockServer::start().unwrap();
+    let reports = data::latest_reports(&served(&server), 2)
+        .unwrap()
+        .into_reports()
+        .unwrap();
+    let dates: Vec<NaiveDate> = reports.iter().map(|(d, _)| *d).collect();
+    assert_eq!(dates, vec![ymd(2020, 3, 21), ymd(2020, 3, 20)]);
+}
+
+#[test]
+fn analytics_of_the_served_series() {
+    let server = MockServer::start().unwrap();
+    let series = data::get_time_series(&served(&server)).unwrap();
+    let confirmed = data::country_series(&series, "Italy", "Confirmed");
+    let growth = analytics::growth_factor(&confirmed).unwrap();
+    assert!(growth > 0.0);
+    let milestones = analytics::milestones(&confirmed);
+    assert!(milestones
+        .windows(2)
+        .all(|w| w[0].0 < w[1].0 && w[0].1 <= w[1].1));
+    assert_eq!(milestones.first().map(|(m, _)| *m), Some(100));
+}
+
+#[test]
+fn export_as_csv() {
+    let server = MockServer::start().unwrap();
+    let chain = served(&server);
+    let days = data::country_series(
+        &data::get_time_series(&chain).unwrap(),
+        "Italy",
+        "Confirmed",
+    )
+    .len();
+    let mut out = Vec::new();
+    export::export_with(
+        &chain,
+        &italy(),
+        Metric::Confirmed,
+        &Transform::default(),
+        &mut CsvWriter {
+            out: &mut out,
+            layout: Layout::Long,
+        },
+    )
+    .unwrap();
+    let csv = String::from_utf8(out).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("date,country,province,metric,value"));
+    let rows: Vec<&str> = lines.collect();
+    assert_eq!(rows.len(), days);
+    assert!(rows.iter().all(|r| r.contains(",Italy,,confirmed,")));
+}
+
+#[test]
+fn export_as_json() {
+    let server = MockServer::start().unwrap();
+    let chain = served(&server);
+    let mut out = Vec::new();
+    export::export_with(
+        &chain,
+        &italy(),
+        Metric::Deaths,
+        &Transform::default(),
+        &mut JsonWriter { out: &mut out },
+    )
+    .unwrap();
+    let document = json::parse(&String::from_utf8(out).unwrap()).unwrap();
+    assert_eq!(
+        document.get("source").and_then(|s| s.get("name")),
+        Some(&json::Value::String("jhu".to_string()))
+    );
+    match document.get("countries") {
+        Some(json::Value::Array(countries)) => {
+            assert_eq!(countries.len(), 1);
+            assert!(countries[0]
+                .get("metrics")
+                .and_then(|m| m.get("deaths"))
+                .is_some());
+        }
+        other => panic!("no countries in {:?}", other),
+    }
+}
+
+#[test]
+fn export_of_nothing_is_an_error() {
+    let server = MockServer::start().unwrap();
+    let filter = LocationFilter {
+        country: Some("Atlantis".to_string()),
+        ..LocationFilter::default()
+    };
+    let mut out = Vec::new();
+    let result = export::export_with(
+        &served(&server),
+        &filter,
+        Metric::Confirmed,
+        &Transform::default(),
+        &mut CsvWriter {
+            out: &mut out,
+            layout: Layout::Long,
+        },
+    );
+    assert!(result.is_err());
+    assert!(out.is_empty());
+}
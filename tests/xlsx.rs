@@ -0,0 +1,177 @@
+// ZIP archives and the XLSX workbooks in them, read back through the central
+// directory the way spreadsheet programs do.
+
+use corona_stats::inflate::{crc32, inflate};
+use corona_stats::xlsx::{self, Cell, Sheet};
+use corona_stats::zip::Zip;
+
+fn u16_at(data: &[u8], at: usize) -> usize {
+    usize::from(u16::from_le_bytes([data[at], data[at + 1]]))
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+// The names and contents of all entries, checking every size and checksum.
+fn unzip(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let end = data.len() - 22;
+    assert_eq!(u32_at(data, end), 0x0605_4b50);
+    assert_eq!(u16_at(data, end + 20), 0, "no comment");
+    let count = u16_at(data, end + 10);
+    let size = u32_at(data, end + 12) as usize;
+    let mut at = u32_at(data, end + 16) as usize;
+    assert_eq!(at + size, end);
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        assert_eq!(u32_at(data, at), 0x0201_4b50);
+        assert_eq!(u16_at(data, at + 10), 8, "deflated");
+        let crc = u32_at(data, at + 16);
+        let compressed = u32_at(data, at + 20) as usize;
+        let length = u32_at(data, at + 24) as usize;
+        let name_length = u16_at(data, at + 28);
+        let offset = u32_at(data, at + 42) as usize;
+        let name = String::from_utf8(data[at + 46..at + 46 + name_length].to_vec()).unwrap();
+        at += 46 + name_length + u16_at(data, at + 30) + u16_at(data, at + 32);
+
+        assert_eq!(u32_at(data, offset), 0x0403_4b50);
+        assert_eq!(u32_at(data, offset + 14), crc);
+        let local_name = u16_at(data, offset + 26);
+        assert_eq!(
+            &data[offset + 30..offset + 30 + local_name],
+            name.as_bytes()
+        );
+        let start = offset + 30 + local_name + u16_at(data, offset + 28);
+        let (content, used) = inflate(&data[start..start + compressed]).unwrap();
+        assert_eq!(used, compressed);
+        assert_eq!(content.len(), length);
+        assert_eq!(crc32(&content), crc);
+        entries.push((name, content));
+    }
+    assert_eq!(at, end);
+    entries
+}
+
+fn text(entries: &[(String, Vec<u8>)], name: &str) -> String {
+    let (_, content) = entries
+        .iter()
+        .find(|(n, _)| n == name)
+        .unwrap_or_else(|| panic!("no {}", name));
+    String::from_utf8(content.clone()).unwrap()
+}
+
+#[test]
+fn empty_archive() {
+    let data = Zip::new().finish();
+    assert_eq!(data.len(), 22);
+    assert!(unzip(&data).is_empty());
+}
+
+#[test]
+fn entries_in_order() {
+    let mut zip = Zip::new();
+    zip.add("a.txt", b"first");
+    zip.add("dir/b.csv", &b"1,2,3\n".repeat(1000));
+    zip.add("empty", b"");
+    let entries = unzip(&zip.finish());
+    let names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, vec!["a.txt", "dir/b.csv", "empty"]);
+    assert_eq!(entries[0].1, b"first");
+    assert_eq!(entries[1].1, b"1,2,3\n".repeat(1000));
+    assert!(entries[2].1.is_empty());
+}
+
+fn sheet(name: &str, rows: Vec<Vec<Cell>>) -> Sheet {
+    Sheet {
+        name: name.to_string(),
+        rows,
+        header: true,
+    }
+}
+
+#[test]
+fn workbook_parts() {
+    let data = xlsx::workbook(&[
+        sheet("Summary", vec![vec![Cell::Text("Country".to_string())]]),
+        sheet("Italy", Vec::new()),
+    ]);
+    let entries = unzip(&data);
+    let names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "[Content_Types].xml",
+            "_rels/.rels",
+            "xl/workbook.xml",
+            "xl/_rels/workbook.xml.rels",
+            "xl/styles.xml",
+            "xl/worksheets/sheet1.xml",
+            "xl/worksheets/sheet2.xml",
+        ]
+    );
+    let workbook = text(&entries, "xl/workbook.xml");
+    assert!(workbook.contains("<sheet name=\"Summary\" sheetId=\"1\" r:id=\"rId1\"/>"));
+    assert!(workbook.contains("<sheet name=\"Italy\" sheetId=\"2\" r:id=\"rId2\"/>"));
+    let rels = text(&entries, "xl/_rels/workbook.xml.rels");
+    assert!(rels.contains("Id=\"rId2\""));
+    assert!(rels.contains("Id=\"rId3\"") && rels.contains("styles.xml"));
+    let types = text(&entries, "[Content_Types].xml");
+    assert!(types.contains("/xl/worksheets/sheet2.xml"));
+}
+
+#[test]
+fn cells() {
+    let rows = vec![
+        vec![
+            Cell::Text("Date".to_string()),
+            Cell::Text("Confirmed".to_string()),
+        ],
+        vec![
+            Cell::Text("2020-03-21".to_string()),
+            Cell::Number(53578.0),
+            Cell::Empty,
+            Cell::Number(f64::NAN),
+            Cell::Number(0.5),
+        ],
+    ];
+    let entries = unzip(&xlsx::workbook(&[sheet("Italy", rows)]));
+    let xml = text(&entries, "xl/worksheets/sheet1.xml");
+    assert!(xml.contains("state=\"frozen\""));
+    assert!(
+        xml.contains("<row r=\"1\"><c r=\"A1\" s=\"1\" t=\"inlineStr\"><is><t>Date</t></is></c>")
+    );
+    assert!(xml.contains("<c r=\"B2\"><v>53578</v></c>"));
+    assert!(xml.contains("<c r=\"E2\"><v>0.5</v></c>"));
+    // empty and not a number are left out
+    assert!(!xml.contains("C2") && !xml.contains("D2"));
+}
+
+#[test]
+fn columns_past_z() {
+    let row: Vec<Cell> = (0..30).map(|n| Cell::Number(f64::from(n))).collect();
+    let entries = unzip(&xlsx::workbook(&[Sheet {
+        name: "Wide".to_string(),
+        rows: vec![row],
+        header: false,
+    }]));
+    let xml = text(&entries, "xl/worksheets/sheet1.xml");
+    assert!(xml.contains("<c r=\"Z1\"><v>25</v></c>"));
+    assert!(xml.contains("<c r=\"AA1\"><v>26</v></c>"));
+    assert!(xml.contains("<c r=\"AD1\"><v>29</v></c>"));
+    assert!(!xml.contains("frozen"));
+}
+
+#[test]
+fn names_and_text_are_escaped() {
+    let rows = vec![vec![Cell::Text(
+        "Bonaire, Sint Eustatius & Saba <1>\u{1}".to_string(),
+    )]];
+    let entries = unzip(&xlsx::workbook(&[sheet(
+        "Korea: South [a/b] with a very long name indeed",
+        rows,
+    )]));
+    let workbook = text(&entries, "xl/workbook.xml");
+    assert!(workbook.contains("name=\"Korea_ South _a_b_ with a very \""));
+    let xml = text(&entries, "xl/worksheets/sheet1.xml");
+    assert!(xml.contains("<t>Bonaire, Sint Eustatius &amp; Saba &lt;1&gt;</t>"));
+}